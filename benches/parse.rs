@@ -0,0 +1,99 @@
+// Benchmarks for parsing large logs.
+//
+// The synthetic log is built by repeating the sample report in `messages` between runs of
+// firewall noise, giving a log of PARSEOOM_BENCH_MIB mebibytes (1 GiB by default) that contains
+// hundreds of oom-killer reports. "read and parse" writes it to a temporary file and reads it
+// back each time, as parseoom does with a log file. Use a smaller value for quick local runs:
+//
+//      PARSEOOM_BENCH_MIB=64 cargo bench
+//
+// Reading and parsing the last report of the 1 GiB log must take less than TARGET, and the
+// benchmark fails when it doesn't. A smaller log has less to read but as much around the report
+// to scan, and has the same target.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use parseoom::input::{FileSource, InputSource};
+use parseoom::structured::JsonFields;
+use parseoom::{last_report, OomEvent};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const SAMPLE: &str = include_str!("../messages");
+const NOISE: &str = "Dec 20 03:17:52 localhost kernel: [20401616.476879] [UFW BLOCK] IN=eth0 OUT= MAC=f2:3c:91:e2:47:cc:00:1b:54:c2:50:c1:08:00 SRC=89.248.165.204 DST=72.14.187.238 LEN=40 TOS=0x00 PREC=0x00 TTL=243 ID=39600 PROTO=TCP SPT=40402 DPT=52734 WINDOW=1024 RES=0x00 SYN URGP=0\n";
+const EVENTS: usize = 500;
+const TARGET: Duration = Duration::from_secs(2);
+
+// The fastest of a few runs of reading and parsing the log, so one slow run on a busy machine
+// doesn't fail the target
+fn fastest_read_and_parse(path: &Path) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            let log = FileSource::new(path)
+                .read_log(&JsonFields::default())
+                .unwrap();
+            OomEvent::parse(&log).unwrap();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+// Build a log of roughly `size` bytes with EVENTS oom-killer reports spread evenly through it.
+fn synthetic_log(size: usize) -> String {
+    let mut log = String::with_capacity(size + SAMPLE.len());
+    let gap = size / EVENTS;
+
+    while log.len() < size {
+        let next = log.len() + gap;
+        while log.len() < next {
+            log.push_str(NOISE);
+        }
+        log.push_str(SAMPLE);
+    }
+
+    log
+}
+
+fn parse_benchmark(c: &mut Criterion) {
+    let mib = env::var("PARSEOOM_BENCH_MIB")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(1024);
+    let log = synthetic_log(mib * 1024 * 1024);
+    let report = last_report(&log).unwrap();
+    let path = env::temp_dir().join(format!("parseoom-bench-{}.log", std::process::id()));
+    fs::write(&path, &log).unwrap();
+
+    let took = fastest_read_and_parse(&path);
+    assert!(
+        took < TARGET,
+        "reading and parsing {} MiB took {:?}, more than the target of {:?}",
+        mib,
+        took,
+        TARGET
+    );
+
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    group.bench_function("read and parse", |b| {
+        b.iter(|| {
+            let log = FileSource::new(&path)
+                .read_log(&JsonFields::default())
+                .unwrap();
+            OomEvent::parse(&log).unwrap()
+        })
+    });
+    group.bench_function("last event", |b| b.iter(|| OomEvent::parse(&log).unwrap()));
+    group.bench_function("single report", |b| {
+        b.iter(|| OomEvent::from_report(report).unwrap())
+    });
+    group.finish();
+
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, parse_benchmark);
+criterion_main!(benches);
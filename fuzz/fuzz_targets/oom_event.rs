@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parseoom::OomEvent;
+
+// Parse arbitrary bytes as a whole log, the same way the parseoom binary reads its input.
+fuzz_target!(|data: &[u8]| {
+    let input = String::from_utf8_lossy(data);
+    let _ = OomEvent::parse(&input);
+});
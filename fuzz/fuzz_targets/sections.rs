@@ -0,0 +1,37 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parseoom::*;
+
+// Feed arbitrary text straight into each section parser, skipping the search for a report.
+fuzz_target!(|data: &[u8]| {
+    let cleaned = clean_report(&String::from_utf8_lossy(data));
+
+    let _ = parse_meminfo_total(&cleaned);
+    let _ = parse_meminfo_swap(&cleaned);
+    let _ = parse_meminfo_slab(&cleaned);
+    let _ = parse_meminfo_reclaimable_slab(&cleaned);
+    let _ = parse_meminfo_isolated(&cleaned);
+    let _ = parse_meminfo_cma(&cleaned);
+    let _ = parse_zones(&cleaned);
+    let _ = parse_compressed_swap(&cleaned);
+    let _ = parse_thp(&cleaned);
+    let _ = parse_hypervisor(&cleaned);
+    let _ = parse_balloon_drivers(&cleaned);
+    let _ = parse_gfp_zone(&cleaned);
+    let _ = parse_alloc_order(&cleaned);
+    let _ = parse_mems_allowed(&cleaned);
+    let _ = parse_meminfo_hugepages(&cleaned);
+    let _ = parse_meminfo_shared(&cleaned);
+    let _ = parse_unreclaimable_slab(&cleaned);
+    let _ = parseoom::sections::parse_memcg_usage(&cleaned);
+
+    if let Some((header, pid_col)) = parse_ps_header(&cleaned) {
+        if let Some(ps) = parse_ps_list(&cleaned) {
+            let extra = parse_extra_columns(&header[pid_col..]);
+            let (processes, _) = parse_processes(ps, pid_col, &extra);
+            let task_unit = parse_task_unit(&cleaned).unwrap_or(PAGE_SIZE);
+            let _ = top_consumers(&processes, task_unit);
+        }
+    }
+});
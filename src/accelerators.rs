@@ -0,0 +1,121 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// System RAM that a GPU or accelerator driver said it had pinned or mapped for the device. The
+// driver holds these pages for DMA, so they belong to no process and don't show up in the RSS
+// of the process list. Sizes are in KiB.
+#[derive(Serialize, JsonSchema)]
+pub struct AcceleratorMemory {
+    pub driver: &'static str,
+    pub gtt_kib: Option<f64>,
+    pub pinned_kib: Option<f64>,
+    pub last: String,
+}
+
+// Kernel message prefixes of the drivers, and the name they're reported under
+const ACCELERATOR_DRIVERS: [(&str, &str); 5] = [
+    ("amdgpu", "amdgpu"),
+    ("NVRM", "nvidia"),
+    ("nvidia-uvm", "nvidia"),
+    ("habanalabs", "habanalabs"),
+    ("i915", "i915"),
+];
+
+// A size in a driver message, converted to KiB
+fn size_kib(number: &str, unit: &str) -> Option<f64> {
+    let number = number.parse::<f64>().ok()?;
+    let scale = match unit {
+        "kB" | "KB" | "KiB" => 1.0,
+        "M" | "MB" | "MiB" => 1024.0,
+        "G" | "GB" | "GiB" => 1024.0 * 1024.0,
+        _ => return None,
+    };
+
+    Some(number * scale)
+}
+
+// Find what GPU and accelerator drivers logged about system memory before the last oom-killer
+// report: the size of amdgpu's GTT, the system memory the GPU may map, and any amount the
+// driver said it pinned, ie:
+//
+//      [drm] amdgpu: 16384M of GTT memory ready.
+//      habanalabs hl0: pinned 8192 MB of host memory for the device
+//
+// The last amount of each kind a driver logged is kept.
+pub fn parse_accelerator_memory(log: &str) -> Vec<AcceleratorMemory> {
+    const GTT_RE: &str = r"(\d+)(M) of GTT memory ready";
+    const PINNED_RE: &str = r"(?i)\bpinn(?:ed|ing)\b\D*?(\d+)\s*(kB|KiB|MB|MiB|GB|GiB)\b|(\d+)\s*(kB|KiB|MB|MiB|GB|GiB)\b[^.;]*?\bpinned\b";
+
+    let end = log.rfind("invoked oom-killer").unwrap_or(log.len());
+    let gtt_re = Regex::new(GTT_RE).unwrap();
+    let pinned_re = Regex::new(PINNED_RE).unwrap();
+
+    let mut found: Vec<AcceleratorMemory> = Vec::new();
+    for line in log[..end].lines() {
+        let driver = match ACCELERATOR_DRIVERS
+            .iter()
+            .find(|(prefix, _)| line.contains(prefix))
+        {
+            Some((_, driver)) => *driver,
+            None => continue,
+        };
+
+        let gtt_kib = gtt_re
+            .captures(line)
+            .and_then(|caps| size_kib(&caps[1], &caps[2]));
+        let pinned_kib = pinned_re.captures(line).and_then(|caps| {
+            let number = caps.get(1).or_else(|| caps.get(3))?;
+            let unit = caps.get(2).or_else(|| caps.get(4))?;
+            size_kib(number.as_str(), unit.as_str())
+        });
+        if gtt_kib.is_none() && pinned_kib.is_none() {
+            continue;
+        }
+
+        let i = match found.iter().position(|a| a.driver == driver) {
+            Some(i) => i,
+            None => {
+                found.push(AcceleratorMemory {
+                    driver,
+                    gtt_kib: None,
+                    pinned_kib: None,
+                    last: String::new(),
+                });
+                found.len() - 1
+            }
+        };
+        let accelerator = &mut found[i];
+        accelerator.gtt_kib = gtt_kib.or(accelerator.gtt_kib);
+        accelerator.pinned_kib = pinned_kib.or(accelerator.pinned_kib);
+        accelerator.last = line.trim().to_string();
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_accelerator_memory() {
+        let log = format!(
+            "{}{}",
+            "kernel: [    4.120000] [drm] amdgpu: 16384M of GTT memory ready.\n\
+             kernel: [    4.130000] [drm] amdgpu: 8192M of VRAM memory ready\n\
+             kernel: [ 9000.000000] habanalabs hl0: pinned 8192 MB of host memory\n\
+             kernel: [ 9100.000000] habanalabs hl0: 12 GB of host memory pinned for DMA\n",
+            include_str!("../messages")
+        );
+
+        let found = parse_accelerator_memory(&log);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].driver, "amdgpu");
+        assert_eq!(found[0].gtt_kib, Some(16384.0 * 1024.0));
+        assert_eq!(found[0].pinned_kib, None);
+        assert_eq!(found[1].pinned_kib, Some(12.0 * 1024.0 * 1024.0));
+
+        assert!(parse_accelerator_memory(include_str!("../messages")).is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::rollup::parse_timestamp;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// A process killed by Android's low memory killer rather than the kernel's oom-killer: the
+// lowmemorykiller driver of kernels before v4.12, or lmkd, the userspace daemon that replaced
+// it. Memory is in KiB.
+#[derive(Serialize, JsonSchema)]
+pub struct LmkKill {
+    pub killer: &'static str,
+    pub timestamp: Option<String>,
+    pub process: String,
+    pub pid: u32,
+    pub uid: Option<u32>,
+    pub adj: i32,
+    pub freed_kib: f64,
+    pub reason: Option<String>,
+}
+
+// The importance the activity manager gives an app at each oom_score_adj, from most to least
+// important. lmkd kills from the bottom of the list up, so kills above "cached" mean the device
+// was short of memory for what the user was doing.
+const ADJ_CATEGORIES: [(i32, &str); 11] = [
+    (-1000, "native"),
+    (-900, "system"),
+    (-800, "persistent"),
+    (0, "foreground"),
+    (100, "visible"),
+    (200, "perceptible"),
+    (300, "backup"),
+    (400, "heavy weight"),
+    (500, "service"),
+    (600, "home"),
+    (900, "cached"),
+];
+
+// The activity manager's name for an oom_score_adj, ie. 905 is "cached"
+pub fn adj_category(adj: i32) -> &'static str {
+    ADJ_CATEGORIES
+        .iter()
+        .rev()
+        .find(|(min, _)| adj >= *min)
+        .map_or("native", |(_, name)| name)
+}
+
+// Find the low memory killer's kills in a kernel log or logcat output, in the order they were
+// logged. The kernel driver logs over several lines, lmkd on one:
+//
+//      lowmemorykiller: Killing 'com.android.chrome' (4312), adj 904,
+//         to free 93412kB on behalf of 'kswapd0' (86) because
+//         cache 180220kB is below limit 184320kB for oom_score_adj 900
+//      05-12 10:34:56.789  512  512 I lowmemorykiller: Kill 'com.example.app' (12345), uid 10123,
+//          oom_score_adj 905 to free 102400kB rss, 20480kB swap; reason: low watermark is
+//          breached
+//
+// Kernels before v4.4 logged the size of the victim in pages instead:
+//
+//      lowmemorykiller: send sigkill to 4312 (com.android.chrome), adj 904, size 23353
+pub fn parse_lmk_kills(log: &str) -> Vec<LmkKill> {
+    const LMKD_RE: &str = r"lowmemorykiller: Kill(?:ing)? '([^']+)' \((\d+)\), uid (\d+), (?:oom_score_adj|oom_adj|adj) (-?\d+),? to free (\d+)kB(?:.*?; reason: (.*))?";
+    const DRIVER_RE: &str = r"lowmemorykiller: Killing '([^']+)' \((\d+)\), adj (-?\d+),";
+    const DRIVER_FREE_RE: &str = r"to free (\d+)kB";
+    const DRIVER_REASON_RE: &str =
+        r"because\s+(cache \d+kB is below limit \d+kB for oom_score_adj -?\d+)";
+    const SIGKILL_RE: &str =
+        r"lowmemorykiller: send sigkill to (\d+) \(([^)]+)\), adj (-?\d+), size (\d+)";
+    const LOGCAT_TIME_RE: &str = r"^(\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d{3})";
+
+    let lmkd_re = Regex::new(LMKD_RE).unwrap();
+    let driver_re = Regex::new(DRIVER_RE).unwrap();
+    let driver_free_re = Regex::new(DRIVER_FREE_RE).unwrap();
+    let driver_reason_re = Regex::new(DRIVER_REASON_RE).unwrap();
+    let sigkill_re = Regex::new(SIGKILL_RE).unwrap();
+    let logcat_time_re = Regex::new(LOGCAT_TIME_RE).unwrap();
+
+    let lines = log.lines().collect::<Vec<_>>();
+    let mut kills = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if !line.contains("lowmemorykiller: ") {
+            continue;
+        }
+        let timestamp = parse_timestamp(line).or_else(|| {
+            let caps = logcat_time_re.captures(line)?;
+            Some(caps[1].to_string())
+        });
+
+        let kill = if let Some(caps) = lmkd_re.captures(line) {
+            LmkKill {
+                killer: "lmkd",
+                timestamp,
+                process: caps[1].to_string(),
+                pid: caps[2].parse().unwrap_or(0),
+                uid: caps[3].parse().ok(),
+                adj: caps[4].parse().unwrap_or(0),
+                freed_kib: caps[5].parse::<f64>().unwrap_or(0.0),
+                reason: caps.get(6).map(|m| m.as_str().trim().to_string()),
+            }
+        } else if let Some(caps) = driver_re.captures(line) {
+            // The rest of the message follows on the next lines
+            let message = lines[i..lines.len().min(i + 4)].join(" ");
+            let free = driver_free_re.captures(&message);
+            let reason = driver_reason_re.captures(&message);
+            LmkKill {
+                killer: "lowmemorykiller",
+                timestamp,
+                process: caps[1].to_string(),
+                pid: caps[2].parse().unwrap_or(0),
+                uid: None,
+                adj: caps[3].parse().unwrap_or(0),
+                freed_kib: free.and_then(|c| c[1].parse::<f64>().ok()).unwrap_or(0.0),
+                reason: reason.map(|c| c[1].to_string()),
+            }
+        } else if let Some(caps) = sigkill_re.captures(line) {
+            LmkKill {
+                killer: "lowmemorykiller",
+                timestamp,
+                process: caps[2].to_string(),
+                pid: caps[1].parse().unwrap_or(0),
+                uid: None,
+                adj: caps[3].parse().unwrap_or(0),
+                freed_kib: caps[4].parse::<f64>().unwrap_or(0.0) * 4.0,
+                reason: None,
+            }
+        } else {
+            continue;
+        };
+
+        kills.push(kill);
+    }
+
+    kills
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lmk_kills() {
+        let log = "\
+[ 5123.456789] lowmemorykiller: Killing 'com.android.chrome' (4312), adj 904,
+   to free 93412kB on behalf of 'kswapd0' (86) because
+   cache 180220kB is below limit 184320kB for oom_score_adj 900
+   Free memory is 3112kB above reserved
+[ 5130.000000] lowmemorykiller: send sigkill to 4400 (com.whatsapp), adj 200, size 25600
+05-12 10:34:56.789   512   512 I lowmemorykiller: Kill 'com.example.app' (12345), uid 10123, \
+oom_score_adj 905 to free 102400kB rss, 20480kB swap; reason: low watermark is breached
+";
+        let kills = parse_lmk_kills(log);
+        assert_eq!(kills.len(), 3);
+
+        assert_eq!(kills[0].killer, "lowmemorykiller");
+        assert_eq!(kills[0].process, "com.android.chrome");
+        assert_eq!(kills[0].adj, 904);
+        assert_eq!(kills[0].freed_kib, 93412.0);
+        assert_eq!(
+            kills[0].reason.as_deref(),
+            Some("cache 180220kB is below limit 184320kB for oom_score_adj 900")
+        );
+
+        assert_eq!(kills[1].pid, 4400);
+        assert_eq!(kills[1].freed_kib, 25600.0 * 4.0);
+        assert_eq!(adj_category(kills[1].adj), "perceptible");
+
+        assert_eq!(kills[2].killer, "lmkd");
+        assert_eq!(kills[2].uid, Some(10123));
+        assert_eq!(kills[2].timestamp.as_deref(), Some("05-12 10:34:56.789"));
+        assert_eq!(
+            kills[2].reason.as_deref(),
+            Some("low watermark is breached")
+        );
+        assert_eq!(adj_category(kills[2].adj), "cached");
+        assert_eq!(adj_category(-1000), "native");
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{OomEvent, Warning};
+
+// The most RAM a 64-bit CPU can address, 4 PiB with 52-bit physical addresses, in KiB. A report
+// with more was damaged on its way into the log.
+const MAX_RAM_KIB: f64 = (1u64 << 42) as f64;
+
+// How many times RAM and swap the RSS of every process may add up to. Pages shared between
+// processes are counted in each one's RSS, so the sum can be well over RAM on a host of forked
+// database workers, but not by this much.
+const RSS_SUM_FACTOR: f64 = 10.0;
+
+impl OomEvent {
+    // Check that the values of the report add up. Kernel messages written under memory pressure
+    // get interleaved and truncated, and a digit lost or gained makes for numbers that can't be
+    // right. Each impossible value is a problem, so the results aren't trusted without a look at
+    // the log.
+    pub(crate) fn note_inconsistencies(&mut self) {
+        let mut implausible = Vec::new();
+        let ram = self.total_ram_kib;
+
+        // A memory cgroup's report states no RAM at all
+        if ram <= 0.0 && self.memcg.is_none() {
+            implausible.push("RAM is no more than the pages reserved".to_string());
+        } else if ram > MAX_RAM_KIB {
+            implausible.push(format!(
+                "{:.0} GiB of RAM is more than a 64-bit CPU can address",
+                ram / 1024.0 / 1024.0
+            ));
+        }
+        if self.free_swap_kib > self.total_swap_kib {
+            implausible.push("free swap is more than total swap".to_string());
+        }
+        if ram > 0.0 {
+            let parts = [
+                ("free memory", self.free_kib.unwrap_or(0.0)),
+                (
+                    "slab",
+                    self.unreclaimable_slab_kib + self.reclaimable_slab_kib,
+                ),
+                ("shared memory", self.shmem_kib),
+                ("huge pages", self.hugepages_kib()),
+            ];
+            for (name, kib) in parts {
+                if kib > ram {
+                    implausible.push(format!("{} is more than RAM", name));
+                }
+            }
+        }
+
+        let negative = self
+            .processes
+            .iter()
+            .filter(|p| p.total_vm < 0 || p.rss < 0 || p.pgtables < 0 || p.swapents < 0)
+            .count();
+        if negative > 0 {
+            implausible.push(format!("{} processes have negative sizes", negative));
+        }
+        let unit = self.task_unit;
+        let overflowing = self
+            .processes
+            .iter()
+            .filter(|p| {
+                [p.total_vm, p.rss, p.swapents]
+                    .iter()
+                    .any(|size| size.checked_mul(unit).is_none())
+            })
+            .count();
+        if overflowing > 0 {
+            implausible.push(format!(
+                "{} processes have sizes too large to count in bytes",
+                overflowing
+            ));
+        }
+        let rss_kib = |rss: i64| rss as f64 * unit as f64 / 1024.0;
+        if let Some(largest) = self.processes.iter().max_by_key(|p| p.rss) {
+            if ram > 0.0 && rss_kib(largest.rss) > ram {
+                implausible.push(format!(
+                    "the RSS of {} (pid {}) is more than RAM",
+                    largest.name, largest.pid
+                ));
+            }
+        }
+        let rss_sum = self.processes.iter().map(|p| rss_kib(p.rss)).sum::<f64>();
+        if ram > 0.0 && rss_sum > RSS_SUM_FACTOR * (ram + self.total_swap_kib) {
+            implausible.push(format!(
+                "processes' RSS adds up to {:.0} times RAM and swap",
+                rss_sum / (ram + self.total_swap_kib)
+            ));
+        }
+
+        for message in implausible {
+            self.warnings.push(Warning::problem(
+                "consistency",
+                format!(
+                    "{}: values look implausible, the log may be corrupted",
+                    message
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_inconsistencies() {
+        let log = include_str!("../messages");
+        let messages = |event: &OomEvent| {
+            event
+                .warnings
+                .iter()
+                .filter(|w| w.section == "consistency")
+                .map(|w| w.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(messages(&OomEvent::parse(log).unwrap()).is_empty());
+
+        // A lost line break runs two numbers together
+        let damaged = log.replace("5241540 pages RAM", "5241544212132178 pages RAM");
+        let found = messages(&OomEvent::parse(&damaged).unwrap());
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert!(found[0].starts_with("19994904374 GiB of RAM is more than a 64-bit CPU"));
+        assert!(found[0].ends_with(": values look implausible, the log may be corrupted"));
+
+        let damaged = log
+            .replace("  422324  ", "  42232400000  ")
+            .replace("  787   ", "  -787   ");
+        let event = OomEvent::parse(&damaged).unwrap();
+        let found = messages(&event);
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found[0].starts_with("1 processes have negative sizes: "));
+        assert!(found[1].starts_with("the RSS of clamd (pid 517) is more than RAM: "));
+        assert!(found[2].starts_with("processes' RSS adds up to "));
+        assert!(event.warnings.iter().any(|w| w.problem));
+
+        // Sizes that overflow when counted in bytes are reported rather than crashing
+        let damaged = log.replace("  422324  ", "  9000000000000000  ");
+        let found = messages(&OomEvent::parse(&damaged).unwrap());
+        assert!(found[0].starts_with("1 processes have sizes too large to count in bytes: "));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::sections::registry;
+use crate::{last_report, report_start_line};
+use regex::Regex;
+use std::error::Error;
+
+// Lines of a report that belong to no section: the header naming the allocation and the task
+// that made it, and the stack trace and registers after it
+const HEADER_CLAIMS: [&str; 11] = [
+    r"invoked oom-killer:",
+    r"^CPU: \d+ (?:UID: \d+ )?PID: \d+ Comm:",
+    r"^Hardware name:",
+    r"^(?:Call Trace|Call trace|Workqueue):",
+    r"^\s*(?:\? )?[\w.$]+\+0x[0-9a-f]+/0x[0-9a-f]+",
+    // Stack frames and words as older and 32-bit kernels log them
+    r"^\s*\[<[0-9a-f]+>\]",
+    r"^\s*(?:[0-9a-f]{8,16}\s*)+$",
+    r"^\s*</?(?:TASK|IRQ)>",
+    r"^(?:RIP|RSP|RAX|RDX|RBP|R\d+|Code):",
+    r"\bcpuset=\S* mems_allowed=",
+    r"^mems_allowed=",
+];
+
+// A line of the last report that no section parser claims, with its line number in the log,
+// counting from 1, and its text without the log's prefix
+pub struct UnclaimedLine {
+    pub line: usize,
+    pub text: String,
+}
+
+// Unclaimed lines that look alike, their numbers taken out, ie. every "Node N Normal per-cpu:"
+// line. Groups are in the order their first line appears.
+pub struct UnclaimedGroup {
+    pub shape: String,
+    pub lines: Vec<UnclaimedLine>,
+}
+
+// The text of a log line after its syslog or dmesg prefix
+fn message(line: &str) -> &str {
+    const PREFIX_RE: &str = r"^(?:.*?\bkernel:\s?)?(?:\[\s*\d+\.\d+\]\s?)?";
+
+    let prefix = Regex::new(PREFIX_RE).unwrap();
+    let end = prefix.find(line).map_or(0, |m| m.end());
+    &line[end..]
+}
+
+// What lines of the same kind have in common: the text up to the first colon or equals sign,
+// or the first three words, with every number written as N
+fn shape(text: &str) -> String {
+    const NUMBER_RE: &str = r"0x[0-9a-fA-F]+|\b[0-9a-f]*\d[0-9a-f]*\b|\d+";
+
+    let text = text.trim();
+    let head = match text.find([':', '=']) {
+        Some(i) if i < 40 => &text[..=i],
+        _ => {
+            let end = text
+                .match_indices(' ')
+                .nth(2)
+                .map_or(text.len(), |(i, _)| i);
+            &text[..end]
+        }
+    };
+
+    Regex::new(NUMBER_RE)
+        .unwrap()
+        .replace_all(head, "N")
+        .into_owned()
+}
+
+// Every line of the last report in the log that neither a section parser nor the header
+// claims, grouped by shape
+pub fn unclaimed_lines(log: &str) -> Result<Vec<UnclaimedGroup>, Box<dyn Error>> {
+    let oom = last_report(log)?;
+    let first = report_start_line(log);
+
+    let sections = registry();
+    let claims = sections
+        .iter()
+        .flat_map(|section| section.claims().iter())
+        .chain(HEADER_CLAIMS.iter())
+        .map(|pattern| Regex::new(pattern).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut groups: Vec<UnclaimedGroup> = Vec::new();
+    for (i, line) in oom.lines().enumerate() {
+        let text = message(line);
+        if text.trim().is_empty() || claims.iter().any(|re| re.is_match(text)) {
+            continue;
+        }
+
+        let unclaimed = UnclaimedLine {
+            line: first + i,
+            text: text.to_string(),
+        };
+        let shape = shape(text);
+        match groups.iter_mut().find(|g| g.shape == shape) {
+            Some(group) => group.lines.push(unclaimed),
+            None => groups.push(UnclaimedGroup {
+                shape,
+                lines: vec![unclaimed],
+            }),
+        }
+    }
+
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_unclaimed_lines() {
+        let log = include_str!("../messages");
+        assert!(unclaimed_lines(log).unwrap().is_empty());
+
+        // A line from a newer kernel, logged once per node
+        let line = log
+            .lines()
+            .find(|l| l.contains("hugepages_size=2048kB"))
+            .unwrap();
+        let new = format!(
+            "{}\nDec 20 03:17:52 localhost kernel: [75669.633200] Node 0 memtier=2 demoted:0kB\n\
+             Dec 20 03:17:52 localhost kernel: [75669.633201] Node 1 memtier=3 demoted:4kB",
+            line
+        );
+        let log = log.replace(line, &new);
+        let groups = unclaimed_lines(&log).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].shape, "Node N memtier=");
+        assert_eq!(groups[0].lines.len(), 2);
+        assert_eq!(groups[0].lines[0].line, 48);
+        assert_eq!(groups[0].lines[1].text, "Node 1 memtier=3 demoted:4kB");
+    }
+}
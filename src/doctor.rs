@@ -0,0 +1,240 @@
+use crate::rollup::split_events;
+use crate::sections::registry;
+use crate::{
+    clean_report, last_report, parse_gfp_zone, parse_hypervisor, parse_meminfo_cma,
+    parse_meminfo_hugepages, parse_meminfo_isolated, parse_meminfo_reclaimable_slab,
+    parse_meminfo_shared, parse_meminfo_slab, parse_meminfo_swap, parse_meminfo_total,
+    parse_meminfo_total_swap, parse_ps_header, parse_ps_list, parse_task_unit, parse_thp,
+    parse_unreclaimable_slab, parse_victim_pid, parse_zones, OomEvent,
+};
+use regex::Regex;
+use serde::Serialize;
+
+// How the lines of a log are prefixed, which tells us what wrote it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    // /var/log/messages or syslog: "Dec 20 03:17:52 host kernel: ..."
+    Syslog,
+    // journalctl -o short-iso: "2023-12-20T03:17:52+0000 host kernel: ..."
+    Journal,
+    // dmesg or the console: "[75669.585004] ..."
+    Dmesg,
+    Unknown,
+}
+
+impl LogFormat {
+    // Detect the format from the line that invoked the oom-killer
+    pub fn detect(line: &str) -> LogFormat {
+        const FORMATS: [(&str, LogFormat); 3] = [
+            (
+                r"^[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2} ",
+                LogFormat::Syslog,
+            ),
+            (r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}", LogFormat::Journal),
+            (r"^\[\s*\d+\.\d+\]", LogFormat::Dmesg),
+        ];
+
+        FORMATS
+            .iter()
+            .find(|(re, _)| Regex::new(re).unwrap().is_match(line))
+            .map_or(LogFormat::Unknown, |(_, format)| *format)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogFormat::Syslog => "syslog",
+            LogFormat::Journal => "journal",
+            LogFormat::Dmesg => "dmesg",
+            LogFormat::Unknown => "unknown",
+        }
+    }
+}
+
+// Whether one part of the report was found. Required parts stop the report from being parsed
+// when they're missing; the rest only leave a section of the output empty.
+#[derive(Serialize)]
+pub struct Check {
+    pub section: &'static str,
+    pub name: &'static str,
+    pub required: bool,
+    pub found: bool,
+}
+
+// What parseoom could and couldn't find in a log, so that a user can tell us exactly which part
+// of their report was missed
+#[derive(Serialize)]
+pub struct Diagnosis {
+    pub format: LogFormat,
+    pub reports: usize,
+    pub memcg: bool,
+    pub sections: Vec<(&'static str, bool)>,
+    pub checks: Vec<Check>,
+    pub error: Option<String>,
+}
+
+impl Diagnosis {
+    pub fn new(input: &str) -> Diagnosis {
+        let start = input
+            .rfind("invoked oom-killer")
+            .map_or(0, |i| input[..i].rfind('\n').map_or(0, |n| n + 1));
+        let format = LogFormat::detect(&input[start..]);
+        let reports = split_events(input).len();
+
+        let oom = match last_report(input) {
+            Ok(oom) => oom,
+            Err(e) => {
+                return Diagnosis {
+                    format,
+                    reports,
+                    memcg: false,
+                    sections: Vec::new(),
+                    checks: Vec::new(),
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        let cleaned = clean_report(oom);
+        let s = cleaned.as_str();
+        let checks = [
+            (
+                "meminfo",
+                "total pages RAM",
+                true,
+                parse_meminfo_total(s).is_some(),
+            ),
+            (
+                "meminfo",
+                "free swap",
+                true,
+                parse_meminfo_swap(s).is_some(),
+            ),
+            (
+                "meminfo",
+                "total swap",
+                false,
+                parse_meminfo_total_swap(s).is_some(),
+            ),
+            (
+                "meminfo",
+                "huge pages",
+                true,
+                parse_meminfo_hugepages(s).is_some(),
+            ),
+            ("meminfo", "shmem", true, parse_meminfo_shared(s).is_some()),
+            (
+                "meminfo",
+                "isolated pages",
+                false,
+                parse_meminfo_isolated(s).is_some(),
+            ),
+            ("meminfo", "free CMA", false, parse_meminfo_cma(s).is_some()),
+            ("meminfo", "zones", false, !parse_zones(s).is_empty()),
+            (
+                "meminfo",
+                "gfp_mask zone",
+                false,
+                parse_gfp_zone(s).is_some(),
+            ),
+            (
+                "meminfo",
+                "hardware name",
+                false,
+                parse_hypervisor(s).is_some(),
+            ),
+            (
+                "thp",
+                "transparent huge pages",
+                false,
+                parse_thp(s).is_some(),
+            ),
+            (
+                "slab",
+                "unreclaimable slab",
+                true,
+                parse_meminfo_slab(s).is_some(),
+            ),
+            (
+                "slab",
+                "reclaimable slab",
+                true,
+                parse_meminfo_reclaimable_slab(s).is_some(),
+            ),
+            (
+                "slab",
+                "slab table",
+                false,
+                parse_unreclaimable_slab(s).is_some(),
+            ),
+            ("processes", "header", true, parse_ps_header(s).is_some()),
+            ("processes", "list", true, parse_ps_list(s).is_some()),
+            (
+                "processes",
+                "memory unit",
+                false,
+                parse_task_unit(s).is_some(),
+            ),
+            (
+                "victim",
+                "oom-kill summary",
+                false,
+                parse_victim_pid(oom).is_some(),
+            ),
+        ]
+        .iter()
+        .map(|&(section, name, required, found)| Check {
+            section,
+            name,
+            required,
+            found,
+        })
+        .collect();
+
+        // Which of the section parsers would run on this report
+        let sections = registry()
+            .iter()
+            .map(|p| (p.name(), Regex::new(p.trigger()).unwrap().is_match(oom)))
+            .collect();
+
+        Diagnosis {
+            format,
+            reports,
+            memcg: oom.contains("Memory cgroup out of memory"),
+            sections,
+            checks,
+            error: OomEvent::from_report(oom).err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_doctor() {
+        let log = include_str!("../messages");
+        let diagnosis = Diagnosis::new(log);
+        assert_eq!(diagnosis.format, LogFormat::Syslog);
+        assert_eq!(diagnosis.reports, 1);
+        assert!(diagnosis.sections.contains(&("meminfo", true)));
+        assert!(diagnosis.sections.contains(&("memcg", false)));
+        assert!(diagnosis.error.is_none());
+        let missing = diagnosis
+            .checks
+            .iter()
+            .filter(|c| !c.found)
+            .map(|c| c.name)
+            .collect::<Vec<_>>();
+        assert_eq!(missing, vec!["gfp_mask zone", "slab table"]);
+
+        let diagnosis = Diagnosis::new(&log.replace("shmem:", "shmem="));
+        assert!(diagnosis.error.is_some());
+        let shmem = diagnosis.checks.iter().find(|c| c.name == "shmem").unwrap();
+        assert!(!shmem.found);
+
+        assert_eq!(LogFormat::detect("[   12.000000] x"), LogFormat::Dmesg);
+        assert!(Diagnosis::new("nothing here").checks.is_empty());
+    }
+}
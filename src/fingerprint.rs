@@ -0,0 +1,131 @@
+use crate::thresholds::{Status, Thresholds};
+use crate::{top_consumers, OomEvent};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// What kind of failure an oom-kill was, in terms that don't change from host to host: which
+// command was killed, what the allocation was constrained by, which command held the most
+// memory and which categories of memory use were judged the likely cause. id is a hash of the
+// rest, for fleet tooling to group events by; it only changes when one of them does.
+#[derive(Default, Serialize, JsonSchema)]
+pub struct Fingerprint {
+    pub id: String,
+    pub victim: Option<String>,
+    pub constraint: String,
+    pub dominant_consumer: Option<String>,
+    pub verdict: Vec<String>,
+}
+
+// FNV-1a, as the hash must give the same id on every build and platform, which std's hasher
+// doesn't promise
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    data.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+// Return the constraint of the oom-kill, ie. "none" from:
+//
+//      oom-kill:constraint=CONSTRAINT_NONE,nodemask=(null),cpuset=/,mems_allowed=0,...
+//
+// Kernels before v4.19 don't log it, so it's "memcg" for a cgroup oom-kill and "none" otherwise.
+pub fn parse_constraint(oom: &str) -> String {
+    const CONSTRAINT_RE: &str = r"oom-kill:constraint=CONSTRAINT_(\w+)";
+
+    match Regex::new(CONSTRAINT_RE).unwrap().captures(oom) {
+        Some(caps) => caps[1].to_lowercase(),
+        None if oom.contains("Memory cgroup out of memory") => "memcg".to_string(),
+        None => "none".to_string(),
+    }
+}
+
+// Return the command of the killed task from the line naming it, ie. "java" from:
+//
+//      Out of memory: Kill process 23111 (java) score 873 or sacrifice child
+//
+// for when the process list doesn't have it.
+fn parse_killed_command(oom: &str, pid: &str) -> Option<String> {
+    let re = Regex::new(&format!(r"Kill(?:ed)? process {} \(([^)]*)\)", pid)).unwrap();
+
+    Some(re.captures(oom)?.get(1)?.as_str().to_string())
+}
+
+impl Fingerprint {
+    // The fingerprint of a parsed report, given the text of the report it was parsed from
+    pub fn new(event: &OomEvent, oom: &str) -> Fingerprint {
+        let victim = event.victim_pid.as_ref().and_then(|pid| {
+            match event.processes.iter().find(|p| p.pid.to_string() == *pid) {
+                Some(process) => Some(process.name.clone()),
+                None => parse_killed_command(oom, pid),
+            }
+        });
+
+        let consumers = top_consumers(&event.processes, event.task_unit);
+        let dominant_consumer = consumers
+            .iter()
+            .max_by_key(|(_, rss)| **rss)
+            .map(|(command, _)| command.clone());
+
+        let rss_total = consumers
+            .values()
+            .fold(0, |sum: i64, rss| sum.saturating_add(*rss));
+        let verdict = Thresholds::default()
+            .evaluate(event, rss_total)
+            .iter()
+            .filter(|s| s.status == Status::Red)
+            .map(|s| s.category.to_string())
+            .collect::<Vec<_>>();
+
+        let mut fingerprint = Fingerprint {
+            id: String::new(),
+            victim,
+            constraint: parse_constraint(oom),
+            dominant_consumer,
+            verdict,
+        };
+        let key = format!(
+            "{}\n{}\n{}\n{}",
+            fingerprint.victim.as_deref().unwrap_or(""),
+            fingerprint.constraint,
+            fingerprint.dominant_consumer.as_deref().unwrap_or(""),
+            fingerprint.verdict.join(",")
+        );
+        fingerprint.id = format!("{:016x}", fnv1a(key.as_bytes()));
+
+        fingerprint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_fingerprint() {
+        let messages = include_str!("../messages");
+        let event = OomEvent::parse(messages).unwrap();
+        let fingerprint = &event.fingerprint;
+        assert_eq!(fingerprint.victim.as_deref(), Some("clamd"));
+        assert_eq!(fingerprint.constraint, "none");
+        assert_eq!(fingerprint.dominant_consumer.as_deref(), Some("clamd"));
+        assert_eq!(fingerprint.verdict, vec!["swap"]);
+        assert_eq!(fingerprint.id.len(), 16);
+
+        // The same failure on another host at another time
+        let log = messages
+            .replace("localhost", "mail07")
+            .replace("Dec 20 03:17:52", "Jan  3 14:40:09")
+            .replace("75669.", "10231.");
+        let other = OomEvent::parse(&log).unwrap();
+        assert_eq!(other.fingerprint.id, fingerprint.id);
+
+        let log = messages.replace("CONSTRAINT_NONE", "CONSTRAINT_CPUSET");
+        let other = OomEvent::parse(&log).unwrap();
+        assert_eq!(other.fingerprint.constraint, "cpuset");
+        assert_ne!(other.fingerprint.id, fingerprint.id);
+    }
+}
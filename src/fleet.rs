@@ -0,0 +1,265 @@
+use crate::rollup::{dedup_events, sorted_counts, split_events, EventSummary, Rollup};
+use crate::timestamps::{json_time, parse_log_times, Zone};
+use crate::OomEvent;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// What the logs of one host of a fleet show: how many oom-killer invocations and kills there
+// were and when, and for the last invocation, the command killed, the categories of memory use
+// judged the likely cause and the fingerprint id. first and last are the times as logged,
+// first_time and last_time the same in UTC so hosts in different zones compare. error is why
+// the host's logs or last report couldn't be read.
+#[derive(Default, Serialize)]
+pub struct HostSummary {
+    pub host: String,
+    pub events: usize,
+    pub kills: usize,
+    pub first: Option<String>,
+    pub last: Option<String>,
+    pub first_time: Option<String>,
+    pub last_time: Option<String>,
+    pub last_victim: Option<String>,
+    pub verdict: Vec<String>,
+    pub fingerprint: Option<String>,
+    pub kills_by_command: Vec<(String, usize)>,
+    pub error: Option<String>,
+}
+
+impl HostSummary {
+    // Summarize a host's log. Syslog dates are counted from `year`, as Rollup::new() does, and
+    // times without a zone are taken to be in `log_zone`.
+    pub fn new(host: &str, log: &str, year: i32, log_zone: Zone) -> HostSummary {
+        let (chunks, _) = dedup_events(split_events(log));
+        let summaries = chunks
+            .iter()
+            .map(|chunk| EventSummary::new(chunk))
+            .collect::<Vec<_>>();
+        let rollup = Rollup::new(&summaries, year);
+        let timestamps = summaries
+            .iter()
+            .map(|s| s.timestamp.clone())
+            .collect::<Vec<_>>();
+        let utc = parse_log_times(&timestamps, year)
+            .into_iter()
+            .filter_map(|time| time?.to_utc(log_zone))
+            .collect::<Vec<_>>();
+
+        let mut summary = HostSummary {
+            host: host.to_string(),
+            events: rollup.events,
+            kills: rollup.kills,
+            first: rollup.first,
+            last: rollup.last,
+            first_time: utc.first().copied().map(json_time),
+            last_time: utc.last().copied().map(json_time),
+            kills_by_command: rollup.kills_by_command,
+            ..HostSummary::default()
+        };
+
+        if let Some(chunk) = chunks.last() {
+            match OomEvent::parse(chunk) {
+                Ok(event) => {
+                    summary.last_victim = event.fingerprint.victim.clone();
+                    summary.verdict = event.fingerprint.verdict.clone();
+                    summary.fingerprint = Some(event.fingerprint.id.clone());
+                }
+                Err(e) => summary.error = Some(e.to_string()),
+            }
+        }
+
+        summary
+    }
+
+    // A host whose logs couldn't be read
+    pub fn failed(host: &str, error: String) -> HostSummary {
+        HostSummary {
+            host: host.to_string(),
+            error: Some(error),
+            ..HostSummary::default()
+        }
+    }
+}
+
+// How often the oom-killer killed a command across a fleet, and on how many hosts
+#[derive(Serialize)]
+pub struct CommandKills {
+    pub command: String,
+    pub kills: usize,
+    pub hosts: usize,
+}
+
+// Totals across the hosts of a fleet: the hosts ranked by their number of oom-killer
+// invocations, the commands killed most often, and the hosts whose last invocation was judged
+// to be caused by each category of memory use, such as hugepages or slab.
+#[derive(Serialize)]
+pub struct FleetStats {
+    pub hosts: usize,
+    pub affected_hosts: usize,
+    pub events: usize,
+    pub kills: usize,
+    pub events_by_host: Vec<(String, usize)>,
+    pub kills_by_command: Vec<CommandKills>,
+    pub hosts_by_verdict: BTreeMap<String, Vec<String>>,
+}
+
+impl FleetStats {
+    pub fn new(summaries: &[HostSummary]) -> FleetStats {
+        let affected = summaries.iter().filter(|s| s.events > 0);
+
+        let mut kills: BTreeMap<&str, (usize, BTreeSet<&str>)> = BTreeMap::new();
+        let mut hosts_by_verdict: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for summary in summaries {
+            for (command, n) in &summary.kills_by_command {
+                let entry = kills.entry(command).or_default();
+                entry.0 += n;
+                entry.1.insert(&summary.host);
+            }
+            for category in &summary.verdict {
+                let hosts = hosts_by_verdict.entry(category.clone()).or_default();
+                hosts.push(summary.host.clone());
+            }
+        }
+
+        let mut kills_by_command = kills
+            .into_iter()
+            .map(|(command, (kills, hosts))| CommandKills {
+                command: command.to_string(),
+                kills,
+                hosts: hosts.len(),
+            })
+            .collect::<Vec<_>>();
+        kills_by_command.sort_by_key(|c| std::cmp::Reverse((c.kills, c.hosts)));
+
+        FleetStats {
+            hosts: summaries.len(),
+            affected_hosts: affected.clone().count(),
+            events: summaries.iter().map(|s| s.events).sum(),
+            kills: summaries.iter().map(|s| s.kills).sum(),
+            events_by_host: sorted_counts(affected.map(|s| (s.host.clone(), s.events)).collect()),
+            kills_by_command,
+            hosts_by_verdict,
+        }
+    }
+}
+
+// The hosts in a directory of per-host logs, such as a central syslog server's:
+//
+//      logs/db01/messages
+//      logs/db01/messages-20240301.gz
+//      logs/web07/messages
+//
+// Each subdirectory is a host, named after it, and holds that host's logs. Files directly in
+// the directory belong to no host and are left out. Hosts are sorted by name.
+pub fn host_dirs(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut hosts = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+            hosts.push((name.unwrap_or_default(), path));
+        }
+    }
+    hosts.sort();
+
+    Ok(hosts)
+}
+
+// Apply f to every item on up to `jobs` threads, returning the results in the order of the
+// items. Each thread takes the next item not yet started, so one host with a huge log doesn't
+// hold up the rest.
+pub fn map_parallel<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<Option<R>>>());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let item = match items.get(i) {
+                    Some(item) => item,
+                    None => break,
+                };
+                let result = f(item);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_fleet_hosts() {
+        let dir = std::env::temp_dir().join(format!("parseoom-fleet-{}", std::process::id()));
+        fs::create_dir_all(dir.join("web07")).unwrap();
+        fs::create_dir_all(dir.join("db01")).unwrap();
+        fs::write(dir.join("README"), "not a host").unwrap();
+
+        let hosts = host_dirs(&dir).unwrap();
+        let names = hosts
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["db01", "web07"]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let messages = include_str!("../messages");
+        let summary = HostSummary::new("db01", messages, 2023, Zone::Utc);
+        assert_eq!(summary.events, 1);
+        assert_eq!(summary.kills, 1);
+        assert_eq!(summary.last.as_deref(), Some("Dec 20 03:17:52"));
+        assert_eq!(summary.last_time.as_deref(), Some("2023-12-20T03:17:52Z"));
+        assert_eq!(summary.last_victim.as_deref(), Some("clamd"));
+        assert_eq!(summary.verdict, vec!["swap"]);
+        assert!(summary.error.is_none());
+
+        let quiet = HostSummary::new(
+            "web07",
+            "Dec 20 03:17:52 web07 kernel: eth0 up\n",
+            2023,
+            Zone::Utc,
+        );
+        assert_eq!(quiet.events, 0);
+        assert!(quiet.fingerprint.is_none());
+
+        let mut other = HostSummary::new("db02", messages, 2023, Zone::Utc);
+        other.verdict.push("hugepages".to_string());
+        let stats = FleetStats::new(&[summary, other, quiet]);
+        assert_eq!((stats.hosts, stats.affected_hosts, stats.kills), (3, 2, 2));
+        assert_eq!(stats.events_by_host.len(), 2);
+        assert_eq!(stats.kills_by_command[0].command, "clamd");
+        assert_eq!(
+            (
+                stats.kills_by_command[0].kills,
+                stats.kills_by_command[0].hosts
+            ),
+            (2, 2)
+        );
+        assert_eq!(stats.hosts_by_verdict["hugepages"], ["db02"]);
+        assert_eq!(stats.hosts_by_verdict["swap"], ["db01", "db02"]);
+
+        let lengths = map_parallel(&["a", "bb", "ccc", "dddd"], 3, |s| s.len());
+        assert_eq!(lengths, [1, 2, 3, 4]);
+        assert!(map_parallel(&[] as &[&str], 4, |s| s.len()).is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::report_end;
+use std::error::Error;
+use std::fs::{self, File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// The most of a report that's waited for while it's being written. A process list of a big host
+// is tens of thousands of lines written in a second or two, so a report past either limit has
+// lost the line that ends it, and is dropped so that what follows it can be read and the journal
+// cursor saved.
+const MAX_PENDING_LINES: usize = 500_000;
+const MAX_PENDING_TIME: Duration = Duration::from_secs(300);
+
+// Watch a growing log file like tail -F and hand back each oom-killer report once all of it has
+// been written. Rotation (the path now names a different file) and truncation (the file got
+// shorter) are both detected, so following /var/log/messages survives logrotate.
+pub struct Follower {
+    path: PathBuf,
+    file: File,
+    id: Option<u64>,
+    pos: u64,
+    partial: Vec<u8>,
+    pending: String,
+    since: Option<Instant>,
+    warnings: Vec<String>,
+}
+
+// Identify the file behind a path, to notice when it has been replaced
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<u64> {
+    None
+}
+
+impl Follower {
+    // Start following at the current end of the file, so only reports written from now on are
+    // returned.
+    pub fn new(path: &Path) -> Result<Follower, Box<dyn Error>> {
+        let mut file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let id = file_id(&file.metadata()?);
+        let pos = file.seek(SeekFrom::End(0))?;
+
+        Ok(Follower {
+            path: path.to_path_buf(),
+            file,
+            id,
+            pos,
+            partial: Vec::new(),
+            pending: String::new(),
+            since: None,
+            warnings: Vec::new(),
+        })
+    }
+
+    // Read whatever has been appended since the last call and return any reports it completed.
+    pub fn poll(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        // The path may briefly not exist in the middle of a rotation, so keep reading the file we
+        // have until a new one shows up.
+        if let Ok(meta) = fs::metadata(&self.path) {
+            if self.id.is_some() && file_id(&meta) != self.id {
+                // Rotated: finish what was written to the old file, then start the new one from
+                // the beginning.
+                self.read_new()?;
+                self.file = File::open(&self.path)?;
+                self.id = file_id(&meta);
+                self.pos = 0;
+                self.partial.clear();
+            } else if meta.len() < self.pos {
+                // Truncated in place, ie. copytruncate
+                self.file.seek(SeekFrom::Start(0))?;
+                self.pos = 0;
+                self.partial.clear();
+            }
+        }
+
+        self.read_new()?;
+
+        let reports = take_reports(&mut self.pending);
+        if !reports.is_empty() {
+            self.since = None;
+        }
+        self.warnings.extend(drop_stuck(
+            &mut self.pending,
+            &mut self.since,
+            Instant::now(),
+        ));
+
+        Ok(reports)
+    }
+
+    // The warnings about reports dropped since the last call
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // Append new complete lines to the pending text. A trailing partial line is held back so
+    // that lines, and multi-byte characters, aren't split between reads.
+    fn read_new(&mut self) -> Result<(), Box<dyn Error>> {
+        let n = self.file.read_to_end(&mut self.partial)?;
+        self.pos += n as u64;
+
+        if let Some(i) = self.partial.iter().rposition(|&b| b == b'\n') {
+            let lines: Vec<u8> = self.partial.drain(..=i).collect();
+            self.pending.push_str(&String::from_utf8_lossy(&lines));
+        }
+
+        Ok(())
+    }
+}
+
+// Remove each complete report from the pending text. A report is complete once the whole of
+// the line that ends it has been written, or once what follows shows where it ended, as found by
+// report_end().
+fn take_reports(pending: &mut String) -> Vec<String> {
+    let mut reports = Vec::new();
+
+    loop {
+        let start = match pending.find("invoked oom-killer") {
+            Some(i) => pending[..i].rfind('\n').map_or(0, |n| n + 1),
+            None => {
+                // Nothing that could become a report, no need to keep it
+                pending.clear();
+                break;
+            }
+        };
+
+        let end = match report_end(&pending[start..]).map(|end| start + end) {
+            // The marker's line is part of the report
+            Some(end) if end > 0 && !pending[..end].ends_with('\n') => {
+                match pending[end..].find('\n') {
+                    Some(n) => end + n + 1,
+                    None => {
+                        pending.drain(..start);
+                        break;
+                    }
+                }
+            }
+            Some(end) => end,
+            None => {
+                pending.drain(..start);
+                break;
+            }
+        };
+
+        reports.push(pending[start..end].to_string());
+        pending.drain(..end);
+    }
+
+    reports
+}
+
+// Drop the report at the start of the pending text once it has waited longer for its end than
+// any report takes to be written, returning a warning saying so. since is when it started
+// waiting, and is reset whenever a report is taken, as the one pending may be a new one.
+fn drop_stuck(pending: &mut String, since: &mut Option<Instant>, now: Instant) -> Option<String> {
+    if pending.is_empty() {
+        *since = None;
+        return None;
+    }
+
+    let waited = now.duration_since(*since.get_or_insert(now));
+    let lines = pending.matches('\n').count();
+    if lines <= MAX_PENDING_LINES && waited <= MAX_PENDING_TIME {
+        return None;
+    }
+
+    pending.clear();
+    *since = None;
+    Some(format!(
+        "dropped an oom-killer report with no end after {} lines and {} seconds",
+        lines,
+        waited.as_secs()
+    ))
+}
+
+// Watch the kernel messages in the systemd journal and hand back each oom-killer report once
+// all of it has been written. Where it got to is a journal cursor, which is written to the state
+// file, if there is one, so that a restarted parseoom picks up where the last one stopped rather
+// than reporting events twice or missing the ones logged while it wasn't running.
+pub struct JournalFollower {
+    state: Option<PathBuf>,
+    cursor: Option<String>,
+    pending: String,
+    since: Option<Instant>,
+    warnings: Vec<String>,
+}
+
+// Split journalctl --show-cursor output into the messages and the cursor of the last of them,
+// from its final line:
+//
+//      -- cursor: s=0b3d...;i=1f2e;b=7c1a...;m=4a9c1d2e;t=60cf...;x=9e4b...
+//
+// There's no cursor line when there were no new messages.
+fn split_cursor(output: &str) -> (&str, Option<&str>) {
+    const CURSOR_PREFIX: &str = "-- cursor: ";
+
+    let body = output.trim_end_matches('\n');
+    let start = body.rfind('\n').map_or(0, |n| n + 1);
+    match body[start..].strip_prefix(CURSOR_PREFIX) {
+        Some(cursor) => (&output[..start], Some(cursor.trim())),
+        None => (output, None),
+    }
+}
+
+// Run journalctl for kernel messages, with --show-cursor so that the output ends with the cursor
+// of the last one
+fn journalctl(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("journalctl")
+        .args(["-k", "--no-pager", "--show-cursor"])
+        .args(args)
+        .output()
+        .map_err(|e| format!("journalctl -k: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "journalctl -k: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl JournalFollower {
+    // Start following after the cursor in the state file, or at the newest message when there's
+    // no state file yet, so that only reports logged from now on are returned.
+    pub fn new(state: Option<&Path>) -> Result<JournalFollower, Box<dyn Error>> {
+        let saved = match state {
+            Some(path) if path.exists() => {
+                let text =
+                    fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                Some(text.trim().to_string()).filter(|c| !c.is_empty())
+            }
+            _ => None,
+        };
+
+        let cursor = match saved {
+            Some(cursor) => Some(cursor),
+            None => split_cursor(&journalctl(&["-n", "1"])?)
+                .1
+                .map(str::to_string),
+        };
+
+        let follower = JournalFollower {
+            state: state.map(Path::to_path_buf),
+            cursor,
+            pending: String::new(),
+            since: None,
+            warnings: Vec::new(),
+        };
+        follower.save()?;
+
+        Ok(follower)
+    }
+
+    // Read the messages logged since the last call and return any reports they completed.
+    pub fn poll(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        let after = self
+            .cursor
+            .as_ref()
+            .map(|c| format!("--after-cursor={}", c));
+        let output = journalctl(&after.iter().map(String::as_str).collect::<Vec<_>>())?;
+        let (messages, cursor) = split_cursor(&output);
+        let cursor = match cursor {
+            Some(cursor) => cursor.to_string(),
+            None => return Ok(Vec::new()),
+        };
+
+        self.pending.push_str(messages);
+        let reports = take_reports(&mut self.pending);
+        if !reports.is_empty() {
+            self.since = None;
+        }
+        self.warnings.extend(drop_stuck(
+            &mut self.pending,
+            &mut self.since,
+            Instant::now(),
+        ));
+        self.cursor = Some(cursor);
+
+        // The start of a report that is still being written would be lost if we stopped now, so
+        // the state file keeps the cursor from before it until the report is complete, or is
+        // dropped. A report completed in the same poll as another one starts is seen again after
+        // a restart.
+        if self.pending.is_empty() {
+            self.save()?;
+        }
+
+        Ok(reports)
+    }
+
+    // Write the cursor to the state file. It's written to a temporary file that is renamed over
+    // the state file, so a crash can't leave half a cursor behind.
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        let (path, cursor) = match (&self.state, &self.cursor) {
+            (Some(path), Some(cursor)) => (path, cursor),
+            _ => return Ok(()),
+        };
+
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, format!("{}\n", cursor))
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Ok(())
+    }
+
+    // The warnings about reports dropped since the last call
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn report_follow() {
+        let log = include_str!("../messages");
+        let (head, tail) = log.split_at(log.len() / 2);
+        let dir = std::env::temp_dir().join(format!("parseoom-follow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("messages");
+        fs::write(&path, log).unwrap();
+
+        // Reports already in the file are skipped
+        let mut follower = Follower::new(&path).unwrap();
+        assert!(follower.poll().unwrap().is_empty());
+
+        let append = |text: &str| {
+            let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+
+        append(head);
+        assert!(follower.poll().unwrap().is_empty());
+        append(tail);
+        let reports = follower.poll().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(crate::OomEvent::parse(&reports[0]).is_ok());
+
+        // Truncated in place
+        fs::write(&path, "").unwrap();
+        assert!(follower.poll().unwrap().is_empty());
+        append(log);
+        assert_eq!(follower.poll().unwrap().len(), 1);
+
+        // Rotated, with a report written to both the old and the new file
+        if cfg!(unix) {
+            append(log);
+            fs::rename(&path, dir.join("messages.1")).unwrap();
+            fs::write(&path, log).unwrap();
+            assert_eq!(follower.poll().unwrap().len(), 2);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        // A report that lost its marker is complete once the next one starts, and isn't merged
+        // with it
+        let lost = log.replace("Out of memory", "Killed");
+        let mut pending = lost.repeat(2);
+        let reports = take_reports(&mut pending);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].matches("invoked oom-killer").count(), 1);
+        assert!(pending.contains("invoked oom-killer"));
+
+        // The last of them never ends, and is dropped once it has waited too long, or grown too
+        // long, for the lines after it
+        let start = Instant::now();
+        let mut since = None;
+        assert_eq!(drop_stuck(&mut pending, &mut since, start), None);
+        assert_eq!(since, Some(start));
+        let later = start + MAX_PENDING_TIME + Duration::from_secs(1);
+        let warning = drop_stuck(&mut pending, &mut since, later).unwrap();
+        assert!(warning.ends_with(" lines and 301 seconds"), "{}", warning);
+        assert!(pending.is_empty());
+        assert_eq!(since, None);
+
+        let mut pending = lost + &"kernel: [ 2178.112240] no end\n".repeat(MAX_PENDING_LINES);
+        assert!(take_reports(&mut pending).is_empty());
+        assert!(drop_stuck(&mut pending, &mut since, start).is_some());
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn report_journal_cursor() {
+        let output = "Dec 20 03:17:52 localhost kernel: Out of memory: Killed process 1234\n\
+                      -- cursor: s=0b3d;i=1f2e;b=7c1a;m=4a9c;t=60cf;x=9e4b\n";
+        let (messages, cursor) = split_cursor(output);
+        assert_eq!(
+            messages,
+            "Dec 20 03:17:52 localhost kernel: Out of memory: Killed process 1234\n"
+        );
+        assert_eq!(cursor, Some("s=0b3d;i=1f2e;b=7c1a;m=4a9c;t=60cf;x=9e4b"));
+        assert_eq!(
+            split_cursor("-- No entries --\n"),
+            ("-- No entries --\n", None)
+        );
+
+        let dir = std::env::temp_dir().join(format!("parseoom-cursor-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cursor");
+        let follower = JournalFollower {
+            state: Some(path.clone()),
+            cursor: cursor.map(str::to_string),
+            pending: String::new(),
+            since: None,
+            warnings: Vec::new(),
+        };
+        follower.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "s=0b3d;i=1f2e;b=7c1a;m=4a9c;t=60cf;x=9e4b\n"
+        );
+        assert!(!path.with_extension("tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
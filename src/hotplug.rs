@@ -0,0 +1,82 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tracing::instrument;
+
+// Memory taken offline before the oom-killer ran, by memory hotplug on a virtual machine, a CXL
+// device being removed, or an administrator writing to /sys/devices/system/memory. RAM that
+// shrank after boot explains an oom-kill on a machine that looks like it had plenty.
+#[derive(Default, Serialize, JsonSchema)]
+pub struct MemoryHotplug {
+    pub offlined_kib: f64,
+    pub offlines: usize,
+    pub hot_removes: usize,
+    pub failed_offlines: usize,
+    pub last: String,
+}
+
+// Find memory hotplug messages in the log before the last oom-killer report, ie:
+//
+//      Offlined Pages 32768
+//      pseries-hotplug-mem: Memory at 20000000 (drc index 80000002) was hot-removed
+//      memory offlining [mem 0x140000000-0x147ffffff] failed due to failure to isolate range
+//
+// Offlined pages are counted with the kernel's page size of 4 KiB.
+#[instrument(level = "debug", skip_all, fields(bytes = log.len()))]
+pub fn parse_memory_hotplug(log: &str) -> Option<MemoryHotplug> {
+    const OFFLINED_RE: &str = r"Offlined Pages (\d+)";
+    const HOT_REMOVED_RE: &str = r"Memory at [0-9a-f]+ \(drc index [0-9a-f]+\) was hot-removed";
+    const OFFLINE_FAILED_RE: &str = r"memory offlining \[mem 0x[0-9a-f]+-0x[0-9a-f]+\] failed";
+
+    let end = log.rfind("invoked oom-killer").unwrap_or(log.len());
+    let offlined_re = Regex::new(OFFLINED_RE).unwrap();
+    let hot_removed_re = Regex::new(HOT_REMOVED_RE).unwrap();
+    let offline_failed_re = Regex::new(OFFLINE_FAILED_RE).unwrap();
+
+    let mut hotplug = MemoryHotplug::default();
+    for line in log[..end].lines() {
+        if let Some(caps) = offlined_re.captures(line) {
+            hotplug.offlined_kib += caps[1].parse::<f64>().unwrap_or(0.0) * 4.0;
+            hotplug.offlines += 1;
+        } else if hot_removed_re.is_match(line) {
+            hotplug.hot_removes += 1;
+        } else if offline_failed_re.is_match(line) {
+            hotplug.failed_offlines += 1;
+        } else {
+            continue;
+        }
+        hotplug.last = line.trim().to_string();
+    }
+
+    if hotplug.last.is_empty() {
+        None
+    } else {
+        Some(hotplug)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_memory_hotplug() {
+        let log = format!(
+            "{}{}{}",
+            "kernel: [70000.000000] Offlined Pages 32768\n\
+             kernel: [70001.000000] Offlined Pages 32768\n\
+             kernel: [70002.000000] memory offlining [mem 0x140000000-0x147ffffff] failed due to \
+             failure to isolate range\n",
+            include_str!("../messages"),
+            "kernel: [80000.000000] Offlined Pages 32768\n"
+        );
+
+        let hotplug = parse_memory_hotplug(&log).unwrap();
+        assert_eq!(hotplug.offlined_kib, 2.0 * 32768.0 * 4.0);
+        assert_eq!(hotplug.offlines, 2);
+        assert_eq!(hotplug.failed_offlines, 1);
+        assert!(hotplug.last.contains("70002.000000"));
+
+        assert!(parse_memory_hotplug(include_str!("../messages")).is_none());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::fingerprint::fnv1a;
+use crate::rollup::parse_timestamp;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{info, warn};
+
+// Version of the cached index format. Bump it when EventIndex changes so old caches are rebuilt
+// rather than misread.
+const CACHE_VERSION: u32 = 1;
+
+// Where each oom-killer invocation in a log starts, found in a single pass over the log that
+// keeps only the line being read in memory. An event is read back from its offset when it's
+// asked for, so one report can be pulled out of a multi-gigabyte archive without loading the
+// rest of it.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventIndex {
+    pub bytes: u64,
+    pub events: Vec<IndexedEvent>,
+}
+
+// One invocation: the byte offset and line, counting from 1, of its first line, the timestamp
+// of that line, and the process the kernel killed, when the kill follows before the next
+// invocation
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndexedEvent {
+    pub offset: u64,
+    pub line: usize,
+    pub timestamp: Option<String>,
+    pub victim: Option<String>,
+    pub victim_pid: Option<String>,
+}
+
+impl EventIndex {
+    // Read a log from where the reader is to its end, noting each line that invokes the
+    // oom-killer and the first kill after it
+    pub fn build<R: BufRead>(mut reader: R) -> io::Result<EventIndex> {
+        const INVOKED_RE: &str = r"invoked oom-killer";
+        const KILLED_RE: &str = r"Killed process (\d+) \((.*?)\)";
+
+        let invoked_re = Regex::new(INVOKED_RE).unwrap();
+        let killed_re = Regex::new(KILLED_RE).unwrap();
+
+        let mut index = EventIndex::default();
+        let mut line = Vec::new();
+        for n in 1.. {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+
+            if invoked_re.is_match(&line) {
+                let text = String::from_utf8_lossy(&line);
+                index.events.push(IndexedEvent {
+                    offset: index.bytes,
+                    line: n,
+                    timestamp: parse_timestamp(&text),
+                    victim: None,
+                    victim_pid: None,
+                });
+            } else if let Some(caps) = killed_re.captures(&line) {
+                if let Some(event) = index.events.last_mut().filter(|e| e.victim.is_none()) {
+                    event.victim_pid = Some(String::from_utf8_lossy(&caps[1]).into_owned());
+                    event.victim = Some(String::from_utf8_lossy(&caps[2]).into_owned());
+                }
+            }
+            index.bytes += read as u64;
+        }
+
+        Ok(index)
+    }
+
+    // Read the nth event, counting from 0, from its first line up to the next invocation. Like
+    // the input sources, invalid UTF-8 is replaced rather than rejected.
+    pub fn read_event<R: Read + Seek>(&self, reader: &mut R, n: usize) -> io::Result<String> {
+        let event = self.events.get(n).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "event {} not found, the log has {}",
+                    n + 1,
+                    self.events.len()
+                ),
+            )
+        })?;
+        let end = self
+            .events
+            .get(n + 1)
+            .map_or(self.bytes, |next| next.offset);
+
+        reader.seek(SeekFrom::Start(event.offset))?;
+        let mut bytes = Vec::new();
+        reader.take(end - event.offset).read_to_end(&mut bytes)?;
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    // The index of a log file, read from the cache file when the log has the same size and
+    // modification time as when it was cached, or built and cached otherwise. The cache is only
+    // a shortcut, so failing to write it isn't an error.
+    pub fn cached<F>(log: &Path, cache: &Path, build: F) -> Result<EventIndex, Box<dyn Error>>
+    where
+        F: FnOnce() -> Result<EventIndex, Box<dyn Error>>,
+    {
+        let metadata = fs::metadata(log).map_err(|e| format!("{}: {}", log.display(), e))?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let key = CacheKey {
+            version: CACHE_VERSION,
+            log: log.display().to_string(),
+            size: metadata.len(),
+            modified: modified.to_string(),
+        };
+
+        let saved = fs::read_to_string(cache)
+            .ok()
+            .and_then(|text| serde_json::from_str::<CachedIndex>(&text).ok())
+            .filter(|saved| saved.key == key);
+        if let Some(saved) = saved {
+            info!(cache = %cache.display(), "read the index from the cache");
+            return Ok(saved.index);
+        }
+
+        let cached = CachedIndex {
+            key,
+            index: build()?,
+        };
+        let temporary = cache.with_extension("tmp");
+        let saved = cache
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&temporary, serde_json::to_string(&cached)?))
+            .and_then(|_| fs::rename(&temporary, cache));
+        match saved {
+            Ok(()) => info!(cache = %cache.display(), "wrote the index to the cache"),
+            Err(e) => warn!(cache = %cache.display(), error = %e, "could not cache the index"),
+        }
+
+        Ok(cached.index)
+    }
+}
+
+// What a log file looked like when its index was cached: its size and modification time, in
+// nanoseconds since the epoch as a string, since JSON numbers can't hold it exactly
+#[derive(PartialEq, Serialize, Deserialize)]
+struct CacheKey {
+    version: u32,
+    log: String,
+    size: u64,
+    modified: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedIndex {
+    key: CacheKey,
+    index: EventIndex,
+}
+
+// Where the index of a log file is cached: in $XDG_CACHE_HOME/parseoom, or ~/.cache/parseoom,
+// under a hash of the log's full path, or next to the log when there's no home directory
+pub fn cache_path(log: &Path) -> Option<PathBuf> {
+    let log = fs::canonicalize(log).ok()?;
+    let dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+
+    match dir {
+        Some(dir) => {
+            let hash = fnv1a(log.to_string_lossy().as_bytes());
+            Some(dir.join("parseoom").join(format!("{:016x}.json", hash)))
+        }
+        None => {
+            let mut name = log.file_name()?.to_os_string();
+            name.push(".parseoom-index");
+            Some(log.with_file_name(name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rollup::split_events;
+    use std::io::Cursor;
+
+    #[test]
+    fn report_event_index() {
+        let log = include_str!("../tests/fixtures/rhel-7-3.10.log").repeat(3);
+        let index = EventIndex::build(log.as_bytes()).unwrap();
+        let chunks = split_events(&log);
+
+        assert_eq!(index.bytes, log.len() as u64);
+        assert_eq!(index.events.len(), chunks.len());
+        for (n, chunk) in chunks.iter().enumerate() {
+            let event = &index.events[n];
+            assert_eq!(index.read_event(&mut Cursor::new(&log), n).unwrap(), *chunk);
+            assert_eq!(
+                log[..event.offset as usize].matches('\n').count() + 1,
+                event.line
+            );
+            assert_eq!(
+                event.timestamp,
+                chunk.lines().next().and_then(parse_timestamp)
+            );
+        }
+        assert!(index.events[0].victim.is_some());
+        assert!(index
+            .read_event(&mut Cursor::new(&log), chunks.len())
+            .is_err());
+    }
+
+    #[test]
+    fn report_cached_index() {
+        let dir = std::env::temp_dir().join(format!("parseoom-{}-index", std::process::id()));
+        let (log, cache) = (dir.join("messages"), dir.join("cache/index.json"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&log, include_str!("../messages")).unwrap();
+
+        let build = || Ok(EventIndex::build(fs::read(&log)?.as_slice())?);
+        let built = EventIndex::cached(&log, &cache, build).unwrap();
+        assert_eq!(built.events.len(), 1);
+        assert!(cache.exists());
+
+        // A fresh cache is used without reading the log, and a changed log is indexed again
+        let read = EventIndex::cached(&log, &cache, || Err("log was read".into())).unwrap();
+        assert_eq!(read, built);
+        fs::write(&log, include_str!("../messages").repeat(2)).unwrap();
+        let rebuilt = EventIndex::cached(&log, &cache, build).unwrap();
+        assert_eq!(rebuilt.events.len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,564 @@
+use crate::structured::{is_structured, plain_text, JsonFields};
+use flate2::read::MultiGzDecoder;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// A place to read a log containing oom-killer reports from. Sources only hand back the text of
+// the log; finding and parsing reports is left to last_report() and OomEvent, so adding a
+// source never requires touching the parser.
+pub trait InputSource {
+    // A short description of the source, used in error messages
+    fn name(&self) -> String;
+
+    // Read the whole log. Logs written under memory pressure may contain invalid UTF-8, so
+    // sources replace it rather than rejecting the log outright. A log of JSON lines has its
+    // message and time in the given fields.
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>>;
+
+    // The log as a reader that can seek, for reading single events out of it by offset with an
+    // EventIndex. Sources that can't seek are read into memory; files are read from disk as
+    // needed, which keeps the memory used by a multi-gigabyte archive to a line at a time.
+    fn open_seekable(
+        &mut self,
+        fields: &JsonFields,
+    ) -> Result<Box<dyn SeekableLog>, Box<dyn Error>> {
+        Ok(Box::new(Cursor::new(self.read_log(fields)?.into_bytes())))
+    }
+}
+
+// A log that can be read from any point
+pub trait SeekableLog: BufRead + Seek {}
+
+impl<T: BufRead + Seek> SeekableLog for T {}
+
+// Pick the source for a path given on the command line: "-" is stdin, a directory is a bundle
+// of logs, a URL is fetched with the remote feature, and a .gz file is decompressed on the fly.
+pub fn source_for_path(path: &Path) -> Result<Box<dyn InputSource>, Box<dyn Error>> {
+    let url = path.to_str().filter(|p| {
+        ["https://", "http://", "s3://"]
+            .iter()
+            .any(|s| p.starts_with(s))
+    });
+
+    if let Some(url) = url {
+        url_source(url)
+    } else if path == Path::new("-") {
+        Ok(Box::new(StdinSource))
+    } else if path.is_dir() {
+        Ok(Box::new(DirectorySource::new(path)))
+    } else if is_gzip(path) {
+        Ok(Box::new(GzipSource::open(path)?))
+    } else {
+        Ok(Box::new(FileSource::new(path)))
+    }
+}
+
+#[cfg(feature = "remote")]
+fn url_source(url: &str) -> Result<Box<dyn InputSource>, Box<dyn Error>> {
+    if url.starts_with("s3://") {
+        Ok(Box::new(S3Source::new(url)))
+    } else {
+        Ok(Box::new(HttpSource::new(url)))
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+fn url_source(url: &str) -> Result<Box<dyn InputSource>, Box<dyn Error>> {
+    Err(format!("{}: parseoom was built without the remote feature", url).into())
+}
+
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+// Turn the bytes of a log into text. Logs in a structured format, such as a journal export, are
+// converted to plain lines first.
+fn decode_log(bytes: Vec<u8>, fields: &JsonFields) -> String {
+    if let Some(text) = plain_text(&bytes, fields) {
+        return text;
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }
+}
+
+// A plain log file, such as /var/log/messages
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: &Path) -> FileSource {
+        FileSource {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl InputSource for FileSource {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        let bytes = fs::read(&self.path).map_err(|e| format!("{}: {}", self.name(), e))?;
+        Ok(decode_log(bytes, fields))
+    }
+
+    // A structured log's offsets aren't those of its lines of text, so it's converted in memory
+    fn open_seekable(
+        &mut self,
+        fields: &JsonFields,
+    ) -> Result<Box<dyn SeekableLog>, Box<dyn Error>> {
+        let file = File::open(&self.path).map_err(|e| format!("{}: {}", self.name(), e))?;
+        let mut reader = BufReader::new(file);
+        if is_structured(reader.fill_buf()?, fields) {
+            return Ok(Box::new(Cursor::new(self.read_log(fields)?.into_bytes())));
+        }
+
+        Ok(Box::new(reader))
+    }
+}
+
+// A log piped to standard input
+pub struct StdinSource;
+
+impl InputSource for StdinSource {
+    fn name(&self) -> String {
+        "stdin".to_string()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        Ok(decode_log(bytes, fields))
+    }
+}
+
+// Run a command and use its output as the log
+fn command_output(
+    command: &mut Command,
+    name: &str,
+    fields: &JsonFields,
+) -> Result<String, Box<dyn Error>> {
+    let output = command.output().map_err(|e| format!("{}: {}", name, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{}: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(decode_log(output.stdout, fields))
+}
+
+// Kernel messages from the systemd journal. The default short output format matches syslog, so
+// reports read from it look the same as ones read from /var/log/messages.
+pub struct JournaldSource;
+
+impl InputSource for JournaldSource {
+    fn name(&self) -> String {
+        "journalctl -k".to_string()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        command_output(
+            Command::new("journalctl").args(["-k", "--no-pager"]),
+            &self.name(),
+            fields,
+        )
+    }
+}
+
+// The kernel ring buffer, as printed by dmesg. Only holds reports since the buffer last wrapped.
+pub struct KmsgSource;
+
+impl InputSource for KmsgSource {
+    fn name(&self) -> String {
+        "dmesg".to_string()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        command_output(&mut Command::new("dmesg"), &self.name(), fields)
+    }
+}
+
+// An anonymized oom-killer report built into parseoom, for trying it out and smoke-testing a
+// package without a log at hand
+pub struct DemoSource;
+
+impl InputSource for DemoSource {
+    fn name(&self) -> String {
+        "demo".to_string()
+    }
+
+    fn read_log(&mut self, _fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        Ok(include_str!("demo.log").to_string())
+    }
+}
+
+// A log on a remote host, read over ssh. The destination is host[:path], where host may include
+// a user. Without a path the remote syslog is used, falling back to the journal on hosts that
+// don't have one. An IPv6 address is written in brackets, as scp takes it, [fe80::1]:path, or
+// bare with an absolute path, fe80::1:/var/log/messages, its last colon before the path's
+// first slash ending it.
+pub struct SshSource {
+    host: String,
+    path: Option<String>,
+}
+
+impl SshSource {
+    pub fn new(destination: &str) -> SshSource {
+        // A user name comes before the host, so its @ is before any colon or bracket
+        let at = destination
+            .find('@')
+            .filter(|&i| !destination[..i].contains([':', '[']))
+            .map_or(0, |i| i + 1);
+        let (user, rest) = destination.split_at(at);
+
+        let (host, path) = match rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            Some((address, path)) => (address, path.strip_prefix(':')),
+            None => {
+                let head = &rest[..rest.find('/').unwrap_or(rest.len())];
+                let colon = match head.matches(':').count() {
+                    1 => head.find(':'),
+                    // An IPv6 address, followed by a path only when there's one
+                    n if n > 1 && head.len() < rest.len() => head.rfind(':'),
+                    _ => None,
+                };
+                match colon {
+                    Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+                    None => (rest, None),
+                }
+            }
+        };
+
+        SshSource {
+            host: format!("{}{}", user, host),
+            path: path.filter(|p| !p.is_empty()).map(str::to_string),
+        }
+    }
+
+    // The command run on the remote host to print the log
+    fn remote_command(&self) -> String {
+        match &self.path {
+            Some(path) if path.ends_with(".gz") => format!("zcat -- {}", shell_quote(path)),
+            Some(path) => format!("cat -- {}", shell_quote(path)),
+            None => "if [ -r /var/log/messages ]; then cat /var/log/messages; \
+                     else journalctl -k --no-pager; fi"
+                .to_string(),
+        }
+    }
+}
+
+// Quote a string for a POSIX shell
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+impl InputSource for SshSource {
+    fn name(&self) -> String {
+        let host = match self.host.rsplit_once('@') {
+            Some((user, address)) if address.contains(':') => format!("{}@[{}]", user, address),
+            None if self.host.contains(':') => format!("[{}]", self.host),
+            _ => self.host.clone(),
+        };
+        match &self.path {
+            Some(path) => format!("{}:{}", host, path),
+            None => host,
+        }
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        command_output(
+            Command::new("ssh")
+                .arg("--")
+                .arg(&self.host)
+                .arg(self.remote_command()),
+            &self.name(),
+            fields,
+        )
+    }
+}
+
+// A log served over HTTP(S). Content-Encoding is handled by the HTTP client, and a URL ending
+// in .gz is decompressed as it's downloaded.
+#[cfg(feature = "remote")]
+pub struct HttpSource {
+    url: String,
+}
+
+#[cfg(feature = "remote")]
+impl HttpSource {
+    pub fn new(url: &str) -> HttpSource {
+        HttpSource {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl InputSource for HttpSource {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        let response = ureq::get(&self.url)
+            .call()
+            .map_err(|e| format!("{}: {}", self.url, e))?;
+        let mut reader = response.into_body().into_reader();
+
+        if is_gzip(Path::new(&self.url)) {
+            return GzipSource::new(&self.url, Box::new(reader)).read_log(fields);
+        }
+
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("{}: {}", self.url, e))?;
+        Ok(decode_log(bytes, fields))
+    }
+}
+
+// A log in an S3 bucket, downloaded with the aws command so the usual credential chain and
+// profiles apply
+#[cfg(feature = "remote")]
+pub struct S3Source {
+    url: String,
+}
+
+#[cfg(feature = "remote")]
+impl S3Source {
+    pub fn new(url: &str) -> S3Source {
+        S3Source {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl InputSource for S3Source {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        let mut child = Command::new("aws")
+            .args(["s3", "cp", "--quiet", &self.url, "-"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("{}: aws: {}", self.url, e))?;
+        let stdout = child.stdout.take().ok_or("aws: no output")?;
+
+        let log = if is_gzip(Path::new(&self.url)) {
+            GzipSource::new(&self.url, Box::new(stdout)).read_log(fields)
+        } else {
+            let mut bytes = Vec::new();
+            io::BufReader::new(stdout).read_to_end(&mut bytes)?;
+            Ok(decode_log(bytes, fields))
+        };
+
+        if !child.wait()?.success() {
+            return Err(format!("{}: aws s3 cp failed", self.url).into());
+        }
+
+        log
+    }
+}
+
+// A gzip compressed stream, such as a rotated messages-20240301.gz. Multiple gzip members are
+// read one after another, as with zcat.
+pub struct GzipSource {
+    name: String,
+    reader: Box<dyn Read>,
+}
+
+impl GzipSource {
+    pub fn new(name: &str, reader: Box<dyn Read>) -> GzipSource {
+        GzipSource {
+            name: name.to_string(),
+            reader,
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<GzipSource, Box<dyn Error>> {
+        let name = path.display().to_string();
+        let file = File::open(path).map_err(|e| format!("{}: {}", name, e))?;
+        Ok(GzipSource::new(&name, Box::new(file)))
+    }
+}
+
+impl InputSource for GzipSource {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        MultiGzDecoder::new(&mut self.reader)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("{}: {}", self.name, e))?;
+        Ok(decode_log(bytes, fields))
+    }
+}
+
+// A directory of logs, such as an extracted sosreport or supportconfig. Every file below it is
+// read, oldest first by modification time, so rotated logs come before the current one and the
+// last report in the combined text is the most recent.
+pub struct DirectorySource {
+    path: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new(path: &Path) -> DirectorySource {
+        DirectorySource {
+            path: path.to_path_buf(),
+        }
+    }
+
+    // The files under a directory. Links to files are followed but links to directories
+    // aren't, as one pointing back up the tree would be walked forever.
+    fn files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                DirectorySource::files(&path, files)?;
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// How much of a file is looked at to tell whether it's a log
+const TEXT_CHECK_BYTES: u64 = 8192;
+
+// Whether a file in a directory of logs holds text rather than something else left there, such
+// as a core dump or a binary journal file, told by a NUL byte in its first few kilobytes. A
+// compressed file is looked at once decompressed, and a structured log is text even when it
+// holds binary fields, as a journal export can.
+fn is_text_log(path: &Path, fields: &JsonFields) -> io::Result<bool> {
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if is_gzip(path) {
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut start = Vec::new();
+    reader.take(TEXT_CHECK_BYTES).read_to_end(&mut start)?;
+
+    Ok(is_structured(&start, fields) || !start.contains(&0))
+}
+
+impl InputSource for DirectorySource {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn read_log(&mut self, fields: &JsonFields) -> Result<String, Box<dyn Error>> {
+        let mut files = Vec::new();
+        DirectorySource::files(&self.path, &mut files)
+            .map_err(|e| format!("{}: {}", self.name(), e))?;
+        files.retain(|path| is_text_log(path, fields).unwrap_or(true));
+
+        files.sort_by_key(|path| {
+            let mtime = fs::metadata(path).and_then(|m| m.modified()).ok();
+            (mtime, path.clone())
+        });
+
+        let mut log = String::new();
+        for path in files {
+            let text = if is_gzip(&path) {
+                GzipSource::open(&path)?.read_log(fields)?
+            } else {
+                FileSource::new(&path).read_log(fields)?
+            };
+            log.push_str(&text);
+            if !log.ends_with('\n') {
+                log.push('\n');
+            }
+        }
+
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn report_input_sources() {
+        let dir = std::env::temp_dir().join(format!("parseoom-input-{}", std::process::id()));
+        fs::create_dir_all(dir.join("var/log")).unwrap();
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(b"older\n").unwrap();
+        fs::write(dir.join("var/log/messages-1.gz"), gz.finish().unwrap()).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(dir.join("var/log/messages"), b"newer \xff").unwrap();
+        fs::write(dir.join("var/log/core"), b"\x7fELF\x02\x01\x01\0\0").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("..", dir.join("var/log/up")).unwrap();
+
+        let mut gz = source_for_path(&dir.join("var/log/messages-1.gz")).unwrap();
+        assert_eq!(gz.read_log(&JsonFields::default()).unwrap(), "older\n");
+        let mut bundle = source_for_path(&dir).unwrap();
+        assert_eq!(
+            bundle.read_log(&JsonFields::default()).unwrap(),
+            "older\nnewer \u{fffd}\n"
+        );
+        let mut missing = source_for_path(&dir.join("missing")).unwrap();
+        assert!(missing.read_log(&JsonFields::default()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn report_ssh_destination() {
+        let ssh = SshSource::new("root@db01:/var/log/messages-2024.gz");
+        assert_eq!(ssh.host, "root@db01");
+        assert_eq!(ssh.remote_command(), "zcat -- '/var/log/messages-2024.gz'");
+
+        let ssh = SshSource::new("db01");
+        assert_eq!(ssh.path, None);
+        assert_eq!(ssh.name(), "db01");
+        let ssh = SshSource::new("db01:messages");
+        assert_eq!(
+            (ssh.host.as_str(), ssh.path.as_deref()),
+            ("db01", Some("messages"))
+        );
+
+        // IPv6 addresses, in brackets or bare
+        let ssh = SshSource::new("root@[fe80::1%eth0]:/var/log/messages");
+        assert_eq!(ssh.host, "root@fe80::1%eth0");
+        assert_eq!(ssh.path.as_deref(), Some("/var/log/messages"));
+        assert_eq!(ssh.name(), "root@[fe80::1%eth0]:/var/log/messages");
+        let ssh = SshSource::new("db01:/var/log/a@b");
+        assert_eq!(ssh.host, "db01");
+        let ssh = SshSource::new("2001:db8::7:/var/log/messages");
+        assert_eq!(ssh.host, "2001:db8::7");
+        assert_eq!(ssh.path.as_deref(), Some("/var/log/messages"));
+        let ssh = SshSource::new("2001:db8::7");
+        assert_eq!((ssh.host.as_str(), ssh.path), ("2001:db8::7", None));
+        let ssh = SshSource::new("[2001:db8::7]");
+        assert_eq!((ssh.host.as_str(), ssh.path), ("2001:db8::7", None));
+
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
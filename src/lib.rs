@@ -0,0 +1,2907 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use tracing::{debug, debug_span, info_span, instrument};
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+pub mod accelerators;
+pub mod android;
+pub mod consistency;
+pub mod coverage;
+pub mod doctor;
+pub mod fingerprint;
+pub mod fleet;
+pub mod follow;
+pub mod hotplug;
+pub mod index;
+pub mod input;
+pub mod metrics;
+pub mod numbers;
+pub mod pages;
+pub mod partial;
+pub mod precursors;
+pub mod pressure;
+pub mod recommendations;
+pub mod render;
+pub mod report;
+pub mod rollup;
+pub mod rules;
+pub mod sections;
+pub mod service;
+pub mod snapshot;
+pub mod strict;
+pub mod structured;
+pub mod template;
+pub mod text;
+pub mod thresholds;
+pub mod timestamps;
+pub mod workloads;
+
+use accelerators::{parse_accelerator_memory, AcceleratorMemory};
+use fingerprint::Fingerprint;
+use hotplug::{parse_memory_hotplug, MemoryHotplug};
+use pages::PageCounts;
+use partial::PartialReport;
+use precursors::{Precursor, PRECURSOR_WINDOW_SECONDS};
+use rollup::{EventSummary, FreedMemory, Kill};
+use rules::CustomValue;
+use sections::{registry, MemcgUsage};
+use workloads::DatabaseShmem;
+
+const OOM_END_RE: &str = r"(?i)out of memory:?";
+
+// How far before the report to look for what was set up at boot, when the boot it was logged in
+// is further back than that or not in the log at all
+const LOG_CONTEXT_BYTES: usize = 64 * 1024 * 1024;
+const PS_LIST_END_RE: &str = r"(?i)out of memory:?|oom-kill:|memory cgroup";
+const PS_LIST_RE: &str = r"(.*pid.+\bname\b)(?s)(.*)";
+
+pub const PAGE_SIZE: i64 = 4096;
+
+// The range of oom_score_adj. Tasks at the minimum, such as sshd and systemd-udevd, are never
+// chosen by the oom-killer.
+pub const OOM_SCORE_ADJ_MIN: i64 = -1000;
+pub const OOM_SCORE_ADJ_MAX: i64 = 1000;
+
+// Version of the JSON output format. Bump this whenever a field is removed, renamed, or changes
+// meaning so downstream consumers can detect the break; adding fields doesn't require a bump.
+pub const SCHEMA_VERSION: u32 = 2;
+
+// Warn when a single command has at least this many tasks
+pub(crate) const FORK_BOMB_TASKS: usize = 1000;
+
+// Warn when free CMA pages make up at least this share of RAM
+pub(crate) const CMA_WARNING_RATIO: f64 = 0.05;
+
+// Warn when the per-cpu page lists hold at least this share of RAM
+pub(crate) const PCP_WARNING_RATIO: f64 = 0.01;
+
+// Warn when a system-wide kill freed less than this share of RAM
+pub(crate) const FREED_WARNING_RATIO: f64 = 0.02;
+
+// The values parseoom reports on, extracted from a single oom-killer report. Memory sizes are
+// stored in KiB.
+#[derive(Default, Serialize, JsonSchema)]
+pub struct OomEvent {
+    #[serde(skip)]
+    pub cleaned: String,
+    // The line of the report each line of the cleaned text came from, counting from 0
+    #[serde(skip)]
+    pub(crate) cleaned_lines: Vec<usize>,
+    // The time of the report in UTC, when its timestamp is a date, as RFC 3339
+    pub time: Option<String>,
+    pub total_ram_kib: f64,
+    pub free_swap_kib: f64,
+    pub total_swap_kib: f64,
+    pub hugepages_2mb_kib: f64,
+    pub hugepages_1gb_kib: f64,
+    pub hugepages_free_kib: f64,
+    pub unreclaimable_slab_kib: f64,
+    pub reclaimable_slab_kib: f64,
+    pub shmem_kib: f64,
+    pub database_shmem: Option<DatabaseShmem>,
+    pub isolated_anon_kib: f64,
+    pub isolated_file_kib: f64,
+    pub free_cma_kib: f64,
+    pub free_kib: Option<f64>,
+    pub pages: PageCounts,
+    pub pcp: Option<PerCpuPages>,
+    pub ram_exhausted: Option<bool>,
+    pub zones: Vec<Zone>,
+    pub gfp_zone: Option<&'static str>,
+    pub alloc_order: Option<u32>,
+    pub lowmem_exhausted: bool,
+    pub mems_allowed: Option<Vec<u32>>,
+    pub kernel_32bit: bool,
+    pub highmem: Option<HighMem>,
+    pub compressed_swap: CompressedSwap,
+    pub thp: Option<Thp>,
+    pub virtualization: Virtualization,
+    pub memory_hotplug: Option<MemoryHotplug>,
+    pub accelerators: Vec<AcceleratorMemory>,
+    pub ps_header: Vec<String>,
+    pub processes: Vec<ProcessEntry>,
+    pub task_unit: i64,
+    pub victim_pid: Option<String>,
+    pub victim_memcg: Option<String>,
+    pub kills: Vec<Kill>,
+    pub freed: Option<FreedMemory>,
+    pub oom_group: Option<String>,
+    pub memcg: Option<MemcgUsage>,
+    pub custom: Vec<CustomValue>,
+    pub precursors: Vec<Precursor>,
+    pub partial_reports: Vec<PartialReport>,
+    pub fingerprint: Fingerprint,
+    pub warnings: Vec<Warning>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+// A line of the report the parser left out of the results, and why. The cleaner drops the lines
+// that end the process list, and the process list and slab parsers drop rows they can't read.
+// The line is that of the report, counting from 1.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SkippedLine {
+    pub section: &'static str,
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+// Something worth a look that doesn't stop a report from being parsed. Problems are parts of
+// the report the parser had to skip or guess at, which lower confidence in the results and fail
+// --strict. A warning about a single line of the log gives its line number, counting from 1,
+// and its text as the excerpt.
+#[derive(Serialize, JsonSchema)]
+pub struct Warning {
+    pub section: &'static str,
+    pub message: String,
+    pub problem: bool,
+    pub line: Option<usize>,
+    pub excerpt: Option<String>,
+}
+
+impl Warning {
+    pub(crate) fn new(section: &'static str, message: String) -> Warning {
+        Warning {
+            section,
+            message,
+            problem: false,
+            line: None,
+            excerpt: None,
+        }
+    }
+
+    pub(crate) fn problem(section: &'static str, message: String) -> Warning {
+        Warning {
+            problem: true,
+            ..Warning::new(section, message)
+        }
+    }
+
+    pub(crate) fn skipped(
+        section: &'static str,
+        message: String,
+        line: usize,
+        excerpt: &str,
+    ) -> Warning {
+        Warning {
+            line: Some(line),
+            excerpt: Some(excerpt.to_string()),
+            ..Warning::problem(section, message)
+        }
+    }
+}
+
+impl OomEvent {
+    // Parse the last oom-killer report found in a log.
+    pub fn parse(input: &str) -> Result<OomEvent, Box<dyn Error>> {
+        OomEvent::parse_with_window(input, PRECURSOR_WINDOW_SECONDS)
+    }
+
+    // As parse(), looking window_seconds before the report for warning signs
+    pub fn parse_with_window(input: &str, window_seconds: f64) -> Result<OomEvent, Box<dyn Error>> {
+        let mut event = OomEvent::from_report(last_report(input)?)?;
+        event.note_report_lines(input);
+        event.note_log_context(input);
+        event.note_kills(input);
+        event.note_precursors(input, window_seconds);
+        Ok(event)
+    }
+
+    // Compare the RAM in the report with what the guest was provisioned with. A cloud burst
+    // instance or an overcommitted hypervisor may balloon a guest down to well below its size,
+    // and the oom-killer then runs in a machine smaller than anyone expected.
+    pub fn note_expected_ram(&mut self, expected_kib: f64) {
+        let missing = expected_kib - self.total_ram_kib;
+        if self.total_ram_kib <= 0.0 || missing <= expected_kib * EXPECTED_RAM_TOLERANCE {
+            return;
+        }
+
+        self.virtualization.missing_ram_kib = Some(missing);
+        self.warnings.push(Warning::new(
+            "meminfo",
+            format!(
+                "the guest saw {:.1} GiB of RAM, {:.1} GiB less than the {:.1} GiB expected; \
+                 the hypervisor may have ballooned it down",
+                self.total_ram_kib / 1024.0 / 1024.0,
+                missing / 1024.0 / 1024.0,
+                expected_kib / 1024.0 / 1024.0
+            ),
+        ));
+    }
+
+    // Number the lines warnings are about by their line in the log, rather than in the report
+    // from_report() was given. Call once, with the log the report came from.
+    pub fn note_report_lines(&mut self, log: &str) {
+        let offset = report_start_line(log) - 1;
+        for warning in self.warnings.iter_mut() {
+            if let Some(line) = &mut warning.line {
+                *line += offset;
+            }
+        }
+    }
+
+    // Look through the rest of the log for things set up at boot, well before any oom-killer
+    // report: zram devices, zswap, memory balloon drivers and GPU drivers. Memory taken offline
+    // before the report is noted too. Only the report's context is read, not all of a log that
+    // may be gigabytes long.
+    pub fn note_log_context(&mut self, log: &str) {
+        let log = report_context(log);
+        let _span = info_span!("log_context", bytes = log.len()).entered();
+        let found = parse_compressed_swap(log);
+        self.compressed_swap.zram |= found.zram;
+        self.compressed_swap.zswap |= found.zswap;
+
+        for driver in parse_balloon_drivers(log) {
+            if !self.virtualization.balloon_drivers.contains(&driver) {
+                self.warnings.push(Warning::new(
+                    "meminfo",
+                    format!(
+                        "{} is loaded, guest RAM may have been reclaimed by the hypervisor",
+                        driver
+                    ),
+                ));
+                self.virtualization.balloon_drivers.push(driver);
+            }
+        }
+
+        self.accelerators = parse_accelerator_memory(log);
+        self.memory_hotplug = parse_memory_hotplug(log);
+        if let Some(hotplug) = &self.memory_hotplug {
+            if hotplug.offlined_kib > 0.0 || hotplug.hot_removes > 0 {
+                self.warnings.push(Warning::new(
+                    "meminfo",
+                    format!(
+                        "{:.1} MiB of memory and {} hot-removed blocks were taken offline before \
+                         the oom-kill, RAM had shrunk since boot",
+                        hotplug.offlined_kib / 1024.0,
+                        hotplug.hot_removes
+                    ),
+                ));
+            }
+        }
+    }
+
+    // Find the processes killed for the last report in the log, from the lines after it. That's
+    // usually the one task the summary line names, but with memory.oom.group set on its cgroup
+    // the kernel kills every task in the cgroup, logging each:
+    //
+    //      Memory cgroup out of memory: Killed process 4711 (java) total-vm:..., anon-rss:...
+    //      Tasks in /kubepods/pod1 are going to be killed due to memory.oom.group set
+    //      Memory cgroup out of memory: Killed process 4712 (envoy) total-vm:..., anon-rss:...
+    //
+    // The RSS each one gave up, and what oom_reaper reaped of it, is how much the kill freed.
+    pub fn note_kills(&mut self, log: &str) {
+        const OOM_GROUP_RE: &str =
+            r"Tasks in (\S+) are going to be killed due to memory\.oom\.group set";
+
+        let start = log
+            .rfind("invoked oom-killer")
+            .map_or(0, |i| log[..i].rfind('\n').map_or(0, |n| n + 1));
+        let after = &log[start..];
+        self.kills = EventSummary::new(after).kills;
+        self.freed = FreedMemory::new(&self.kills, self.total_ram_kib);
+        self.oom_group = Regex::new(OOM_GROUP_RE)
+            .unwrap()
+            .captures(after)
+            .map(|caps| caps[1].to_string());
+
+        if let Some(group) = &self.oom_group {
+            self.warnings.push(Warning::new(
+                "victim",
+                format!(
+                    "memory.oom.group is set on {}, {} tasks were killed together",
+                    group,
+                    self.kills.len()
+                ),
+            ));
+        }
+
+        // A cgroup's limit, not RAM, is what a memcg kill has to relieve
+        let freed = self.freed.as_ref();
+        if let Some(ratio) = freed.and_then(|f| f.ram_ratio) {
+            if self.memcg.is_none() && ratio < FREED_WARNING_RATIO {
+                self.warnings.push(Warning::new(
+                    "victim",
+                    format!(
+                        "the kill freed {:.1} MiB, {:.1}% of RAM, too little to relieve the \
+                         memory pressure for long",
+                        freed.map_or(0.0, |f| f.rss_kib) / 1024.0,
+                        ratio * 100.0
+                    ),
+                ));
+            }
+        }
+    }
+
+    // Parse a single oom-killer report, as returned by last_report(). Each section of the report
+    // is handled by one of the parsers in sections::registry().
+    pub fn from_report(oom: &str) -> Result<OomEvent, Box<dyn Error>> {
+        let _span = info_span!("parse_report", bytes = oom.len()).entered();
+        let (cleaned, cleaned_lines, skipped) = clean_report_lines(oom);
+        for line in &skipped {
+            debug!(section = line.section, line = line.line, reason = %line.reason, text = %line.text, "skipped");
+        }
+        let mut event = OomEvent {
+            cleaned,
+            cleaned_lines,
+            skipped,
+            task_unit: PAGE_SIZE,
+            ..Default::default()
+        };
+
+        for parser in registry() {
+            let _section = debug_span!("section", name = parser.name()).entered();
+            let before = event.skipped.len();
+            if Regex::new(parser.trigger()).unwrap().is_match(oom) {
+                parser.parse(oom, &mut event)?;
+                debug!("parsed");
+            } else {
+                parser.missing(&mut event)?;
+                debug!("missing from the report");
+            }
+            for line in &event.skipped[before..] {
+                debug!(line = line.line, reason = %line.reason, text = %line.text, "skipped");
+            }
+        }
+        event.note_inconsistencies();
+        event.database_shmem = DatabaseShmem::new(&event);
+        event.fingerprint = Fingerprint::new(&event, oom);
+
+        Ok(event)
+    }
+}
+
+// The machine-readable form of a parsed report, tagged with the schema version
+#[derive(Serialize, JsonSchema)]
+pub struct JsonReport<'a> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub event: &'a OomEvent,
+}
+
+impl<'a> JsonReport<'a> {
+    pub fn new(event: &'a OomEvent) -> JsonReport<'a> {
+        JsonReport {
+            schema_version: SCHEMA_VERSION,
+            event,
+        }
+    }
+}
+
+// Return the JSON Schema describing the output of parse_to_json().
+pub fn json_schema() -> String {
+    let schema = schemars::schema_for!(JsonReport<'static>);
+
+    serde_json::to_string_pretty(&schema).unwrap_or_default()
+}
+
+// Parse the last oom-killer report in a log and return it as a JSON object. Errors are returned
+// as {"error": "..."} so callers without access to Rust error types, such as a web page, can
+// report them.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_to_json(input: &str) -> String {
+    event_to_json(OomEvent::parse(input))
+}
+
+// The JSON parse_to_json() returns for a report, or for the error parsing it
+pub fn event_to_json(event: Result<OomEvent, Box<dyn Error>>) -> String {
+    let value = match event {
+        Ok(event) => serde_json::to_value(JsonReport::new(&event)),
+        Err(e) => Ok(serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "error": e.to_string(),
+        })),
+    };
+
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+// Parse every oom-killer report in a log and return the RSS of the largest commands at each, as
+// parseoom --all prints it, along with the time of each event. For the web page's chart of
+// which commands grew into the kill. Errors are returned as parse_to_json() returns them.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn timeline_to_json(input: &str) -> String {
+    let (chunks, _) = rollup::dedup_events(rollup::split_events(input));
+    if chunks.is_empty() {
+        let value = serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "error": "string 'invoked oom-killer' not found",
+        });
+        return value.to_string();
+    }
+
+    let events = chunks
+        .iter()
+        .map(|chunk| OomEvent::parse(chunk).ok())
+        .collect::<Vec<_>>();
+    let parsed = events.iter().map(Option::as_ref).collect::<Vec<_>>();
+    let timestamps = chunks
+        .iter()
+        .map(|chunk| rollup::EventSummary::new(chunk).timestamp)
+        .collect::<Vec<_>>();
+
+    let value = serde_json::json!({
+        "schema_version": SCHEMA_VERSION,
+        "timestamps": timestamps,
+        "timeline": rollup::command_timeline(&parsed, 10),
+    });
+
+    value.to_string()
+}
+
+// Return the text of the last oom-killer report in the log, from the invocation of the oom killer
+// to the end of the process list, just before the end of the report.
+#[instrument(level = "info", skip_all, fields(bytes = input.len()))]
+pub fn last_report(input: &str) -> Result<&str, Box<dyn Error>> {
+    // Searching backwards for the last invocation keeps the regex from having to scan the whole
+    // log, which matters for multi-gigabyte archives.
+    let i = input
+        .rfind("invoked oom-killer")
+        .ok_or("string 'invoked oom-killer' not found")?;
+    let start = input[..i].rfind('\n').map_or(0, |n| n + 1);
+    let end =
+        report_end(&input[start..]).ok_or("Could not match an oom kill message in this file")?;
+    debug!(start, end = start + end, "found the last report");
+
+    Ok(&input[i..start + end])
+}
+
+// The part of the log that tells of the boot the last report was logged in: from the boot's
+// "Linux version" line, or LOG_CONTEXT_BYTES before the report when that's nearer, to the end of
+// the report
+pub fn report_context(log: &str) -> &str {
+    const BOOT: &str = "Linux version ";
+
+    let start = log
+        .rfind("invoked oom-killer")
+        .map_or(0, |i| log[..i].rfind('\n').map_or(0, |n| n + 1));
+    let end = report_end(&log[start..]).map_or(log.len(), |end| start + end);
+    let mut from = start.saturating_sub(LOG_CONTEXT_BYTES);
+    while !log.is_char_boundary(from) {
+        from += 1;
+    }
+    if let Some(boot) = log[from..start].rfind(BOOT) {
+        from = log[..from + boot].rfind('\n').map_or(0, |n| n + 1);
+    }
+
+    &log[from..end]
+}
+
+// Consecutive lines from something other than the kernel that end a report without its
+// "Out of memory" line
+const NON_KERNEL_RUN: usize = 5;
+
+// Where the oom-killer report on the first line of text ends: at the end of the "Out of memory"
+// line that follows the process list, which names the task to kill, with the marker in any case
+// and after whatever tag a forwarder put before it.
+// A report whose marker was lost or reworded ends at the start of the next invocation of the
+// oom-killer, or of a run of NON_KERNEL_RUN lines logged by something other than the kernel,
+// told apart by the "kernel:" of syslog or the [uptime] of dmesg that the first line has. None
+// when the end hasn't been logged yet.
+pub fn report_end(text: &str) -> Option<usize> {
+    let end_re = Regex::new(OOM_END_RE).unwrap();
+    let first = text.lines().next().unwrap_or("");
+    let syslog = first.contains("kernel:");
+    let dmesg = !syslog && first.trim_start().starts_with('[');
+    let is_kernel = |line: &str| match (syslog, dmesg) {
+        (true, _) => line.contains("kernel:"),
+        (_, true) => line.trim_start().starts_with('['),
+        _ => true,
+    };
+
+    let mut offset = 0;
+    let mut run: Option<(usize, usize)> = None;
+    for (n, line) in text.split_inclusive('\n').enumerate() {
+        if end_re.is_match(line) {
+            return Some(offset + line.trim_end_matches(&['\r', '\n'][..]).len());
+        }
+        if n > 0 && line.contains("invoked oom-killer") {
+            return Some(run.map_or(offset, |(start, _)| start));
+        }
+
+        run = match run {
+            _ if is_kernel(line) => None,
+            Some((start, lines)) => Some((start, lines + 1)),
+            None => Some((offset, 1)),
+        };
+        if let Some((start, NON_KERNEL_RUN)) = run {
+            return Some(start);
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+// Return the cleaned text of the last oom-killer report in the log, for attaching to a bug
+// report. The end marker that clean_report() drops is put back, so the text parses again to the
+// same report.
+pub fn extract_report(input: &str) -> Result<String, Box<dyn Error>> {
+    let mut cleaned = clean_report(last_report(input)?);
+    cleaned.push_str("Out of memory\n");
+
+    Ok(cleaned)
+}
+
+// Clean up the oom kill report for ease of parsing
+pub fn clean_report(oom: &str) -> String {
+    clean_report_lines(oom).0
+}
+
+// Clean a report as clean_report() does, also returning the line of the report each line of the
+// cleaned text came from and the lines that were dropped
+fn clean_report_lines(oom: &str) -> (String, Vec<usize>, Vec<SkippedLine>) {
+    let mut cleaned = String::with_capacity(oom.len());
+    let mut lines = Vec::new();
+    let mut skipped = Vec::new();
+    let oom_end = Regex::new(PS_LIST_END_RE).unwrap();
+
+    // Strip out end of report summary and PID column brackets
+    for (i, line) in oom.lines().enumerate() {
+        // These patterns appear immediately after the end of the ps list.
+        // Do not include them in the new string so we know where to stop.
+        if oom_end.is_match(line) {
+            skipped.push(SkippedLine {
+                section: "cleaner",
+                line: i + 1,
+                text: line.to_string(),
+                reason: "marks the end of the process list".to_string(),
+            });
+            continue;
+        }
+
+        let s = line.replace('[', ""); // clean up PID entries
+        let s = s.replace(']', "");
+
+        cleaned.push_str(&s);
+        cleaned.push('\n');
+        lines.push(i);
+    }
+
+    (cleaned, lines, skipped)
+}
+
+// The line number in the log of the first line of the last oom-killer report, counting from 1
+pub fn report_start_line(log: &str) -> usize {
+    let start = log
+        .rfind("invoked oom-killer")
+        .and_then(|i| log[..i].rfind('\n').map(|n| n + 1))
+        .unwrap_or(0);
+
+    log[..start].matches('\n').count() + 1
+}
+
+// Read a count the kernel printed. One too large for a u64 can only come from a corrupted log,
+// and is kept as the largest count rather than lost, so the consistency check reports it.
+fn parse_count(digits: &str) -> Option<u64> {
+    match digits.parse::<u64>() {
+        Ok(count) => Some(count),
+        Err(_) if digits.bytes().all(|b| b.is_ascii_digit()) => Some(u64::MAX),
+        Err(_) => None,
+    }
+}
+
+// The first count captured by a regular expression
+fn first_count(s: &str, re: &str) -> Option<u64> {
+    parse_count(Regex::new(re).unwrap().captures(s)?.get(1)?.as_str())
+}
+
+// The size in KiB of a number of pages
+pub fn pages_kib(pages: u64) -> f64 {
+    pages as f64 * PAGE_SIZE as f64 / 1024.0
+}
+
+// Find the pages of RAM and the pages of it reserved, as the kernel counted them
+pub fn parse_meminfo_total_pages(s: &str) -> Option<(u64, u64)> {
+    const PAGES_RAM_RE: &str = r"(\d+) pages RAM";
+    const PAGES_RESERVED_RE: &str = r"(\d+) pages reserved";
+
+    Some((
+        first_count(s, PAGES_RAM_RE)?,
+        first_count(s, PAGES_RESERVED_RE)?,
+    ))
+}
+
+// Find total pages of RAM and return value in KiB
+pub fn parse_meminfo_total(s: &str) -> Option<f64> {
+    let (pages_ram, pages_reserved) = parse_meminfo_total_pages(s)?;
+
+    // subtract reserved pages from the RAM total, which may not agree with what other tools report
+    Some(pages_kib(pages_ram) - pages_kib(pages_reserved))
+}
+
+// Find free swap in kB, as the kernel printed it
+pub fn parse_meminfo_swap_kb(s: &str) -> Option<u64> {
+    const FREE_SWAP_RE: &str = r"Free swap\s+=\s+(\d+)";
+
+    first_count(s, FREE_SWAP_RE)
+}
+
+// Report free swap in KiB, which is what the kernel's kB are
+pub fn parse_meminfo_swap(s: &str) -> Option<f64> {
+    parse_meminfo_swap_kb(s).map(|kb| kb as f64)
+}
+
+// Find total swap in kB, as the kernel printed it
+pub fn parse_meminfo_total_swap_kb(s: &str) -> Option<u64> {
+    const TOTAL_SWAP_RE: &str = r"Total swap\s+=\s+(\d+)";
+
+    first_count(s, TOTAL_SWAP_RE)
+}
+
+// Report total swap in KiB
+pub fn parse_meminfo_total_swap(s: &str) -> Option<f64> {
+    parse_meminfo_total_swap_kb(s).map(|kb| kb as f64)
+}
+
+// Find the pages of unreclaimable slab
+pub fn parse_meminfo_slab_pages(s: &str) -> Option<u64> {
+    const UNRECLAIMABLE_SLAB_RE: &str = r"slab_unreclaimable:(\d+)";
+
+    // The first slab_unreclaimable entry in MemInfo contains the total for all zones, in pages
+    first_count(s, UNRECLAIMABLE_SLAB_RE)
+}
+
+// Report unreclaimable slab usage in KiB
+pub fn parse_meminfo_slab(s: &str) -> Option<f64> {
+    parse_meminfo_slab_pages(s).map(pages_kib)
+}
+
+// Find the pages of reclaimable slab
+pub fn parse_meminfo_reclaimable_slab_pages(s: &str) -> Option<u64> {
+    const RECLAIMABLE_SLAB_RE: &str = r"slab_reclaimable:(\d+)";
+
+    first_count(s, RECLAIMABLE_SLAB_RE)
+}
+
+// Report reclaimable slab usage in KiB. This is mostly dentry and inode caches, which the
+// kernel can shrink under pressure, unlike unreclaimable slab.
+pub fn parse_meminfo_reclaimable_slab(s: &str) -> Option<f64> {
+    parse_meminfo_reclaimable_slab_pages(s).map(pages_kib)
+}
+
+// Find the number of 2 MiB and 1 GiB huge pages allocated, summed across nodes
+// t.0 is the number of 2 MiB huge pages
+// t.1 is the number of 1 GiB huge pages
+pub fn parse_meminfo_hugepages_count(s: &str) -> Option<(u64, u64)> {
+    const HUGEPAGES_2MB_RE: &str = r"hugepages_total=(\d+).+?hugepages_size=2048kB";
+    const HUGEPAGES_1GB_RE: &str = r"hugepages_total=(\d+).+?hugepages_size=1048576kB";
+
+    let sum = |re: &str| {
+        Regex::new(re)
+            .unwrap()
+            .captures_iter(s)
+            .try_fold(0u64, |sum, caps| {
+                Some(sum.saturating_add(parse_count(&caps[1])?))
+            })
+    };
+
+    Some((sum(HUGEPAGES_2MB_RE)?, sum(HUGEPAGES_1GB_RE)?))
+}
+
+// The size in KiB of the 2 MiB and 1 GiB huge pages counted by parse_meminfo_hugepages_count()
+pub fn hugepages_kib((num_2_mb_hugepages, num_1_gb_hugepages): (u64, u64)) -> (f64, f64) {
+    (
+        num_2_mb_hugepages as f64 * 2048.0,
+        num_1_gb_hugepages as f64 * 1048576.0,
+    )
+}
+
+// Find and return size of 2 MiB and 1 GiB huge page allocations in KiB
+// t.0 is the sum of 2 MB huge pages in KiB
+// t.1 is the sum of 1 GB huge pages in KiB
+pub fn parse_meminfo_hugepages(s: &str) -> Option<(f64, f64)> {
+    parse_meminfo_hugepages_count(s).map(hugepages_kib)
+}
+
+// Find the huge pages that were reserved but not in use, summed across nodes, by their size
+// in kB
+pub fn parse_meminfo_hugepages_free_count(s: &str) -> Option<BTreeMap<u64, u64>> {
+    const HUGEPAGES_FREE_RE: &str = r"hugepages_free=(\d+).+?hugepages_size=(\d+)kB";
+
+    let re = Regex::new(HUGEPAGES_FREE_RE).unwrap();
+    let mut free = BTreeMap::new();
+    for caps in re.captures_iter(s) {
+        let pages = parse_count(&caps[1])?;
+        let count = free.entry(parse_count(&caps[2])?).or_insert(0u64);
+        *count = count.saturating_add(pages);
+    }
+
+    Some(free)
+}
+
+// The size in KiB of the huge pages counted by parse_meminfo_hugepages_free_count()
+pub fn hugepages_free_kib(free: &BTreeMap<u64, u64>) -> f64 {
+    free.iter()
+        .map(|(size, pages)| *pages as f64 * *size as f64)
+        .sum()
+}
+
+// Find the huge pages of either size that were reserved but not in use, in KiB, summed across
+// nodes
+pub fn parse_meminfo_hugepages_free(s: &str) -> Option<f64> {
+    parse_meminfo_hugepages_free_count(s).map(|free| hugepages_free_kib(&free))
+}
+
+// Find the pages of shared memory
+pub fn parse_meminfo_shared_pages(s: &str) -> Option<u64> {
+    const SHMEM_RE: &str = r"shmem:(\d+)";
+
+    first_count(s, SHMEM_RE)
+}
+
+// Report shared memory in KiB
+pub fn parse_meminfo_shared(s: &str) -> Option<f64> {
+    parse_meminfo_shared_pages(s).map(pages_kib)
+}
+
+// Find the pages isolated for migration or reclaim
+// t.0 is isolated anonymous pages
+// t.1 is isolated file-backed pages
+pub fn parse_meminfo_isolated_pages(s: &str) -> Option<(u64, u64)> {
+    // The summary line gives totals in pages; the per-node isolated(anon) values are in kB and
+    // aren't matched here
+    const ISOLATED_ANON_RE: &str = r"isolated_anon:(\d+)\b";
+    const ISOLATED_FILE_RE: &str = r"isolated_file:(\d+)\b";
+
+    Some((
+        first_count(s, ISOLATED_ANON_RE)?,
+        first_count(s, ISOLATED_FILE_RE)?,
+    ))
+}
+
+// Report pages isolated for migration or reclaim in KiB
+// t.0 is isolated anonymous memory
+// t.1 is isolated file-backed memory
+pub fn parse_meminfo_isolated(s: &str) -> Option<(f64, f64)> {
+    let (anon, file) = parse_meminfo_isolated_pages(s)?;
+
+    Some((pages_kib(anon), pages_kib(file)))
+}
+
+// Find the free pages of the whole machine, from the Mem-Info summary line:
+//
+//      free:13191 free_pcp:370 free_cma:0
+//
+// The per-zone free:8092kB values are in kB and don't match.
+pub fn parse_meminfo_free_pages(s: &str) -> Option<u64> {
+    const FREE_RE: &str = r"(?m)(?:^|\s)free:(\d+)(?:\s|$)";
+
+    first_count(s, FREE_RE)
+}
+
+// Report the free pages of the whole machine in KiB
+pub fn parse_meminfo_free(s: &str) -> Option<f64> {
+    parse_meminfo_free_pages(s).map(pages_kib)
+}
+
+// Free pages held in the per-cpu page lists, in KiB. Each CPU keeps a list of pages per zone to
+// allocate from without taking the zone lock; they aren't counted in the free pages, and only
+// the CPU that holds them allocates from them. cpus is the number of CPUs the report lists
+// pagesets for, which kernels before v4.8 do.
+#[derive(Serialize, JsonSchema)]
+pub struct PerCpuPages {
+    pub free_kib: f64,
+    pub cpus: Option<usize>,
+}
+
+// Find the pages in the per-cpu lists, from the Mem-Info summary line or, before v4.8, from the
+// per-cpu pagesets, ie:
+//
+//      free:13191 free_pcp:370 free_cma:0
+//
+//      Normal per-cpu:
+//      CPU    0: hi:  186, btch:  31 usd:  12
+//      CPU    1: hi:  186, btch:  31 usd:   8
+pub fn parse_pcp(s: &str) -> Option<PerCpuPages> {
+    const FREE_PCP_RE: &str = r"(?m)(?:^|\s)free_pcp:(\d+)(?:\s|$)";
+    const PAGESET_RE: &str = r"CPU\s+(\d+): hi:\s*\d+, btch:\s*\d+ usd:\s*(\d+)";
+
+    let mut cpus = BTreeSet::new();
+    let mut used = 0.0;
+    for caps in Regex::new(PAGESET_RE).unwrap().captures_iter(s) {
+        cpus.insert(caps[1].to_string());
+        used += caps[2].parse::<f64>().ok()?;
+    }
+
+    let summary = Regex::new(FREE_PCP_RE)
+        .unwrap()
+        .captures(s)
+        .and_then(|caps| caps[1].parse::<f64>().ok());
+    let pages = match (summary, cpus.is_empty()) {
+        (Some(pages), _) => pages,
+        (None, false) => used,
+        (None, true) => return None,
+    };
+
+    Some(PerCpuPages {
+        free_kib: (pages * 4096.0) / 1024.0,
+        cpus: Some(cpus.len()).filter(|n| *n > 0),
+    })
+}
+
+// Free memory within this multiple of the zones' min watermarks means RAM really ran out. The
+// kernel reclaims until free memory is back above the high watermark, 1.5 times min, so a
+// machine that is merely busy sits above it.
+pub const EXHAUSTED_WATERMARK_RATIO: f64 = 2.0;
+
+// Free memory below this share of RAM counts as exhausted when the zones' watermarks are missing
+pub const EXHAUSTED_FREE_RATIO: f64 = 0.02;
+
+// Whether RAM as a whole ran out, rather than an allocation failing on the watermark of the
+// zones or nodes it was restricted to, or for want of contiguous pages, while the machine still
+// had memory free
+pub fn ram_exhausted(free_kib: f64, total_ram_kib: f64, zones: &[Zone]) -> bool {
+    let min_kib = zones.iter().map(|z| z.min_kib).sum::<f64>();
+    if min_kib > 0.0 {
+        free_kib <= min_kib * EXHAUSTED_WATERMARK_RATIO
+    } else {
+        free_kib <= total_ram_kib * EXHAUSTED_FREE_RATIO
+    }
+}
+
+// Find the free pages in the contiguous memory allocator (CMA) area
+pub fn parse_meminfo_cma_pages(s: &str) -> Option<u64> {
+    // Skip the per-zone free_cma:0kB entries, the summary line is in pages
+    const FREE_CMA_RE: &str = r"free_cma:(\d+)\b";
+
+    first_count(s, FREE_CMA_RE)
+}
+
+// Report free pages in the contiguous memory allocator (CMA) area in KiB
+pub fn parse_meminfo_cma(s: &str) -> Option<f64> {
+    parse_meminfo_cma_pages(s).map(pages_kib)
+}
+
+// Swap backed by compressed RAM rather than a disk. zswap_kib is the compressed size of the
+// zswap pool and zswapped_kib the memory stored in it, from cgroup v2 memory stats.
+#[derive(Default, Serialize, JsonSchema)]
+pub struct CompressedSwap {
+    pub zram: bool,
+    pub zswap: bool,
+    pub zswap_kib: Option<f64>,
+    pub zswapped_kib: Option<f64>,
+}
+
+// Find signs of zram or zswap, ie:
+//
+//      Adding 8388604k swap on /dev/zram0.  Priority:100 extents:1 across:8388604k SSFS
+//      zswap: loaded using pool lzo/zbud
+//      zswap 104857600
+//
+// The patterns start with their literal text, not a \b, so the regex engine can search for it
+// first, which is what keeps a scan of a large log fast.
+pub fn parse_compressed_swap(s: &str) -> CompressedSwap {
+    const ZRAM_RE: &str = r"swap on /dev/zram\d+";
+    const ZSWAP_RE: &str = r"zswap: loaded using pool";
+    const ZSWAP_STAT_RE: &str = r"zswap (\d+)";
+    const ZSWAPPED_STAT_RE: &str = r"zswapped (\d+)";
+
+    // memory.stat values are in bytes
+    let stat_kib = |re: &str| -> Option<f64> {
+        let re = Regex::new(re).unwrap();
+        Some(re.captures(s)?.get(1)?.as_str().parse::<f64>().ok()? / 1024.0)
+    };
+
+    let zswap_kib = stat_kib(ZSWAP_STAT_RE);
+    let zswapped_kib = stat_kib(ZSWAPPED_STAT_RE);
+
+    CompressedSwap {
+        zram: Regex::new(ZRAM_RE).unwrap().is_match(s),
+        zswap: Regex::new(ZSWAP_RE).unwrap().is_match(s) || zswapped_kib.is_some_and(|k| k > 0.0),
+        zswap_kib,
+        zswapped_kib,
+    }
+}
+
+// Transparent huge page usage in KiB, summed across nodes
+#[derive(Serialize, JsonSchema)]
+pub struct Thp {
+    pub anon_kib: f64,
+    pub shmem_kib: f64,
+    pub file_kib: f64,
+}
+
+impl Thp {
+    // The usage found by parse_thp_kb()
+    pub fn from_kb((anon, shmem, file): (u64, u64, u64)) -> Thp {
+        Thp {
+            anon_kib: anon as f64,
+            shmem_kib: shmem as f64,
+            file_kib: file as f64,
+        }
+    }
+}
+
+// Find transparent huge page usage in the per-node lines, ie:
+//
+//      Node 0 ... shmem_thp: 0kB shmem_pmdmapped: 0kB anon_thp: 856064kB ...
+//
+// file_thp was added in v5.4 and is treated as zero when missing. Returns None if the kernel
+// doesn't report THP usage at all. The sums are in kB, as the kernel printed them:
+// t.0 is anonymous memory, t.1 shared memory and t.2 file-backed memory.
+pub fn parse_thp_kb(s: &str) -> Option<(u64, u64, u64)> {
+    const ANON_THP_RE: &str = r"\banon_thp:\s*(\d+)kB";
+    const SHMEM_THP_RE: &str = r"\bshmem_thp:\s*(\d+)kB";
+    const FILE_THP_RE: &str = r"\bfile_thp:\s*(\d+)kB";
+
+    let sum_kb = |re: &str| -> Option<u64> {
+        let re = Regex::new(re).unwrap();
+        let mut found = false;
+        let mut total = 0u64;
+
+        for caps in re.captures_iter(s) {
+            total = total.saturating_add(parse_count(&caps[1])?);
+            found = true;
+        }
+
+        found.then_some(total)
+    };
+
+    Some((
+        sum_kb(ANON_THP_RE)?,
+        sum_kb(SHMEM_THP_RE).unwrap_or(0),
+        sum_kb(FILE_THP_RE).unwrap_or(0),
+    ))
+}
+
+// Find transparent huge page usage in KiB, as parse_thp_kb() does
+pub fn parse_thp(s: &str) -> Option<Thp> {
+    parse_thp_kb(s).map(Thp::from_kb)
+}
+
+// Whether the report came from a virtual machine, and which memory balloon drivers the log
+// mentions. A balloon lets the hypervisor take RAM back from the guest, which can leave it short
+// of memory without anything inside the guest having grown.
+// missing_ram_kib is how far RAM fell short of the size the guest was provisioned with, when
+// --expected-ram gives it and the shortfall is more than reserved pages account for.
+#[derive(Default, Serialize, JsonSchema)]
+pub struct Virtualization {
+    pub hypervisor: Option<&'static str>,
+    pub balloon_drivers: Vec<String>,
+    pub missing_ram_kib: Option<f64>,
+}
+
+// How far below the expected RAM the report's total may be before the guest is taken to have
+// been given less: the kernel's reserved pages and firmware regions take a few percent
+pub const EXPECTED_RAM_TOLERANCE: f64 = 0.1;
+
+// Parse a size such as 16G, 512MiB or 1.5T into KiB. A number without a unit is in bytes.
+pub fn parse_size_kib(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let scale = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0 / 1024.0,
+        "K" | "KB" | "KIB" => 1.0,
+        "M" | "MB" | "MIB" => 1024.0,
+        "G" | "GB" | "GIB" => 1024.0 * 1024.0,
+        "T" | "TB" | "TIB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+
+    Some(number * scale)
+}
+
+// Identify the hypervisor from the hardware name in the report header, ie:
+//
+//      Hardware name: Red Hat KVM, BIOS 1.15.0 04/01/2014
+pub fn parse_hypervisor(s: &str) -> Option<&'static str> {
+    const HARDWARE_RE: &str = r"Hardware name: (.*)";
+    const HYPERVISORS: [(&str, &str); 7] = [
+        ("KVM", "KVM"),
+        ("QEMU", "KVM"),
+        ("VMware", "VMware"),
+        ("Microsoft Corporation Virtual Machine", "Hyper-V"),
+        ("Xen", "Xen"),
+        ("Amazon EC2", "Amazon EC2"),
+        ("Google Compute Engine", "Google Compute Engine"),
+    ];
+
+    let re = Regex::new(HARDWARE_RE).unwrap();
+    let hardware = re.captures(s)?.get(1)?.as_str();
+
+    HYPERVISORS
+        .iter()
+        .find(|(pattern, _)| hardware.contains(pattern))
+        .map(|(_, name)| *name)
+}
+
+// Find memory balloon drivers mentioned in a log. As in parse_compressed_swap(), there's no \b
+// before the names.
+pub fn parse_balloon_drivers(s: &str) -> Vec<String> {
+    const BALLOON_RE: &str =
+        r"(virtio_balloon|vmw_balloon|vmware_balloon|hv_balloon|xen[-_]balloon)\b";
+
+    let re = Regex::new(BALLOON_RE).unwrap();
+    let mut drivers: Vec<String> = Vec::new();
+
+    for caps in re.captures_iter(s) {
+        if !drivers.iter().any(|d| d == &caps[1]) {
+            drivers.push(caps[1].to_string());
+        }
+    }
+
+    drivers
+}
+
+// Count the tasks in the process list for each command, largest first
+pub fn task_counts(processes: &[ProcessEntry]) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for process in processes.iter() {
+        *counts.entry(&process.name).or_insert(0) += 1;
+    }
+
+    let mut counts = counts
+        .into_iter()
+        .map(|(command, n)| (command.to_string(), n))
+        .collect::<Vec<_>>();
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+
+    counts
+}
+
+// Daemons whose helper processes have names of their own, and the names of those helpers
+const PROCESS_FAMILIES: [(&str, &[&str]); 2] = [
+    (
+        "postfix",
+        &[
+            "master",
+            "pickup",
+            "qmgr",
+            "tlsmgr",
+            "smtpd",
+            "smtp",
+            "cleanup",
+            "trivial-rewrite",
+            "bounce",
+            "proxymap",
+            "showq",
+            "verify",
+            "scache",
+            "postscreen",
+        ],
+    ),
+    (
+        "dovecot",
+        &[
+            "anvil",
+            "config",
+            "log",
+            "stats",
+            "auth",
+            "imap",
+            "imap-login",
+            "pop3",
+            "pop3-login",
+            "lmtp",
+            "indexer",
+            "indexer-worker",
+        ],
+    ),
+];
+
+// Guess the program a task belongs to from its name, so that workers are counted with the
+// process that started them: "nginx: worker" and "kworker/0:1" lose everything from the
+// colon or slash, "gunicorn-worker-3" loses its worker suffix and number, and the helpers of
+// daemons such as postfix and dovecot are named after the daemon.
+pub fn parent_name(name: &str) -> &str {
+    let re = Regex::new(r"^(.+?)(?:[-_ ](?:worker|child|thread)s?)?(?:[-_#]\d+)?$").unwrap();
+
+    if let Some((family, _)) = PROCESS_FAMILIES
+        .iter()
+        .find(|(_, helpers)| helpers.contains(&name))
+    {
+        return family;
+    }
+
+    let base = name.split([':', '/']).next().unwrap().trim();
+    re.captures(base)
+        .and_then(|caps| caps.get(1))
+        .map_or(name, |m| m.as_str())
+}
+
+// Free memory and the min watermark of a single memory zone, in KiB. managed_kib is the memory
+// the zone hands out, when the report gives it. lowmem_reserve_kib is the memory the zone keeps
+// back from allocations that could have used a higher zone, indexed by the highest zone the
+// allocation may use; it's empty when the report doesn't list it.
+#[derive(Serialize, JsonSchema)]
+pub struct Zone {
+    pub node: u32,
+    pub name: String,
+    pub free_kib: f64,
+    pub min_kib: f64,
+    pub managed_kib: Option<f64>,
+    pub lowmem_reserve_kib: Vec<f64>,
+}
+
+// The order of the kernel's zones, which lowmem_reserve is indexed by. Zones the kernel wasn't
+// built with are left out, so the order differs between 32 and 64-bit kernels.
+const ZONES_64BIT: [&str; 5] = ["DMA", "DMA32", "Normal", "Movable", "Device"];
+const ZONES_32BIT: [&str; 4] = ["DMA", "Normal", "HighMem", "Movable"];
+
+// Return the index of a zone in the kernel's order of zones
+pub fn zone_index(name: &str, kernel_32bit: bool) -> Option<usize> {
+    if kernel_32bit {
+        ZONES_32BIT.iter().position(|z| *z == name)
+    } else {
+        ZONES_64BIT.iter().position(|z| *z == name)
+    }
+}
+
+// The names of the zones in the report, once each in the kernel's order, ie. DMA and DMA32 on
+// a small VM whose RAM all sits below 4 GiB
+pub fn zone_names(zones: &[Zone], kernel_32bit: bool) -> Vec<&str> {
+    let mut names: Vec<&str> = Vec::new();
+    for zone in zones {
+        if !names.contains(&zone.name.as_str()) {
+            names.push(&zone.name);
+        }
+    }
+    names.sort_by_key(|name| zone_index(name, kernel_32bit).unwrap_or(usize::MAX));
+
+    names
+}
+
+// Find the per-node zone lines and the lowmem_reserve line after each, in pages, ie:
+//
+//      Node 0 DMA free:8092kB min:356kB low:444kB high:532kB ... managed:15908kB ...
+//      lowmem_reserve[]: 0 1935 1935 1935 1935
+//      Node 0 DMA32 free:44672kB boost:0kB min:44696kB low:55868kB high:67040kB ...
+//
+// Kernels built without NUMA support before v4.8 leave out the node, which is then node 0, and
+// kernels before v5.0 leave out boost.
+pub fn parse_zones(cleaned: &str) -> Vec<Zone> {
+    const ZONE_RE: &str = r"(?:Node (\d+) )?\b(\w+) free:(\d+)kB (?:boost:\d+kB )?min:(\d+)kB";
+    const MANAGED_RE: &str = r"\bmanaged:(\d+)kB";
+    const LOWMEM_RESERVE_RE: &str = r"lowmem_reserve(?:\[\])?:((?: \d+)+)";
+
+    let re = Regex::new(ZONE_RE).unwrap();
+    let managed_re = Regex::new(MANAGED_RE).unwrap();
+    let reserve_re = Regex::new(LOWMEM_RESERVE_RE).unwrap();
+
+    let mut zones: Vec<Zone> = Vec::new();
+    for line in cleaned.lines() {
+        if let Some(caps) = re.captures(line) {
+            let zone = (|| {
+                Some(Zone {
+                    node: caps.get(1).map_or(Some(0), |m| m.as_str().parse().ok())?,
+                    name: caps[2].to_string(),
+                    free_kib: caps[3].parse::<f64>().ok()?,
+                    min_kib: caps[4].parse::<f64>().ok()?,
+                    managed_kib: managed_re
+                        .captures(line)
+                        .and_then(|m| m[1].parse::<f64>().ok()),
+                    lowmem_reserve_kib: Vec::new(),
+                })
+            })();
+            zones.extend(zone);
+        } else if let Some(caps) = reserve_re.captures(line) {
+            // Each zone is followed by its own reserves
+            if let Some(zone) = zones.last_mut().filter(|z| z.lowmem_reserve_kib.is_empty()) {
+                zone.lowmem_reserve_kib = caps[1]
+                    .split_whitespace()
+                    .filter_map(|pages| pages.parse::<f64>().ok())
+                    .map(|pages| pages * 4.0)
+                    .collect();
+            }
+        }
+    }
+
+    zones
+}
+
+// The zone modifier bits of a gfp_mask. They have been stable since before v3.10.
+const GFP_DMA: u64 = 0x01;
+const GFP_HIGHMEM: u64 = 0x02;
+const GFP_DMA32: u64 = 0x04;
+
+// Return the gfp flags of the failing allocation
+pub fn parse_gfp_mask(cleaned: &str) -> Option<u64> {
+    const GFP_MASK_RE: &str = r"invoked oom-killer: gfp_mask=0x([0-9a-f]+)";
+
+    let re = Regex::new(GFP_MASK_RE).unwrap();
+
+    u64::from_str_radix(re.captures(cleaned)?.get(1)?.as_str(), 16).ok()
+}
+
+// Return the zone the failing allocation was restricted to, if its gfp flags required DMA or
+// DMA32 memory.
+pub fn parse_gfp_zone(cleaned: &str) -> Option<&'static str> {
+    let gfp_mask = parse_gfp_mask(cleaned)?;
+
+    if gfp_mask & GFP_DMA != 0 {
+        Some("DMA")
+    } else if gfp_mask & GFP_DMA32 != 0 {
+        Some("DMA32")
+    } else {
+        None
+    }
+}
+
+// Return the order of the failing allocation, ie. 2^order contiguous pages were requested:
+//
+//      invoked oom-killer: gfp_mask=0x100cca(GFP_HIGHUSER_MOVABLE), order=0, oom_score_adj=0
+//
+pub fn parse_alloc_order(cleaned: &str) -> Option<u32> {
+    const ORDER_RE: &str = r"invoked oom-killer: .*\border=(\d+)";
+
+    let re = Regex::new(ORDER_RE).unwrap();
+
+    re.captures(cleaned)?.get(1)?.as_str().parse().ok()
+}
+
+// The free memory a zone needs for an allocation limited to gfp_zone to be taken from it: its
+// min watermark plus, for a zone below gfp_zone, the reserve it keeps back from such allocations
+pub fn zone_threshold_kib(zone: &Zone, gfp_zone: &str, kernel_32bit: bool) -> f64 {
+    let reserve = zone_index(gfp_zone, kernel_32bit)
+        .and_then(|i| zone.lowmem_reserve_kib.get(i))
+        .copied()
+        .unwrap_or(0.0);
+
+    zone.min_kib + reserve
+}
+
+// True when every zone the allocation could use was below its watermark while some zone it
+// couldn't use still had memory to spare. The oom-killer ran because of the zone restriction,
+// not because the machine was out of memory. A zone below the required one counts as usable
+// only above its lowmem_reserve for the required zone. Without the reserves in the report, DMA
+// is assumed to be reserved from LowMem allocations on a 32-bit kernel.
+pub fn lowmem_exhausted(zones: &[Zone], gfp_zone: &str, kernel_32bit: bool) -> bool {
+    let required = match zone_index(gfp_zone, kernel_32bit) {
+        Some(i) => i,
+        None => return false,
+    };
+    let (usable, others): (Vec<&Zone>, Vec<&Zone>) = zones
+        .iter()
+        .filter(|z| !z.lowmem_reserve_kib.is_empty() || gfp_zone != "Normal" || z.name != "DMA")
+        .partition(|z| {
+            zone_index(&z.name, kernel_32bit).is_some_and(|i| {
+                i == required || (i < required && !z.lowmem_reserve_kib.is_empty())
+            })
+        });
+
+    usable.iter().any(|z| z.name == gfp_zone)
+        && usable
+            .iter()
+            .all(|z| z.free_kib < zone_threshold_kib(z, gfp_zone, kernel_32bit))
+        && others.iter().any(|z| z.free_kib > z.min_kib)
+}
+
+// The split of RAM on a 32-bit kernel between LowMem, which the kernel keeps mapped and
+// allocates its own memory from, and HighMem, which only user pages and the page cache can use.
+// Sizes are in KiB.
+#[derive(Serialize, JsonSchema)]
+pub struct HighMem {
+    pub lowmem_kib: f64,
+    pub lowmem_free_kib: f64,
+    pub highmem_kib: f64,
+    pub highmem_free_kib: f64,
+}
+
+// Return the LowMem/HighMem split when the report has a HighMem zone, from the zones and the
+// HighMem line of the memory totals:
+//
+//      557826 pages HighMem/MovableOnly
+//
+pub fn parse_highmem(cleaned: &str, zones: &[Zone], total_ram_kib: f64) -> Option<HighMem> {
+    const HIGHMEM_PAGES_RE: &str = r"(\d+) pages HighMem/MovableOnly";
+
+    if !zones.iter().any(|z| z.name == "HighMem") {
+        return None;
+    }
+
+    let re = Regex::new(HIGHMEM_PAGES_RE).unwrap();
+    let highmem_kib = re.captures(cleaned)?.get(1)?.as_str().parse::<f64>().ok()? * 4.0;
+    let free_kib = |highmem: bool| {
+        zones
+            .iter()
+            .filter(|z| (z.name == "HighMem") == highmem)
+            .map(|z| z.free_kib)
+            .sum()
+    };
+
+    Some(HighMem {
+        lowmem_kib: (total_ram_kib - highmem_kib).max(0.0),
+        lowmem_free_kib: free_kib(false),
+        highmem_kib,
+        highmem_free_kib: free_kib(true),
+    })
+}
+
+// True when the allocation was limited to LowMem: on a 32-bit kernel, any allocation without
+// __GFP_HIGHMEM, which includes every allocation the kernel makes for itself
+pub fn lowmem_only(gfp_mask: u64) -> bool {
+    gfp_mask & (GFP_HIGHMEM | GFP_DMA | GFP_DMA32) == 0
+}
+
+// True when the report comes from a 32-bit kernel: it has a HighMem zone, an x86 register dump
+// with EIP rather than RIP, or a kernel release built for a 32-bit architecture, ie:
+//
+//      CPU: 1 PID: 3021 Comm: find Not tainted 3.16.0-4-686-pae #1 Debian 3.16.43-2+deb8u5
+//      CPU: 0 PID: 871 Comm: java Not tainted 2.6.32-754.el6.i686 #1
+pub fn parse_kernel_32bit(cleaned: &str) -> bool {
+    const KERNEL_32BIT_RE: &str = r"(?m)\bHighMem free:|^\s*EIP:|Comm: .*\d+\.\d+\S*(?:i[3-6]86|686(?:-pae)?|armv[67]\w*|armmp)\b";
+
+    Regex::new(KERNEL_32BIT_RE).unwrap().is_match(cleaned)
+}
+
+// Return the NUMA nodes the task was allowed to allocate from, from its cpuset line or, since
+// v4.19, the oom-kill summary line:
+//
+//      java cpuset=/ mems_allowed=0-1
+//      oom-kill:constraint=CONSTRAINT_NONE,nodemask=(null),cpuset=/,mems_allowed=0,...
+pub fn parse_mems_allowed(oom: &str) -> Option<Vec<u32>> {
+    const MEMS_ALLOWED_RE: &str = r"mems_allowed=([\d,-]+)";
+    // The kernel's limit on NUMA nodes, CONFIG_NODES_SHIFT=10
+    const MAX_NUMNODES: u32 = 1024;
+
+    let re = Regex::new(MEMS_ALLOWED_RE).unwrap();
+    let list = re.captures(oom)?.get(1)?.as_str();
+
+    let mut nodes = Vec::new();
+    for range in list.split(',').filter(|r| !r.is_empty()) {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let (first, last) = (first.parse::<u32>().ok()?, last.parse::<u32>().ok()?);
+        if last >= MAX_NUMNODES {
+            return None;
+        }
+        nodes.extend(first..=last);
+    }
+
+    Some(nodes)
+}
+
+// The nodes with memory zones in the report, in order
+pub fn online_nodes(zones: &[Zone]) -> Vec<u32> {
+    let mut nodes = zones.iter().map(|z| z.node).collect::<Vec<_>>();
+    nodes.sort_unstable();
+    nodes.dedup();
+    nodes
+}
+
+// Format a list of nodes the way the kernel does, ie. "0-1,3"
+pub fn format_node_list(nodes: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &node in nodes {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == node => *last = node,
+            _ => ranges.push((node, node)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Find the largest unreclaimable slab caches. Return the name, used, and total column headers
+// along with the slab entries sorted by total size, largest first. Sizes are in KB.
+pub fn parse_unreclaimable_slab(cleaned: &str) -> Option<(Vec<String>, Vec<Vec<String>>)> {
+    parse_unreclaimable_slab_rows(cleaned).map(|(header, slabs, _)| (header, slabs))
+}
+
+// Parse the unreclaimable slab table as parse_unreclaimable_slab() does, also returning the rows
+// that were dropped. The line of a dropped row is that of the cleaned text, counting from 0.
+#[allow(clippy::type_complexity)]
+pub fn parse_unreclaimable_slab_rows(
+    cleaned: &str,
+) -> Option<(Vec<String>, Vec<Vec<String>>, Vec<SkippedRow>)> {
+    // Starting in v4.15 the kernel will report the total size of all unreclaimable slabs if
+    // unreclaimable slab usage is greater than user memory. This report is similar to the
+    // process list and is sorted the same way.
+    //
+    // [261004.638833] Unreclaimable slab info:
+    // [261004.641825] Name                      Used          Total
+    // [261004.645467] nfs_commit_data           15KB         15KB
+    // --->8
+    // [261005.268039] kmem_cache               118KB        118KB
+    // [261005.271794] Tasks state (memory values in pages):
+
+    const SLAB_INFO_RE: &str = r"(?sx:      # allow . to match \n; ignore whitespace
+        (Unreclaimable\sslab\sinfo:)
+        (.*?Name\s+Used\s+Total)            # match header
+        (.*KB)                              # match lines with a slab entry
+        (.*Tasks\sstate)                    # match end of slab list
+        )";
+
+    let re: Regex = Regex::new(SLAB_INFO_RE).unwrap();
+
+    // Return early if unreclaimable slab wasn't reported by the oom-killer
+    let caps = re.captures(cleaned)?;
+    let slab_header = caps.get(2)?.as_str().trim();
+
+    let slab_header_vec = slab_header
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    let slab_name_col = slab_header_vec.iter().position(|x| x == "Name")?;
+    let header = slab_header_vec
+        .get(slab_name_col..slab_name_col + 3)?
+        .to_vec();
+
+    let slabs = caps.get(3)?;
+    let first_line = cleaned[..slabs.start()].matches('\n').count();
+
+    // Keep only the name, used, and total columns of well-formed entries. The first line is what
+    // follows the header on its line.
+    let mut slab_vec = Vec::new();
+    let mut skipped = Vec::new();
+    for (i, text) in slabs.as_str().lines().enumerate() {
+        let line = text
+            .replace("KB", "")
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if line.is_empty() {
+            continue;
+        }
+        let reason = match line.get(slab_name_col + 2) {
+            Some(total) if total.parse::<i64>().is_ok() => {
+                slab_vec.push(line[slab_name_col..slab_name_col + 3].to_vec());
+                continue;
+            }
+            Some(total) => format!("total {:?} is not a number", total),
+            None => format!("{} columns, expected {}", line.len(), slab_name_col + 3),
+        };
+        skipped.push(SkippedRow {
+            line: first_line + i,
+            text: text.trim().to_string(),
+            reason,
+        });
+    }
+
+    slab_vec
+        .sort_by(|a, b| (b[2].parse::<i64>().unwrap_or(0)).cmp(&a[2].parse::<i64>().unwrap_or(0)));
+
+    Some((header, slab_vec, skipped))
+}
+
+// Split the process list header into a vector. Return the ps header as a vector along with the
+// position of the pid column.
+pub fn parse_ps_header(cleaned: &str) -> Option<(Vec<String>, usize)> {
+    // Split each line of the ps string at whitespace and add the line to a vector of strings.
+    //
+    //      [
+    //        ["Dec", "20", "03:17:52", "localhost", "kernel:", "75669.642775", "199", "0",
+    //          "199", "14838", "226", "102400", "14", "-250", "systemd-journal"],
+    //        ...
+    //      ]
+    //
+    let re = Regex::new(PS_LIST_RE).unwrap();
+    let ps_header = re.captures(cleaned)?.get(1)?.as_str().trim();
+    let header_vec = ps_header
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let pid_col = header_vec.iter().position(|x| x == "pid")?;
+
+    // pid through name must all be present, or the column offsets used elsewhere are meaningless
+    if header_vec.len() < pid_col + 9 {
+        return None;
+    }
+
+    Some((header_vec, pid_col))
+}
+
+// The columns of the process list the parser doesn't know, as positions counted from the pid
+// column of the header. Some kernels add their own between pid and name, such as nr_pmds from
+// v4.0 to v4.14:
+//
+//      [  pid  ]   uid  tgid total_vm      rss nr_ptes nr_pmds swapents oom_score_adj name
+//
+// Their values are kept in ProcessEntry.extra, so the other columns still line up.
+pub fn parse_extra_columns(header: &[String]) -> Vec<usize> {
+    const KNOWN: [&str; 10] = [
+        "pid",
+        "uid",
+        "tgid",
+        "total_vm",
+        "rss",
+        "pgtables_bytes",
+        "nr_ptes",
+        "swapents",
+        "oom_score_adj",
+        "name",
+    ];
+
+    header
+        .iter()
+        .enumerate()
+        .take(header.len().saturating_sub(1))
+        .filter(|(_, h)| !KNOWN.contains(&h.as_str()))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Capture the values in the process list after the header, including the surrounding log metadata,
+// and return the list as a &str.
+pub fn parse_ps_list(cleaned: &str) -> Option<&str> {
+    // We're keeping the ps entry lines, including log entry noise.
+    //
+    //      "Dec 20 03:17:52 localhost kernel: 75669.642775     199     0   199    14838 \n
+    //          226   102400       14          -250 systemd-journal"
+    //
+    let re = Regex::new(PS_LIST_RE).unwrap();
+
+    if let Some(x) = re.captures(cleaned) {
+        let ps = x.get(2)?.as_str().trim();
+        Some(ps)
+    } else {
+        None
+    }
+}
+
+// Find the process list from the shape of its rows, for when its header is missing or has been
+// localized by a vendor kernel so that parse_ps_header() can't find it. A row is eight numbers,
+// pid through oom_score_adj, followed by the name:
+//
+//      Dec 20 03:17:52 localhost kernel: 75669.642775     199     0   199    14838      226   102400       14          -250 systemd-journal
+//
+// Kernels from v4.0 to v4.14 print a ninth, nr_pmds, after nr_ptes, which is left out. Returns the
+// header the kernel would have printed for the rows, from the pid column on, and the processes.
+pub fn parse_ps_rows(cleaned: &str) -> Option<(Vec<String>, Vec<ProcessEntry>)> {
+    const PS_ROW_RE: &str = r"(?m)(?:^|\s)(\d+)\s+(\d+)\s+(\d+)\s+(\d+)\s+(\d+)\s+(\d+)\s+(?:(\d+)\s+)?(\d+)\s+(-?\d+)\s+(\S.*?)\s*$";
+
+    let re = Regex::new(PS_ROW_RE).unwrap();
+    let mut nr_pmds = false;
+    let processes = re
+        .captures_iter(cleaned)
+        .filter_map(|caps| {
+            nr_pmds |= caps.get(7).is_some();
+            let fields = [1, 2, 3, 4, 5, 6, 8, 9, 10]
+                .iter()
+                .map(|&i| caps[i].to_string())
+                .collect::<Vec<_>>();
+            ProcessEntry::from_fields(&fields, &[]).ok()
+        })
+        .collect::<Vec<_>>();
+
+    if processes.is_empty() {
+        return None;
+    }
+
+    // Without a header, page table sizes in bytes are told from a count of pages by being whole
+    // pages
+    let pgtables_bytes = !nr_pmds
+        && processes
+            .iter()
+            .all(|p| p.pgtables > 0 && p.pgtables % PAGE_SIZE == 0);
+    let pgtables = if pgtables_bytes {
+        "pgtables_bytes"
+    } else {
+        "nr_ptes"
+    };
+    let header = [
+        "pid",
+        "uid",
+        "tgid",
+        "total_vm",
+        "rss",
+        pgtables,
+        "swapents",
+        "oom_score_adj",
+        "name",
+    ];
+
+    Some((header.iter().map(|h| h.to_string()).collect(), processes))
+}
+
+// Return the size in bytes of the unit the process list reports total_vm, rss, and swapents in.
+// Most kernels use pages and say so in the line preceding the process list:
+//
+//      Tasks state (memory values in pages):
+//
+// but some vendor kernels report kB instead. Older kernels print no note at all and use pages.
+// Return None if the unit isn't stated or isn't recognized.
+pub fn parse_task_unit(cleaned: &str) -> Option<i64> {
+    const TASKS_STATE_RE: &str = r"Tasks state \(memory values in (\w+)\)";
+
+    let re = Regex::new(TASKS_STATE_RE).unwrap();
+    let unit = re.captures(cleaned)?.get(1)?.as_str().to_lowercase();
+
+    match unit.as_str() {
+        "pages" => Some(PAGE_SIZE),
+        "kb" | "kib" | "kilobytes" => Some(1024),
+        "bytes" => Some(1),
+        _ => None,
+    }
+}
+
+// One row of the process list. Memory values are in the unit the list states, as returned by
+// parse_task_unit(); pgtables is in bytes when the header names it pgtables_bytes (since v4.15)
+// and in pages when it names it nr_ptes. extra holds the values of the columns found by
+// parse_extra_columns(), in the order of the header.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub uid: u32,
+    pub tgid: u32,
+    pub total_vm: i64,
+    pub rss: i64,
+    pub pgtables: i64,
+    pub swapents: i64,
+    pub oom_score_adj: i64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<i64>,
+}
+
+impl ProcessEntry {
+    // Read a row from its fields, starting at the pid column. The kernel has printed the same
+    // columns in the same order since v3.10, only renaming nr_ptes to pgtables_bytes:
+    //
+    //      [  pid  ]   uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name
+    //
+    // A row that can't be read gives the reason, ie. "could not parse rss value 'o226'". An
+    // oom_score_adj outside of -1000 to 1000 means the columns were misread. The columns at
+    // the positions in extra, as from parse_extra_columns(), are set aside first.
+    fn from_fields(fields: &[String], extra: &[usize]) -> Result<ProcessEntry, String> {
+        const COLUMNS: [&str; 9] = [
+            "pid",
+            "uid",
+            "tgid",
+            "total_vm",
+            "rss",
+            "pgtables",
+            "swapents",
+            "oom_score_adj",
+            "name",
+        ];
+
+        if fields.len() < COLUMNS.len() + extra.len() {
+            return Err(format!(
+                "{} columns, expected {}",
+                fields.len(),
+                COLUMNS.len() + extra.len()
+            ));
+        }
+        let mut extra_values = Vec::new();
+        for &i in extra {
+            let value = fields[i].parse::<i64>();
+            extra_values.push(value.map_err(|_| format!("could not parse value '{}'", fields[i]))?);
+        }
+        let fields = fields
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !extra.contains(i))
+            .map(|(_, field)| field.clone())
+            .collect::<Vec<_>>();
+        let error = |i: usize| format!("could not parse {} value '{}'", COLUMNS[i], fields[i]);
+        let number = |i: usize| fields[i].parse::<i64>().map_err(|_| error(i));
+        let id = |i: usize| fields[i].parse::<u32>().map_err(|_| error(i));
+
+        let oom_score_adj = number(7)?;
+        if !(OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(&oom_score_adj) {
+            return Err(format!(
+                "oom_score_adj value '{}' out of range {} to {}",
+                fields[7], OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX
+            ));
+        }
+
+        Ok(ProcessEntry {
+            pid: id(0)?,
+            uid: id(1)?,
+            tgid: id(2)?,
+            total_vm: number(3)?,
+            rss: number(4)?,
+            pgtables: number(5)?,
+            swapents: number(6)?,
+            oom_score_adj,
+            // The name is the last column, and may have spaces in it, ie. "tuned: profile a"
+            name: fields[8..].join(" "),
+            extra: extra_values,
+        })
+    }
+}
+
+// A process list row that couldn't be read: the line of the list it starts on, counting from 0,
+// the row as the parser saw it, and why it was skipped
+#[derive(Debug, PartialEq, Eq)]
+pub struct SkippedRow {
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+// Read the process list into a ProcessEntry per task. Rows that are too short to hold every
+// column or that have a non-numeric value are dropped, since corrupted logs are not unusual in
+// the presence of memory pressure. The dropped rows are returned alongside the processes.
+// extra is the columns from parse_extra_columns().
+pub fn parse_processes(
+    ps: &str,
+    pid_col: usize,
+    extra: &[usize],
+) -> (Vec<ProcessEntry>, Vec<SkippedRow>) {
+    let columns = pid_col + 9 + extra.len();
+    let mut rows: Vec<(usize, Vec<String>)> = Vec::new();
+
+    // Remote syslog collectors may wrap long kernel lines, splitting a row across two or more
+    // lines and sometimes marking the break with a trailing backslash:
+    //
+    //      Dec 20 03:17:52 localhost kernel: 75669.642775     199     0   199    14838 \
+    //          226   102400       14          -250 systemd-journal
+    //
+    // A fragment with too few columns that follows another incomplete row is joined to it.
+    for (i, line) in ps.lines().enumerate() {
+        let line = line.trim_end().trim_end_matches('\\');
+        let fields = line
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        if fields.is_empty() {
+            continue;
+        }
+
+        match rows.last_mut() {
+            Some((_, last)) if last.len() < columns && fields.len() < columns => {
+                last.extend(fields)
+            }
+            _ => rows.push((i, fields)),
+        }
+    }
+
+    let mut processes = Vec::new();
+    let mut skipped = Vec::new();
+    for (line, row) in rows {
+        let process = match row.get(pid_col..) {
+            Some(fields) => ProcessEntry::from_fields(fields, extra),
+            None => Err(format!("{} columns, expected {}", row.len(), columns)),
+        };
+        match process {
+            Ok(process) => processes.push(process),
+            Err(reason) => skipped.push(SkippedRow {
+                line,
+                text: row.join(" "),
+                reason,
+            }),
+        }
+    }
+
+    (processes, skipped)
+}
+
+// Sum the RSS of the processes by command and return a map of commands -> RSS in bytes.
+// task_unit is the size in bytes of the values in the process list, as returned by
+// parse_task_unit().
+pub fn top_consumers(
+    processes: &[ProcessEntry],
+    task_unit: i64,
+) -> std::collections::BTreeMap<String, i64> {
+    // Iterate over each line in the matrix to create a map
+    // of unique commands with their total RSS usage.
+    //
+    //      {"agetty": 241664, "anvil": 774144, ...}
+    //
+    // A corrupted log can have sizes too large to count in bytes, which saturate rather than
+    // overflow so the consistency check can report them.
+    let mut commands: BTreeMap<String, i64> = BTreeMap::new();
+
+    for process in processes.iter() {
+        let rss = commands.entry(process.name.clone()).or_insert(0);
+        *rss = rss.saturating_add(process.rss.saturating_mul(task_unit));
+    }
+
+    commands
+}
+
+// Find the pid of the task the kernel chose to kill. Since v4.19 the oom-kill summary line
+// following the process list names it:
+//
+//      oom-kill:constraint=CONSTRAINT_NONE,...,task=clamd,pid=517,uid=111
+//
+// Older kernels only name it on the "Out of memory" line that ends the report, or the first
+// line logged as a task is killed, which is the chosen one when memory.oom.group takes the rest
+// of its cgroup along:
+//
+//      Out of memory: Kill process 23111 (java) score 873 or sacrifice child
+//      Killed process 23111 (java) total-vm:15811552kB, anon-rss:14404492kB, ...
+//
+pub fn parse_victim_pid(oom: &str) -> Option<String> {
+    const VICTIM_RE: &str = r"oom-kill:.*\btask=.*?,pid=(\d+)";
+    const KILLED_RE: &str = r"Kill(?:ed)? process (\d+)";
+
+    let captures = Regex::new(VICTIM_RE)
+        .unwrap()
+        .captures(oom)
+        .or_else(|| Regex::new(KILLED_RE).unwrap().captures(oom))?;
+
+    Some(captures.get(1)?.as_str().to_string())
+}
+
+// Find the memory cgroup of the task the kernel chose to kill, from the same summary line:
+//
+//      oom-kill:...,task_memcg=/system.slice/clamav-daemon.service,task=clamd,pid=517,uid=111
+//
+pub fn parse_victim_memcg(oom: &str) -> Option<String> {
+    const VICTIM_MEMCG_RE: &str = r"oom-kill:.*\btask_memcg=([^,]*),task=";
+
+    let re = Regex::new(VICTIM_MEMCG_RE).unwrap();
+
+    Some(re.captures(oom)?.get(1)?.as_str().to_string())
+}
+
+// The kernel's badness heuristic for a single process
+#[derive(Serialize)]
+pub struct Badness {
+    pub pid: String,
+    pub name: String,
+    pub oom_score_adj: i64,
+    pub points: i64,
+    pub oom_score: i64,
+}
+
+// Approximate the badness score the kernel assigned to each process and return them ranked from
+// most to least likely victim. This follows oom_badness() in mm/oom_kill.c:
+//
+//      points = rss + swapents + page tables (in pages)
+//      points += oom_score_adj * total_pages / 1000
+//
+// where total_pages is RAM plus swap. task_unit is the size in bytes of the values in the process
+// list, as returned by parse_task_unit(). Processes with oom_score_adj -1000 are never selected and
+// are left out. oom_score normalizes points to the 0-1000 scale shown in /proc/<pid>/oom_score.
+pub fn badness_ranking(
+    processes: &[ProcessEntry],
+    ps_header: &[String],
+    task_unit: i64,
+    total_pages: f64,
+) -> Vec<Badness> {
+    // Since v4.15 page tables are reported in bytes, older kernels report nr_ptes in pages
+    let pgtables_in_bytes = ps_header.iter().any(|h| h == "pgtables_bytes");
+
+    let mut ranking = processes
+        .iter()
+        .filter_map(|process| {
+            let oom_score_adj = process.oom_score_adj;
+
+            if oom_score_adj == OOM_SCORE_ADJ_MIN {
+                return None;
+            }
+
+            let pages = |value: i64| value.saturating_mul(task_unit) / PAGE_SIZE;
+            let pgtables = if pgtables_in_bytes {
+                process.pgtables / PAGE_SIZE
+            } else {
+                process.pgtables
+            };
+
+            let mut points = pages(process.rss)
+                .saturating_add(pages(process.swapents))
+                .saturating_add(pgtables);
+            points = points.saturating_add((oom_score_adj as f64 * total_pages / 1000.0) as i64);
+            let points = points.max(1);
+
+            Some(Badness {
+                pid: process.pid.to_string(),
+                name: process.name.clone(),
+                oom_score_adj,
+                points,
+                oom_score: ((points as f64 * 1000.0 / total_pages) as i64).min(1000),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    ranking.sort_by_key(|b| std::cmp::Reverse(b.points));
+
+    ranking
+}
+
+// Processes and RSS (in bytes) sharing a range of oom_score_adj values
+#[derive(Serialize)]
+pub struct OomScoreAdjBand {
+    pub label: &'static str,
+    pub processes: usize,
+    pub rss: i64,
+}
+
+// Summarize how many processes, and how much RSS, sit in each oom_score_adj band. The kernel
+// never selects a task with oom_score_adj -1000, prefers tasks with positive values, and avoids
+// tasks with negative values, so this explains much of the victim selection.
+pub fn oom_score_adj_bands(processes: &[ProcessEntry], task_unit: i64) -> Vec<OomScoreAdjBand> {
+    let mut bands = ["-1000 (exempt)", "negative", "0", "positive"]
+        .iter()
+        .map(|label| OomScoreAdjBand {
+            label,
+            processes: 0,
+            rss: 0,
+        })
+        .collect::<Vec<_>>();
+
+    for process in processes.iter() {
+        let band = match process.oom_score_adj {
+            OOM_SCORE_ADJ_MIN => 0,
+            i64::MIN..=-1 => 1,
+            0 => 2,
+            _ => 3,
+        };
+
+        bands[band].processes += 1;
+        bands[band].rss = bands[band]
+            .rss
+            .saturating_add(process.rss.saturating_mul(task_unit));
+    }
+
+    bands
+}
+
+// Processes and RSS (in bytes) in a range of RSS sizes, and the RSS of every process up to and
+// including the range
+#[derive(Serialize)]
+pub struct RssBucket {
+    pub label: &'static str,
+    pub processes: usize,
+    pub rss: i64,
+    pub cumulative_rss: i64,
+}
+
+// The upper bounds of the RSS histogram's ranges, in bytes
+const RSS_BUCKETS: [(&str, i64); 4] = [
+    ("< 10 MiB", 10 << 20),
+    ("10-100 MiB", 100 << 20),
+    ("100 MiB-1 GiB", 1 << 30),
+    ("> 1 GiB", i64::MAX),
+];
+
+// Count processes, and their RSS, by the size of their RSS. Memory held by a few giants calls
+// for a look at those processes, memory spread across thousands of small tasks for a look at
+// what started them.
+pub fn rss_histogram(processes: &[ProcessEntry], task_unit: i64) -> Vec<RssBucket> {
+    let mut buckets = RSS_BUCKETS
+        .iter()
+        .map(|(label, _)| RssBucket {
+            label,
+            processes: 0,
+            rss: 0,
+            cumulative_rss: 0,
+        })
+        .collect::<Vec<_>>();
+
+    for process in processes.iter() {
+        let rss = process.rss.saturating_mul(task_unit);
+        let bucket = RSS_BUCKETS
+            .iter()
+            .position(|(_, max)| rss < *max)
+            .unwrap_or(RSS_BUCKETS.len() - 1);
+
+        buckets[bucket].processes += 1;
+        buckets[bucket].rss = buckets[bucket].rss.saturating_add(rss);
+    }
+
+    let mut cumulative_rss: i64 = 0;
+    for bucket in buckets.iter_mut() {
+        cumulative_rss = cumulative_rss.saturating_add(bucket.rss);
+        bucket.cumulative_rss = cumulative_rss;
+    }
+
+    buckets
+}
+
+// Container runtime processes that each start one container. Names are matched by prefix, as
+// the kernel truncates them to 15 characters and runc renames itself to "runc:[2:INIT]".
+const CONTAINER_SHIMS: [&str; 3] = ["containerd-shim", "conmon", "runc"];
+
+// The tasks that most likely make up one container
+#[derive(Serialize)]
+pub struct ContainerGroup {
+    pub shim: String,
+    pub shim_pid: String,
+    pub commands: Vec<String>,
+    pub tasks: usize,
+    pub rss: i64,
+}
+
+// Estimate per-container memory on hosts where the log has no memcg information. The process
+// list is in pid order and a shim starts its container right after it is started itself, so
+// the tasks between one shim and the next are counted as that shim's container. Host processes
+// started later land in the last container before them, so this is only an estimate. The shims
+// themselves are left out, as their memory belongs to the host.
+pub fn container_groups(processes: &[ProcessEntry], task_unit: i64) -> Vec<ContainerGroup> {
+    let mut groups: Vec<ContainerGroup> = Vec::new();
+
+    for process in processes.iter() {
+        let name = &process.name;
+        if CONTAINER_SHIMS.iter().any(|shim| name.starts_with(shim)) {
+            groups.push(ContainerGroup {
+                shim: name.clone(),
+                shim_pid: process.pid.to_string(),
+                commands: Vec::new(),
+                tasks: 0,
+                rss: 0,
+            });
+        } else if let Some(group) = groups.last_mut() {
+            if !group.commands.contains(name) {
+                group.commands.push(name.clone());
+            }
+            group.tasks += 1;
+            group.rss = group
+                .rss
+                .saturating_add(process.rss.saturating_mul(task_unit));
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_total_ram() {
+        const TOTAL_RAM_RE: &str = r"(\d+) pages RAM";
+        let re = Regex::new(TOTAL_RAM_RE).unwrap();
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.637758 5241544212132178 pages RAM\n Dec 20 03:17:52 localhost kernel: 75669.637798 132311 pages reserved";
+        assert!(re.is_match(s));
+        assert_eq!(parse_meminfo_total(s), Some(2.096_617_684_799_946_8e16));
+    }
+
+    #[test]
+    fn report_swap() {
+        const FREE_SWAP_RE: &str = r"Free swap\s+=.*";
+        let re = Regex::new(FREE_SWAP_RE).unwrap();
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.636534 Free swap  = 0kB";
+        assert!(re.is_match(s));
+        assert_eq!(parse_meminfo_swap(s).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn report_slab() {
+        const UNRECLAIMABLE_SLAB_RE: &str = r"slab_unreclaimable:(\d+)";
+        let re = Regex::new(UNRECLAIMABLE_SLAB_RE).unwrap();
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.607722  slab_reclaimable:4158 slab_unreclaimable:12849311288";
+        assert!(re.is_match(s));
+        assert_eq!(parse_meminfo_slab(s).unwrap(), 51397245152.0);
+        assert_eq!(parse_meminfo_reclaimable_slab(s).unwrap(), 16632.0);
+    }
+
+    #[test]
+    fn report_hugepages() {
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.631773 Node 0 hugepages_total=2 hugepages_free=0 hugepages_surp=0 hugepages_size=1048576kB\n Dec 20 03:17:52 localhost kernel: 75669.633105 Node 0 hugepages_total=12 hugepages_free=0 hugepages_surp=0 hugepages_size=2048kB";
+        let (m, g) = parse_meminfo_hugepages(s).unwrap();
+        assert_eq!(m, 24576.0);
+        assert_eq!(g, 2097152.0);
+        assert_eq!(parse_meminfo_hugepages_free(s), Some(0.0));
+        let free = s.replace("total=2 hugepages_free=0", "total=2 hugepages_free=1");
+        let free = free.replace("total=12 hugepages_free=0", "total=12 hugepages_free=4");
+        assert_eq!(
+            parse_meminfo_hugepages_free(&free),
+            Some(1048576.0 + 8192.0)
+        );
+
+        const HUGEPAGES_RE: &str = r"hugepages_total=(\d+)";
+        let re = Regex::new(HUGEPAGES_RE).unwrap();
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.631773 Node 0 hugepages_total=512 hugepages_free=0 hugepages_surp=0 hugepages_size=1048576kB";
+        assert!(re.is_match(s));
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.633105 Node 0 hugepages_total=512 hugepages_free=0 hugepages_surp=0 hugepages_size=2048kB";
+        assert!(re.is_match(s));
+    }
+
+    #[test]
+    fn report_shared() {
+        const SHMEM_RE: &str = r"shmem:(\d+)";
+        let re = Regex::new(SHMEM_RE).unwrap();
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.607722  mapped:70 shmem:147 pagetables:2089 bounce:0";
+        assert!(re.is_match(s));
+        assert_eq!(parse_meminfo_shared(s).unwrap(), 588.0);
+    }
+
+    #[test]
+    fn report_isolated_and_cma() {
+        let log = include_str!("../messages");
+        assert_eq!(parse_meminfo_isolated(log).unwrap(), (0.0, 8.0));
+        assert_eq!(parse_meminfo_cma(log).unwrap(), 0.0);
+
+        // The per-zone values are in kB and only the summary line should match
+        let s = "Node 0 DMA free:8092kB free_cma:1024kB\n free:13191 free_pcp:370 free_cma:65536";
+        assert_eq!(parse_meminfo_cma(s).unwrap(), 262144.0);
+
+        let log = log.replace("free_cma:0\n", "free_cma:262144\n");
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.free_cma_kib, 1048576.0);
+        assert!(event.warnings.iter().any(|w| w.message.contains("CMA")));
+    }
+
+    #[test]
+    fn report_ram_exhausted() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        assert_eq!(event.free_kib, Some(13191.0 * 4.0));
+        assert_eq!(event.ram_exhausted, Some(true));
+
+        // A quarter of RAM free, well above the watermarks
+        let log = log.replace(" free:13191 ", " free:1310385 ");
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.ram_exhausted, Some(false));
+        assert!((event.free_fraction().unwrap() - 0.25).abs() < 0.01);
+
+        assert!(!ram_exhausted(100.0, 1000.0, &[]));
+        assert!(ram_exhausted(10.0, 1000.0, &[]));
+    }
+
+    #[test]
+    fn report_pcp() {
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let pcp = event.pcp.as_ref().unwrap();
+        assert_eq!((pcp.free_kib, pcp.cpus), (370.0 * 4.0, None));
+
+        // Before v4.8 only the pagesets of each CPU are listed
+        let log = include_str!("../tests/fixtures/debian-8-3.16-686-pae.log");
+        let pcp = parse_pcp(&clean_report(log)).unwrap();
+        assert_eq!(pcp.cpus, Some(2));
+        assert!(pcp.free_kib > 0.0);
+
+        // Thousands of free pages spread over the lists of a big machine's CPUs
+        let log = include_str!("../messages").replace("free_pcp:370 ", "free_pcp:104857 ");
+        let event = OomEvent::parse(&log).unwrap();
+        assert!(event
+            .warnings
+            .iter()
+            .any(|w| w.message.contains("free in the per-cpu page lists")));
+        assert!(parse_pcp("free:100 free_cma:0").is_none());
+    }
+
+    #[test]
+    fn report_expected_ram() {
+        assert_eq!(parse_size_kib("16G"), Some(16.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_size_kib("512MiB"), Some(512.0 * 1024.0));
+        assert_eq!(
+            parse_size_kib("1.5 tb"),
+            Some(1.5 * 1024.0 * 1024.0 * 1024.0)
+        );
+        assert_eq!(parse_size_kib("4096"), Some(4.0));
+        assert_eq!(parse_size_kib("16Q"), None);
+
+        // The sample report has 19.9 GiB
+        let mut event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let warnings = event.warnings.len();
+        event.note_expected_ram(parse_size_kib("20G").unwrap());
+        assert_eq!(event.virtualization.missing_ram_kib, None);
+        event.note_expected_ram(parse_size_kib("32G").unwrap());
+        let missing = event.virtualization.missing_ram_kib.unwrap();
+        assert!((missing / 1024.0 / 1024.0 - 12.1).abs() < 0.1);
+        assert_eq!(event.warnings.len(), warnings + 1);
+        assert!(event.warnings[warnings]
+            .message
+            .contains("32.0 GiB expected"));
+    }
+
+    #[test]
+    fn report_zones_on_small_vms() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        assert_eq!(zone_names(&event.zones, false), ["DMA", "DMA32"]);
+        assert_eq!(event.zones[0].managed_kib, Some(15908.0));
+
+        // Since v5.0 the boost watermark comes between free and min
+        let log = log
+            .replace("DMA free:8092kB min", "DMA free:8092kB boost:0kB min")
+            .replace("DMA32 free:44672kB min", "DMA32 free:44672kB boost:0kB min");
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.zones.len(), 2);
+        assert_eq!(event.zones[1].min_kib, 44696.0);
+
+        let zone = |node, name: &str| Zone {
+            node,
+            name: name.to_string(),
+            free_kib: 0.0,
+            min_kib: 0.0,
+            managed_kib: None,
+            lowmem_reserve_kib: Vec::new(),
+        };
+        let zones = [
+            zone(0, "Normal"),
+            zone(0, "Movable"),
+            zone(1, "Normal"),
+            zone(0, "DMA32"),
+            zone(1, "Device"),
+        ];
+        assert_eq!(
+            zone_names(&zones, false),
+            ["DMA32", "Normal", "Movable", "Device"]
+        );
+    }
+
+    #[test]
+    fn report_lowmem_exhaustion() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        assert_eq!(event.zones.len(), 2);
+        assert_eq!(event.zones[1].name, "DMA32");
+        assert_eq!(event.gfp_zone, None);
+        assert!(!event.lowmem_exhausted);
+
+        let log = log.replace(
+            "gfp_mask=0x100cca(GFP_HIGHUSER_MOVABLE)",
+            "gfp_mask=0xcc4(GFP_KERNEL|GFP_DMA32)",
+        );
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.gfp_zone, Some("DMA32"));
+        assert_eq!(event.zones[0].lowmem_reserve_kib[1], 1935.0 * 4.0);
+
+        // DMA's free memory is all held back by its reserve for DMA32 allocations, so the machine
+        // was out of memory, not just DMA32
+        assert!(!event.lowmem_exhausted);
+
+        let log = log.replacen("lowmem_reserve[]: 0 1935 1935 1935 1935", "", 1);
+        let event = OomEvent::parse(&log).unwrap();
+        assert!(event.zones[0].lowmem_reserve_kib.is_empty());
+        assert!(event.lowmem_exhausted);
+    }
+
+    #[test]
+    fn report_highmem() {
+        let log = include_str!("../tests/fixtures/debian-8-3.16-686-pae.log");
+        let event = OomEvent::parse(log).unwrap();
+        assert!(event.kernel_32bit);
+        assert_eq!(event.zones.len(), 3);
+        assert_eq!(event.zones[2].node, 0);
+        assert_eq!(event.gfp_zone, Some("Normal"));
+        assert!(event.lowmem_exhausted);
+
+        let highmem = event.highmem.as_ref().unwrap();
+        assert_eq!(highmem.highmem_kib, 557826.0 * 4.0);
+        assert!(highmem.lowmem_free_kib < 8.0 * 1024.0);
+
+        // User pages can come from HighMem, so a page fault isn't limited to LowMem
+        let log = log.replace("gfp_mask=0xd0,", "gfp_mask=0x200da,");
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.gfp_zone, None);
+        assert!(!event.lowmem_exhausted);
+
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        assert!(!event.kernel_32bit);
+        assert!(event.highmem.is_none());
+    }
+
+    #[test]
+    fn report_compressed_swap() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        assert!(!event.compressed_swap.zram && !event.compressed_swap.zswap);
+
+        let boot = "Dec 19 08:00:01 localhost kernel: [    4.120113] Adding 8388604k swap on /dev/zram0.  Priority:100 extents:1 across:8388604k SSFS\n";
+        let event = OomEvent::parse(&format!("{}{}", boot, log)).unwrap();
+        assert!(event.compressed_swap.zram);
+
+        let stats = parse_compressed_swap("kernel: zswap 1048576\nkernel: zswapped 4194304\n");
+        assert!(stats.zswap);
+        assert_eq!(stats.zswap_kib, Some(1024.0));
+        assert_eq!(stats.zswapped_kib, Some(4096.0));
+    }
+
+    #[test]
+    fn report_thp() {
+        let thp = parse_thp(include_str!("../messages")).unwrap();
+        assert_eq!(thp.anon_kib, 856064.0);
+        assert_eq!(thp.shmem_kib, 0.0);
+
+        let s = "Node 0 shmem_thp:0kB anon_thp:1024kB file_thp:2048kB\nNode 1 shmem_thp:1024kB anon_thp:1024kB file_thp:0kB";
+        let thp = parse_thp(s).unwrap();
+        assert_eq!(
+            (thp.anon_kib, thp.shmem_kib, thp.file_kib),
+            (2048.0, 1024.0, 2048.0)
+        );
+        assert!(parse_thp("Node 0 active_anon:278816kB").is_none());
+    }
+
+    #[test]
+    fn report_virtualization() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        assert!(event.virtualization.balloon_drivers.is_empty());
+
+        let boot = "Dec 19 08:00:01 localhost kernel: [    2.301107] virtio_balloon virtio3: free page reporting enabled\n";
+        let event = OomEvent::parse(&format!("{}{}", boot, log)).unwrap();
+        assert_eq!(event.virtualization.balloon_drivers, vec!["virtio_balloon"]);
+        assert!(event
+            .warnings
+            .iter()
+            .any(|w| w.message.contains("virtio_balloon")));
+
+        assert_eq!(
+            parse_hypervisor("Hardware name: Red Hat KVM, BIOS 1.15.0 04/01/2014"),
+            Some("KVM")
+        );
+        assert_eq!(
+            parse_hypervisor("Hardware name: Dell Inc. PowerEdge R740/0WRPXK"),
+            None
+        );
+    }
+
+    #[test]
+    fn report_log_context() {
+        let log = include_str!("../messages");
+        let balloon = "Dec 19 08:00:01 localhost kernel: [    2.301107] virtio_balloon virtio3: free page reporting enabled\n";
+        let boot = "Dec 19 09:00:00 localhost kernel: [    0.000000] Linux version 5.15.0-91-generic (buildd@lcy02-amd64-045)\n";
+
+        // From the boot the report was logged in to the end of the report
+        let rebooted = format!("{}{}{}", balloon, boot, log);
+        let context = report_context(&rebooted);
+        assert!(context.starts_with(boot));
+        assert!(context.ends_with("UID:111 pgtables:4292kB oom_score_adj:0"));
+
+        // so a balloon driver of the boot before isn't this one's
+        let event = OomEvent::parse(&rebooted).unwrap();
+        assert!(event.virtualization.balloon_drivers.is_empty());
+        let event = OomEvent::parse(&format!("{}{}{}", boot, balloon, log)).unwrap();
+        assert_eq!(event.virtualization.balloon_drivers, vec!["virtio_balloon"]);
+
+        // Without a boot in the log, it's as far back as LOG_CONTEXT_BYTES
+        let noise = "Dec 19 10:00:00 localhost kernel: [ 3600.000000] [UFW BLOCK] IN=eth0\n";
+        let long = format!(
+            "{}{}{}",
+            balloon,
+            noise.repeat(LOG_CONTEXT_BYTES / noise.len() + 1),
+            log
+        );
+        assert!(!report_context(&long).contains("virtio_balloon"));
+    }
+
+    #[test]
+    fn report_task_counts() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        let counts = task_counts(&event.processes);
+        assert_eq!(counts[0], ("rspamd".to_string(), 5));
+        assert!(!event
+            .warnings
+            .iter()
+            .any(|w| w.message.contains("fork bomb")));
+
+        let line = log.lines().find(|l| l.contains(" znc")).unwrap();
+        let bomb = log.replace(line, &format!("{}\n", line).repeat(1200));
+        let event = OomEvent::parse(&bomb).unwrap();
+        assert!(event
+            .warnings
+            .iter()
+            .any(|w| w.message == "1200 tasks of znc, possible fork bomb"));
+    }
+
+    #[test]
+    fn report_parent_name() {
+        assert_eq!(parent_name("nginx: worker"), "nginx");
+        assert_eq!(parent_name("kworker/0:1"), "kworker");
+        assert_eq!(parent_name("gunicorn-worker-3"), "gunicorn");
+        assert_eq!(parent_name("php-fpm7.4"), "php-fpm7.4");
+        assert_eq!(parent_name("qmgr"), "postfix");
+        assert_eq!(parent_name(":1"), ":1");
+    }
+
+    #[test]
+    fn report_container_groups() {
+        let log = include_str!("../messages")
+            .replace("haveged", "conmon")
+            .replace("unattended-upgr", "containerd-shim");
+        let event = OomEvent::parse(&log).unwrap();
+        let groups = container_groups(&event.processes, event.task_unit);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].shim_pid, "348");
+        assert_eq!(groups[0].tasks, 9);
+        assert_eq!(groups[0].commands[0], "cron");
+        assert_eq!(groups[1].shim, "containerd-shim");
+
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        assert!(container_groups(&event.processes, event.task_unit).is_empty());
+    }
+
+    #[test]
+    fn report_mems_allowed() {
+        assert_eq!(
+            parse_mems_allowed("mems_allowed=0-2,5"),
+            Some(vec![0, 1, 2, 5])
+        );
+        assert_eq!(format_node_list(&[0, 1, 2, 5]), "0-2,5");
+
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        assert_eq!(event.mems_allowed, Some(vec![0]));
+        assert!(event.warnings.iter().all(|w| w.section != "zones"));
+
+        // A second node the task wasn't allowed to use
+        let line = log
+            .lines()
+            .find(|l| l.contains("Node 0 DMA32 free:"))
+            .unwrap();
+        let log = log.replace(
+            line,
+            &format!("{}\n{}", line, line.replace("Node 0", "Node 1")),
+        );
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(online_nodes(&event.zones), vec![0, 1]);
+        assert!(event.warnings.iter().any(|w| w
+            .message
+            .contains("restricted to node 0 of online nodes 0-1")));
+    }
+
+    #[test]
+    fn report_extract() {
+        let log = include_str!("../messages");
+        let extracted = extract_report(log).unwrap();
+        assert!(!extracted.contains('['));
+        assert_eq!(
+            OomEvent::parse(&extracted).unwrap().cleaned,
+            OomEvent::parse(log).unwrap().cleaned
+        );
+    }
+
+    #[test]
+    fn report_ps_usage() {
+        const PS_LIST_RE: &str = r"(.*pid.+\bname\b)(?s)(.*)";
+        let re = Regex::new(PS_LIST_RE).unwrap();
+        let s = "Dec 20 03:17:52 localhost kernel: 75669.641206   pid     uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name
+Dec 20 03:17:52 localhost kernel: 75669.642775     199     0   199    14838      226   102400       14          -250 systemd-journal
+Dec 20 03:17:52 localhost kernel: 75669.644513     255     0   255     5316      159    69632       37         -1000 systemd-udevd";
+        assert!(re.is_match(s));
+    }
+
+    #[test]
+    fn report_last_event() {
+        let log = include_str!("../messages");
+        let twice = format!("{}{}", log, log.replace("clamd", "clamx"));
+        let event = OomEvent::parse(&twice).unwrap();
+        assert!(event.cleaned.contains("clamx"));
+        assert!(!event.cleaned.contains("clamd"));
+        assert_eq!(event.processes.len(), 35);
+    }
+
+    #[test]
+    fn report_oom_group() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        assert_eq!(event.kills.len(), 1);
+        assert_eq!(event.kills[0].command, "clamd");
+        assert!(event.oom_group.is_none());
+        let freed = event.freed.as_ref().unwrap();
+        assert_eq!(freed.anon_kib, 1689296.0);
+        assert_eq!(freed.reaped_kib, Some(freed.rss_kib));
+        assert!((freed.ram_ratio.unwrap() - 0.081).abs() < 0.001);
+
+        let prefix = "Dec 20 03:17:52 localhost kernel: [75669.703300] ";
+        let group = format!(
+            "{}{}Tasks in /system.slice/clamav-daemon.service are going to be killed due to \
+             memory.oom.group set\n{}Out of memory: Killed process 518 (freshclam) \
+             total-vm:100kB, anon-rss:2048kB, file-rss:1024kB, shmem-rss:0kB, UID:111\n",
+            log, prefix, prefix
+        );
+        let event = OomEvent::parse(&group).unwrap();
+        let killed = event
+            .kills
+            .iter()
+            .map(|k| k.pid.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(killed, ["517", "518"]);
+        assert_eq!(event.kills[1].rss_kib, 3072.0);
+        assert_eq!(
+            event.oom_group.as_deref(),
+            Some("/system.slice/clamav-daemon.service")
+        );
+        assert!(event.warnings.iter().any(|w| w.message.contains("2 tasks")));
+        let freed = event.freed.as_ref().unwrap();
+        assert_eq!(freed.file_kib, 1024.0);
+        assert_eq!(freed.reaped_kib, Some(1689296.0));
+
+        // Older kernels leave out shmem-rss, and a small victim frees too little to help
+        let small = log
+            .replace(
+                "anon-rss:1689296kB, file-rss:0kB, shmem-rss:0kB",
+                "anon-rss:4096kB, file-rss:0kB",
+            )
+            .replace("oom_reaper", "reaper");
+        let event = OomEvent::parse(&small).unwrap();
+        let freed = event.freed.as_ref().unwrap();
+        assert_eq!((freed.rss_kib, freed.reaped_kib), (4096.0, None));
+        assert!(event
+            .warnings
+            .iter()
+            .any(|w| w.message.contains("too little")));
+    }
+
+    #[test]
+    fn report_end_markers() {
+        let log = include_str!("../messages");
+        let expected = OomEvent::parse(log).unwrap().processes;
+
+        // A forwarder that changes the case of the marker or tags it
+        let shouted = log.replace("Out of memory", "[fwd] OUT OF MEMORY");
+        assert_eq!(OomEvent::parse(&shouted).unwrap().processes, expected);
+
+        // Without a marker, the report ends at the next invocation, rather than taking in the
+        // next report's process list
+        let lost = log.replace("Out of memory", "Killed");
+        let start = lost[..lost.find("invoked oom-killer").unwrap()]
+            .rfind('\n')
+            .unwrap()
+            + 1;
+        let twice = format!("{}{}", lost, &lost[start..]);
+        let end = report_end(&twice[start..]).unwrap();
+        assert_eq!(&twice[start..start + end], &lost[start..]);
+
+        // or at a run of lines from other programs
+        let other = "Dec 20 03:17:53 localhost systemd[1]: clamav-daemon.service: Failed\n";
+        let tail = format!("{}{}", &lost[start..], other.repeat(NON_KERNEL_RUN));
+        assert_eq!(report_end(&tail), Some(lost.len() - start));
+        assert_eq!(report_end(&format!("{}{}", &lost[start..], other)), None);
+    }
+
+    #[test]
+    fn report_crlf_line_endings() {
+        // Logs copied to or saved on Windows machines often end up with CRLF line endings
+        let log = include_str!("../messages").replace('\n', "\r\n");
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.total_ram_kib, 20900008.0);
+        assert_eq!(event.processes.len(), 35);
+        assert_eq!(event.processes[0].name, "systemd-journal");
+    }
+
+    #[test]
+    fn report_oom_score_adj_bands() {
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let bands = oom_score_adj_bands(&event.processes, event.task_unit);
+        let counts = bands.iter().map(|b| b.processes).collect::<Vec<_>>();
+        assert_eq!(counts, vec![2, 2, 31, 0]);
+        assert_eq!(bands[0].rss, (159 + 207) * 4096); // systemd-udevd, sshd
+        assert_eq!(bands[1].rss, (226 + 139) * 4096); // systemd-journal, dbus-daemon
+    }
+
+    #[test]
+    fn report_oom_score_adj_values() {
+        let ps = "\
+1 0 1 100 10 4096 0 -1000 exempt
+2 0 2 100 20 4096 0 -998 sshd
+3 0 3 100 30 4096 0 0 plain
+4 0 4 100 40 4096 0 1000 chrome
+5 0 5 100 50 4096 0 -1001 beyond
+6 0 6 100 60 4096 0 - dash";
+        let (processes, skipped) = parse_processes(ps, 0, &[]);
+        let adj = processes
+            .iter()
+            .map(|p| p.oom_score_adj)
+            .collect::<Vec<_>>();
+        assert_eq!(adj, vec![-1000, -998, 0, 1000]);
+        assert_eq!(
+            skipped[0].reason,
+            "oom_score_adj value '-1001' out of range -1000 to 1000"
+        );
+        assert_eq!(skipped[1].reason, "could not parse oom_score_adj value '-'");
+
+        let bands = oom_score_adj_bands(&processes, PAGE_SIZE);
+        let counts = bands.iter().map(|b| b.processes).collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 1, 1, 1]);
+
+        let header = ["pgtables_bytes".to_string()];
+        let ranking = badness_ranking(&processes, &header, PAGE_SIZE, 1000.0);
+        let names = ranking.iter().map(|b| b.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["chrome", "plain", "sshd"]);
+        assert_eq!(ranking[0].oom_score, 1000);
+    }
+
+    #[test]
+    fn report_rss_histogram() {
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let buckets = rss_histogram(&event.processes, event.task_unit);
+        let counts = buckets.iter().map(|b| b.processes).collect::<Vec<_>>();
+        assert_eq!(counts.iter().sum::<usize>(), event.processes.len());
+        assert_eq!(counts, vec![27, 7, 0, 1]);
+        assert_eq!(buckets[3].rss, 422324 * 4096); // clamd
+        assert_eq!(buckets[3].cumulative_rss, 517275 * 4096);
+    }
+
+    #[test]
+    fn report_badness_ranking() {
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let total_pages = (event.total_ram_kib + event.total_swap_kib) / 4.0;
+        let ranking = badness_ranking(
+            &event.processes,
+            &event.ps_header,
+            event.task_unit,
+            total_pages,
+        );
+
+        // rss + swapents + pgtables_bytes / 4096
+        assert_eq!(ranking[0].name, "clamd");
+        assert_eq!(ranking[0].points, 422324 + 107099 + 1073);
+        assert_eq!(Some(&ranking[0].pid), event.victim_pid.as_ref());
+
+        // systemd-udevd and sshd are exempt
+        assert_eq!(ranking.len(), 33);
+        assert!(ranking.iter().all(|b| b.name != "sshd"));
+
+        // A positive oom_score_adj makes a small process the preferred victim
+        let (ps, _) = parse_processes(
+            "1 0 1 100 50000 4096 0 0 big\n2 0 2 100 10 4096 0 500 small",
+            0,
+            &[],
+        );
+        let header = &event.ps_header;
+        let ranking = badness_ranking(&ps, header, PAGE_SIZE, 200000.0);
+        assert_eq!(ranking[0].name, "small");
+        assert_eq!(ranking[0].points, 10 + 1 + 100000);
+        assert_eq!(ranking[0].oom_score, 500);
+
+        // The same process list reported in kB
+        let ranking = badness_ranking(&ps, header, 1024, 200000.0);
+        assert_eq!(ranking[1].name, "big");
+        assert_eq!(ranking[1].points, 50000 / 4 + 1);
+    }
+
+    #[test]
+    fn report_ps_without_header() {
+        let log = include_str!("../messages");
+        let header = "uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name";
+        let localized = log.replace(header, "UID  TGID VM       RSS TABLES SWAP ADJ NOM");
+        let event = OomEvent::parse(&localized).unwrap();
+        assert_eq!(event.processes, OomEvent::parse(log).unwrap().processes);
+        assert_eq!(event.ps_header[5], "pgtables_bytes");
+        assert!(event
+            .warnings
+            .iter()
+            .any(|w| w.problem && w.message.contains("no process list header")));
+
+        // A v4.4 kernel, with nr_pmds between nr_ptes and swapents
+        let rows = "kernel: [ 2178.112233] [  411]     0   411    11216      891      24       3        0         -1000 systemd-udevd
+kernel: [ 2178.112240] [  517]   111   517   590364   422324     860       5   107099             0 clamd";
+        let (header, processes) = parse_ps_rows(&rows.replace(['[', ']'], "")).unwrap();
+        assert_eq!(header[5], "nr_ptes");
+        assert_eq!(processes.len(), 2);
+        assert_eq!(processes[1].pgtables, 860);
+        assert_eq!(processes[1].swapents, 107099);
+        assert_eq!(processes[1].oom_score_adj, 0);
+        assert_eq!(processes[1].name, "clamd");
+    }
+
+    #[test]
+    fn report_task_unit() {
+        assert_eq!(
+            parse_task_unit("Tasks state (memory values in pages):"),
+            Some(4096)
+        );
+        assert_eq!(
+            parse_task_unit("Tasks state (memory values in kB):"),
+            Some(1024)
+        );
+        assert_eq!(
+            parse_task_unit("[ pid ]   uid  tgid total_vm      rss nr_ptes"),
+            None
+        );
+
+        let log = include_str!("../messages");
+        let kb = log.replace("values in pages", "values in kB");
+        let pages = OomEvent::parse(log).unwrap();
+        let kb = OomEvent::parse(&kb).unwrap();
+        let pages_total: i64 = top_consumers(&pages.processes, pages.task_unit)
+            .values()
+            .sum();
+        let kb_total: i64 = top_consumers(&kb.processes, kb.task_unit).values().sum();
+        assert_eq!(pages_total, kb_total * 4);
+    }
+
+    #[test]
+    fn report_wrapped_rows() {
+        let log = include_str!("../messages");
+        let wrapped = log
+            .replace("14838      226", "14838 \\\n      226")
+            .replace("590364   422324", "590364\n   422324")
+            .replace("-1000 sshd", "-1000\nsshd");
+        let event = OomEvent::parse(&wrapped).unwrap();
+        assert_eq!(event.processes.len(), 35);
+
+        let commands = top_consumers(&event.processes, event.task_unit);
+        assert_eq!(commands["systemd-journal"], 226 * 4096);
+        assert_eq!(commands["clamd"], 422324 * 4096);
+        assert_eq!(commands["sshd"], 207 * 4096);
+    }
+
+    #[test]
+    fn report_names_with_spaces() {
+        let log = include_str!("../messages").replace("-1000 sshd", "-1000 sshd: admin [priv]");
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.processes.len(), 35);
+        assert!(event.warnings.iter().all(|w| !w.problem));
+
+        let commands = top_consumers(&event.processes, event.task_unit);
+        assert_eq!(commands["sshd: admin priv"], 207 * 4096);
+        assert!(!commands.contains_key("sshd:"));
+
+        let ps = "1 0 1 100 10 4096 0 0 tuned: profile a";
+        let (processes, _) = parse_processes(ps, 0, &[]);
+        assert_eq!(processes[0].name, "tuned: profile a");
+        assert_eq!(processes[0].oom_score_adj, 0);
+        let (_, processes) = parse_ps_rows(ps).unwrap();
+        assert_eq!(processes[0].name, "tuned: profile a");
+
+        // nr_pmds is set aside rather than read as swapents
+        let (header, pid_col) = parse_ps_header(
+            "pid uid tgid total_vm rss nr_ptes nr_pmds swapents oom_score_adj name\n",
+        )
+        .unwrap();
+        let extra = parse_extra_columns(&header[pid_col..]);
+        assert_eq!(extra, [6]);
+        let ps = "1 0 1 100 10 4 3 7 -900 tuned: profile a";
+        let (processes, _) = parse_processes(ps, pid_col, &extra);
+        assert_eq!(processes[0].swapents, 7);
+        assert_eq!(processes[0].oom_score_adj, -900);
+        assert_eq!(processes[0].name, "tuned: profile a");
+        assert_eq!(processes[0].extra, [3]);
+    }
+
+    #[test]
+    fn report_json() {
+        let json = parse_to_json(include_str!("../messages"));
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["schema_version"], SCHEMA_VERSION);
+        assert_eq!(v["total_ram_kib"], 20900008.0);
+        assert_eq!(v["processes"].as_array().unwrap().len(), 35);
+        assert!(v.get("cleaned").is_none());
+
+        let json = parse_to_json("no report here");
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["error"], "string 'invoked oom-killer' not found");
+    }
+
+    #[test]
+    fn report_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&json_schema()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("schema_version"));
+        assert!(properties.contains_key("total_ram_kib"));
+        assert!(properties.contains_key("warnings"));
+        assert!(!properties.contains_key("cleaned"));
+    }
+
+    #[test]
+    fn report_timeline_json() {
+        let log = include_str!("../messages");
+        let later = log
+            .replace("Dec 20 03:17:52", "Dec 20 04:02:10")
+            .replace("75669.", "78347.")
+            .replace("590364   422324", "590364   522324");
+        let json = timeline_to_json(&format!("{}{}", log, later));
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            v["timestamps"],
+            serde_json::json!(["Dec 20 03:17:52", "Dec 20 04:02:10"])
+        );
+        assert_eq!(v["timeline"][0]["command"], "clamd");
+        assert_eq!(
+            v["timeline"][0]["rss"],
+            serde_json::json!([422324 * 4096, 522324 * 4096])
+        );
+
+        let v: serde_json::Value = serde_json::from_str(&timeline_to_json("no report")).unwrap();
+        assert_eq!(v["error"], "string 'invoked oom-killer' not found");
+    }
+
+    #[test]
+    fn report_warnings() {
+        let log = include_str!("../messages");
+        assert!(OomEvent::parse(log).unwrap().warnings.is_empty());
+
+        let log = log
+            .replace("Tasks state (memory values in pages):", "")
+            .replace("14838      226", "14838      2x6");
+        let json = parse_to_json(&log);
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let warnings = v["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0]["section"], "processes");
+        assert_eq!(
+            warnings[0]["message"],
+            "skipped process list row: could not parse rss value '2x6'"
+        );
+        assert!(warnings[0]["excerpt"]
+            .as_str()
+            .unwrap()
+            .ends_with("2x6 102400 14 -250 systemd-journal"));
+        let line = log.lines().position(|l| l.contains("2x6")).unwrap();
+        assert_eq!(warnings[0]["line"], line + 1);
+        assert_eq!(
+            warnings[1]["message"],
+            "process list memory unit not stated, assuming pages"
+        );
+    }
+
+    #[test]
+    fn report_malformed_input() {
+        // Non-ASCII digits match \d but do not parse as numbers
+        assert_eq!(parse_meminfo_slab("slab_unreclaimable:\u{0663}"), None);
+        assert_eq!(
+            parse_meminfo_total("\u{0663} pages RAM\n1 pages reserved"),
+            None
+        );
+
+        // A header with no name column and a truncated process row
+        assert_eq!(parse_ps_header("pid uid tgid rss\n"), None);
+        let ps = "1 0 1 100 10 4096 0 0 init\n2 0 2 100\n3 0 3 100 o226 4096 0 0 bad";
+        let (processes, skipped) = parse_processes(ps, 0, &[]);
+        assert_eq!(processes.len(), 1);
+        assert_eq!(processes[0].rss, 10);
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0].reason, "4 columns, expected 9");
+        assert_eq!(skipped[1].line, 2);
+        assert_eq!(skipped[1].text, "3 0 3 100 o226 4096 0 0 bad");
+        assert_eq!(skipped[1].reason, "could not parse rss value 'o226'");
+
+        let log = include_str!("../messages");
+        assert!(OomEvent::parse(&log[..log.len() / 2]).is_err());
+        assert!(OomEvent::parse("invoked oom-killer pid name\nOut of memory").is_err());
+    }
+
+    #[test]
+    fn report_skipped_lines() {
+        let log = include_str!("../tests/fixtures/ubuntu-22.04-5.15.log")
+            .replace(
+                "[542091.479765] kmalloc-4k",
+                "[542091.479000] kmalloc-8k  12KB  ??KB\n[542091.479765] kmalloc-4k",
+            )
+            .replace("  3906   ", "  39x6   ");
+        let event = OomEvent::parse(&log).unwrap();
+        let skipped = event
+            .skipped
+            .iter()
+            .map(|s| (s.section, s.line, s.text.as_str(), s.reason.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            skipped[2..],
+            [
+                (
+                    "slab",
+                    42,
+                    "542091.479000 kmalloc-8k  12KB  ??KB",
+                    "total \"??\" is not a number"
+                ),
+                (
+                    "processes",
+                    62,
+                    "542091.502032 1490 998 1490 11718 39x6 110592 65 0 chronyd",
+                    "could not parse rss value '39x6'"
+                ),
+            ]
+        );
+        assert_eq!(skipped[0].0, "cleaner");
+        assert_eq!(skipped[0].1, 88);
+        assert!(skipped[0].2.contains("oom-kill:constraint="));
+        assert!(skipped[1]
+            .2
+            .starts_with("[542091.529828] Out of memory: Killed process 8703 (node)"));
+    }
+}
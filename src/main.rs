@@ -1,535 +1,1935 @@
 #![allow(non_snake_case)] // for MiB, GiB
 
+use chrono::DateTime;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand};
+use clap_complete::{generate, Shell};
+use clap_mangen::Man;
+use parseoom::android::{adj_category, parse_lmk_kills, LmkKill};
+use parseoom::coverage::{unclaimed_lines, UnclaimedGroup};
+use parseoom::doctor::Diagnosis;
+use parseoom::fleet::{host_dirs, map_parallel, FleetStats, HostSummary};
+use parseoom::follow::{Follower, JournalFollower};
+use parseoom::index::{cache_path, EventIndex};
+use parseoom::input::{
+    source_for_path, DemoSource, DirectorySource, InputSource, JournaldSource, KmsgSource,
+    SshSource,
+};
+use parseoom::numbers::NumberFormat;
+use parseoom::partial::find_partial_reports;
+use parseoom::precursors::PRECURSOR_WINDOW_SECONDS;
+use parseoom::pressure::{parse_sar, parse_time_of_day, parse_vmstat, pressure_before};
+use parseoom::render::{output_path, render_events, write_output, Format, Renderer, Renderers};
+use parseoom::report::{Rank, Report, Section, Top};
+use parseoom::rollup::{
+    approx_duration, command_timeline, dedup_events, parse_timestamp, predict_recurrence,
+    split_events, EventSummary, Rollup, TimelineRow,
+};
+use parseoom::rules::Rule;
+use parseoom::service::ServiceUnits;
+use parseoom::snapshot::{compare_meminfo, parse_proc_meminfo};
+use parseoom::structured::JsonFields;
+use parseoom::template::render_template;
+use parseoom::text::{
+    fit_columns, write_columns, write_kills, write_meminfo_changes, write_partial_reports,
+    write_pressure, write_report, Align, Column, TableStyle, TextOptions, Units,
+};
+use parseoom::timestamps::{
+    default_year, json_time, parse_log_time, parse_log_times, LogTime, Zone,
+};
+use parseoom::workloads::memcg_jvm_usage;
+use parseoom::{
+    event_to_json, extract_report, json_schema, last_report, parse_size_kib, report_start_line,
+    JsonReport, OomEvent, SCHEMA_VERSION,
+};
 use regex::Regex;
-use std::collections::BTreeMap;
 use std::env;
 use std::error::Error;
 use std::fs;
-use std::iter::FromIterator;
-use std::process;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use terminal_size::{terminal_size, Width};
+use tracing::{info, info_span, Level};
+use tracing_subscriber::fmt::format::FmtSpan;
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "A utility for parsing the output of an oom-kill message",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    group(ArgGroup::new("many_events").args(["all", "hosts"]))
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Log file or directory of logs containing an oom-killer report, or - for stdin.
+    /// Files ending in .gz are decompressed
+    #[arg(required_unless_present_any = ["journald", "kmsg", "ssh"])]
+    logfile: Option<PathBuf>,
+
+    /// Read kernel messages from the systemd journal
+    #[arg(long, conflicts_with_all = ["logfile", "kmsg", "ssh"])]
+    journald: bool,
+
+    /// Read kernel messages from the ring buffer with dmesg
+    #[arg(long, conflicts_with_all = ["logfile", "ssh"])]
+    kmsg: bool,
+
+    /// Read the log from a remote host over ssh. Without a path, /var/log/messages is read,
+    /// or the journal if it doesn't exist. An IPv6 address goes in brackets, as in
+    /// [fe80::1]:/var/log/messages
+    #[arg(long, value_name = "HOST[:PATH]", conflicts_with = "logfile")]
+    ssh: Option<String>,
+
+    /// Keep watching the log file, or the journal with --journald, and analyze new oom-killer
+    /// reports as they are written. Rotation and truncation are detected
+    #[arg(long, conflicts_with_all = ["kmsg", "ssh"])]
+    follow: bool,
+
+    /// With --journald --follow, keep the journal cursor of the last message read in this file,
+    /// so that a restart carries on from there
+    #[arg(long, value_name = "PATH", requires_all = ["journald", "follow"])]
+    state: Option<PathBuf>,
+
+    /// Analyze every oom-killer report in the log, not just the last, and finish with totals
+    /// across all of them
+    #[arg(long, conflicts_with = "follow")]
+    all: bool,
+
+    /// Analyze the Nth oom-killer report in the log, counting from 1, instead of the last. A
+    /// file is scanned once for where its reports start and only that report is read, so this
+    /// stays fast on multi-gigabyte archives. The events subcommand lists the numbers
+    #[arg(long, value_name = "N", conflicts_with_all = ["all", "follow"],
+          value_parser = clap::value_parser!(u64).range(1..))]
+    event: Option<u64>,
+
+    /// Keep the list of reports in a log file found for --event and the events subcommand in
+    /// $XDG_CACHE_HOME/parseoom, so the file isn't scanned again until it changes
+    #[arg(long, global = true)]
+    index_cache: bool,
+
+    /// Year of the first event, for syslog dates that have none. Later events move to the next
+    /// year when the month goes back, as from Dec to Jan. Defaults to this year, or last year if
+    /// that would put the log in the future
+    #[arg(long)]
+    year: Option<i32>,
+
+    /// With --all, only analyze events at or after this time, ie. "Dec 20 03:00:00" or
+    /// 2023-12-20T03:00:00+01:00
+    #[arg(long, value_name = "TIME", requires = "all")]
+    since: Option<String>,
+
+    /// With --all, only analyze events at or before this time
+    #[arg(long, value_name = "TIME", requires = "all")]
+    until: Option<String>,
+
+    /// With --all, show the time of each event in this time zone, and with --hosts the time of
+    /// each host's last event [UTC, local, or a name such as Europe/Berlin]. JSON always gives
+    /// times in UTC
+    #[arg(long, value_name = "ZONE", requires = "many_events")]
+    tz: Option<Zone>,
+
+    /// Time zone the log's timestamps are in when they don't say, as with syslog dates [UTC,
+    /// local, or a name such as Europe/Berlin]
+    #[arg(long, value_name = "ZONE", default_value = "local")]
+    log_tz: Zone,
+
+    /// For logs of JSON lines, as exported from Elasticsearch, the field holding each line's
+    /// message. A dotted name looks into nested objects, ie. event.original
+    #[arg(long, value_name = "FIELD", default_value = "message")]
+    json_field: String,
+
+    /// For logs of JSON lines, the field holding each line's time, as an RFC 3339 date or
+    /// milliseconds since the epoch
+    #[arg(long, value_name = "FIELD", default_value = "@timestamp")]
+    json_time: String,
+
+    /// RAM the host was provisioned with, ie. 16G. A report with much less, as when the
+    /// hypervisor balloons a guest down, gets a warning
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    expected_ram: Option<f64>,
+
+    /// Also list Mem-Info dumps and process lists with no "invoked oom-killer" line before
+    /// them, left behind when the ring buffer overwrote the start of a report
+    #[arg(long, conflicts_with = "follow")]
+    scan_partial: bool,
+
+    /// Treat each subdirectory of the $LOGFILE directory as the logs of one host, analyze every
+    /// host and print a summary of the fleet
+    #[arg(
+        long,
+        requires = "logfile",
+        conflicts_with_all = ["follow", "all", "strict", "format", "output", "extract"]
+    )]
+    hosts: bool,
+
+    /// With --hosts, also write each host's last report to this directory as HOST.txt and
+    /// HOST.json
+    #[arg(long, value_name = "DIR", requires = "hosts")]
+    output_dir: Option<PathBuf>,
+
+    /// With --hosts, analyze this many hosts at once. Defaults to the number of CPUs
+    #[arg(long, value_name = "N", requires = "hosts")]
+    jobs: Option<usize>,
+
+    /// Print numbers without thousands separators and with a decimal point whatever the
+    /// locale, for scripts that read the text output
+    #[arg(long)]
+    plain_numbers: bool,
+
+    /// Leave out the bars drawn next to the command, program and memory tables for the share
+    /// of RAM each row takes
+    #[arg(long, global = true)]
+    no_graphics: bool,
+
+    /// Lay out tables for a terminal this many columns wide instead of the width of the
+    /// terminal the output goes to. Output to a pipe or file keeps a fixed layout
+    #[arg(long, value_name = "COLUMNS", global = true)]
+    width: Option<usize>,
+
+    /// Log each step to stderr with how long it took, to find out why a log is slow or fails to
+    /// parse: -v for reading, finding the reports and rendering, -vv for each section of a
+    /// report as well
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Never shorten command names in tables or leave out columns to fit the terminal, and
+    /// show process list columns only some kernels print, such as nr_pmds
+    #[arg(long, global = true)]
+    wide: bool,
+
+    /// Draw the process, slab and diff tables as aligned columns or inside borders [plain,
+    /// ascii, unicode]
+    #[arg(long, default_value = "plain", global = true)]
+    table_style: TableStyle,
+
+    /// Show the memory totals in KiB, MiB and GiB, or as the page counts the kernel printed
+    /// with their exact size in KiB alongside, for quoting in kernel bug reports. Tables of
+    /// processes stay in MiB [bytes, pages]
+    #[arg(long, default_value = "bytes", global = true)]
+    units: Units,
+
+    /// Fail on any part of the last report that couldn't be parsed, such as a missing section
+    /// or a skipped process list row, giving the line of the log for each. For checking the
+    /// format of oom-killer reports in CI
+    #[arg(long, conflicts_with_all = ["all", "follow"])]
+    strict: bool,
+
+    /// Fail when the last report has lines no section parser claims, listing them grouped by
+    /// what they look like. For adding support for a new kernel's output
+    #[arg(long, conflicts_with_all = ["all", "follow"])]
+    fail_on_unknown_section: bool,
+
+    /// Print JSON instead of text. The same as --format json
+    #[arg(long, conflicts_with_all = ["follow", "format"])]
+    json: bool,
+
+    /// Comma-separated list of formats to write the last report in [text, json, yaml, csv, tsv,
+    /// markdown, html, prometheus]. More than one needs --output. With --all, one of text,
+    /// json, csv, tsv or html, the tables with the number of each event on its rows and the
+    /// page with a chart of the top commands across the events
+    #[arg(long, value_delimiter = ',', conflicts_with = "follow")]
+    format: Vec<Format>,
+
+    /// Write the report to this file instead of stdout, compressed with gzip when the name ends
+    /// in .gz. With more than one --format, each format is written to the path with its
+    /// extension added, ie. report.txt and report.json. With --all, the JSON, CSV, TSV or HTML of
+    /// every event
+    #[arg(short, long, value_name = "PATH", conflicts_with = "follow")]
+    output: Option<PathBuf>,
+
+    /// Render the report with a minijinja template instead of the usual text, such as an
+    /// organization's incident report format. Requires the templates feature
+    #[arg(long, value_name = "PATH", conflicts_with = "json")]
+    template: Option<PathBuf>,
+
+    /// Only show commands and processes whose name matches this regular expression in the
+    /// command and process tables
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    filter: Option<Regex>,
+
+    /// Write the cleaned text of the last oom-killer report to a file, to attach when reporting
+    /// a parsing problem
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["all", "follow"])]
+    extract: Option<PathBuf>,
+
+    /// JSON file of extra values to extract from the report, each with a name, a regular
+    /// expression capturing a number, and a unit [kb, kib, mb, pages, bytes, count]
+    #[arg(long, value_name = "PATH")]
+    rules: Option<PathBuf>,
+
+    /// /proc/meminfo saved before the oom-kill, such as from a sosreport, to compare with the
+    /// values in the report
+    #[arg(long, value_name = "PATH", conflicts_with = "all")]
+    meminfo: Option<PathBuf>,
+
+    /// Text sar export covering the time before the oom-kill, ie. from sar -r -B -W, to show how
+    /// memory pressure built up
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["all", "vmstat"])]
+    sar: Option<PathBuf>,
+
+    /// vmstat output covering the time before the oom-kill, preferably with -t timestamps
+    #[arg(long, value_name = "PATH", conflicts_with = "all")]
+    vmstat: Option<PathBuf>,
+
+    /// How far before the oom-killer report to look for allocation stalls, hung tasks and
+    /// other warning signs, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = PRECURSOR_WINDOW_SECONDS)]
+    precursor_window: f64,
+
+    /// Column to rank the command and process tables by [rss, vsz, pgtables, swap]
+    #[arg(long, default_value = "rss")]
+    rank: Rank,
+
+    /// Number of rows to print in the command, program, victim and process tables, or all.
+    /// With all, the output is shown in $PAGER when printing to a terminal on unix
+    #[arg(long, default_value = "10")]
+    top: Top,
+
+    /// Don't show the output in $PAGER with --top all
+    #[arg(long)]
+    no_pager: bool,
+
+    /// Comma-separated list of report sections to print
+    /// [status, meminfo, thp, slab, custom, commands, parents, containers, tasks,
+    /// oom_score_adj, victim, processes, rss, precursors, recommendations]
+    #[arg(long, value_delimiter = ',')]
+    sections: Vec<Section>,
+}
 
-const OOM_KILL_RE: &str = r"(?s)((\w+\s)?invoked oom-killer.*?)(?-s:.*?[oO]ut of memory:?){1}?";
-const PS_LIST_END_RE: &str = r"Out of memory:?|oom-kill:|Memory cgroup";
-const PS_LIST_RE: &str = r"(.*pid.+\bname\b)(?s)(.*)";
+// Parse a size given on the command line into KiB
+fn parse_size(s: &str) -> Result<f64, String> {
+    parse_size_kib(s).ok_or(format!("invalid size '{}', expected ie. 16G or 512M", s))
+}
 
-// Find total pages of RAM and return value in KiB
-fn parse_meminfo_total(s: &str) -> Option<f64> {
-    const PAGES_RAM_RE: &str = r"(\d+) pages RAM";
-    const PAGES_RESERVED_RE: &str = r"(\d+) pages reserved";
+impl Cli {
+    // The formats to write the report in, text unless asked otherwise
+    fn formats(&self) -> Vec<Format> {
+        if self.json {
+            vec![Format::Json]
+        } else if self.format.is_empty() {
+            vec![Format::Text]
+        } else {
+            self.format.clone()
+        }
+    }
 
-    let re = Regex::new(PAGES_RAM_RE).unwrap();
-    let pages_ram = re.captures(s)?.get(1)?.as_str().parse::<f64>().unwrap();
+    // The fields of a JSON log holding each line's message and time
+    fn json_fields(&self) -> JsonFields {
+        JsonFields {
+            message: self.json_field.clone(),
+            time: self.json_time.clone(),
+        }
+    }
 
-    let re = Regex::new(PAGES_RESERVED_RE).unwrap();
-    let pages_reserved = re.captures(s)?.get(1)?.as_str().parse::<f64>().unwrap();
+    // How to write the text output. Tables fit the terminal when the output goes to one, or
+    // --width.
+    fn text_options(&self) -> TextOptions {
+        let terminal = self.output.is_none() && io::stdout().is_terminal();
+        let terminal_width = self.width.or_else(|| {
+            let (Width(width), _) = terminal_size().filter(|_| terminal)?;
+            Some(width as usize)
+        });
+
+        TextOptions {
+            number_format: if self.plain_numbers {
+                NumberFormat::plain()
+            } else {
+                NumberFormat::from_env()
+            },
+            table_style: self.table_style,
+            terminal_width,
+            graphics: !self.no_graphics,
+            units: self.units,
+            wide: self.wide,
+        }
+    }
 
-    // subtract reserved pages from the RAM total, which may not agree with what other tools report
-    let total_ram_kib = ((pages_ram - pages_reserved) * 4096.0) / 1024.0;
+    // Render every section unless asked otherwise, always in the usual order
+    fn sections(&self) -> Vec<Section> {
+        if self.sections.is_empty() {
+            Section::ALL.to_vec()
+        } else {
+            Section::ALL
+                .iter()
+                .copied()
+                .filter(|s| self.sections.contains(s))
+                .collect()
+        }
+    }
+}
 
-    Some(total_ram_kib)
+// The usual text report, or the --template, with the comparisons asked for on the command line
+struct TextRenderer<'a> {
+    input: &'a str,
+    cli: &'a Cli,
 }
 
-// Report free swap in KiB
-fn parse_meminfo_swap(s: &str) -> Option<f64> {
-    const FREE_SWAP_RE: &str = r"Free swap\s+=\s+(\d+)";
+impl Renderer for TextRenderer<'_> {
+    fn name(&self) -> &'static str {
+        Format::Text.name()
+    }
 
-    let re = Regex::new(FREE_SWAP_RE).unwrap();
+    fn extension(&self) -> &'static str {
+        "txt"
+    }
 
-    if let Some(x) = re.captures(s) {
-        let swap = x.get(1)?.as_str().parse::<f64>().unwrap();
-        let swap = swap * (2048.0 / 1.024);
-        Some(swap)
-    } else {
-        None
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        render_text(report, self.input, self.cli)
     }
 }
 
-// Report unreclaimable slab usage in KiB
-fn parse_meminfo_slab(s: &str) -> Option<f64> {
-    const UNRECLAIMABLE_SLAB_RE: &str = r"slab_unreclaimable:(\d+)";
+#[derive(Subcommand)]
+enum Command {
+    /// Print the JSON Schema for machine-readable output
+    Schema,
+
+    /// Show which parts of the last oom-killer report in a log were found, to help report
+    /// parsing problems
+    Doctor {
+        /// Log file or directory of logs, or - for stdin
+        logfile: PathBuf,
+    },
+
+    /// List the oom-killer reports in a log, numbered as --event takes them, with the line each
+    /// starts on, its time, and the process killed, without parsing them
+    Events {
+        /// Log file or directory of logs, or - for stdin
+        logfile: PathBuf,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare the last oom-killer report in two logs side by side
+    Diff {
+        /// Log with the earlier oom-killer report
+        old: PathBuf,
+
+        /// Log with the later oom-killer report
+        new: PathBuf,
+    },
+
+    /// Analyze an anonymized oom-killer report built into parseoom, to see what the output
+    /// looks like
+    Demo {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a completion script for a shell, generated from the command line options
+    Completions {
+        /// Shell to complete in
+        shell: Shell,
+    },
+
+    /// Print the man page in roff format, generated from the command line options
+    Man,
+
+    /// Install a hardened systemd service that runs parseoom with the given options, following
+    /// the journal by default, or a timer that runs it periodically with --every
+    InstallService {
+        /// Run parseoom at this interval from a timer instead of as a long-running service, ie.
+        /// 15min or 1h
+        #[arg(long, value_name = "SPAN")]
+        every: Option<String>,
+
+        /// Name of the service and timer units
+        #[arg(long, default_value = "parseoom")]
+        name: String,
+
+        /// Directory to write the units to
+        #[arg(long, value_name = "DIR", default_value = "/etc/systemd/system")]
+        dir: PathBuf,
+
+        /// Print the units instead of writing them
+        #[arg(long)]
+        print: bool,
+
+        /// Options and log for parseoom to run with, after --
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
 
-    // The first slab_unreclaimable entry in MemInfo contains the total for all zones, in pages
-    let re = Regex::new(UNRECLAIMABLE_SLAB_RE).unwrap();
+// Read a file given alongside the log
+fn read_text(path: &Path) -> Result<String, Box<dyn Error>> {
+    Ok(fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?)
+}
 
-    if let Some(x) = re.captures(s) {
-        let slab = x.get(1)?.as_str();
-        let slab_kib = (slab.parse::<f64>().unwrap() * 4096.0) / 1024.0;
-        Some(slab_kib)
-    } else {
-        None
+// Print the kills of Android's low memory killer, for logs from a device that never reached the
+// kernel's oom-killer
+fn print_lmk_kills(kills: &[LmkKill], top: Top, options: &TextOptions) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let freed = kills.iter().map(|k| k.freed_kib).sum::<f64>();
+
+    writeln!(stdout, "\nAndroid low memory killer:")?;
+    writeln!(
+        stdout,
+        "    {} kills freed {} MiB",
+        kills.len(),
+        options.num(freed / 1024.0, 1)
+    )?;
+
+    writeln!(stdout, "\n    Kills by category:\n")?;
+    let mut categories: Vec<(&str, usize, f64)> = Vec::new();
+    for kill in kills {
+        let category = adj_category(kill.adj);
+        match categories.iter_mut().find(|(c, _, _)| *c == category) {
+            Some((_, count, kib)) => {
+                *count += 1;
+                *kib += kill.freed_kib;
+            }
+            None => categories.push((category, 1, kill.freed_kib)),
+        }
+    }
+    for (category, count, kib) in categories {
+        writeln!(
+            stdout,
+            "    {:<14} {:>6} kills {:>12} MiB",
+            category,
+            count,
+            options.num(kib / 1024.0, 1)
+        )?;
+    }
+
+    // The most recent kills are the ones closest to whatever the user noticed
+    let shown = kills.len().min(top.rows());
+    writeln!(stdout, "\n    {}:\n", top.title("recent kills"))?;
+    writeln!(
+        stdout,
+        "    {:<20} {:>7}  {:<32} {:>6}  {:<14} {:>12}",
+        "time", "pid", "process", "adj", "category", "freed MiB"
+    )?;
+    for kill in &kills[kills.len() - shown..] {
+        writeln!(
+            stdout,
+            "    {:<20} {:>7}  {:<32} {:>6}  {:<14} {:>12}",
+            kill.timestamp.as_deref().unwrap_or("-"),
+            kill.pid,
+            kill.process,
+            kill.adj,
+            adj_category(kill.adj),
+            options.num(kill.freed_kib / 1024.0, 1)
+        )?;
+        if let Some(reason) = &kill.reason {
+            writeln!(stdout, "    {:<20}          {}", "", reason)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Count the lines in the log containing a pattern, like grep -c
+fn count_lines(input: &str, pattern: &str) -> usize {
+    input.lines().filter(|line| line.contains(pattern)).count()
+}
+
+// Where the text given to analyze() came from, for messages about a line of it: the name of the
+// input source and the line of the log the text starts on
+#[derive(Clone, Copy)]
+struct Origin<'a> {
+    name: &'a str,
+    first_line: usize,
+}
+
+impl Origin<'_> {
+    // A line of the text as "name:line", numbered as in the whole log
+    fn locate(self, line: usize) -> String {
+        format!("{}:{}", self.name, self.first_line + line - 1)
     }
 }
 
-// Find and return size of 2 MiB and 1 GiB huge page allocations in KiB
-// t.0 is the sum of 2 MB huge pages in KiB
-// t.1 is the sum of 1 GB huge pages in KiB
-fn parse_meminfo_hugepages(s: &str) -> Option<(f64, f64)> {
-    const HUGEPAGES_2MB_RE: &str = r"hugepages_total=(\d+).+?hugepages_size=2048kB";
-    const HUGEPAGES_1GB_RE: &str = r"hugepages_total=(\d+).+?hugepages_size=1048576kB";
-    let mut t = (0.0, 0.0);
+// Parse the last oom-killer report in a log, printing its warnings. Warnings and errors about a
+// line of the log give its number when the origin of the log is known.
+fn build_report(
+    input: &str,
+    origin: Option<Origin>,
+    cli: &Cli,
+    rules: &[Rule],
+) -> Result<Report, Box<dyn Error>> {
+    let mut event = OomEvent::from_report(last_report(input)?).map_err(|e| match origin {
+        Some(origin) => format!("{}: {}", origin.locate(report_start_line(input)), e),
+        None => e.to_string(),
+    })?;
+    event.note_report_lines(input);
+    event.note_log_context(input);
+    event.note_kills(input);
+    event.note_precursors(input, cli.precursor_window);
+    note_event_time(&mut event, input, cli);
+    event.apply_rules(rules);
+    if let Some(expected) = cli.expected_ram {
+        event.note_expected_ram(expected);
+    }
+    // With --all, the whole log is scanned once rather than each event's part of it
+    if cli.scan_partial && !cli.all {
+        event.note_partial_reports(input);
+    }
+
+    for warning in &event.warnings {
+        match (origin, warning.line) {
+            (Some(origin), Some(line)) => eprintln!(
+                "warning: {}: {}: {}",
+                origin.locate(line),
+                warning.section,
+                warning.message
+            ),
+            _ => eprintln!("warning: {}: {}", warning.section, warning.message),
+        }
+        if let Some(excerpt) = &warning.excerpt {
+            eprintln!("    {}", excerpt);
+        }
+    }
 
-    let re = Regex::new(HUGEPAGES_2MB_RE).unwrap();
-    let mut num_2_mb_hugepages = 0.0;
+    Ok(new_report(event, cli))
+}
 
-    for caps in re.captures_iter(s) {
-        num_2_mb_hugepages += &caps[1].parse::<f64>().unwrap();
+// The report of an event, ranked and filtered as the command line asks
+fn new_report(event: OomEvent, cli: &Cli) -> Report {
+    let mut report = Report::new(event);
+    if cli.rank != Rank::Rss {
+        report.rank_by(cli.rank);
+    }
+    if let Some(re) = &cli.filter {
+        report.filter(re);
     }
 
-    // convert total 2 MiB huge page allocation from kb to kib
-    t.0 = num_2_mb_hugepages * (2048.0 / 1.024);
+    report
+}
+
+// Render a report as text, or with the --template, followed by the comparisons with the files
+// given alongside the log
+fn render_text(report: &Report, input: &str, cli: &Cli) -> Result<String, Box<dyn Error>> {
+    let mut out = String::new();
+    let out = &mut out;
+    let options = cli.text_options();
 
-    let re = Regex::new(HUGEPAGES_1GB_RE).unwrap();
-    let mut num_1_gb_hugepages = 0.0;
+    match &cli.template {
+        Some(path) => out.push_str(&render_template(&read_text(path)?, report)?),
+        None => write_report(out, &options, report, &cli.sections(), cli.top),
+    }
 
-    for caps in re.captures_iter(s) {
-        num_1_gb_hugepages += &caps[1].parse::<f64>().unwrap();
+    if let Some(path) = &cli.meminfo {
+        let text = read_text(path)?;
+        write_meminfo_changes(
+            out,
+            &options,
+            &compare_meminfo(&parse_proc_meminfo(&text), &report.event),
+        );
     }
 
-    // convert total 1 GiB huge page allocation from kb to kib
-    t.1 = num_1_gb_hugepages * (1048576.0 / 1.024);
+    let samples = match (&cli.sar, &cli.vmstat) {
+        (Some(path), _) => Some(parse_sar(&read_text(path)?)),
+        (_, Some(path)) => Some(parse_vmstat(&read_text(path)?)),
+        _ => None,
+    };
+    if let Some(samples) = samples {
+        // The kill's time of day, if the log has syslog dates
+        let start = input.rfind("invoked oom-killer").unwrap_or(0);
+        let line = &input[input[..start].rfind('\n').map_or(0, |n| n + 1)..];
+        let kill_time = parse_timestamp(line).and_then(|t| parse_time_of_day(&t));
+
+        let (before, onset) = pressure_before(&samples, kill_time);
+        write_pressure(out, &options, &before, onset);
+    }
 
-    Some(t)
+    Ok(out.to_string())
 }
 
-// Report shared memory in KiB
-fn parse_meminfo_shared(s: &str) -> Option<f64> {
-    const SHMEM_RE: &str = r"shmem:(\d+)";
+// Parse and print the last oom-killer report in a log
+fn analyze(
+    input: &str,
+    origin: Option<Origin>,
+    cli: &Cli,
+    rules: &[Rule],
+) -> Result<(), Box<dyn Error>> {
+    // read from beginning of last oom kill to end of log
+    let oom = last_report(input)?;
 
-    let re = Regex::new(SHMEM_RE).unwrap();
+    if oom.contains("Memory cgroup out of memory") {
+        let mut stdout = io::stdout().lock();
+        writeln!(
+            stdout,
+            "Out of memory killer was triggered by exceeding cgroup limit."
+        )?;
+        writeln!(
+            stdout,
+            "Lines matching 'invoked oom-killer': {}",
+            count_lines(input, "invoked oom-killer")
+        )?;
+        writeln!(
+            stdout,
+            "Lines matching 'Memory cgroup out of memory': {}",
+            count_lines(input, "Memory cgroup out of memory")
+        )?;
+        if let Some(jvm) = memcg_jvm_usage(oom) {
+            writeln!(stdout, "\n{}", jvm.hint())?;
+        }
 
-    if let Some(x) = re.captures(s) {
-        let shmem = x.get(1)?.as_str();
-        let shmem_kib = (shmem.parse::<f64>().unwrap() * 4096.0) / 1024.0;
-        Some(shmem_kib)
-    } else {
-        None
-    }
-}
-
-// Print largest unreclaimable slab caches
-fn print_unreclaimable_slab(cleaned: &str) {
-    // Starting in v4.15 the kernel will report the total size of all unreclaimable slabs if
-    // unreclaimable slab usage is greater than user memory. This report is similar to the
-    // process list and is sorted the same way.
-    //
-    // [261004.638833] Unreclaimable slab info:
-    // [261004.641825] Name                      Used          Total
-    // [261004.645467] nfs_commit_data           15KB         15KB
-    // --->8
-    // [261005.268039] kmem_cache               118KB        118KB
-    // [261005.271794] Tasks state (memory values in pages):
-
-    const SLAB_INFO_RE: &str = r"(?sx:      # allow . to match \n; ignore whitespace
-        (Unreclaimable\sslab\sinfo:)
-        (.*?Name\s+Used\s+Total)            # match header
-        (.*KB)                              # match lines with a slab entry
-        (.*Tasks\sstate)                    # match end of slab list
-        )";
-
-    let re: Regex = Regex::new(SLAB_INFO_RE).unwrap();
-
-    // Return early if unreclaimable slab wasn't reported by the oom-killer
-    if !re.is_match(cleaned) {
-        return;
+        let report = build_report(input, origin, cli, rules)?;
+        let mut out = String::new();
+        write_kills(&mut out, &cli.text_options(), &report.event);
+        write!(stdout, "{}", out)?;
+
+        return Ok(());
     }
 
-    let slab_header = re
-        .captures(cleaned)
-        .unwrap()
-        .get(2)
-        .unwrap()
-        .as_str()
-        .trim();
+    let report = build_report(input, origin, cli, rules)?;
+    let text =
+        info_span!("render", format = "text").in_scope(|| render_text(&report, input, cli))?;
+    write!(io::stdout().lock(), "{}", text)?;
 
-    let slab_header_vec = slab_header
-        .split_whitespace()
-        .map(str::to_string)
-        .collect::<Vec<_>>();
+    Ok(())
+}
 
-    let slab_name_col = slab_header_vec.iter().position(|x| x == "Name").unwrap();
-
-    let slabs = re
-        .captures(cleaned)
-        .unwrap()
-        .get(3)
-        .unwrap()
-        .as_str()
-        .trim();
-
-    let mut slab_vec = slabs
-        .lines()
-        .map(|s| {
-            s.replace("KB", "")
-                .split_whitespace()
-                .map(String::from)
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
+// Parse the last oom-killer report once and write it in each of the --format formats: to the
+// --output path, with the format's extension added when there's more than one, or to stdout
+fn emit(input: &str, origin: Origin, cli: &Cli, rules: &[Rule]) -> Result<(), Box<dyn Error>> {
+    let formats = cli.formats();
+    if formats.len() > 1 && cli.output.is_none() {
+        return Err("--format with more than one format needs --output".into());
+    }
+    let report = build_report(input, Some(origin), cli, rules)?;
+    let mut renderers = Renderers::new();
+    renderers.register(Box::new(TextRenderer { input, cli }));
+
+    for format in formats.iter() {
+        let renderer = renderers
+            .get(format.name())
+            .ok_or(format!("no renderer for {}", format.name()))?;
+        let rendered =
+            info_span!("render", format = renderer.name()).in_scope(|| renderer.render(&report))?;
+
+        match &cli.output {
+            Some(path) if formats.len() > 1 => {
+                write_output(&output_path(path, renderer.extension()), &rendered)?
+            }
+            Some(path) => write_output(path, &rendered)?,
+            None => write!(io::stdout().lock(), "{}", rendered)?,
+        }
+    }
 
-    slab_vec.sort_by(|a, b| {
-        (b[slab_name_col + 2].parse::<i64>().unwrap())
-            .cmp(&a[slab_name_col + 2].parse::<i64>().unwrap())
-    });
+    Ok(())
+}
 
-    println!("\n    Largest unreclaimable slabs:\n");
-    println!(
-        "    {:<24} {:>15} {:>15}",
-        slab_header_vec[slab_name_col],     // name
-        slab_header_vec[slab_name_col + 1], // used
-        slab_header_vec[slab_name_col + 2], // total
-    );
+// Put the events of a log in the order they happened, as logs merged from several files may not
+// be, and keep those between --since and --until. Events are only reordered when every one has
+// a date that can be compared with the others'. Each event is returned with its time.
+#[allow(clippy::type_complexity)]
+fn order_events<'a>(
+    chunks: Vec<&'a str>,
+    cli: &Cli,
+    year: i32,
+) -> Result<Vec<(&'a str, Option<LogTime>)>, Box<dyn Error>> {
+    let timestamps = chunks
+        .iter()
+        .map(|chunk| chunk.lines().next().and_then(parse_timestamp))
+        .collect::<Vec<_>>();
+    let times = parse_log_times(&timestamps, year);
 
-    for line in slab_vec.into_iter().take(10) {
-        println!(
-            "    {:<24} {:>12} KB {:>12} KB",
-            line[slab_name_col],     // name
-            line[slab_name_col + 1], // used
-            line[slab_name_col + 2], // total
-        );
+    let mut events = chunks.into_iter().zip(times).collect::<Vec<_>>();
+    if let Some(Some(first)) = events.first().map(|(_, time)| *time) {
+        let since_first = |time: &Option<LogTime>| time.as_ref()?.seconds_since(&first);
+        if first.is_date() && events.iter().all(|(_, time)| since_first(time).is_some()) {
+            events.sort_by(|a, b| since_first(&a.1).partial_cmp(&since_first(&b.1)).unwrap());
+        }
     }
+
+    let bound = |value: &Option<String>| -> Result<Option<LogTime>, Box<dyn Error>> {
+        match value {
+            Some(value) => Ok(Some(
+                parse_log_time(value, year).ok_or(format!("invalid time '{}'", value))?,
+            )),
+            None => Ok(None),
+        }
+    };
+    let (since, until) = (bound(&cli.since)?, bound(&cli.until)?);
+
+    // An event that can't be placed against the bounds is left out
+    let within = |time: &Option<LogTime>| {
+        let after = since.is_none_or(|since| {
+            time.and_then(|time| time.seconds_since(&since))
+                .is_some_and(|s| s >= 0.0)
+        });
+        let before = until.is_none_or(|until| {
+            time.and_then(|time| time.seconds_since(&until))
+                .is_some_and(|s| s <= 0.0)
+        });
+        after && before
+    };
+
+    Ok(events
+        .into_iter()
+        .filter(|(_, time)| within(time))
+        .collect())
 }
 
-// Split the process list header into a vector. Return the ps header as a vector along with the
-// position of the pid column.
-fn parse_ps_header(cleaned: &str) -> Option<(Vec<String>, usize)> {
-    // Split each line of the ps string at whitespace and add the line to a vector of strings.
-    //
-    //      [
-    //        ["Dec", "20", "03:17:52", "localhost", "kernel:", "75669.642775", "199", "0",
-    //          "199", "14838", "226", "102400", "14", "-250", "systemd-journal"],
-    //        ...
-    //      ]
-    //
-    let re = Regex::new(PS_LIST_RE).unwrap();
-    let ps_header = re.captures(cleaned)?.get(1)?.as_str().trim();
-    let header_vec = ps_header
-        .split_whitespace()
-        .map(str::to_string)
+// The year the log's syslog dates start in, from --year or the current date
+fn log_year(chunks: &[&str], cli: &Cli) -> i32 {
+    let timestamps = chunks
+        .iter()
+        .map(|chunk| chunk.lines().next().and_then(parse_timestamp))
         .collect::<Vec<_>>();
-    let pid_col = header_vec.iter().position(|x| x == "pid")?;
+    let now = chrono::DateTime::<chrono::Utc>::from(SystemTime::now()).naive_utc();
+
+    cli.year.unwrap_or_else(|| default_year(&timestamps, now))
+}
 
-    Some((header_vec, pid_col))
+// The log from its start to the end of an event in it, for the warning signs logged before the
+// event. Events are slices of the log, so where one starts is where it points into the log.
+fn log_through<'a>(log: &'a str, event: &'a str) -> &'a str {
+    let start = (event.as_ptr() as usize).wrapping_sub(log.as_ptr() as usize);
+    start
+        .checked_add(event.len())
+        .and_then(|end| log.get(..end))
+        .filter(|through| through.ends_with(event))
+        .unwrap_or(event)
 }
 
-// Capture the values in the process list after the header, including the surrounding log metadata,
-// and return the list as a &str.
-fn parse_ps_list(cleaned: &str) -> Option<&str> {
-    // We're keeping the ps entry lines, including log entry noise.
-    //
-    //      "Dec 20 03:17:52 localhost kernel: 75669.642775     199     0   199    14838 \n
-    //          226   102400       14          -250 systemd-journal"
-    //
-    let re = Regex::new(PS_LIST_RE).unwrap();
+// Parse the last oom-killer report in a log for --json, with its time in UTC
+fn parse_event(input: &str, cli: &Cli) -> Result<OomEvent, Box<dyn Error>> {
+    let mut event = OomEvent::parse_with_window(input, cli.precursor_window)?;
+    note_event_time(&mut event, input, cli);
 
-    if let Some(x) = re.captures(cleaned) {
-        let ps = x.get(2)?.as_str().trim();
-        Some(ps)
-    } else {
-        None
+    Ok(event)
+}
+
+// Note the time of the last oom-killer report in a log, the event the report was parsed from
+fn note_event_time(event: &mut OomEvent, input: &str, cli: &Cli) {
+    let chunks = split_events(input);
+    if let Some(report) = chunks.last() {
+        event.note_time(report, log_year(&chunks, cli), cli.log_tz);
     }
 }
 
-// Transform the process list into a matrix of strings
-fn parse_ps_matrix(ps: &str) -> Vec<Vec<String>> {
-    let ps_matrix = ps
-        .lines()
-        .map(|s| s.split_whitespace().map(String::from).collect::<Vec<_>>())
+// Parse and print every oom-killer report in a log, then the totals across them
+fn analyze_all(
+    input: &str,
+    origin: Origin,
+    cli: &Cli,
+    rules: &[Rule],
+) -> Result<(), Box<dyn Error>> {
+    let (chunks, duplicates) = dedup_events(split_events(input));
+
+    if chunks.is_empty() {
+        return Err("string 'invoked oom-killer' not found".into());
+    }
+    let year = log_year(&chunks, cli);
+    let (chunks, times): (Vec<_>, Vec<_>) = order_events(chunks, cli, year)?.into_iter().unzip();
+    if chunks.is_empty() {
+        return Err("no oom-killer reports between --since and --until".into());
+    }
+
+    let summaries = chunks
+        .iter()
+        .map(|chunk| EventSummary::new(chunk))
+        .collect::<Vec<_>>();
+    let utc = times
+        .iter()
+        .map(|time| time.as_ref()?.to_utc(cli.log_tz))
         .collect::<Vec<_>>();
 
-    ps_matrix
-}
-
-// Parse the ps matrix and return a map of commands -> RSS.
-fn top_consumers(
-    ps_matrix: &[Vec<String>],
-    pid_col: usize,
-) -> std::collections::BTreeMap<String, i64> {
-    // Iterate over each line in the matrix to create a map
-    // of unique commands with their total RSS usage.
-    //
-    //      {"agetty": 59, "anvil": 189, ...}
-    //
-    let mut commands: BTreeMap<String, i64> = BTreeMap::new();
-
-    for line in ps_matrix.iter() {
-        *commands.entry(line[pid_col + 8].clone()).or_insert(0) +=
-            line[pid_col + 4].parse::<i64>().unwrap();
-    }
-
-    commands
-}
-
-// Print the commands using the most memory.
-fn print_top_commands(commands: BTreeMap<String, i64>) {
-    // To sort the key (command name) by its value (RSS) we need to convert
-    // the map to a vector:
-    let mut command_vec = Vec::from_iter(commands.iter());
-    command_vec.sort_by(|a, b| b.1.cmp(a.1));
-
-    // Now we have a list of command names sorted by RSS usage we can
-    // print the top consumers by name.
-    println!("\nTop 10 unique commands using memory:\n");
-    for line in command_vec.iter().take(10) {
-        let rss = *line.1 as f64;
-        println!(
-            "    {:15}    {:8.1} MiB",
-            line.0,
-            (rss * 4096.0) / 1024.0 / 1024.0
-        );
+    // Times are shown as logged unless --tz asks for them in one zone
+    let shown = summaries
+        .iter()
+        .zip(utc.iter())
+        .map(|(summary, utc)| match (cli.tz, utc) {
+            (Some(tz), Some(utc)) => Some(tz.format(*utc)),
+            _ => summary.timestamp.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut rollup = Rollup::new(&summaries, year);
+    rollup.duplicates = duplicates;
+    if cli.tz.is_some() {
+        rollup.first = shown.iter().flatten().next().cloned();
+        rollup.last = shown.iter().flatten().next_back().cloned();
+    }
+    let events = chunks
+        .iter()
+        .zip(utc.iter())
+        .map(|(chunk, utc)| {
+            let mut event = OomEvent::parse(chunk)?;
+            event.note_precursors(log_through(input, chunk), cli.precursor_window);
+            event.time = utc.map(json_time);
+            event.apply_rules(rules);
+            if let Some(expected) = cli.expected_ram {
+                event.note_expected_ram(expected);
+            }
+            Ok(event)
+        })
+        .collect::<Vec<Result<_, Box<dyn Error>>>>();
+    let parsed = events.iter().map(|e| e.as_ref().ok()).collect::<Vec<_>>();
+    let timeline = command_timeline(&parsed, 10);
+    let freed = parsed.last().and_then(|e| e.as_ref()?.freed.as_ref());
+    rollup.recurrence = predict_recurrence(
+        &timeline,
+        &times,
+        rollup.mean_seconds_between_kills,
+        freed.map(|f| f.rss_kib),
+    );
+    let partials = if cli.scan_partial {
+        find_partial_reports(input)
+    } else {
+        Vec::new()
+    };
+
+    if cli.formats() == [Format::Json] {
+        // Each event has its time in UTC, so events from hosts in different zones line up, and
+        // one that couldn't be parsed is given it alongside the error
+        let events = events
+            .into_iter()
+            .zip(utc.iter())
+            .map(|(event, utc)| match event {
+                Ok(event) => serde_json::to_value(event),
+                Err(e) => Ok(serde_json::json!({
+                    "error": e.to_string(),
+                    "time": utc.map(json_time),
+                })),
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+
+        let value = serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "events": events,
+            "rollup": rollup,
+            "timeline": timeline,
+            "partial_reports": partials,
+        });
+        match &cli.output {
+            Some(path) => write_output(path, &format!("{}\n", value))?,
+            None => writeln!(io::stdout().lock(), "{}", value)?,
+        }
+
+        return Ok(());
+    }
+
+    // A table or page of every event, numbered as the text output numbers them
+    if let [format @ (Format::Csv | Format::Tsv | Format::Html)] = cli.formats()[..] {
+        let mut reports = Vec::new();
+        for (i, event) in events.into_iter().enumerate() {
+            match event {
+                Ok(event) => reports.push((i + 1, new_report(event, cli))),
+                Err(e) => eprintln!("error: event {}: {}", i + 1, e),
+            }
+        }
+        let rendered = render_events(&reports, format)?;
+        match &cli.output {
+            Some(path) => write_output(path, &rendered)?,
+            None => write!(io::stdout().lock(), "{}", rendered)?,
+        }
+
+        return Ok(());
+    }
+
+    for (i, (chunk, shown)) in chunks.iter().zip(shown.iter()).enumerate() {
+        writeln!(
+            io::stdout().lock(),
+            "\n==== Event {} of {}: {} ====",
+            i + 1,
+            chunks.len(),
+            shown.as_deref().unwrap_or("unknown time")
+        )?;
+
+        // Each chunk is a slice of the log, so the line it starts on can be found from where it
+        // starts
+        let offset = chunk.as_ptr() as usize - input.as_ptr() as usize;
+        let chunk_origin = Origin {
+            first_line: origin.first_line + input[..offset].matches('\n').count(),
+            ..origin
+        };
+
+        // Keep going past a damaged report, the rest are still worth seeing
+        match analyze(chunk, Some(chunk_origin), cli, rules) {
+            Err(e) if is_broken_pipe(e.as_ref()) => return Err(e),
+            Err(e) => eprintln!("error: event {}: {}", i + 1, e),
+            Ok(()) => (),
+        }
     }
+
+    let options = cli.text_options();
+    print_rollup(&rollup, &options)?;
+    print_timeline(&timeline, chunks.len(), &options)?;
+    let mut out = String::new();
+    write_partial_reports(&mut out, &options, &partials);
+    write!(io::stdout().lock(), "{}", out)?;
+
+    Ok(())
 }
 
-// Sort and print the process list.
-fn print_ps_list(mut ps_matrix: Vec<Vec<String>>, header_vec: Vec<String>, pid_col: usize) {
-    // Sort and display the process list.
-    // The format may change depending on kernel version, but the number of columns and the
-    // position of pid, rss, and name should remain fixed, ie:
-    //
-    //      [  pid  ]   uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name
-    //
-    // Print the header from header_vec first.
-    println!("\nProcesses using most memory:\n");
-    println!(
-        "{:^7}  {:>8}  {:>6}  {:>10}  {:>8}  {:>16}  {:>10}  {:>15}  {:<15}  {:>8}",
-        header_vec[pid_col],     // pid
-        header_vec[pid_col + 1], // uid
-        header_vec[pid_col + 2],
-        header_vec[pid_col + 3],
-        header_vec[pid_col + 4], // rss
-        header_vec[pid_col + 5],
-        header_vec[pid_col + 6],
-        header_vec[pid_col + 7],
-        header_vec[pid_col + 8], // name
-        "MiB"
-    );
+// Write a host's last report to the --output-dir as text and JSON
+fn write_host_report(
+    dir: &Path,
+    host: &str,
+    log: &str,
+    cli: &Cli,
+    rules: &[Rule],
+) -> Result<(), Box<dyn Error>> {
+    let origin = Origin {
+        name: host,
+        first_line: 1,
+    };
+    let report = build_report(log, Some(origin), cli, rules)?;
+
+    let text = render_text(&report, log, cli)?;
+    let json = serde_json::to_string(&JsonReport::new(&report.event))? + "\n";
+    for (extension, rendered) in [("txt", text), ("json", json)] {
+        let path = dir.join(format!("{}.{}", host, extension));
+        fs::write(&path, rendered).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
 
-    // Sort and display the entire process list from the matrix we started with.
-    // The RSS field must be converted from a string to an integer in order to sort.
-    ps_matrix.sort_by(|a, b| {
-        (b[pid_col + 4].parse::<i64>().unwrap()).cmp(&a[pid_col + 4].parse::<i64>().unwrap())
+// Analyze the logs of every host in a directory of per-host logs, several hosts at once, and
+// print a summary of the fleet
+fn analyze_hosts(dir: &Path, cli: &Cli, rules: &[Rule]) -> Result<(), Box<dyn Error>> {
+    let hosts = host_dirs(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    if hosts.is_empty() {
+        return Err(format!("{}: no host directories", dir.display()).into());
+    }
+    if let Some(out) = &cli.output_dir {
+        fs::create_dir_all(out).map_err(|e| format!("{}: {}", out.display(), e))?;
+    }
+    let jobs = cli
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let summaries = map_parallel(&hosts, jobs, |(host, path)| {
+        let log = match DirectorySource::new(path).read_log(&cli.json_fields()) {
+            Ok(log) => log,
+            Err(e) => return HostSummary::failed(host, e.to_string()),
+        };
+        let year = log_year(&split_events(&log), cli);
+        let mut summary = HostSummary::new(host, &log, year, cli.log_tz);
+
+        if let (Some(out), true) = (&cli.output_dir, summary.events > 0) {
+            if let Err(e) = write_host_report(out, host, &log, cli, rules) {
+                summary.error.get_or_insert(e.to_string());
+            }
+        }
+        summary
     });
 
-    // Iterate over the sorted process matrix and display the top results.
-    for line in ps_matrix.into_iter().take(10) {
-        println!(
-            "{:>7}  {:>8}  {:>6}  {:>10}  {:>8}  {:>16}  {:>10}  {:>15}  {:<15}  {:>8.1}",
-            line[pid_col],     // pid
-            line[pid_col + 1], // uid
-            line[pid_col + 2],
-            line[pid_col + 3],
-            line[pid_col + 4], // rss
-            line[pid_col + 5],
-            line[pid_col + 6],
-            line[pid_col + 7],
-            line[pid_col + 8], // name
-            (line[pid_col + 4].parse::<f64>().unwrap() * 4096.0) / 1024.0 / 1024.0  // size MiB
+    if cli.formats() == [Format::Json] {
+        let value = serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "hosts": summaries,
+            "fleet": FleetStats::new(&summaries),
+        });
+        writeln!(io::stdout().lock(), "{}", value)?;
+        return Ok(());
+    }
+
+    print_hosts(&summaries, cli.tz)?;
+    print_fleet_stats(&FleetStats::new(&summaries))?;
+
+    Ok(())
+}
+
+// Print a line for each host of a fleet, then the errors reading any of them. The last event is
+// shown as logged unless a zone is given to show it in.
+fn print_hosts(summaries: &[HostSummary], tz: Option<Zone>) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let affected = summaries.iter().filter(|s| s.events > 0).count();
+    writeln!(
+        stdout,
+        "\n==== Fleet: {} hosts, {} with oom-kills ====\n",
+        summaries.len(),
+        affected
+    )?;
+
+    writeln!(
+        stdout,
+        "    {:20}  {:>6}  {:>6}  {:23}  {:15}  verdict",
+        "host", "events", "kills", "last event", "last victim"
+    )?;
+    for summary in summaries {
+        let last = match (tz, summary.last_time.as_deref()) {
+            (Some(tz), Some(utc)) => DateTime::parse_from_rfc3339(utc)
+                .ok()
+                .map(|utc| tz.format(utc.to_utc())),
+            _ => summary.last.clone(),
+        };
+        let line = format!(
+            "    {:20}  {:>6}  {:>6}  {:23}  {:15}  {}",
+            summary.host,
+            summary.events,
+            summary.kills,
+            last.as_deref().unwrap_or("-"),
+            summary.last_victim.as_deref().unwrap_or("-"),
+            summary.verdict.join(", ")
         );
+        writeln!(stdout, "{}", line.trim_end())?;
+    }
+
+    let errors = summaries.iter().filter(|s| s.error.is_some());
+    if errors.clone().next().is_some() {
+        writeln!(stdout, "\nErrors:\n")?;
+        for summary in errors {
+            writeln!(
+                stdout,
+                "    {}: {}",
+                summary.host,
+                summary.error.as_deref().unwrap_or("")
+            )?;
+        }
     }
+
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut args = env::args();
+// Print the rankings across a fleet: the hosts with the most oom-killer invocations, the
+// commands killed most often, and the hosts whose last invocation had each likely cause
+fn print_fleet_stats(stats: &FleetStats) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(
+        stdout,
+        "\nAcross the fleet: {} events, {} processes killed\n",
+        stats.events, stats.kills
+    )?;
+
+    if !stats.events_by_host.is_empty() {
+        writeln!(stdout, "Hosts by oom-killer invocations:\n")?;
+        for (host, events) in stats.events_by_host.iter().take(10) {
+            writeln!(stdout, "    {:20}  {:>6}", host, events)?;
+        }
+    }
 
-    if args.len() < 2 {
-        eprintln!("USAGE: parseoom [filename]");
-        process::exit(1);
+    if !stats.kills_by_command.is_empty() {
+        writeln!(stdout, "\nMost killed commands:\n")?;
+        writeln!(
+            stdout,
+            "    {:15}  {:>6}  {:>6}",
+            "command", "kills", "hosts"
+        )?;
+        for command in stats.kills_by_command.iter().take(10) {
+            writeln!(
+                stdout,
+                "    {:15}  {:>6}  {:>6}",
+                command.command, command.kills, command.hosts
+            )?;
+        }
     }
 
-    args.next();
+    if !stats.hosts_by_verdict.is_empty() {
+        writeln!(stdout, "\nHosts by likely cause of their last oom-kill:\n")?;
+        for (category, hosts) in &stats.hosts_by_verdict {
+            writeln!(
+                stdout,
+                "    {:15}  {:>6}  {}",
+                category,
+                hosts.len(),
+                hosts.join(", ")
+            )?;
+        }
+    }
 
-    let filename = args.next().ok_or("Filename not provided")?;
+    writeln!(stdout)?;
 
-    // read from beginning of last oom kill to end of log
-    let input = fs::read_to_string(&filename)?;
-    let i = input
-        .rfind("invoked oom-killer")
-        .ok_or("string 'invoked oom-killer' not found")?;
-    let contents = &input[i..];
-
-    // match from invocation of oom killer to end of process list, just before end of report
-    let oom_kill_re = Regex::new(OOM_KILL_RE).unwrap();
-    let mat = oom_kill_re
-        .captures(contents)
-        .ok_or("Could not match an oom kill message in this file")?;
-
-    let oom = mat
-        .get(0)
-        .expect("Match for 'invoked oom-killer' not found")
-        .as_str();
+    Ok(())
+}
 
-    if oom.contains("Memory cgroup out of memory") {
-        println!("Out of memory killer was triggered by exceeding cgroup limit.");
-        let output = process::Command::new("grep")
-            .arg("-c")
-            .arg("invoked oom-killer")
-            .arg(&filename)
-            .output()
-            .expect("failed to execute process 'grep'");
-        print!(
-            "grep -c 'invoked oom-killer': {}",
-            String::from_utf8_lossy(&output.stdout)
-        );
+// Print how the largest commands' RSS changed from event to event, one column per event. The
+// last column is the change from the first event the command appeared in to the last.
+fn print_timeline(
+    timeline: &[TimelineRow],
+    events: usize,
+    options: &TextOptions,
+) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "\nRSS of top commands by event (MiB):\n")?;
+
+    let columns = (1..=events)
+        .map(|i| format!("{:>8}", format!("#{}", i)))
+        .collect::<String>();
+    writeln!(stdout, "    {:15}{}  {:>9}", "command", columns, "change")?;
+
+    for row in timeline {
+        let cells = row
+            .rss
+            .iter()
+            .map(|rss| match rss {
+                Some(rss) => format!("{:>8}", options.num(*rss as f64 / 1024.0 / 1024.0, 1)),
+                None => format!("{:>8}", "-"),
+            })
+            .collect::<String>();
+
+        let seen = row
+            .rss
+            .iter()
+            .flatten()
+            .filter(|&&rss| rss > 0)
+            .collect::<Vec<_>>();
+        let change = match (seen.first(), seen.last()) {
+            (Some(&&first), Some(&&last)) => {
+                format!(
+                    "{:>9}",
+                    options.num_signed((last - first) as f64 / 1024.0 / 1024.0, 1)
+                )
+            }
+            _ => format!("{:>9}", "-"),
+        };
+
+        writeln!(stdout, "    {:15}{}  {}", row.command, cells, change)?;
+    }
 
-        let output = process::Command::new("grep")
-            .arg("-c")
-            .arg("Memory cgroup out of memory")
-            .arg(&filename)
-            .output()
-            .expect("failed to execute process 'grep'");
-        print!(
-            "grep -c 'Memory cgroup out of memory': {}",
-            String::from_utf8_lossy(&output.stdout)
-        );
+    Ok(())
+}
+
+// Print the totals across all events in a log
+fn print_rollup(rollup: &Rollup, options: &TextOptions) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "\n==== All Events ====")?;
+    writeln!(stdout, "\n    Events: {}", rollup.events)?;
+    writeln!(stdout, "    Processes killed: {}", rollup.kills)?;
+    if rollup.duplicates > 0 {
+        writeln!(
+            stdout,
+            "    Duplicate events collapsed: {} (logged more than once)",
+            rollup.duplicates
+        )?;
+    }
+    writeln!(
+        stdout,
+        "    Memory reclaimed by oom_reaper: {} MiB",
+        options.num(rollup.reclaimed_kib / 1024.0, 1)
+    )?;
+    writeln!(
+        stdout,
+        "    First event: {}",
+        rollup.first.as_deref().unwrap_or("unknown")
+    )?;
+    writeln!(
+        stdout,
+        "    Last event: {}",
+        rollup.last.as_deref().unwrap_or("unknown")
+    )?;
+    if let Some(mean) = rollup.mean_seconds_between_kills {
+        writeln!(
+            stdout,
+            "    Mean time between kills: {} minutes",
+            options.num(mean / 60.0, 1)
+        )?;
+    }
+    match &rollup.recurrence {
+        Some(r) if r.basis == "growth" => writeln!(
+            stdout,
+            "    At the current growth of {} ({} MiB/h), next oom-kill expected in {}",
+            r.command,
+            options.num_signed(r.growth_bytes_per_hour.unwrap_or(0.0) / 1024.0 / 1024.0, 1),
+            approx_duration(r.seconds_to_next)
+        )?,
+        Some(r) => writeln!(
+            stdout,
+            "    At the current rate of kills, next oom-kill expected in {}",
+            approx_duration(r.seconds_to_next)
+        )?,
+        None => (),
+    }
+
+    writeln!(stdout, "\nKills by command:\n")?;
+    for (command, kills) in rollup.kills_by_command.iter() {
+        writeln!(stdout, "    {:15}    {:6}", command, kills)?;
+    }
 
-        std::process::exit(0);
+    writeln!(stdout, "\nKills by cgroup:\n")?;
+    for (cgroup, kills) in rollup.kills_by_cgroup.iter() {
+        writeln!(stdout, "    {:50}    {:6}", cgroup, kills)?;
     }
 
-    // Clean up the oom kill report for ease of parsing
-    let mut cleaned = String::new();
-    let oom_end = Regex::new(PS_LIST_END_RE).unwrap();
+    writeln!(stdout)?;
+
+    Ok(())
+}
 
-    // Strip out end of report summary and PID column brackets
-    for line in oom.lines() {
-        // These patterns appear immediately after the end of the ps list.
-        // Do not include them in the new string so we know where to stop.
-        if oom_end.is_match(line) {
-            continue;
+// Print the lines of the last report no section parser claims to stderr, each kind of line with
+// how often it appears and where
+fn print_unclaimed_lines(groups: &[UnclaimedGroup], origin: &Origin) {
+    if groups.is_empty() {
+        return;
+    }
+
+    eprintln!("Lines no section parser claims:");
+    for group in groups {
+        eprintln!("\n    {} ({} lines)", group.shape, group.lines.len());
+        for line in group.lines.iter() {
+            eprintln!("        {}: {}", origin.locate(line.line), line.text);
         }
+    }
+}
 
-        let s = line.replace('[', ""); // clean up PID entries
-        let s = s.replace(']', "");
+// Print what doctor found in a log, one line per part of the report
+fn print_diagnosis(diagnosis: &Diagnosis) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "\nLog format: {}", diagnosis.format.name())?;
+    writeln!(stdout, "oom-killer reports: {}", diagnosis.reports)?;
+    if diagnosis.memcg {
+        writeln!(
+            stdout,
+            "The last report is for a memory cgroup limit, only a summary is printed"
+        )?;
+    }
 
-        cleaned.push_str(&s);
-        cleaned.push('\n');
+    if !diagnosis.sections.is_empty() {
+        writeln!(stdout, "\nSections in the last report:\n")?;
+    }
+    for (name, found) in diagnosis.sections.iter() {
+        writeln!(
+            stdout,
+            "    {:10} {}",
+            name,
+            if *found { "found" } else { "missing" }
+        )?;
     }
 
-    let total_ram_KiB = parse_meminfo_total(&cleaned).ok_or("No match for total pages RAM.")?;
-    let free_swap_KiB = parse_meminfo_swap(&cleaned).ok_or("No match for swap.")?;
-    let (m, g) = parse_meminfo_hugepages(&cleaned).ok_or("No match for huge pages.")?;
-    let total_2_MiB_hugepages_MiB = m / 1024.0;
-    let total_1_GiB_hugepages_GiB = g / 1024.0 / 1024.0;
-    let unreclaimable_slab_KiB = parse_meminfo_slab(&cleaned).ok_or("No match for slab.")?;
-    let shmem_KiB = parse_meminfo_shared(&cleaned).ok_or("No match for shmem")?;
-    let (header_vec, pid_col) = parse_ps_header(&cleaned).ok_or("Could not find PID column")?;
-    let ps_string = parse_ps_list(&cleaned).ok_or("Failed to parse process list")?;
-    let ps_matrix = parse_ps_matrix(ps_string);
-    let commands = top_consumers(&ps_matrix, pid_col);
-    let mut rss_sum = 0;
+    if !diagnosis.checks.is_empty() {
+        writeln!(stdout, "\nValues in the last report:\n")?;
+    }
+    for check in diagnosis.checks.iter() {
+        let status = match (check.found, check.required) {
+            (true, _) => "found",
+            (false, true) => "MISSING (required)",
+            (false, false) => "missing",
+        };
+        writeln!(
+            stdout,
+            "    {:10} {:24} {}",
+            check.section, check.name, status
+        )?;
+    }
 
-    // Calculate total memory consumed by user processes
-    for value in commands.values() {
-        rss_sum += value;
+    match &diagnosis.error {
+        Some(e) => writeln!(stdout, "\nThe report can't be parsed: {}", e)?,
+        None => writeln!(stdout, "\nThe report can be parsed.")?,
     }
+    writeln!(stdout)?;
 
-    println!("\nMemory total:");
-    println!("    Total RAM: {:.1} GiB ", total_ram_KiB / 1024.0 / 1024.0);
+    Ok(())
+}
 
-    println!("\nSwap:");
-    println!("    Free swap: {} KiB", free_swap_KiB);
+// Print where each oom-killer invocation in a log starts, for picking one with --event
+fn print_events(index: &EventIndex) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(
+        stdout,
+        "\n==== {} oom-killer reports ====\n",
+        index.events.len()
+    )?;
+
+    writeln!(
+        stdout,
+        "    {:>5}  {:>10}  {:32}  victim",
+        "event", "line", "time"
+    )?;
+    for (n, event) in index.events.iter().enumerate() {
+        let victim = match (&event.victim, &event.victim_pid) {
+            (Some(victim), Some(pid)) => format!("{} ({})", victim, pid),
+            _ => "-".to_string(),
+        };
+        writeln!(
+            stdout,
+            "    {:>5}  {:>10}  {:32}  {}",
+            n + 1,
+            event.line,
+            event.timestamp.as_deref().unwrap_or("-"),
+            victim
+        )?;
+    }
+    writeln!(stdout)?;
 
-    println!("\nHuge Pages:");
-    println!(
-        "    Allocated 2 MiB huge pages: {:9.1} GiB  --  ({:.1}%)",
-        total_2_MiB_hugepages_MiB / 1024.0,
-        (m / total_ram_KiB) * 100.0
-    );
-    println!(
-        "    Allocated 1 GiB huge pages: {:9.1} GiB  --  ({:.1}%)",
-        total_1_GiB_hugepages_GiB,
-        (g / total_ram_KiB) * 100.0
-    );
+    Ok(())
+}
 
-    println!("\nSlab:");
-    println!(
-        "    Unreclaimable slab: {:.1} MiB  --  ({:.1}%)",
-        unreclaimable_slab_KiB / 1024.0,
-        (unreclaimable_slab_KiB / total_ram_KiB) * 100.0
-    );
+// Where to cache the index of a log with --index-cache. Only files have a size and modification
+// time to tell when the cached index is out of date.
+fn index_cache(path: &Path, cli: &Cli) -> Option<PathBuf> {
+    if !cli.index_cache || !path.is_file() {
+        return None;
+    }
 
-    print_unreclaimable_slab(&cleaned);
+    cache_path(path)
+}
 
-    println!("\nShared Memory:");
-    println!(
-        "    Shared memory: {:.1} MiB  --  ({:.1}%)",
-        shmem_KiB / 1024.0,
-        (shmem_KiB / total_ram_KiB) * 100.0
+// Read the nth oom-killer invocation of a log, counting from 0, and the line of the log it
+// starts on. Only the invocation itself is held in memory when the source can seek. cache is
+// the log file and where its index is cached, so the log is only scanned for invocations when
+// it has changed since the last time.
+fn read_event(
+    source: &mut dyn InputSource,
+    fields: &JsonFields,
+    n: usize,
+    cache: Option<(&Path, &Path)>,
+) -> Result<(String, usize), Box<dyn Error>> {
+    let _span = info_span!("read_event", source = %source.name(), event = n + 1).entered();
+    let mut log = source.open_seekable(fields)?;
+    let index = match cache {
+        Some((path, cache)) => {
+            EventIndex::cached(path, cache, || Ok(EventIndex::build(&mut log)?))?
+        }
+        None => EventIndex::build(&mut log)?,
+    };
+    info!(
+        events = index.events.len(),
+        bytes = index.bytes,
+        "indexed the log"
     );
+    let input = index
+        .read_event(&mut log, n)
+        .map_err(|e| format!("{}: {}", source.name(), e))?;
 
-    print_top_commands(commands);
-    print_ps_list(ps_matrix, header_vec, pid_col);
-    println!(
-        "\nTotal RSS utilized by user processes: {:.1} MiB   --  ({:.1}%)",
-        (rss_sum as f64 * 4096.0) / 1024.0 / 1024.0,
-        ((rss_sum as f64 * 4096.0) / 1024.0) / total_ram_KiB * 100.0
-    );
+    Ok((input, index.events[n].line))
+}
+
+// Read a log and build the report for the last oom-killer invocation in it
+fn read_report(path: &Path, fields: &JsonFields) -> Result<Report, Box<dyn Error>> {
+    let input = source_for_path(path)?.read_log(fields)?;
+    let mut event = OomEvent::from_report(last_report(&input)?)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    event.note_report_lines(&input);
+    event.note_log_context(&input);
+    event.note_kills(&input);
+
+    Ok(Report::new(event))
+}
+
+// Write rows of old and new values in MiB, and the change between them, as a table. A dash
+// stands for a value that one of the reports doesn't have.
+fn print_diff_table(
+    rows: &[(&str, Option<f64>, Option<f64>)],
+    options: &TextOptions,
+) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let cell = |value: Option<f64>| match value {
+        Some(mib) => options.num(mib, 1),
+        None => "-".to_string(),
+    };
+    let change = |old: Option<f64>, new: Option<f64>| match (old, new) {
+        (Some(old), Some(new)) => options.num_signed(new - old, 1),
+        _ => "-".to_string(),
+    };
+
+    let mut columns = vec![
+        Column::new(
+            "",
+            28,
+            Align::Left,
+            rows.iter().map(|r| r.0.to_string()).collect(),
+        ),
+        Column::new(
+            "old",
+            12,
+            Align::Right,
+            rows.iter().map(|r| cell(r.1)).collect(),
+        ),
+        Column::new(
+            "new",
+            12,
+            Align::Right,
+            rows.iter().map(|r| cell(r.2)).collect(),
+        ),
+        Column::new(
+            "change",
+            12,
+            Align::Right,
+            rows.iter().map(|r| change(r.1, r.2)).collect(),
+        ),
+    ];
+
+    let mut out = String::new();
+    fit_columns(&mut columns, options, 4, 0);
+    write_columns(&mut out, options, &columns, 4, 0);
+    write!(stdout, "{}", out)?;
+
+    Ok(())
+}
+
+// The memory breakdown compared by print_diff(), in MiB
+fn memory_mib(report: &Report) -> [(&'static str, f64); 9] {
+    let event = &report.event;
+
+    [
+        ("Total RAM", event.total_ram_kib / 1024.0),
+        ("Total swap", event.total_swap_kib / 1024.0),
+        ("Free swap", event.free_swap_kib / 1024.0),
+        ("2 MiB huge pages", event.hugepages_2mb_kib / 1024.0),
+        ("1 GiB huge pages", event.hugepages_1gb_kib / 1024.0),
+        ("Unreclaimable slab", event.unreclaimable_slab_kib / 1024.0),
+        ("Reclaimable slab", event.reclaimable_slab_kib / 1024.0),
+        ("Shared memory", event.shmem_kib / 1024.0),
+        ("Total RSS", report.rss_total as f64 / 1024.0 / 1024.0),
+    ]
+}
+
+// Print the memory breakdown and the largest commands of two reports in adjacent columns
+fn print_diff(old: &Report, new: &Report, options: &TextOptions) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "\nMemory (MiB):\n")?;
+    let rows = memory_mib(old)
+        .iter()
+        .zip(memory_mib(new).iter())
+        .map(|((name, old), (_, new))| (*name, Some(*old), Some(*new)))
+        .collect::<Vec<_>>();
+    print_diff_table(&rows, options)?;
+
+    // The top commands of either report, largest first by whichever report has more of them
+    let rss = |report: &Report, command: &str| {
+        report
+            .top_commands
+            .iter()
+            .find(|(c, _)| c == command)
+            .map(|(_, bytes)| *bytes as f64 / 1024.0 / 1024.0)
+    };
+    let mut commands = old
+        .top_commands
+        .iter()
+        .take(10)
+        .chain(new.top_commands.iter().take(10))
+        .map(|(command, _)| command.as_str())
+        .collect::<Vec<_>>();
+    commands.sort_unstable();
+    commands.dedup();
+    commands.sort_by(|a, b| {
+        let peak = |c| rss(old, c).unwrap_or(0.0).max(rss(new, c).unwrap_or(0.0));
+        peak(b).total_cmp(&peak(a))
+    });
+
+    writeln!(stdout, "\nTop commands using memory (MiB):\n")?;
+    let rows = commands
+        .into_iter()
+        .map(|command| (command, rss(old, command), rss(new, command)))
+        .collect::<Vec<_>>();
+    print_diff_table(&rows, options)?;
+
+    writeln!(stdout)?;
+
+    Ok(())
+}
+
+// Run parseoom again with the same arguments, its output piped into $PAGER, or less if it isn't
+// set. less is told to quit when the output fits on one screen and to keep colors. $PAGER is a
+// shell command, so this is only done where there's a POSIX shell to run it.
+#[cfg(unix)]
+fn run_in_pager() -> Result<(), Box<dyn Error>> {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| "less".to_string());
+
+    let mut child = process::Command::new(env::current_exe()?)
+        .args(env::args_os().skip(1))
+        .arg("--no-pager")
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let output = child.stdout.take().ok_or("no output from parseoom")?;
+
+    let mut pager = process::Command::new("sh")
+        .arg("-c")
+        .arg(&pager)
+        .env(
+            "LESS",
+            env::var("LESS").unwrap_or_else(|_| "FRX".to_string()),
+        )
+        .stdin(Stdio::from(output))
+        .spawn()
+        .map_err(|e| format!("{}: {}", pager, e))?;
+    pager.wait()?;
+
+    // The error, if any, has already been printed to stderr
+    let status = child.wait()?;
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
 
-    println!();
+// Write the systemd units for parseoom install-service, after checking the options they run
+// parseoom with
+fn install_service(
+    name: &str,
+    dir: &Path,
+    args: &[String],
+    every: Option<&str>,
+    print: bool,
+) -> Result<(), Box<dyn Error>> {
+    let exec = env::current_exe()?;
+    let mut units = ServiceUnits::new(name, &exec, args, every);
+
+    let cli =
+        Cli::try_parse_from(std::iter::once("parseoom".to_string()).chain(units.args.clone()))
+            .map_err(|e| {
+                let message = e.to_string();
+                let first = message.lines().next().unwrap_or("");
+                format!(
+                    "parseoom {}: {}",
+                    units.args.join(" "),
+                    first.trim_start_matches("error: ")
+                )
+            })?;
+    if cli.command.is_some() {
+        return Err("install-service runs parseoom on a log, not a subcommand".into());
+    }
+    if every.is_none() && !cli.follow {
+        return Err("a service without --every must --follow, or it exits straight away".into());
+    }
+    if every.is_some() && cli.follow {
+        return Err("a service run from a timer can't --follow".into());
+    }
+    // The dynamic user the service runs as has no home, so ssh would find no key or known hosts
+    if cli.ssh.is_some() {
+        return Err(
+            "a service can't read a log over --ssh, as its dynamic user has no ssh key; \
+             install the service on that host instead"
+                .into(),
+        );
+    }
+    let paths = [&cli.logfile, &cli.output]
+        .iter()
+        .filter_map(|path| path.as_deref())
+        .collect::<Vec<_>>();
+    units.absolute_paths(&paths, &env::current_dir()?);
+
+    let files = units.files();
+    if print {
+        let mut stdout = io::stdout().lock();
+        for (i, (file, contents)) in files.iter().enumerate() {
+            if i > 0 {
+                writeln!(stdout)?;
+            }
+            writeln!(stdout, "# {}", file)?;
+            write!(stdout, "{}", contents)?;
+        }
+        return Ok(());
+    }
+
+    for (file, contents) in &files {
+        let path = dir.join(file);
+        fs::write(&path, contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        eprintln!("Wrote {}", path.display());
+    }
+    let unit = &files.last().ok_or("no units")?.0;
+    eprintln!(
+        "Start it with: systemctl daemon-reload && systemctl enable --now {}",
+        unit
+    );
 
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn report_total_ram() {
-        const TOTAL_RAM_RE: &str = r"(\d+) pages RAM";
-        let re = Regex::new(TOTAL_RAM_RE).unwrap();
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.637758 5241544212132178 pages RAM\n Dec 20 03:17:52 localhost kernel: 75669.637798 132311 pages reserved";
-        assert!(re.is_match(s));
-        assert_eq!(parse_meminfo_total(s), Some(2.096_617_684_799_946_8e16));
-    }
-
-    #[test]
-    fn report_swap() {
-        const FREE_SWAP_RE: &str = r"Free swap\s+=.*";
-        let re = Regex::new(FREE_SWAP_RE).unwrap();
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.636534 Free swap  = 0kB";
-        assert!(re.is_match(s));
-        assert_eq!(parse_meminfo_swap(s).unwrap(), 0.0);
-    }
-
-    #[test]
-    fn report_slab() {
-        const UNRECLAIMABLE_SLAB_RE: &str = r"slab_unreclaimable:(\d+)";
-        let re = Regex::new(UNRECLAIMABLE_SLAB_RE).unwrap();
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.607722  slab_reclaimable:4158 slab_unreclaimable:12849311288";
-        assert!(re.is_match(s));
-        assert_eq!(parse_meminfo_slab(s).unwrap(), 51397245152.0);
-    }
-
-    #[test]
-    fn report_hugepages() {
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.631773 Node 0 hugepages_total=2 hugepages_free=0 hugepages_surp=0 hugepages_size=1048576kB\n Dec 20 03:17:52 localhost kernel: 75669.633105 Node 0 hugepages_total=12 hugepages_free=0 hugepages_surp=0 hugepages_size=2048kB";
-        let (m, g) = parse_meminfo_hugepages(s).unwrap();
-        assert_eq!(m, 24000.0);
-        assert_eq!(g, 2048000.0);
-
-        const HUGEPAGES_RE: &str = r"hugepages_total=(\d+)";
-        let re = Regex::new(HUGEPAGES_RE).unwrap();
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.631773 Node 0 hugepages_total=512 hugepages_free=0 hugepages_surp=0 hugepages_size=1048576kB";
-        assert!(re.is_match(s));
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.633105 Node 0 hugepages_total=512 hugepages_free=0 hugepages_surp=0 hugepages_size=2048kB";
-        assert!(re.is_match(s));
-    }
-
-    #[test]
-    fn report_shared() {
-        const SHMEM_RE: &str = r"shmem:(\d+)";
-        let re = Regex::new(SHMEM_RE).unwrap();
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.607722  mapped:70 shmem:147 pagetables:2089 bounce:0";
-        assert!(re.is_match(s));
-        assert_eq!(parse_meminfo_shared(s).unwrap(), 588.0);
-    }
-
-    #[test]
-    fn report_ps_usage() {
-        const PS_LIST_RE: &str = r"(.*pid.+\bname\b)(?s)(.*)";
-        let re = Regex::new(PS_LIST_RE).unwrap();
-        let s = "Dec 20 03:17:52 localhost kernel: 75669.641206   pid     uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name
-Dec 20 03:17:52 localhost kernel: 75669.642775     199     0   199    14838      226   102400       14          -250 systemd-journal
-Dec 20 03:17:52 localhost kernel: 75669.644513     255     0   255     5316      159    69632       37         -1000 systemd-udevd";
-        assert!(re.is_match(s));
+// Whether an error is from writing to a pipe whose reader has gone. Quitting a pager or head
+// before the output ends closes the pipe stdout writes to, which is the reader being done
+// rather than an error.
+fn is_broken_pipe(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match run() {
+        Err(e) if is_broken_pipe(e.as_ref()) => Ok(()),
+        result => result,
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    if cli.verbose > 0 {
+        let level = match cli.verbose {
+            1 => Level::INFO,
+            2 => Level::DEBUG,
+            _ => Level::TRACE,
+        };
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(io::stderr)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_target(false)
+            .init();
+    }
+
+    // Every task on a big host is thousands of lines, more than a terminal can scroll back
+    #[cfg(unix)]
+    if cli.top == Top::All
+        && !cli.no_pager
+        && cli.output.is_none()
+        && cli.formats() == [Format::Text]
+        && io::stdout().is_terminal()
+    {
+        return run_in_pager();
+    }
+
+    match &cli.command {
+        // Print the JSON Schema for machine-readable output
+        Some(Command::Schema) => {
+            writeln!(io::stdout().lock(), "{}", json_schema())?;
+            return Ok(());
+        }
+        Some(Command::Doctor { logfile }) => {
+            let input = source_for_path(logfile)?.read_log(&cli.json_fields())?;
+            print_diagnosis(&Diagnosis::new(&input))?;
+            return Ok(());
+        }
+        Some(Command::Events { logfile, json }) => {
+            let build = || {
+                Ok(EventIndex::build(
+                    source_for_path(logfile)?.open_seekable(&cli.json_fields())?,
+                )?)
+            };
+            let index = match index_cache(logfile, &cli) {
+                Some(cache) => EventIndex::cached(logfile, &cache, build)?,
+                None => build()?,
+            };
+            if *json {
+                let value = serde_json::json!({
+                    "schema_version": SCHEMA_VERSION,
+                    "bytes": index.bytes,
+                    "events": index.events,
+                });
+                writeln!(io::stdout().lock(), "{}", value)?;
+            } else {
+                print_events(&index)?;
+            }
+            return Ok(());
+        }
+        Some(Command::Diff { old, new }) => {
+            let fields = cli.json_fields();
+            print_diff(
+                &read_report(old, &fields)?,
+                &read_report(new, &fields)?,
+                &cli.text_options(),
+            )?;
+            return Ok(());
+        }
+        Some(Command::Completions { shell }) => {
+            generate(*shell, &mut Cli::command(), "parseoom", &mut io::stdout());
+            return Ok(());
+        }
+        Some(Command::Man) => {
+            Man::new(Cli::command()).render(&mut io::stdout())?;
+            return Ok(());
+        }
+        Some(Command::InstallService {
+            every,
+            name,
+            dir,
+            print,
+            args,
+        }) => {
+            return install_service(name, dir, args, every.as_deref(), *print);
+        }
+        Some(Command::Demo { json }) => {
+            let input = DemoSource.read_log(&cli.json_fields())?;
+            if *json {
+                writeln!(
+                    io::stdout().lock(),
+                    "{}",
+                    event_to_json(parse_event(&input, &cli))
+                )?;
+                return Ok(());
+            }
+            let origin = Origin {
+                name: "demo",
+                first_line: 1,
+            };
+            return analyze(&input, Some(origin), &cli, &[]);
+        }
+        None => (),
+    }
+
+    let rules = match &cli.rules {
+        Some(path) => Rule::load(path)?,
+        None => Vec::new(),
+    };
+
+    if cli.follow && cli.journald {
+        let mut follower = JournalFollower::new(cli.state.as_deref())?;
+
+        loop {
+            for report in follower.poll()? {
+                match analyze(&report, None, &cli, &rules) {
+                    Err(e) if is_broken_pipe(e.as_ref()) => return Err(e),
+                    Err(e) => eprintln!("error: {}", e),
+                    Ok(()) => (),
+                }
+            }
+            for warning in follower.take_warnings() {
+                eprintln!("warning: {}", warning);
+            }
+
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    if cli.follow {
+        let path = cli.logfile.as_ref().ok_or("Filename not provided")?;
+        let mut follower = Follower::new(path)?;
+
+        loop {
+            for report in follower.poll()? {
+                // A damaged report shouldn't stop us from waiting for the next one
+                match analyze(&report, None, &cli, &rules) {
+                    Err(e) if is_broken_pipe(e.as_ref()) => return Err(e),
+                    Err(e) => eprintln!("error: {}", e),
+                    Ok(()) => (),
+                }
+            }
+            for warning in follower.take_warnings() {
+                eprintln!("warning: {}", warning);
+            }
+
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    if cli.hosts {
+        let dir = cli.logfile.as_ref().ok_or("Filename not provided")?;
+        return analyze_hosts(dir, &cli, &rules);
+    }
+
+    let mut source: Box<dyn InputSource> = if cli.journald {
+        Box::new(JournaldSource)
+    } else if cli.kmsg {
+        Box::new(KmsgSource)
+    } else if let Some(destination) = &cli.ssh {
+        Box::new(SshSource::new(destination))
+    } else {
+        source_for_path(cli.logfile.as_ref().ok_or("Filename not provided")?)?
+    };
+
+    let (input, first_line) = match cli.event {
+        Some(n) => {
+            let cache = cli
+                .logfile
+                .as_deref()
+                .and_then(|path| Some((path, index_cache(path, &cli)?)));
+            let cache = cache.as_ref().map(|(path, cache)| (*path, cache.as_path()));
+            read_event(source.as_mut(), &cli.json_fields(), n as usize - 1, cache)?
+        }
+        None => {
+            let input = info_span!("read_log", source = %source.name())
+                .in_scope(|| source.read_log(&cli.json_fields()))?;
+            info!(bytes = input.len(), "read the log");
+            (input, 1)
+        }
+    };
+    let name = source.name();
+    let origin = Origin {
+        name: &name,
+        first_line,
+    };
+
+    // Written before parsing, so that a report we fail to parse can still be extracted
+    if let Some(path) = &cli.extract {
+        write_output(path, &extract_report(&input)?)?;
+    }
+
+    // A cgroup oom-kill only gets a summary, so there's nothing more to check
+    if cli.strict && !last_report(&input)?.contains("Memory cgroup out of memory") {
+        let mut event = OomEvent::parse(&input)?;
+        event.apply_rules(&rules);
+        let errors = event.strict_errors(&input);
+        for (line, message) in &errors {
+            eprintln!("error: {}: {}", origin.locate(*line), message);
+        }
+        if !errors.is_empty() {
+            process::exit(1);
+        }
+    }
+
+    if cli.fail_on_unknown_section {
+        let groups = unclaimed_lines(&input)?;
+        print_unclaimed_lines(&groups, &origin);
+        if !groups.is_empty() {
+            process::exit(1);
+        }
+    }
+
+    // Android kills with its own low memory killer, usually before the oom-killer ever runs
+    if !input.contains("invoked oom-killer") {
+        let kills = parse_lmk_kills(&input);
+        if !kills.is_empty() && cli.formats() == [Format::Json] {
+            let value = serde_json::json!({
+                "schema_version": SCHEMA_VERSION,
+                "lmk_kills": kills,
+            });
+            writeln!(io::stdout().lock(), "{}", value)?;
+            return Ok(());
+        } else if !kills.is_empty() {
+            print_lmk_kills(&kills, cli.top, &cli.text_options())?;
+            return Ok(());
+        }
+
+        // What's left of reports that lost their first lines is still worth seeing
+        let partials = match cli.scan_partial {
+            true => find_partial_reports(&input),
+            false => Vec::new(),
+        };
+        if !partials.is_empty() {
+            if cli.formats() == [Format::Json] {
+                let value = serde_json::json!({
+                    "schema_version": SCHEMA_VERSION,
+                    "partial_reports": partials,
+                });
+                writeln!(io::stdout().lock(), "{}", value)?;
+            } else {
+                let mut out = String::new();
+                write_partial_reports(&mut out, &cli.text_options(), &partials);
+                write!(io::stdout().lock(), "{}", out)?;
+            }
+            return Ok(());
+        }
+    }
+
+    // --all writes text, JSON, or a table or page of every event; one report's text and JSON
+    // have their own paths below, and every other format goes through the renderers
+    let formats = cli.formats();
+    if cli.all {
+        if !matches!(
+            formats[..],
+            [Format::Text] | [Format::Json] | [Format::Csv] | [Format::Tsv] | [Format::Html]
+        ) {
+            return Err("--all writes one of text, json, csv, tsv or html".into());
+        }
+        if cli.output.is_some() && formats == [Format::Text] {
+            return Err("--output with --all needs --json, or --format csv, tsv or html".into());
+        }
+    } else if cli.output.is_some() || !(formats == [Format::Text] || formats == [Format::Json]) {
+        return emit(&input, origin, &cli, &rules);
+    }
+
+    match (cli.all, cli.formats() == [Format::Json]) {
+        (false, false) => analyze(&input, Some(origin), &cli, &rules),
+        (false, true) if rules.is_empty() && !cli.scan_partial && cli.expected_ram.is_none() => {
+            writeln!(
+                io::stdout().lock(),
+                "{}",
+                event_to_json(parse_event(&input, &cli))
+            )?;
+            Ok(())
+        }
+        (false, true) => {
+            let mut event = parse_event(&input, &cli)?;
+            event.apply_rules(&rules);
+            if let Some(expected) = cli.expected_ram {
+                event.note_expected_ram(expected);
+            }
+            if cli.scan_partial {
+                event.note_partial_reports(&input);
+            }
+            writeln!(
+                io::stdout().lock(),
+                "{}",
+                serde_json::to_string(&JsonReport::new(&event))?
+            )?;
+            Ok(())
+        }
+        (true, _) => analyze_all(&input, origin, &cli, &rules),
     }
 }
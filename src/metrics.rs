@@ -0,0 +1,121 @@
+use crate::report::Report;
+use crate::OomEvent;
+
+// Shares of RAM and other figures derived from a parsed report. The text, JSON and status
+// outputs all use these rather than dividing by total RAM themselves, so a percentage shown in
+// one matches the same percentage in another. Fractions are between 0 and 1.
+impl OomEvent {
+    // The share of RAM that kib KiB make up, or 0 when the report gave no RAM total
+    pub fn ram_fraction(&self, kib: f64) -> f64 {
+        if self.total_ram_kib > 0.0 {
+            kib / self.total_ram_kib
+        } else {
+            0.0
+        }
+    }
+
+    // Memory reserved for 2 MiB and 1 GiB huge pages together, in KiB
+    pub fn hugepages_kib(&self) -> f64 {
+        self.hugepages_2mb_kib + self.hugepages_1gb_kib
+    }
+
+    // Free memory across every zone, in KiB
+    pub fn zone_free_kib(&self) -> f64 {
+        self.zones.iter().map(|z| z.free_kib).sum()
+    }
+
+    // The share of RAM that was free, when the report gives it
+    pub fn free_fraction(&self) -> Option<f64> {
+        self.free_kib.map(|free| self.ram_fraction(free))
+    }
+
+    // Swap in use, in KiB
+    pub fn swap_used_kib(&self) -> f64 {
+        self.total_swap_kib - self.free_swap_kib
+    }
+
+    // The share of RAM held by unreclaimable slab
+    pub fn slab_fraction(&self) -> f64 {
+        self.ram_fraction(self.unreclaimable_slab_kib)
+    }
+
+    // The share of RAM held by reclaimable slab
+    pub fn reclaimable_slab_fraction(&self) -> f64 {
+        self.ram_fraction(self.reclaimable_slab_kib)
+    }
+
+    // The share of RAM reserved for huge pages
+    pub fn hugepage_fraction(&self) -> f64 {
+        self.ram_fraction(self.hugepages_kib())
+    }
+
+    // The share of RAM used by shared memory and tmpfs
+    pub fn shmem_fraction(&self) -> f64 {
+        self.ram_fraction(self.shmem_kib)
+    }
+
+    // The share of RAM free but reserved for CMA
+    pub fn cma_fraction(&self) -> f64 {
+        self.ram_fraction(self.free_cma_kib)
+    }
+
+    // The share of swap in use, or None when no swap was configured
+    pub fn swap_used_fraction(&self) -> Option<f64> {
+        if self.total_swap_kib > 0.0 {
+            Some(self.swap_used_kib() / self.total_swap_kib)
+        } else {
+            None
+        }
+    }
+}
+
+impl Report {
+    // The share of RAM used by the resident memory of every process
+    pub fn user_rss_fraction(&self) -> f64 {
+        self.event.ram_fraction(self.rss_total as f64 / 1024.0)
+    }
+
+    // RAM in bytes that none of the report's categories explain: whatever is left after process
+    // RSS, slab, huge pages, shared memory and free memory are taken away. This is mostly page
+    // cache, kernel stacks, page tables and driver allocations. Shared memory mapped by a process
+    // is also counted in its RSS; a database's shared buffers are taken out of its processes' RSS
+    // so they count once, but other shared memory can't be, so the result can only be a lower
+    // bound and stops at 0.
+    pub fn unaccounted_bytes(&self) -> i64 {
+        let event = &self.event;
+        let accounted_kib = event.unreclaimable_slab_kib
+            + event.reclaimable_slab_kib
+            + event.hugepages_kib()
+            + event.shmem_kib
+            + event.zone_free_kib();
+        let database_kib = event.database_shmem.as_ref().map_or(0.0, |d| d.kib);
+        let rss_kib = self.rss_total as f64 / 1024.0 - database_kib;
+        let unaccounted_kib = event.total_ram_kib - accounted_kib - rss_kib;
+
+        (unaccounted_kib * 1024.0).max(0.0) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_metrics() {
+        let report = Report::new(OomEvent::parse(include_str!("../messages")).unwrap());
+        let event = &report.event;
+
+        assert_eq!(event.ram_fraction(event.total_ram_kib), 1.0);
+        assert_eq!(event.hugepages_kib(), 2121728.0);
+        assert_eq!(event.swap_used_fraction(), Some(1.0));
+        assert!((event.hugepage_fraction() - 2121728.0 / 20900008.0).abs() < 1e-12);
+
+        let rss = report.user_rss_fraction();
+        assert!((rss - 517275.0 * 4.0 / 20900008.0).abs() < 1e-12);
+        assert!(report.unaccounted_bytes() > 0);
+
+        let empty = OomEvent::default();
+        assert_eq!(empty.slab_fraction(), 0.0);
+        assert_eq!(empty.swap_used_fraction(), None);
+    }
+}
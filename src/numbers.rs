@@ -0,0 +1,155 @@
+// How numbers are written in the text report: the character grouping thousands, if any, and the
+// decimal mark. Large KiB and byte counts are hard to read without grouping, so it's on unless
+// asked for plain numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub thousands: Option<char>,
+    pub decimal: char,
+}
+
+// Languages that write 1.234,5 and 1 234,5 rather than 1,234.5. Locales not listed here, and the
+// C and POSIX locales, get the English style.
+const DOT_GROUPING: [&str; 11] = [
+    "de", "es", "it", "nl", "pt", "da", "id", "tr", "el", "ro", "sl",
+];
+const SPACE_GROUPING: [&str; 12] = [
+    "fr", "ru", "pl", "sv", "fi", "nb", "no", "cs", "sk", "uk", "hu", "bg",
+];
+
+impl Default for NumberFormat {
+    fn default() -> NumberFormat {
+        NumberFormat {
+            thousands: Some(','),
+            decimal: '.',
+        }
+    }
+}
+
+impl NumberFormat {
+    // Numbers as Rust prints them, for scripts still scraping the text output
+    pub fn plain() -> NumberFormat {
+        NumberFormat {
+            thousands: None,
+            decimal: '.',
+        }
+    }
+
+    // The format for a locale name such as "de_DE.UTF-8". Swiss German groups with an
+    // apostrophe and keeps the decimal point.
+    pub fn from_locale(locale: &str) -> NumberFormat {
+        let name = locale.split(['.', '@']).next().unwrap_or("");
+        let language = name.split(['_', '-']).next().unwrap_or("");
+
+        if name == "de_CH" {
+            NumberFormat {
+                thousands: Some('\''),
+                decimal: '.',
+            }
+        } else if DOT_GROUPING.contains(&language) {
+            NumberFormat {
+                thousands: Some('.'),
+                decimal: ',',
+            }
+        } else if SPACE_GROUPING.contains(&language) {
+            NumberFormat {
+                thousands: Some(' '),
+                decimal: ',',
+            }
+        } else {
+            NumberFormat::default()
+        }
+    }
+
+    // The format for the numeric locale of the environment, found the way setlocale() does:
+    // LC_ALL, then LC_NUMERIC, then LANG
+    pub fn from_env() -> NumberFormat {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+
+        NumberFormat::from_locale(&locale)
+    }
+
+    // Write a number with the given number of decimal places
+    pub fn format(&self, value: f64, precision: usize) -> String {
+        let s = format!("{:.*}", precision, value);
+        if !value.is_finite() {
+            return s;
+        }
+
+        let (sign, s) = match s.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", s.as_str()),
+        };
+        let (whole, fraction) = match s.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (s, None),
+        };
+
+        let mut out = String::from(sign);
+        out.push_str(&self.group(whole));
+        if let Some(fraction) = fraction {
+            out.push(self.decimal);
+            out.push_str(fraction);
+        }
+
+        out
+    }
+
+    // Write a whole number exactly, however large, where format() would round it to the
+    // nearest f64
+    pub fn format_count(&self, count: u128) -> String {
+        self.group(&count.to_string())
+    }
+
+    // Put the thousands separator between each group of three digits
+    fn group(&self, whole: &str) -> String {
+        let mut out = String::new();
+        for (i, c) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                if let Some(sep) = self.thousands {
+                    out.push(sep);
+                }
+            }
+            out.push(c);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_number_format() {
+        let english = NumberFormat::default();
+        assert_eq!(english.format(51397245152.0, 1), "51,397,245,152.0");
+        assert_eq!(english.format(-1234.56, 1), "-1,234.6");
+        assert_eq!(english.format(999.0, 0), "999");
+        assert_eq!(
+            english.format_count(9007199254740993),
+            "9,007,199,254,740,993"
+        );
+        assert_eq!(
+            NumberFormat::plain().format(51397245152.0, 1),
+            "51397245152.0"
+        );
+
+        let german = NumberFormat::from_locale("de_DE.UTF-8");
+        assert_eq!(german.format(1649.7, 1), "1.649,7");
+        assert_eq!(
+            NumberFormat::from_locale("fr_FR").format(1649.7, 1),
+            "1 649,7"
+        );
+        assert_eq!(
+            NumberFormat::from_locale("de_CH").format(1649.7, 1),
+            "1'649.7"
+        );
+        assert_eq!(NumberFormat::from_locale("C"), english);
+        assert_eq!(NumberFormat::from_locale("C.UTF-8"), english);
+    }
+}
@@ -0,0 +1,95 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// The memory counts of a report as the kernel printed them, before they're converted to KiB:
+// base pages for the Mem-Info summary line and the RAM totals, huge pages of each size summed
+// across nodes, and swap and transparent huge pages in kB, which are always a whole number of
+// pages. The section parsers fill them in as they read the values they convert. --units pages
+// shows these, so a kernel bug report can quote the kernel's own numbers rather than ones that
+// went through floating point.
+#[derive(Debug, Default, PartialEq, Serialize, JsonSchema)]
+pub struct PageCounts {
+    pub ram: Option<u64>,
+    pub reserved: Option<u64>,
+    pub free: Option<u64>,
+    pub free_swap_kb: Option<u64>,
+    pub total_swap_kb: Option<u64>,
+    pub hugepages_2mb: u64,
+    pub hugepages_1gb: u64,
+    // Free huge pages by their size in kB
+    pub hugepages_free: BTreeMap<u64, u64>,
+    pub thp_anon_kb: Option<u64>,
+    pub thp_shmem_kb: Option<u64>,
+    pub thp_file_kb: Option<u64>,
+    pub slab_unreclaimable: Option<u64>,
+    pub slab_reclaimable: Option<u64>,
+    pub shmem: Option<u64>,
+    pub isolated_anon: Option<u64>,
+    pub isolated_file: Option<u64>,
+    pub free_cma: Option<u64>,
+}
+
+impl PageCounts {
+    // The pages of RAM the kernel manages, those it found less those it reserved
+    pub fn usable_ram(&self) -> Option<u64> {
+        self.ram?.checked_sub(self.reserved?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OomEvent;
+
+    #[test]
+    fn report_page_counts() {
+        let log = include_str!("../messages");
+        let event = OomEvent::parse(log).unwrap();
+        let pages = &event.pages;
+
+        assert_eq!(pages.ram, Some(5241540));
+        assert_eq!(
+            pages.usable_ram().map(|p| p as f64 * 4.0),
+            Some(event.total_ram_kib)
+        );
+        assert_eq!(
+            pages.slab_unreclaimable.map(|p| p as f64 * 4.0),
+            Some(event.unreclaimable_slab_kib)
+        );
+        assert_eq!(pages.shmem.map(|p| p as f64 * 4.0), Some(event.shmem_kib));
+        assert_eq!(pages.free.map(|p| p as f64 * 4.0), event.free_kib);
+
+        // and the kernel's kB are KiB, in every view
+        assert_eq!(
+            pages.free_swap_kb.map(|kb| kb as f64),
+            Some(event.free_swap_kib)
+        );
+        assert_eq!(pages.total_swap_kb, Some(524284));
+        assert_eq!(
+            pages.total_swap_kb.map(|kb| kb as f64),
+            Some(event.total_swap_kib)
+        );
+        assert_eq!((pages.hugepages_2mb, pages.hugepages_1gb), (12, 2));
+        assert_eq!(pages.hugepages_2mb as f64 * 2048.0, event.hugepages_2mb_kib);
+        assert_eq!(
+            pages.hugepages_1gb as f64 * 1048576.0,
+            event.hugepages_1gb_kib
+        );
+        let free = pages
+            .hugepages_free
+            .iter()
+            .map(|(size, pages)| size * pages)
+            .sum::<u64>();
+        assert_eq!(free as f64, event.hugepages_free_kib);
+        let thp = event.thp.as_ref().unwrap();
+        assert_eq!(pages.thp_anon_kb, Some(856064));
+        assert_eq!(pages.thp_anon_kb.map(|kb| kb as f64), Some(thp.anon_kib));
+        assert_eq!(pages.thp_shmem_kb.map(|kb| kb as f64), Some(thp.shmem_kib));
+        assert_eq!(pages.thp_file_kb.map(|kb| kb as f64), Some(thp.file_kib));
+
+        // Counts too large for a float to hold exactly are kept to the last page
+        let log = log.replace("5241540 pages RAM", "9007199254740993 pages RAM");
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.pages.ram, Some(9007199254740993));
+    }
+}
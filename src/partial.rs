@@ -0,0 +1,179 @@
+use crate::rollup::parse_timestamp;
+use crate::{parse_ps_rows, top_consumers, OomEvent, Warning, PAGE_SIZE};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+// A Mem-Info dump or process list found outside of any oom-killer report. When the kernel's
+// ring buffer wraps, or syslog drops messages under load, the start of a report can be lost
+// along with its "invoked oom-killer" line, leaving the rest of it behind. line is the line of
+// the log it starts on, counting from 1, and free memory is in KiB.
+#[derive(Serialize, JsonSchema)]
+pub struct PartialReport {
+    pub line: usize,
+    pub timestamp: Option<String>,
+    pub mem_info: bool,
+    pub free_kib: Option<f64>,
+    pub processes: usize,
+    pub largest: Option<String>,
+}
+
+// Find the Mem-Info dumps and process lists in a log that don't follow an "invoked oom-killer"
+// line, ie:
+//
+//      Mem-Info:
+//      active_anon:3811838 inactive_anon:485233 isolated_anon:0
+//       active_file:69 inactive_file:121 isolated_file:0
+//       ...
+//       free:38735 free_pcp:0 free_cma:0
+//      ...
+//      Tasks state (memory values in pages):
+//      [  pid  ]   uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name
+//
+// A process list after a Mem-Info dump belongs to it. Process lists are read from the shape of
+// their rows, and their memory is assumed to be in pages.
+pub fn find_partial_reports(log: &str) -> Vec<PartialReport> {
+    const REPORT_END_RE: &str = r"(?i)out of memory|oom-kill:";
+    const TASKS_RE: &str = r"Tasks state|pid.+\buid\b.+\bname\b";
+    const FREE_RE: &str = r"\bfree:(\d+)";
+
+    let end_re = Regex::new(REPORT_END_RE).unwrap();
+    let tasks_re = Regex::new(TASKS_RE).unwrap();
+    let free_re = Regex::new(FREE_RE).unwrap();
+
+    let mut found = Vec::new();
+    let mut current: Option<PartialReport> = None;
+    let mut rows: Option<String> = None;
+    let mut in_report = false;
+
+    // A partial report ends where its process list does, or at the next report
+    let finish = |current: &mut Option<PartialReport>,
+                  rows: &mut Option<String>,
+                  found: &mut Vec<PartialReport>| {
+        if let Some(mut partial) = current.take() {
+            if let Some((_, processes)) = rows.take().as_deref().and_then(parse_ps_rows) {
+                let consumers = top_consumers(&processes, PAGE_SIZE);
+                partial.processes = processes.len();
+                partial.largest = consumers
+                    .iter()
+                    .max_by_key(|(_, rss)| **rss)
+                    .map(|(command, _)| command.clone());
+            }
+            found.push(partial);
+        }
+        *rows = None;
+    };
+
+    for (i, line) in log.lines().enumerate() {
+        if line.contains("invoked oom-killer") {
+            finish(&mut current, &mut rows, &mut found);
+            in_report = true;
+            continue;
+        }
+        if end_re.is_match(line) {
+            finish(&mut current, &mut rows, &mut found);
+            in_report = false;
+            continue;
+        }
+        if in_report {
+            continue;
+        }
+
+        let mem_info = line.contains("Mem-Info:");
+        let tasks = tasks_re.is_match(line);
+        if mem_info || (tasks && current.is_none()) {
+            finish(&mut current, &mut rows, &mut found);
+            current = Some(PartialReport {
+                line: i + 1,
+                timestamp: parse_timestamp(line),
+                mem_info,
+                free_kib: None,
+                processes: 0,
+                largest: None,
+            });
+        }
+
+        let partial = match current.as_mut() {
+            Some(partial) => partial,
+            None => continue,
+        };
+        if tasks {
+            rows = Some(String::new());
+            continue;
+        }
+        if partial.mem_info && partial.free_kib.is_none() && rows.is_none() {
+            partial.free_kib = free_re
+                .captures(line)
+                .and_then(|caps| caps[1].parse::<f64>().ok())
+                .map(|pages| pages * 4.0);
+        }
+
+        // The process list ends at the first line that isn't one of its rows
+        if let Some(text) = rows.as_mut() {
+            let row = line.replace(['[', ']'], "");
+            if parse_ps_rows(&row).is_some() {
+                text.push_str(&row);
+                text.push('\n');
+            } else {
+                finish(&mut current, &mut rows, &mut found);
+            }
+        }
+    }
+    finish(&mut current, &mut rows, &mut found);
+
+    found
+}
+
+impl OomEvent {
+    // Note the partial reports in the log the report came from, which --scan-partial asks for
+    pub fn note_partial_reports(&mut self, log: &str) {
+        self.partial_reports = find_partial_reports(log);
+        if !self.partial_reports.is_empty() {
+            self.warnings.push(Warning::new(
+                "log",
+                format!(
+                    "{} partial reports without an oom-killer invocation, the ring buffer may \
+                     have overwritten the rest",
+                    self.partial_reports.len()
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_partial_reports() {
+        let messages = include_str!("../messages");
+        assert!(find_partial_reports(messages).is_empty());
+
+        // The same report with its first lines lost, then a lone Mem-Info dump
+        let start = messages.find("Mem-Info:").unwrap();
+        let start = messages[..start].rfind('\n').unwrap() + 1;
+        let mem_info = messages[start..].lines().take(8).collect::<Vec<_>>();
+        let log = format!(
+            "{}{}\n{}",
+            messages,
+            &messages[start..],
+            mem_info.join("\n")
+        );
+        let first = messages.lines().count() + 1;
+
+        let found = find_partial_reports(&log);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].line, first);
+        assert_eq!(found[0].timestamp.as_deref(), Some("Dec 20 03:17:52"));
+        assert!(found[0].mem_info);
+        assert_eq!(found[0].processes, 35);
+        assert_eq!(found[0].largest.as_deref(), Some("clamd"));
+        assert_eq!(found[1].processes, 0);
+        assert_eq!(found[1].free_kib, Some(13191.0 * 4.0));
+
+        let mut event = OomEvent::parse(&log).unwrap();
+        event.note_partial_reports(&log);
+        assert_eq!(event.partial_reports.len(), 2);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::rollup::parse_timestamp;
+use crate::timestamps::{parse_log_time, LogTime};
+use crate::OomEvent;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tracing::instrument;
+
+// How far before the oom-killer report to look for warning signs, by default
+pub const PRECURSOR_WINDOW_SECONDS: f64 = 600.0;
+
+// Lines without a usable timestamp can't be placed in the window, so stop after this many
+const PRECURSOR_MAX_LINES: usize = 100_000;
+
+// The year classic syslog dates are read in, which have none. Only the time between a line and
+// the report matters, and a leap year lets Feb 29 be read.
+const SYSLOG_YEAR: i32 = 2000;
+
+// Kernel messages that often come before an oom-kill and point at the subsystem under pressure
+const PRECURSORS: [(&str, &str); 4] = [
+    ("allocation stall", r"page allocation stalls for \d+ms"),
+    ("allocation failure", r"page allocation failure: order:\d+"),
+    (
+        "XFS allocation deadlock",
+        r"XFS: .*possible memory allocation deadlock",
+    ),
+    ("hung task", r"blocked for more than \d+ seconds"),
+];
+
+// Repeated warnings of one kind before the report, with the first and last of them
+#[derive(Serialize, JsonSchema)]
+pub struct Precursor {
+    pub kind: &'static str,
+    pub count: usize,
+    pub first: String,
+    pub last: String,
+}
+
+// Find warning signs in the window before the last oom-killer report in the log. Lines are read
+// backwards from the report until one is older than the window; when the timestamps can't be
+// compared with the report's, ie. one is uptime and the other a date, every line up to
+// PRECURSOR_MAX_LINES is read.
+pub fn find_precursors(log: &str, window_seconds: f64) -> Vec<Precursor> {
+    let start = match log.rfind("invoked oom-killer") {
+        Some(i) => log[..i].rfind('\n').map_or(0, |n| n + 1),
+        None => return Vec::new(),
+    };
+    let time = |line: &str, year: i32| parse_log_time(&parse_timestamp(line)?, year);
+    let report_time = time(&log[start..], SYSLOG_YEAR);
+
+    // Seconds from a line to the report. A syslog date that would come after the report is from
+    // the year before, as for a line from Dec 31 before a report on Jan 1.
+    let before_report = |line: &str| {
+        let report_time = report_time.as_ref()?;
+        let seconds = report_time.seconds_since(&time(line, SYSLOG_YEAR)?)?;
+        match report_time {
+            LogTime::Local(_) if seconds < 0.0 => {
+                report_time.seconds_since(&time(line, SYSLOG_YEAR - 1)?)
+            }
+            _ => Some(seconds),
+        }
+    };
+    let res = PRECURSORS
+        .iter()
+        .map(|(_, re)| Regex::new(re).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut precursors: Vec<Precursor> = Vec::new();
+    for line in log[..start].lines().rev().take(PRECURSOR_MAX_LINES) {
+        if before_report(line).is_some_and(|seconds| seconds > window_seconds) {
+            break;
+        }
+
+        let kind = match res.iter().position(|re| re.is_match(line)) {
+            Some(i) => PRECURSORS[i].0,
+            None => continue,
+        };
+
+        // Read backwards, so each line found is the earliest so far
+        match precursors.iter_mut().find(|p| p.kind == kind) {
+            Some(p) => {
+                p.count += 1;
+                p.first = line.to_string();
+            }
+            None => precursors.push(Precursor {
+                kind,
+                count: 1,
+                first: line.to_string(),
+                last: line.to_string(),
+            }),
+        }
+    }
+
+    precursors.sort_by_key(|p| std::cmp::Reverse(p.count));
+    precursors
+}
+
+impl OomEvent {
+    // Look for warning signs in the window_seconds before the report
+    #[instrument(level = "info", skip_all, fields(bytes = log.len(), window_seconds = window_seconds))]
+    pub fn note_precursors(&mut self, log: &str, window_seconds: f64) {
+        self.precursors = find_precursors(log, window_seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_precursors() {
+        let stall = "kernel: [75100.000000] kworker/0:1: page allocation stalls for 10012ms, \
+                     order:0, mode:0x14200ca(GFP_HIGHUSER_MOVABLE)\n";
+        let hung = "kernel: [75650.000000] INFO: task xfsaild/dm-0:512 blocked for more than \
+                    120 seconds.\n";
+        let log = format!(
+            "{}{}{}{}",
+            stall,
+            stall.replace("75100", "75200"),
+            hung,
+            include_str!("../messages").replace("Dec 20 03:17:52 localhost kernel: ", "")
+        );
+
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.precursors.len(), 2);
+        assert_eq!(event.precursors[0].kind, "allocation stall");
+        assert_eq!(event.precursors[0].count, 2);
+        assert!(event.precursors[0].first.contains("75100.000000"));
+
+        // The stalls are more than a minute before the report
+        let precursors = find_precursors(&log, 60.0);
+        assert_eq!(precursors.len(), 1);
+        assert_eq!(precursors[0].kind, "hung task");
+
+        // The same with RFC 3339 timestamps, the stall a day before the report
+        let iso = |line: &str, time: &str| {
+            line.replacen(
+                "kernel: [75100.000000]",
+                &format!("{} host kernel:", time),
+                1,
+            )
+        };
+        let log = format!(
+            "{}{}",
+            iso(stall, "2023-12-19T01:00:00+00:00"),
+            include_str!("../messages").replace("Dec 20 03:17:52", "2023-12-20T03:17:52+00:00")
+        );
+        assert!(find_precursors(&log, 600.0).is_empty());
+        assert_eq!(find_precursors(&log, 86400.0 * 2.0).len(), 1);
+
+        // A syslog date from the year before the report
+        let log = format!(
+            "{}{}",
+            iso(stall, "Dec 31 23:59:00"),
+            include_str!("../messages").replace("Dec 20 03:17:52", "Jan  1 00:01:00")
+        );
+        assert_eq!(find_precursors(&log, 600.0).len(), 1);
+        assert!(find_precursors(&log, 60.0).is_empty());
+    }
+}
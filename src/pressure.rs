@@ -0,0 +1,211 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Free memory and paging activity at one point in a sar or vmstat export. Memory is in KiB and
+// rates are per second; a value the export doesn't include is None.
+#[derive(Clone, Default, Serialize)]
+pub struct PressureSample {
+    pub time: Option<u32>,
+    pub free_kib: Option<f64>,
+    pub swap_in: Option<f64>,
+    pub swap_out: Option<f64>,
+    pub pgscan: Option<f64>,
+}
+
+// Seconds since midnight from a sar or vmstat time, ie. "03:10:01", "03:10:01 PM" or
+// "2023-12-20 03:10:01"
+pub fn parse_time_of_day(time: &str) -> Option<u32> {
+    const TIME_RE: &str = r"(\d{1,2}):(\d{2}):(\d{2})(?:\s+([AP]M))?";
+
+    let caps = Regex::new(TIME_RE).unwrap().captures(time)?;
+    let mut hours = caps[1].parse::<u32>().ok()? % 12;
+    if caps
+        .get(4)
+        .map_or(caps[1].parse::<u32>().ok()? >= 12, |m| m.as_str() == "PM")
+    {
+        hours += 12;
+    }
+
+    Some(hours * 3600 + caps[2].parse::<u32>().ok()? * 60 + caps[3].parse::<u32>().ok()?)
+}
+
+// Read the memory, paging and swapping reports from a text sar export, ie. sar -r -B -W or
+// sar -A. Each report starts with a header line naming its columns; the rows of every report
+// are merged by time. Averages are skipped.
+pub fn parse_sar(s: &str) -> Vec<PressureSample> {
+    const ROW_RE: &str = r"^(\d{2}:\d{2}:\d{2}(?:\s+[AP]M)?)\s+(.*)$";
+
+    let re = Regex::new(ROW_RE).unwrap();
+    let mut samples: BTreeMap<u32, PressureSample> = BTreeMap::new();
+    let mut header: Vec<&str> = Vec::new();
+
+    for line in s.lines() {
+        let caps = match re.captures(line.trim()) {
+            Some(caps) => caps,
+            None => continue,
+        };
+        let fields = caps
+            .get(2)
+            .unwrap()
+            .as_str()
+            .split_whitespace()
+            .collect::<Vec<_>>();
+
+        if fields.iter().any(|f| f.parse::<f64>().is_err()) {
+            header = fields;
+            continue;
+        }
+        if fields.len() != header.len() {
+            continue;
+        }
+
+        let time = match parse_time_of_day(&caps[1]) {
+            Some(time) => time,
+            None => continue,
+        };
+        let value = |name: &str| {
+            let i = header.iter().position(|h| *h == name)?;
+            fields[i].parse::<f64>().ok()
+        };
+
+        let sample = samples.entry(time).or_insert_with(|| PressureSample {
+            time: Some(time),
+            ..Default::default()
+        });
+        sample.free_kib = sample.free_kib.or_else(|| value("kbmemfree"));
+        sample.swap_in = sample.swap_in.or_else(|| value("pswpin/s"));
+        sample.swap_out = sample.swap_out.or_else(|| value("pswpout/s"));
+        if let (Some(k), Some(d)) = (value("pgscank/s"), value("pgscand/s")) {
+            sample.pgscan = Some(k + d);
+        }
+    }
+
+    samples.into_values().collect()
+}
+
+// Read vmstat output, with or without -t timestamps. vmstat reports free memory in KiB and swap
+// in and out in KiB per second; it has no page scanning column.
+pub fn parse_vmstat(s: &str) -> Vec<PressureSample> {
+    let mut header: Vec<&str> = Vec::new();
+    let mut samples = Vec::new();
+
+    for line in s.lines() {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+
+        if fields.contains(&"free") && fields.contains(&"si") {
+            // With -t the header ends with the time zone, above the timestamp columns
+            header = fields
+                .into_iter()
+                .take_while(|f| f.chars().all(|c| c.is_ascii_lowercase()))
+                .collect();
+            continue;
+        }
+
+        let numbers = fields
+            .iter()
+            .take(header.len())
+            .map(|f| f.parse::<f64>().ok())
+            .collect::<Option<Vec<_>>>();
+        let numbers = match numbers {
+            Some(n) if !header.is_empty() && n.len() == header.len() => n,
+            _ => continue,
+        };
+        let value = |name: &str| Some(numbers[header.iter().position(|h| *h == name)?]);
+
+        samples.push(PressureSample {
+            time: parse_time_of_day(&fields[header.len()..].join(" ")),
+            free_kib: value("free"),
+            swap_in: value("si"),
+            swap_out: value("so"),
+            pgscan: None,
+        });
+    }
+
+    samples
+}
+
+// How free memory ran out before the kill
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Onset {
+    Gradual,
+    Sudden,
+}
+
+// The samples up to the oom-kill and whether memory ran out gradually or suddenly. Samples
+// after kill_time, in seconds since midnight, are dropped; the export is assumed to cover the
+// same day. Memory ran out suddenly when free memory only fell below halfway between its peak
+// and its final value in the last quarter of the samples.
+pub fn pressure_before(
+    samples: &[PressureSample],
+    kill_time: Option<u32>,
+) -> (Vec<PressureSample>, Option<Onset>) {
+    let before = samples
+        .iter()
+        .filter(|s| match (s.time, kill_time) {
+            (Some(t), Some(kill)) => t <= kill,
+            _ => true,
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let free = before.iter().filter_map(|s| s.free_kib).collect::<Vec<_>>();
+    let (peak, last) = match (free.iter().copied().reduce(f64::max), free.last()) {
+        (Some(peak), Some(&last)) if free.len() >= 3 && peak > last => (peak, last),
+        _ => return (before, None),
+    };
+
+    let halfway = last + (peak - last) / 2.0;
+    let crossed = free.iter().rposition(|&f| f >= halfway).unwrap_or(0) + 1;
+    let onset = if (free.len() - crossed) * 4 <= free.len() {
+        Onset::Sudden
+    } else {
+        Onset::Gradual
+    };
+
+    (before, Some(onset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_pressure() {
+        let sar = "\
+Linux 5.10.0-10-amd64 (localhost) \t12/20/2023 \t_x86_64_\t(4 CPU)
+
+03:00:01 AM kbmemfree   kbavail kbmemused  %memused
+03:05:01 AM   8000000   9000000  12000000     60.00
+03:10:01 AM   7000000   8000000  13000000     65.00
+03:15:01 AM   6000000   7000000  14000000     70.00
+03:20:01 AM    100000    200000  19900000     99.50
+Average:      5275000   6050000  14725000     73.63
+
+03:00:01 AM  pgpgin/s pgpgout/s   fault/s  majflt/s  pgfree/s pgscank/s pgscand/s pgsteal/s    %vmeff
+03:05:01 AM      1.00      2.00      3.00      0.00      4.00      0.00      0.00      0.00      0.00
+03:15:01 AM      1.00      2.00      3.00      0.00      4.00    100.00     50.00      0.00      0.00
+";
+        let samples = parse_sar(sar);
+        assert_eq!(samples.len(), 4);
+        assert_eq!(samples[2].pgscan, Some(150.0));
+        assert_eq!(samples[0].time, Some(3 * 3600 + 5 * 60 + 1));
+
+        let (before, onset) = pressure_before(&samples, parse_time_of_day("03:17:52"));
+        assert_eq!(before.len(), 3);
+        assert_eq!(onset, Some(Onset::Gradual));
+        assert_eq!(pressure_before(&samples, None).1, Some(Onset::Sudden));
+
+        let vmstat = "\
+procs -----------memory---------- ---swap-- -----io---- -system-- ------cpu----- -----timestamp-----
+ r  b   swpd   free   buff  cache   si   so    bi    bo   in   cs us sy id wa st                 UTC
+ 1  0      0 812345  10240 204800    0    0     1     2   50   60  1  1 98  0  0 2023-12-20 15:10:01
+ 2  1   1024  12345  10240 204800   40  900     1     2   50   60  1  1 98  0  0 2023-12-20 15:10:02
+";
+        let samples = parse_vmstat(vmstat);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].swap_out, Some(900.0));
+        assert_eq!(samples[1].time, parse_time_of_day("03:10:02 PM"));
+    }
+}
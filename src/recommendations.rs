@@ -0,0 +1,347 @@
+use crate::report::Report;
+use crate::thresholds::Status;
+use crate::workloads::{recognize_workloads, JvmUsage, UnusedHugepages};
+use serde::Serialize;
+
+// A concrete next step suggested by the report
+#[derive(Serialize)]
+pub struct Recommendation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+// Name and test of each recommendation, in the order they're given. A test returns the advice
+// when it applies to the report.
+type Rule = (&'static str, fn(&Report) -> Option<String>);
+
+const RULES: [Rule; 12] = [
+    ("database_hugepages", database_hugepages),
+    ("hugepages", hugepages),
+    ("lowmem", lowmem),
+    ("restricted", restricted),
+    ("conntrack", conntrack),
+    ("slab", slab),
+    ("service", service),
+    ("swap", swap),
+    ("shmem", shmem),
+    ("fragmentation", fragmentation),
+    ("tasks", tasks),
+    ("jvm", jvm),
+];
+
+// Victims using less than this share of RAM aren't worth limiting on their own
+const SERVICE_RSS_RATIO: f64 = 0.05;
+
+// Give every recommendation that applies to the report, then the notes for the well-known
+// workloads among its largest consumers
+pub fn recommendations(report: &Report) -> Vec<Recommendation> {
+    let rules = RULES.iter().filter_map(|(rule, test)| {
+        Some(Recommendation {
+            rule,
+            message: test(report)?,
+        })
+    });
+    let workloads = recognize_workloads(report)
+        .into_iter()
+        .map(|(workload, command, rss)| Recommendation {
+            rule: workload.name,
+            message: format!(
+                "{} uses {:.1} MiB: {}",
+                command,
+                rss as f64 / 1024.0 / 1024.0,
+                workload.note
+            ),
+        });
+
+    rules.chain(workloads).collect()
+}
+
+// The status and percentage of a category from the status summary
+fn status(report: &Report, category: &str) -> (Status, f64) {
+    report
+        .status
+        .iter()
+        .find(|s| s.category == category)
+        .map_or((Status::Green, 0.0), |s| (s.status, s.percent))
+}
+
+// The name and RSS in KiB of the process that was killed
+fn victim(report: &Report) -> Option<(&str, f64)> {
+    let event = &report.event;
+    let pid = event.victim_pid.as_ref()?.parse::<u32>().ok()?;
+    let process = event.processes.iter().find(|p| p.pid == pid)?;
+    let rss = process.rss as f64 * event.task_unit as f64 / 1024.0;
+
+    Some((&process.name, rss))
+}
+
+fn database_hugepages(report: &Report) -> Option<String> {
+    if status(report, "hugepages").0 == Status::Green {
+        return None;
+    }
+    let unused = UnusedHugepages::new(&report.event)?;
+
+    Some(format!(
+        "Huge pages reserved but not used by {}: {:.1} GiB of the {:.1} GiB reserved was free; set \
+         {} so it uses them, or lower vm.nr_hugepages to what it needs",
+        unused.workload.name,
+        unused.free_kib / 1024.0 / 1024.0,
+        unused.total_kib / 1024.0 / 1024.0,
+        unused.workload.huge_pages.unwrap_or("its huge pages setting")
+    ))
+}
+
+fn hugepages(report: &Report) -> Option<String> {
+    let (status, percent) = status(report, "hugepages");
+    if status == Status::Green || database_hugepages(report).is_some() {
+        return None;
+    }
+
+    let event = &report.event;
+    let gib = event.hugepages_kib() / 1024.0 / 1024.0;
+    Some(format!(
+        "Reduce vm.nr_hugepages: {:.1} GiB ({:.1}% of RAM) is reserved for huge pages, which only \
+         applications configured to use them can have",
+        gib, percent
+    ))
+}
+
+fn lowmem(report: &Report) -> Option<String> {
+    let event = &report.event;
+    let highmem = event.highmem.as_ref()?;
+    if !event.lowmem_exhausted {
+        return None;
+    }
+
+    Some(format!(
+        "Move to a 64-bit kernel: the kernel ran out of its {:.1} MiB of LowMem while {:.1} MiB \
+         of HighMem was free, and only a 64-bit kernel can use all of RAM for itself",
+        highmem.lowmem_kib / 1024.0,
+        highmem.highmem_free_kib / 1024.0
+    ))
+}
+
+// Memory was free, yet neither LowMem nor fragmentation explains the failure
+fn restricted(report: &Report) -> Option<String> {
+    let event = &report.event;
+    if event.ram_exhausted != Some(false)
+        || event.lowmem_exhausted
+        || status(report, "fragmentation").0 != Status::Green
+    {
+        return None;
+    }
+
+    Some(format!(
+        "Check what restricted the allocation: {:.1}% of RAM was still free, so the oom-killer ran \
+         because of the zones, nodes or cgroup the allocation was limited to, not for want of \
+         memory",
+        event.free_fraction()? * 100.0
+    ))
+}
+
+fn conntrack(report: &Report) -> Option<String> {
+    let slab = report.slab.as_ref()?;
+    let conntrack = slab
+        .slabs
+        .iter()
+        .take(3)
+        .find(|s| s[0].starts_with("nf_conntrack"))?;
+
+    Some(format!(
+        "Cap net.netfilter.nf_conntrack_max: the connection tracking table holds {} of \
+         unreclaimable slab",
+        conntrack[2]
+    ))
+}
+
+fn slab(report: &Report) -> Option<String> {
+    let (status, percent) = status(report, "slab");
+    if status != Status::Red || conntrack(report).is_some() {
+        return None;
+    }
+
+    Some(format!(
+        "Unreclaimable slab is {:.1}% of RAM: look for a kernel memory leak in the largest slab \
+         caches and the drivers that own them",
+        percent
+    ))
+}
+
+fn service(report: &Report) -> Option<String> {
+    let memcg = report.event.victim_memcg.as_ref()?;
+    let unit = memcg.rsplit('/').next()?;
+    let (command, rss) = victim(report)?;
+    if !unit.ends_with(".service") || report.event.ram_fraction(rss) < SERVICE_RSS_RATIO {
+        return None;
+    }
+
+    Some(format!(
+        "Set MemoryMax= on {}, so that {} is limited before the whole machine runs out of memory",
+        unit, command
+    ))
+}
+
+fn swap(report: &Report) -> Option<String> {
+    let event = &report.event;
+    let java = victim(report).is_some_and(|(command, _)| command == "java");
+
+    if event.total_swap_kib == 0.0 && java {
+        Some("Add swap or lower the JVM heap (-Xmx) of the java process that was killed".into())
+    } else if event.total_swap_kib == 0.0 {
+        Some(
+            "Add swap, so that rarely used memory can be paged out before the oom-killer runs"
+                .into(),
+        )
+    } else if status(report, "swap").0 == Status::Red {
+        Some(format!(
+            "Swap was exhausted: add more than the {:.1} GiB configured, or reduce memory use",
+            event.total_swap_kib / 1024.0 / 1024.0
+        ))
+    } else {
+        None
+    }
+}
+
+fn shmem(report: &Report) -> Option<String> {
+    let (status, percent) = status(report, "shmem");
+    if status == Status::Green {
+        return None;
+    }
+    if let Some(database) = &report.event.database_shmem {
+        return Some(format!(
+            "Shared memory is {:.1}% of RAM, most of it {}'s {}: size {} to fit in RAM beside \
+             its processes' own memory",
+            percent, database.workload, database.shared_memory, database.shared_memory
+        ));
+    }
+
+    Some(format!(
+        "Shared memory is {:.1}% of RAM: check tmpfs mounts such as /dev/shm and database shared \
+         memory settings",
+        percent
+    ))
+}
+
+fn fragmentation(report: &Report) -> Option<String> {
+    if status(report, "fragmentation").0 == Status::Green {
+        return None;
+    }
+
+    Some(format!(
+        "Raise vm.min_free_kbytes or enable proactive compaction: an order {} allocation failed \
+         with memory still free",
+        report.event.alloc_order.unwrap_or(0)
+    ))
+}
+
+fn tasks(report: &Report) -> Option<String> {
+    let warning = report
+        .event
+        .warnings
+        .iter()
+        .find(|w| w.message.ends_with("possible fork bomb"))?;
+
+    Some(format!(
+        "Limit the number of tasks with TasksMax= or ulimit -u: {}",
+        warning.message
+    ))
+}
+
+fn jvm(report: &Report) -> Option<String> {
+    let event = &report.event;
+    JvmUsage::new(&event.processes, event.task_unit, event.memcg.as_ref()).map(|j| j.hint())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OomEvent;
+
+    fn rules_for(log: &str) -> Vec<&'static str> {
+        let report = Report::new(OomEvent::parse(log).unwrap());
+        recommendations(&report).iter().map(|r| r.rule).collect()
+    }
+
+    #[test]
+    fn report_recommendations() {
+        let log = include_str!("../messages");
+        assert_eq!(rules_for(log), vec!["service", "swap"]);
+
+        let log = log.replace("clamd", "redis-server");
+        assert_eq!(rules_for(&log), vec!["service", "swap", "redis"]);
+
+        // The service rule only applies to a large victim
+        let log = log.replace("590364   422324", "590364     1000");
+        assert!(!rules_for(&log).contains(&"service"));
+    }
+
+    #[test]
+    fn report_recommendations_hugepages() {
+        let log = include_str!("../messages").replace("hugepages_total=2 ", "hugepages_total=10 ");
+        assert!(rules_for(&log).contains(&"hugepages"));
+    }
+
+    #[test]
+    fn report_recommendations_database_hugepages() {
+        let log = include_str!("../tests/fixtures/rhel-9-5.14.log");
+        assert!(!rules_for(log).contains(&"database_hugepages"));
+        let log = log.replace("hugepages_free=4096", "hugepages_free=12288");
+        assert!(rules_for(&log).contains(&"database_hugepages"));
+        assert!(!rules_for(&log).contains(&"hugepages"));
+    }
+
+    #[test]
+    fn report_recommendations_conntrack() {
+        let log = include_str!("../tests/fixtures/ubuntu-22.04-5.15.log");
+        assert!(rules_for(log).contains(&"conntrack"));
+        assert!(!rules_for(log).contains(&"slab"));
+    }
+
+    #[test]
+    fn report_recommendations_lowmem() {
+        let log = include_str!("../tests/fixtures/debian-8-3.16-686-pae.log");
+        assert!(rules_for(log).contains(&"lowmem"));
+    }
+
+    #[test]
+    fn report_recommendations_restricted() {
+        let log = include_str!("../messages");
+        assert!(!rules_for(log).contains(&"restricted"));
+        let log = log.replace(" free:13191 ", " free:1310385 ");
+        assert!(rules_for(&log).contains(&"restricted"));
+    }
+
+    #[test]
+    fn report_recommendations_database_shmem() {
+        let log = include_str!("../tests/fixtures/rhel-9-5.14.log");
+        let before = Report::new(OomEvent::parse(log).unwrap());
+        let log = log.replace(" shmem:1501120 ", " shmem:10001120 ");
+        let report = Report::new(OomEvent::parse(&log).unwrap());
+        let shmem = recommendations(&report)
+            .into_iter()
+            .find(|r| r.rule == "shmem")
+            .unwrap();
+        assert!(shmem.message.contains("most of it oracle's SGA"));
+
+        // The SGA counts once, not in both shmem and the processes' RSS
+        let database = report.event.database_shmem.as_ref().unwrap();
+        let added = report.event.shmem_kib - before.event.shmem_kib;
+        let unaccounted = (before.unaccounted_bytes() - report.unaccounted_bytes()) as f64;
+        assert!((unaccounted / 1024.0 - (added - database.kib)).abs() < 1.0);
+    }
+
+    #[test]
+    fn report_recommendations_jvm() {
+        let log = include_str!("../messages");
+        assert!(!rules_for(log).contains(&"jvm"));
+        assert!(rules_for(&log.replace("clamd", "java")).contains(&"jvm"));
+    }
+
+    #[test]
+    fn report_recommendations_fork_bomb() {
+        let log = include_str!("../messages");
+        let line = log.lines().find(|l| l.contains(" znc")).unwrap();
+        let log = log.replace(line, &format!("{}\n", line).repeat(1000));
+        assert!(rules_for(&log).contains(&"tasks"));
+    }
+}
@@ -0,0 +1,1087 @@
+use crate::recommendations::recommendations;
+use crate::report::{Rank, Report};
+use crate::rollup::command_timeline;
+use crate::text::{Text, TextOptions};
+use crate::{task_counts, top_consumers, JsonReport, OomEvent, ProcessEntry};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::info_span;
+
+// An output format for a parsed report. To add a format, implement this trait and list it in
+// Renderers::new(), or register() it with the renderers of a program built on this crate.
+pub trait Renderer {
+    // Name of the format, as given to --format
+    fn name(&self) -> &'static str;
+
+    // Extension of files written in this format, without the dot
+    fn extension(&self) -> &'static str;
+
+    // Render the whole report in this format
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>>;
+}
+
+// The renderers a report can be written with, found by format name. A renderer may borrow
+// from its caller, as the text renderer does the command line.
+pub struct Renderers<'a> {
+    renderers: Vec<Box<dyn Renderer + 'a>>,
+}
+
+impl<'a> Renderers<'a> {
+    // The renderers built into this crate
+    pub fn new() -> Renderers<'a> {
+        Renderers {
+            renderers: vec![
+                Box::new(Text::default()),
+                Box::new(Json),
+                Box::new(Yaml),
+                Box::new(Csv),
+                Box::new(Tsv),
+                Box::new(Markdown),
+                Box::new(Html),
+                Box::new(Prometheus),
+            ],
+        }
+    }
+
+    // Add a renderer, replacing any with the same name
+    pub fn register(&mut self, renderer: Box<dyn Renderer + 'a>) {
+        self.renderers.retain(|r| r.name() != renderer.name());
+        self.renderers.push(renderer);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&(dyn Renderer + 'a)> {
+        self.renderers
+            .iter()
+            .find(|r| r.name() == name)
+            .map(|r| r.as_ref())
+    }
+
+    // Names of the formats, sorted
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = self.renderers.iter().map(|r| r.name()).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl Default for Renderers<'_> {
+    fn default() -> Self {
+        Renderers::new()
+    }
+}
+
+// The formats built into this crate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Yaml,
+    Csv,
+    Tsv,
+    Markdown,
+    Html,
+    Prometheus,
+}
+
+impl Format {
+    pub const ALL: [Format; 8] = [
+        Format::Text,
+        Format::Json,
+        Format::Yaml,
+        Format::Csv,
+        Format::Tsv,
+        Format::Markdown,
+        Format::Html,
+        Format::Prometheus,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::Markdown => "markdown",
+            Format::Html => "html",
+            Format::Prometheus => "prometheus",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|f| f.name() == s)
+            .ok_or_else(|| {
+                let names = Format::ALL.iter().map(|f| f.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown format '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+// Render a report in one of the built-in formats, as parseoom would write it with --format,
+// for programs that attach the report to an alert or a ticket rather than print it. Text has
+// every section and the top 10 rows of each table, written with the given options.
+pub fn render(
+    report: &Report,
+    format: Format,
+    options: &TextOptions,
+) -> Result<String, Box<dyn Error>> {
+    let _span = info_span!("render", format = format.name()).entered();
+    let mut renderers = Renderers::new();
+    renderers.register(Box::new(Text {
+        options: options.clone(),
+        ..Text::default()
+    }));
+    let renderer = renderers
+        .get(format.name())
+        .ok_or(format!("no renderer for {}", format.name()))?;
+
+    renderer.render(report)
+}
+
+// Render the reports of every event in a log as one document, as --all writes them. CSV has a
+// process list across the events, and TSV each of its tables across them, with the event's
+// number first on each row. HTML has the summary of each event, after a chart of the largest
+// commands' RSS at each when there's more than one. An event that couldn't be parsed is left
+// out but keeps its number.
+pub fn render_events(
+    reports: &[(usize, Report)],
+    format: Format,
+) -> Result<String, Box<dyn Error>> {
+    let _span = info_span!("render", format = format.name(), events = reports.len()).entered();
+    let mut out = String::new();
+
+    match format {
+        Format::Csv => {
+            out.push_str(&format!("event,{}\n", PROCESS_COLUMNS.join(",")));
+            for (n, report) in reports {
+                for process in &report.processes {
+                    let mut fields = process_fields(process, &report.event);
+                    fields.insert(0, n.to_string());
+                    Csv::row(&mut out, &fields);
+                }
+            }
+        }
+        Format::Tsv => {
+            let tables = reports
+                .iter()
+                .map(|(n, report)| (n, Tsv::tables(report)))
+                .collect::<Vec<_>>();
+            // Every report has the same tables in the same order
+            let names = tables.first().map_or(Vec::new(), |(_, t)| {
+                t.iter()
+                    .map(|(name, columns, _)| (*name, *columns))
+                    .collect()
+            });
+            for (i, (name, columns)) in names.into_iter().enumerate() {
+                let columns = std::iter::once("event")
+                    .chain(columns.iter().copied())
+                    .collect::<Vec<_>>();
+                let rows = tables
+                    .iter()
+                    .flat_map(|(n, t)| {
+                        t[i].2.iter().map(move |row| {
+                            std::iter::once(n.to_string())
+                                .chain(row.iter().cloned())
+                                .collect()
+                        })
+                    })
+                    .collect();
+                Tsv::table(&mut out, name, &columns, rows);
+            }
+        }
+        Format::Html => {
+            out = Html::head("oom-killer reports");
+            if reports.len() > 1 {
+                Html::timeline(&mut out, reports);
+            }
+            for (n, report) in reports {
+                let _ = writeln!(out, "<h2>Event {}</h2>", n);
+                Html::summary(&mut out, report, 3);
+            }
+            out.push_str("</body>\n</html>\n");
+        }
+        _ => return Err(format!("no report of every event in {}", format.name()).into()),
+    }
+
+    Ok(out)
+}
+
+// Write rendered output to a file. A name ending in .gz is compressed with gzip as it's written,
+// since the process tables of hundreds of events make exports too big to attach to a ticket.
+pub fn write_output(path: &Path, rendered: &str) -> Result<(), Box<dyn Error>> {
+    let error = |e: io::Error| format!("{}: {}", path.display(), e);
+    let file = File::create(path).map_err(error)?;
+
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        let mut gz = GzEncoder::new(file, Compression::default());
+        gz.write_all(rendered.as_bytes()).map_err(error)?;
+        gz.finish().map_err(error)?;
+    } else {
+        let mut file = file;
+        file.write_all(rendered.as_bytes()).map_err(error)?;
+    }
+
+    Ok(())
+}
+
+// The file to write one of several formats to: the --output path with the format's extension
+// added, before .gz when the output is compressed, ie. report.csv.gz
+pub fn output_path(path: &Path, extension: &str) -> PathBuf {
+    let name = path.display().to_string();
+    match name.strip_suffix(".gz") {
+        Some(stem) => PathBuf::from(format!("{}.{}.gz", stem, extension)),
+        None => PathBuf::from(format!("{}.{}", name, extension)),
+    }
+}
+
+// The report as JSON, one object on a line, as --json prints it
+pub struct Json;
+
+impl Renderer for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string(&JsonReport::new(&report.event))? + "\n")
+    }
+}
+
+// The same document as the JSON, as YAML
+pub struct Yaml;
+
+impl Renderer for Yaml {
+    fn name(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn extension(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        let value = serde_json::to_value(JsonReport::new(&report.event))?;
+        let mut out = String::from("---\n");
+        write_yaml(&mut out, &value, 0);
+
+        Ok(out)
+    }
+}
+
+// A YAML key or string. JSON strings are valid double-quoted YAML scalars, so anything that
+// could be read as another type, or that holds YAML punctuation, is written as JSON.
+fn yaml_string(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c))
+        && s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '/')
+        && !["true", "false", "null", "yes", "no", "on", "off", "y", "n"]
+            .contains(&s.to_ascii_lowercase().as_str());
+
+    if plain {
+        s.to_string()
+    } else {
+        Value::from(s).to_string()
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => yaml_string(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+// Write a mapping or sequence as block YAML, each line indented by indent spaces. Items of a
+// sequence that are themselves collections start on the line of their dash.
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let nested = match value {
+                    Value::Object(m) => !m.is_empty(),
+                    Value::Array(a) => !a.is_empty(),
+                    _ => false,
+                };
+                if nested {
+                    let _ = writeln!(out, "{}{}:", pad, yaml_string(key));
+                    write_yaml(out, value, indent + 2);
+                } else {
+                    let _ = writeln!(out, "{}{}: {}", pad, yaml_string(key), yaml_scalar(value));
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                let mut nested = String::new();
+                write_yaml(&mut nested, item, indent + 2);
+                let _ = write!(out, "{}- {}", pad, &nested[indent + 2..]);
+            }
+        }
+        _ => {
+            let _ = writeln!(out, "{}{}", pad, yaml_scalar(value));
+        }
+    }
+}
+
+// The process list as a table, one process a row in the order of the report's ranking. Sizes
+// are in bytes, whatever unit the kernel printed them in.
+pub struct Csv;
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Csv {
+    fn row(out: &mut String, fields: &[String]) {
+        let fields = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+}
+
+impl Renderer for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        let mut out = PROCESS_COLUMNS.join(",") + "\n";
+        for process in &report.processes {
+            Csv::row(&mut out, &process_fields(process, &report.event));
+        }
+
+        Ok(out)
+    }
+}
+
+// The columns of the process tables of the CSV and TSV output
+const PROCESS_COLUMNS: [&str; 9] = [
+    "pid",
+    "uid",
+    "tgid",
+    "total_vm_bytes",
+    "rss_bytes",
+    "pgtables_bytes",
+    "swap_bytes",
+    "oom_score_adj",
+    "name",
+];
+
+fn process_fields(process: &ProcessEntry, event: &OomEvent) -> Vec<String> {
+    vec![
+        process.pid.to_string(),
+        process.uid.to_string(),
+        process.tgid.to_string(),
+        Rank::Vsz.bytes(process, event).to_string(),
+        Rank::Rss.bytes(process, event).to_string(),
+        Rank::Pgtables.bytes(process, event).to_string(),
+        Rank::Swap.bytes(process, event).to_string(),
+        process.oom_score_adj.to_string(),
+        process.name.clone(),
+    ]
+}
+
+// Every table of the report as tab-separated values, for scripts that would otherwise pick the
+// text output apart. The tables and their columns keep this order from one version to the next,
+// and new ones are only ever added at the end. Each line starts with the name of its table, and
+// each table with its column names, marked by a # before the table name:
+//
+//      #memory     field       kib
+//      memory      ram         20900008
+//      ...
+//      #processes  pid  uid  tgid  total_vm_bytes  rss_bytes  ...  name
+//      processes   517  111  517   2418130944      1729839104 ...  clamd
+//
+// Sizes are plain numbers, in KiB or bytes as the column says, whatever the locale. Tabs and
+// line breaks in values become spaces.
+pub struct Tsv;
+
+// A table of the TSV output: its name, column names and rows
+type Table = (&'static str, &'static [&'static str], Vec<Vec<String>>);
+
+impl Tsv {
+    fn table(out: &mut String, name: &str, columns: &[&str], rows: Vec<Vec<String>>) {
+        let _ = writeln!(out, "#{}\t{}", name, columns.join("\t"));
+        for row in rows {
+            let fields = row
+                .iter()
+                .map(|f| f.replace(['\t', '\n', '\r'], " "))
+                .collect::<Vec<_>>();
+            let _ = writeln!(out, "{}\t{}", name, fields.join("\t"));
+        }
+    }
+
+    // Every table of a report, in order, as its name, column names and rows
+    fn tables(report: &Report) -> Vec<Table> {
+        let event = &report.event;
+        let mut tables: Vec<Table> = Vec::new();
+        let row = |fields: &[&dyn ToString]| fields.iter().map(|f| f.to_string()).collect();
+
+        let mut memory: Vec<Vec<String>> = vec![
+            row(&[&"ram", &event.total_ram_kib]),
+            row(&[&"free", &event.free_kib.unwrap_or(0.0)]),
+            row(&[&"swap_total", &event.total_swap_kib]),
+            row(&[&"swap_used", &event.swap_used_kib()]),
+            row(&[&"slab_unreclaimable", &event.unreclaimable_slab_kib]),
+            row(&[&"slab_reclaimable", &event.reclaimable_slab_kib]),
+            row(&[&"hugepages", &event.hugepages_kib()]),
+            row(&[&"shmem", &event.shmem_kib]),
+            row(&[&"rss", &(report.rss_total as f64 / 1024.0)]),
+        ];
+        if event.free_kib.is_none() {
+            memory.remove(1);
+        }
+        tables.push(("memory", &["field", "kib"], memory));
+
+        let status = report
+            .status
+            .iter()
+            .map(|s| row(&[&s.category, &s.status, &s.percent]))
+            .collect();
+        tables.push(("status", &["category", "status", "percent"], status));
+
+        let victim = event.fingerprint.victim.iter().map(|name| {
+            let pid = event.victim_pid.clone().unwrap_or_default();
+            row(&[name, &pid])
+        });
+        tables.push(("victim", &["name", "pid"], victim.collect()));
+
+        let counts = task_counts(&event.processes)
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let mut commands = top_consumers(&event.processes, event.task_unit)
+            .into_iter()
+            .collect::<Vec<_>>();
+        commands.sort_by_key(|(_, bytes)| Reverse(*bytes));
+        let commands = commands
+            .iter()
+            .map(|(command, bytes)| row(&[command, &counts[command], bytes]))
+            .collect();
+        tables.push(("commands", &["command", "tasks", "rss_bytes"], commands));
+
+        let processes = report
+            .processes
+            .iter()
+            .map(|p| process_fields(p, event))
+            .collect();
+        tables.push(("processes", &PROCESS_COLUMNS, processes));
+
+        let recommendations = recommendations(report)
+            .iter()
+            .map(|r| row(&[&r.rule, &r.message]))
+            .collect();
+        tables.push(("recommendations", &["rule", "message"], recommendations));
+
+        tables
+    }
+}
+
+impl Renderer for Tsv {
+    fn name(&self) -> &'static str {
+        "tsv"
+    }
+
+    fn extension(&self) -> &'static str {
+        "tsv"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        let mut out = String::new();
+        for (name, columns, rows) in Tsv::tables(report) {
+            Tsv::table(&mut out, name, columns, rows);
+        }
+
+        Ok(out)
+    }
+}
+
+// Number of commands listed in the Markdown and HTML summaries
+const SUMMARY_COMMANDS: usize = 10;
+
+fn mib(kib: f64) -> String {
+    format!("{:.1} MiB", kib / 1024.0)
+}
+
+// The figures the Markdown and HTML summaries give about memory, as labels and values
+fn memory_rows(report: &Report) -> Vec<(&'static str, String)> {
+    let event = &report.event;
+    let mut rows = vec![("RAM", mib(event.total_ram_kib))];
+    if let Some(free) = event.free_kib {
+        rows.push(("Free", mib(free)));
+    }
+    rows.push((
+        "Swap used",
+        format!(
+            "{} of {}",
+            mib(event.swap_used_kib()),
+            mib(event.total_swap_kib)
+        ),
+    ));
+    rows.push((
+        "Slab",
+        format!(
+            "{} unreclaimable, {} reclaimable",
+            mib(event.unreclaimable_slab_kib),
+            mib(event.reclaimable_slab_kib)
+        ),
+    ));
+    rows.push(("Huge pages", mib(event.hugepages_kib())));
+    rows.push(("Shared memory", mib(event.shmem_kib)));
+    rows.push(("Process RSS", mib(report.rss_total as f64 / 1024.0)));
+
+    rows
+}
+
+// The process killed, as "name (pid)"
+fn victim(report: &Report) -> Option<String> {
+    let event = &report.event;
+    let name = event.fingerprint.victim.as_ref()?;
+
+    Some(match &event.victim_pid {
+        Some(pid) => format!("{} ({})", name, pid),
+        None => name.clone(),
+    })
+}
+
+// A summary of the report for a ticket or wiki page: the status of each category, the memory
+// figures, the largest commands and the recommendations
+pub struct Markdown;
+
+fn markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+impl Renderer for Markdown {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        let mut out = String::from("# oom-killer report\n\n");
+        if let Some(victim) = victim(report) {
+            let _ = writeln!(out, "Killed: {}\n", markdown_cell(&victim));
+        }
+
+        out.push_str("## Status\n\n| Category | Status | Percent |\n|---|---|---:|\n");
+        for status in &report.status {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {:.1} |",
+                status.category, status.status, status.percent
+            );
+        }
+
+        out.push_str("\n## Memory\n\n");
+        for (label, value) in memory_rows(report) {
+            let _ = writeln!(out, "- {}: {}", label, value);
+        }
+
+        let _ = write!(
+            out,
+            "\n## Top commands by {}\n\n| Command | Size |\n|---|---:|\n",
+            report.rank.description()
+        );
+        for (command, bytes) in report.top_commands.iter().take(SUMMARY_COMMANDS) {
+            let _ = writeln!(
+                out,
+                "| {} | {} |",
+                markdown_cell(command),
+                mib(*bytes as f64 / 1024.0)
+            );
+        }
+
+        let recommendations = recommendations(report);
+        if !recommendations.is_empty() {
+            out.push_str("\n## Recommendations\n\n");
+            for recommendation in recommendations {
+                let _ = writeln!(out, "- {}", recommendation.message);
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+// The Markdown summary as a standalone HTML page
+pub struct Html;
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Renderer for Html {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn extension(&self) -> &'static str {
+        "html"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        let mut out = Html::head("oom-killer report");
+        Html::summary(&mut out, report, 2);
+        out.push_str("</body>\n</html>\n");
+
+        Ok(out)
+    }
+}
+
+impl Html {
+    // The start of the page, up to its heading
+    fn head(title: &str) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+            title
+        )
+    }
+
+    // The summary of a report, its sections headed at the given level
+    fn summary(out: &mut String, report: &Report, level: usize) {
+        if let Some(victim) = victim(report) {
+            let _ = writeln!(out, "<p>Killed: {}</p>", html_escape(&victim));
+        }
+
+        let _ = writeln!(out, "<h{0}>Status</h{0}>\n<table>", level);
+        out.push_str("<tr><th>Category</th><th>Status</th><th>Percent</th></tr>\n");
+        for status in &report.status {
+            let _ = writeln!(
+                out,
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{:.1}</td></tr>",
+                status.status, status.category, status.status, status.percent
+            );
+        }
+        out.push_str("</table>\n");
+
+        let _ = writeln!(out, "<h{0}>Memory</h{0}>\n<table>", level);
+        for (label, value) in memory_rows(report) {
+            let _ = writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", label, value);
+        }
+        out.push_str("</table>\n");
+
+        let _ = writeln!(
+            out,
+            "<h{0}>Top commands by {1}</h{0}>\n<table>\n<tr><th>Command</th><th>Size</th></tr>",
+            level,
+            report.rank.description()
+        );
+        for (command, bytes) in report.top_commands.iter().take(SUMMARY_COMMANDS) {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                html_escape(command),
+                mib(*bytes as f64 / 1024.0)
+            );
+        }
+        out.push_str("</table>\n");
+
+        let recommendations = recommendations(report);
+        if !recommendations.is_empty() {
+            let _ = writeln!(out, "<h{0}>Recommendations</h{0}>\n<ul>", level);
+            for recommendation in recommendations {
+                let _ = writeln!(out, "<li>{}</li>", html_escape(&recommendation.message));
+            }
+            out.push_str("</ul>\n");
+        }
+    }
+
+    // A stacked area chart of the largest commands' RSS at each event, as the web page draws
+    // it, so the command that grew into the kill stands out. The largest is at the bottom of
+    // the stack.
+    fn timeline(out: &mut String, reports: &[(usize, Report)]) {
+        const COLORS: [&str; 10] = [
+            "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+            "#9c755f", "#bab0ac",
+        ];
+        let (width, height, left, bottom, top, right) = (800.0, 300.0, 60.0, 40.0, 10.0, 10.0);
+
+        let events = reports
+            .iter()
+            .map(|(_, r)| Some(&r.event))
+            .collect::<Vec<_>>();
+        let timeline = command_timeline(&events, SUMMARY_COMMANDS);
+        let mib = |rss: Option<i64>| rss.unwrap_or(0) as f64 / 1024.0 / 1024.0;
+        let peak = (0..events.len())
+            .map(|i| timeline.iter().map(|row| mib(row.rss[i])).sum::<f64>())
+            .fold(1.0, f64::max);
+        let x = |i: usize| left + i as f64 * (width - left - right) / (events.len() - 1) as f64;
+        let y = |value: f64| height - bottom - value * (height - bottom - top) / peak;
+
+        out.push_str("<h2>RSS of top commands by event</h2>\n");
+        let _ = writeln!(
+            out,
+            "<svg width=\"{}\" height=\"{}\" font-size=\"11\">",
+            width, height
+        );
+        let mut below = vec![0.0; events.len()];
+        let mut legend = String::new();
+        for (n, row) in timeline.iter().enumerate() {
+            let above = below
+                .iter()
+                .zip(row.rss.iter())
+                .map(|(value, rss)| value + mib(*rss))
+                .collect::<Vec<_>>();
+            let upper = above.iter().enumerate().map(|(i, v)| (x(i), y(*v)));
+            let lower = below.iter().enumerate().rev().map(|(i, v)| (x(i), y(*v)));
+            let points = upper
+                .chain(lower)
+                .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+                .collect::<Vec<_>>();
+            let color = COLORS[n % COLORS.len()];
+            let command = html_escape(&row.command);
+            let _ = writeln!(
+                out,
+                "<polygon points=\"{}\" fill=\"{}\"><title>{}</title></polygon>",
+                points.join(" "),
+                color,
+                command
+            );
+            let _ = writeln!(
+                legend,
+                "<span><i style=\"display: inline-block; width: 0.8em; height: 0.8em; \
+                 background: {}\"></i> {}</span>",
+                color, command
+            );
+            below = above;
+        }
+
+        let _ = writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{1}\" stroke=\"#000\"/>",
+            left,
+            y(0.0),
+            width - right
+        );
+        let _ = writeln!(
+            out,
+            "<text x=\"0\" y=\"{}\">{:.0} MiB</text>",
+            y(peak) + 10.0,
+            peak
+        );
+        for (i, (number, report)) in reports.iter().enumerate() {
+            let label = match &report.event.time {
+                Some(time) => html_escape(time),
+                None => format!("#{}", number),
+            };
+            // The first and last labels end at the edges of the chart rather than past them
+            let anchor = match i {
+                0 => "start",
+                _ if i == reports.len() - 1 => "end",
+                _ => "middle",
+            };
+            let _ = writeln!(
+                out,
+                "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"{}\">{}</text>",
+                x(i),
+                height - bottom + 15.0,
+                anchor,
+                label
+            );
+        }
+        out.push_str("</svg>\n");
+        let _ = writeln!(out, "<p>\n{}</p>", legend);
+    }
+}
+
+// Gauges in the Prometheus text format, for the node exporter's textfile collector
+pub struct Prometheus;
+
+fn prometheus_label(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Renderer for Prometheus {
+    fn name(&self) -> &'static str {
+        "prometheus"
+    }
+
+    fn extension(&self) -> &'static str {
+        "prom"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        let event = &report.event;
+        let mut out = String::new();
+        let mut gauge = |name: &str, help: &str, samples: Vec<(String, f64)>| {
+            let _ = writeln!(out, "# HELP parseoom_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE parseoom_{} gauge", name);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "parseoom_{}{} {}", name, labels, value);
+            }
+        };
+        let bytes = |kib: f64| vec![(String::new(), kib * 1024.0)];
+
+        gauge("ram_bytes", "RAM of the host.", bytes(event.total_ram_kib));
+        if let Some(free) = event.free_kib {
+            gauge("free_bytes", "Free memory.", bytes(free));
+        }
+        gauge(
+            "swap_bytes",
+            "Swap configured.",
+            bytes(event.total_swap_kib),
+        );
+        gauge(
+            "swap_used_bytes",
+            "Swap in use.",
+            bytes(event.swap_used_kib()),
+        );
+        gauge(
+            "slab_unreclaimable_bytes",
+            "Unreclaimable slab.",
+            bytes(event.unreclaimable_slab_kib),
+        );
+        gauge(
+            "slab_reclaimable_bytes",
+            "Reclaimable slab.",
+            bytes(event.reclaimable_slab_kib),
+        );
+        gauge(
+            "hugepages_bytes",
+            "Memory reserved for huge pages.",
+            bytes(event.hugepages_kib()),
+        );
+        gauge(
+            "shmem_bytes",
+            "Shared memory and tmpfs.",
+            bytes(event.shmem_kib),
+        );
+        gauge(
+            "rss_bytes",
+            "Resident memory of every process.",
+            vec![(String::new(), report.rss_total as f64)],
+        );
+        gauge(
+            "category_percent",
+            "Percentage each category of memory use was judged by.",
+            report
+                .status
+                .iter()
+                .map(|s| (format!("{{category=\"{}\"}}", s.category), s.percent))
+                .collect(),
+        );
+        gauge(
+            "command_rss_bytes",
+            "Resident memory of the processes of each command.",
+            report
+                .top_commands
+                .iter()
+                .map(|(command, _)| {
+                    let rss = event
+                        .processes
+                        .iter()
+                        .filter(|p| &p.name == command)
+                        .map(|p| Rank::Rss.bytes(p, event))
+                        .fold(0, |sum: i64, rss| sum.saturating_add(rss));
+                    (
+                        format!("{{command=\"{}\"}}", prometheus_label(command)),
+                        rss as f64,
+                    )
+                })
+                .collect(),
+        );
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TableStyle;
+    use std::collections::BTreeSet;
+
+    struct Upper;
+
+    impl Renderer for Upper {
+        fn name(&self) -> &'static str {
+            "json"
+        }
+
+        fn extension(&self) -> &'static str {
+            "JSON"
+        }
+
+        fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+            Ok(report.event.total_ram_kib.to_string())
+        }
+    }
+
+    #[test]
+    fn report_renderers() {
+        let report = Report::new(OomEvent::parse(include_str!("../messages")).unwrap());
+        let mut renderers = Renderers::new();
+        assert_eq!(
+            renderers.names(),
+            [
+                "csv",
+                "html",
+                "json",
+                "markdown",
+                "prometheus",
+                "text",
+                "tsv",
+                "yaml"
+            ]
+        );
+        for format in Format::ALL.iter() {
+            assert_eq!(format.name().parse::<Format>(), Ok(*format));
+            assert!(!render(&report, *format, &TextOptions::default())
+                .unwrap()
+                .is_empty());
+        }
+        assert!(render(&report, Format::Text, &TextOptions::default())
+            .unwrap()
+            .contains("Top 10 unique commands"));
+        let options = TextOptions {
+            table_style: TableStyle::Ascii,
+            ..TextOptions::default()
+        };
+        assert!(render(&report, Format::Text, &options)
+            .unwrap()
+            .contains("\n+-"));
+
+        let yaml = renderers.get("yaml").unwrap().render(&report).unwrap();
+        assert!(yaml.starts_with("---\n"));
+        assert!(yaml.contains("\nschema_version: "));
+        assert!(yaml.contains("\ntotal_ram_kib: 20900008.0\n"));
+        assert!(yaml.contains("\nprocesses:\n  - name: systemd-journal\n"));
+
+        let csv = renderers.get("csv").unwrap().render(&report).unwrap();
+        assert_eq!(csv.lines().count(), report.processes.len() + 1);
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+
+        let tsv = renderers.get("tsv").unwrap().render(&report).unwrap();
+        assert!(tsv.starts_with("#memory\tfield\tkib\nmemory\tram\t20900008\n"));
+        assert!(tsv.contains("\nvictim\tclamd\t517\n"));
+        assert!(tsv.contains("\n#processes\tpid\tuid\ttgid\ttotal_vm_bytes\t"));
+        let commands = tsv.lines().filter(|l| l.starts_with("commands\t"));
+        assert_eq!(
+            commands
+                .map(|l| l.split('\t').count())
+                .collect::<BTreeSet<_>>(),
+            BTreeSet::from([4])
+        );
+
+        let markdown = renderers.get("markdown").unwrap().render(&report).unwrap();
+        assert!(markdown.contains("Killed: clamd"));
+        let html = renderers.get("html").unwrap().render(&report).unwrap();
+        assert!(html.contains("<td>clamd</td>"));
+        assert_eq!(html_escape("<a&b>"), "&lt;a&amp;b&gt;");
+
+        let prometheus = renderers
+            .get("prometheus")
+            .unwrap()
+            .render(&report)
+            .unwrap();
+        assert!(prometheus.contains("parseoom_ram_bytes 21401608192\n"));
+        assert!(prometheus.contains("parseoom_command_rss_bytes{command=\"clamd\"} "));
+
+        renderers.register(Box::new(Upper));
+        assert_eq!(renderers.names().len(), 8);
+        assert_eq!(renderers.get("json").unwrap().extension(), "JSON");
+    }
+
+    #[test]
+    fn report_render_events() {
+        let report = || Report::new(OomEvent::parse(include_str!("../messages")).unwrap());
+        let processes = report().processes.len();
+        let reports = vec![(1, report()), (3, report())];
+
+        let csv = render_events(&reports, Format::Csv).unwrap();
+        assert!(csv.starts_with("event,pid,uid,tgid,"));
+        assert_eq!(csv.lines().count(), 2 * processes + 1);
+        assert_eq!(
+            csv.lines().filter(|l| l.starts_with("3,")).count(),
+            processes
+        );
+
+        let tsv = render_events(&reports, Format::Tsv).unwrap();
+        assert!(tsv.starts_with("#memory\tevent\tfield\tkib\nmemory\t1\tram\t20900008\n"));
+        assert!(tsv.contains("\nvictim\t1\tclamd\t517\nvictim\t3\tclamd\t517\n"));
+        assert_eq!(tsv.matches("\n#processes\tevent\tpid\t").count(), 1);
+
+        let html = render_events(&reports, Format::Html).unwrap();
+        assert!(html.contains("<h2>RSS of top commands by event</h2>\n<svg "));
+        assert!(html.contains("\"><title>clamd</title></polygon>\n"));
+        assert!(html.contains("<h2>Event 3</h2>\n<p>Killed: clamd"));
+        assert_eq!(html.matches("<h3>Status</h3>\n").count(), 2);
+        let one = render_events(&reports[..1], Format::Html).unwrap();
+        assert!(!one.contains("<svg"));
+        assert!(one.contains("<h2>Event 1</h2>"));
+
+        assert!(render_events(&reports, Format::Yaml).is_err());
+    }
+
+    #[test]
+    fn report_gzip_output() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        assert_eq!(
+            output_path(Path::new("out/report.gz"), "csv"),
+            PathBuf::from("out/report.csv.gz")
+        );
+        assert_eq!(
+            output_path(Path::new("report"), "json"),
+            PathBuf::from("report.json")
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("parseoom-{}-report.json.gz", std::process::id()));
+        write_output(&path, "{}\n").unwrap();
+        let mut text = String::new();
+        GzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(text, "{}\n");
+    }
+}
@@ -0,0 +1,397 @@
+use crate::thresholds::{CategoryStatus, Thresholds};
+use crate::{
+    badness_ranking, container_groups, oom_score_adj_bands, parent_name, parse_unreclaimable_slab,
+    rss_histogram, task_counts, top_consumers, Badness, ContainerGroup, OomEvent, OomScoreAdjBand,
+    ProcessEntry, RssBucket, PAGE_SIZE,
+};
+use regex::Regex;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// The largest unreclaimable slab caches, as returned by parse_unreclaimable_slab()
+#[derive(Serialize)]
+pub struct SlabInfo {
+    pub header: Vec<String>,
+    pub slabs: Vec<Vec<String>>,
+}
+
+// Every section of the parseoom report, computed up front from a parsed oom-killer report so
+// that callers can choose which sections to render. Memory sizes are in bytes unless the field
+// name says otherwise.
+#[derive(Serialize)]
+pub struct Report {
+    pub event: OomEvent,
+    pub status: Vec<CategoryStatus>,
+    pub slab: Option<SlabInfo>,
+    pub top_commands: Vec<(String, i64)>,
+    pub task_counts: Vec<(String, usize)>,
+    pub parents: Vec<ParentGroup>,
+    pub containers: Vec<ContainerGroup>,
+    pub oom_score_adj: Vec<OomScoreAdjBand>,
+    pub rss_histogram: Vec<RssBucket>,
+    pub victims: Vec<Badness>,
+    pub processes: Vec<ProcessEntry>,
+    pub rss_total: i64,
+    pub rank: Rank,
+}
+
+impl Report {
+    pub fn new(event: OomEvent) -> Report {
+        let task_unit = event.task_unit;
+
+        let slab = parse_unreclaimable_slab(&event.cleaned)
+            .map(|(header, slabs)| SlabInfo { header, slabs });
+
+        let rss_total = top_consumers(&event.processes, task_unit)
+            .values()
+            .fold(0, |sum: i64, rss| sum.saturating_add(*rss));
+        let task_counts = task_counts(&event.processes);
+        let containers = container_groups(&event.processes, task_unit);
+        let oom_score_adj = oom_score_adj_bands(&event.processes, task_unit);
+        let rss_histogram = rss_histogram(&event.processes, task_unit);
+        let total_pages = (event.total_ram_kib + event.total_swap_kib) / 4.0;
+        let victims = badness_ranking(&event.processes, &event.ps_header, task_unit, total_pages);
+
+        let status = Thresholds::default().evaluate(&event, rss_total);
+
+        let mut report = Report {
+            event,
+            status,
+            slab,
+            top_commands: Vec::new(),
+            parents: Vec::new(),
+            task_counts,
+            containers,
+            oom_score_adj,
+            rss_histogram,
+            victims,
+            processes: Vec::new(),
+            rss_total,
+            rank: Rank::Rss,
+        };
+        report.rank_by(Rank::Rss);
+
+        report
+    }
+
+    // Rank the command and process tables by a different column, largest first
+    pub fn rank_by(&mut self, rank: Rank) {
+        let event = &self.event;
+
+        let mut commands: BTreeMap<String, i64> = BTreeMap::new();
+        for process in event.processes.iter() {
+            *commands.entry(process.name.clone()).or_insert(0) += rank.bytes(process, event);
+        }
+
+        // To sort the key (command name) by its value we need to convert the map to a vector
+        self.top_commands = commands.into_iter().collect();
+        self.top_commands.sort_by_key(|(_, bytes)| Reverse(*bytes));
+
+        // Threads listed apart from their thread group leader take the leader's name
+        let leaders: BTreeMap<u32, &str> = event
+            .processes
+            .iter()
+            .filter(|process| process.pid == process.tgid)
+            .map(|process| (process.pid, process.name.as_str()))
+            .collect();
+
+        let mut parents: BTreeMap<&str, ParentGroup> = BTreeMap::new();
+        for process in event.processes.iter() {
+            let name = leaders.get(&process.tgid).copied().unwrap_or(&process.name);
+            let parent = parent_name(name);
+            let group = parents.entry(parent).or_insert_with(|| ParentGroup {
+                name: parent.to_string(),
+                commands: Vec::new(),
+                tasks: 0,
+                bytes: 0,
+            });
+            if !group.commands.contains(&process.name) {
+                group.commands.push(process.name.clone());
+            }
+            group.tasks += 1;
+            group.bytes += rank.bytes(process, event);
+        }
+
+        self.parents = parents.into_values().collect();
+        self.parents.sort_by_key(|group| Reverse(group.bytes));
+
+        self.processes = event.processes.clone();
+        self.processes
+            .sort_by_key(|process| Reverse(rank.bytes(process, event)));
+        self.rank = rank;
+    }
+
+    // Keep only the commands and processes whose name matches, so a group such as all JVMs can be
+    // looked at on its own. Totals still cover every process.
+    pub fn filter(&mut self, re: &Regex) {
+        self.top_commands
+            .retain(|(command, _)| re.is_match(command));
+        self.processes.retain(|process| re.is_match(&process.name));
+        self.parents.retain(|group| {
+            re.is_match(&group.name) || group.commands.iter().any(|c| re.is_match(c))
+        });
+    }
+}
+
+// The tasks that parent_name() attributes to one program, with the column the report is ranked
+// by summed across them
+#[derive(Serialize)]
+pub struct ParentGroup {
+    pub name: String,
+    pub commands: Vec<String>,
+    pub tasks: usize,
+    pub bytes: i64,
+}
+
+// The process list column the command and process tables are ranked by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rank {
+    Rss,
+    Vsz,
+    Pgtables,
+    Swap,
+}
+
+impl Rank {
+    pub const ALL: [Rank; 4] = [Rank::Rss, Rank::Vsz, Rank::Pgtables, Rank::Swap];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rank::Rss => "rss",
+            Rank::Vsz => "vsz",
+            Rank::Pgtables => "pgtables",
+            Rank::Swap => "swap",
+        }
+    }
+
+    // What the column measures, for table titles
+    pub fn description(self) -> &'static str {
+        match self {
+            Rank::Rss => "memory",
+            Rank::Vsz => "virtual memory",
+            Rank::Pgtables => "page tables",
+            Rank::Swap => "swap",
+        }
+    }
+
+    // The size of this column in bytes for one row of the process list. Before v4.15 the page
+    // table column was nr_ptes, counted in pages, rather than pgtables_bytes.
+    pub fn bytes(self, process: &ProcessEntry, event: &OomEvent) -> i64 {
+        let nr_ptes = event.ps_header.iter().any(|h| h == "nr_ptes");
+
+        match self {
+            Rank::Rss => process.rss.saturating_mul(event.task_unit),
+            Rank::Vsz => process.total_vm.saturating_mul(event.task_unit),
+            Rank::Pgtables if nr_ptes => process.pgtables.saturating_mul(PAGE_SIZE),
+            Rank::Pgtables => process.pgtables,
+            Rank::Swap => process.swapents.saturating_mul(event.task_unit),
+        }
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Rank {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rank, String> {
+        Rank::ALL
+            .iter()
+            .copied()
+            .find(|rank| rank.name() == s)
+            .ok_or_else(|| {
+                let names = Rank::ALL.iter().map(|r| r.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown rank '{}', expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+// How many rows of the command, program, victim and process tables to show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Top {
+    Rows(usize),
+    All,
+}
+
+impl Top {
+    pub fn rows(self) -> usize {
+        match self {
+            Top::Rows(n) => n,
+            Top::All => usize::MAX,
+        }
+    }
+
+    // A table title such as "Top 10 unique commands", or "All unique commands"
+    pub fn title(self, what: &str) -> String {
+        match self {
+            Top::Rows(n) => format!("Top {} {}", n, what),
+            Top::All => format!("All {}", what),
+        }
+    }
+}
+
+impl fmt::Display for Top {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Top::Rows(n) => write!(f, "{}", n),
+            Top::All => f.write_str("all"),
+        }
+    }
+}
+
+impl FromStr for Top {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Top, String> {
+        match s {
+            "all" => Ok(Top::All),
+            _ => match s.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Top::Rows(n)),
+                _ => Err(format!(
+                    "invalid row count '{}', expected a number or all",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+// The parts of the report that can be selected for rendering, in the order they are rendered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Status,
+    Meminfo,
+    Thp,
+    Slab,
+    Custom,
+    Commands,
+    Parents,
+    Containers,
+    Tasks,
+    OomScoreAdj,
+    Histogram,
+    Victim,
+    Processes,
+    Rss,
+    Precursors,
+    Recommendations,
+}
+
+impl Section {
+    pub const ALL: [Section; 16] = [
+        Section::Status,
+        Section::Meminfo,
+        Section::Thp,
+        Section::Slab,
+        Section::Custom,
+        Section::Commands,
+        Section::Parents,
+        Section::Containers,
+        Section::Tasks,
+        Section::OomScoreAdj,
+        Section::Histogram,
+        Section::Victim,
+        Section::Processes,
+        Section::Rss,
+        Section::Precursors,
+        Section::Recommendations,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Section::Status => "status",
+            Section::Meminfo => "meminfo",
+            Section::Thp => "thp",
+            Section::Slab => "slab",
+            Section::Custom => "custom",
+            Section::Commands => "commands",
+            Section::Parents => "parents",
+            Section::Containers => "containers",
+            Section::Tasks => "tasks",
+            Section::OomScoreAdj => "oom_score_adj",
+            Section::Histogram => "histogram",
+            Section::Victim => "victim",
+            Section::Processes => "processes",
+            Section::Rss => "rss",
+            Section::Precursors => "precursors",
+            Section::Recommendations => "recommendations",
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Section {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Section, String> {
+        Section::ALL
+            .iter()
+            .copied()
+            .find(|section| section.name() == s)
+            .ok_or_else(|| {
+                let names = Section::ALL.iter().map(|s| s.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown section '{}', expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_sections() {
+        let report = Report::new(OomEvent::parse(include_str!("../messages")).unwrap());
+        assert_eq!(report.top_commands[0].0, "clamd");
+        assert_eq!(report.top_commands[1], ("rspamd".to_string(), 309186560));
+        assert_eq!(report.processes[0].name, "clamd");
+        assert_eq!(report.rss_total, 517275 * 4096);
+        assert!(report.slab.is_none());
+
+        let postfix = report.parents.iter().find(|g| g.name == "postfix").unwrap();
+        assert_eq!(postfix.tasks, 4);
+        assert_eq!(postfix.commands, vec!["master", "qmgr", "tlsmgr", "pickup"]);
+
+        let mut report = report;
+        report.rank_by(Rank::Swap);
+        assert_eq!(report.top_commands[0], ("clamd".to_string(), 107099 * 4096));
+        assert_eq!("pgtables".parse::<Rank>(), Ok(Rank::Pgtables));
+
+        report.filter(&Regex::new("^(clam|fresh)").unwrap());
+        assert_eq!(report.top_commands.len(), 2);
+        assert_eq!(report.processes.len(), 2);
+        assert_eq!(report.rss_total, 517275 * 4096);
+
+        assert_eq!("all".parse::<Top>(), Ok(Top::All));
+        assert_eq!("25".parse::<Top>(), Ok(Top::Rows(25)));
+        assert!("0".parse::<Top>().is_err());
+        assert_eq!(Top::Rows(10).title("programs"), "Top 10 programs");
+
+        assert_eq!("oom_score_adj".parse::<Section>(), Ok(Section::OomScoreAdj));
+        assert!("memory".parse::<Section>().is_err());
+        for section in Section::ALL.iter() {
+            assert_eq!(section.to_string().parse::<Section>(), Ok(*section));
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use crate::timestamps::{parse_log_times, LogTime};
+use crate::{top_consumers, OomEvent};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+use tracing::{info, instrument};
+
+// Split a log into one chunk per oom-killer invocation. Each chunk starts at the beginning of the
+// line that invoked the oom-killer and runs up to the next invocation, so it holds the report
+// along with the kill and oom_reaper messages that follow it.
+#[instrument(level = "info", skip_all, fields(bytes = input.len()))]
+pub fn split_events(input: &str) -> Vec<&str> {
+    let starts = input
+        .match_indices("invoked oom-killer")
+        .map(|(i, _)| input[..i].rfind('\n').map_or(0, |n| n + 1))
+        .collect::<Vec<_>>();
+    info!(events = starts.len(), "found oom-killer invocations");
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let end = starts.get(n + 1).copied().unwrap_or(input.len());
+            &input[start..end]
+        })
+        .collect()
+}
+
+// Drop the events a log holds more than once, returning the rest and the number dropped. A host
+// that writes its kernel log both to messages and to a forwarded file gives every event twice
+// when the two are merged. Events are the same when they have the same kernel timestamp, or
+// syslog date if the kernel's is missing, and killed the same process. Events without a kill
+// line are always kept.
+#[instrument(level = "info", skip_all, fields(events = chunks.len()))]
+pub fn dedup_events(chunks: Vec<&str>) -> (Vec<&str>, usize) {
+    const UPTIME_RE: &str = r"\[\s*(\d+\.\d+)\]";
+    const VICTIM_RE: &str = r"Killed process (\d+) \((.*?)\)";
+
+    let uptime_re = Regex::new(UPTIME_RE).unwrap();
+    let victim_re = Regex::new(VICTIM_RE).unwrap();
+
+    let mut seen = BTreeSet::new();
+    let mut kept = Vec::new();
+    for chunk in chunks.iter() {
+        let first = chunk.lines().next().unwrap_or("");
+        let timestamp = uptime_re
+            .captures(first)
+            .map(|caps| caps[1].to_string())
+            .or_else(|| parse_timestamp(first));
+        let key = victim_re
+            .captures(chunk)
+            .and_then(|caps| Some((timestamp.clone()?, caps[2].to_string(), caps[1].to_string())));
+
+        if key.is_none_or(|key| seen.insert(key)) {
+            kept.push(*chunk);
+        }
+    }
+
+    let duplicates = chunks.len() - kept.len();
+    info!(duplicates, "dropped events logged more than once");
+    (kept, duplicates)
+}
+
+// A process killed by the oom-killer. rss_kib is the sum of its anonymous, file and shmem RSS,
+// and reclaimed_kib is what oom_reaper confirmed it took back, when it ran.
+#[derive(Serialize, JsonSchema)]
+pub struct Kill {
+    pub pid: String,
+    pub command: String,
+    pub cgroup: Option<String>,
+    pub anon_kib: f64,
+    pub file_kib: f64,
+    pub shmem_kib: f64,
+    pub rss_kib: f64,
+    pub reclaimed_kib: Option<f64>,
+}
+
+// The memory given back by the processes one oom-killer invocation killed. Anonymous memory is
+// freed outright; file pages stay in the page cache and shmem stays in tmpfs or the segment until
+// something else lets go of them. reaped_kib is what oom_reaper confirmed, and ram_ratio the
+// share of RAM the kill freed.
+#[derive(Serialize, JsonSchema)]
+pub struct FreedMemory {
+    pub anon_kib: f64,
+    pub file_kib: f64,
+    pub shmem_kib: f64,
+    pub rss_kib: f64,
+    pub reaped_kib: Option<f64>,
+    pub ram_ratio: Option<f64>,
+}
+
+impl FreedMemory {
+    pub fn new(kills: &[Kill], total_ram_kib: f64) -> Option<FreedMemory> {
+        if kills.is_empty() {
+            return None;
+        }
+
+        let sum = |f: fn(&Kill) -> f64| kills.iter().map(f).sum::<f64>();
+        let rss_kib = sum(|k| k.rss_kib);
+        let reaped = kills.iter().filter_map(|k| k.reclaimed_kib);
+        Some(FreedMemory {
+            anon_kib: sum(|k| k.anon_kib),
+            file_kib: sum(|k| k.file_kib),
+            shmem_kib: sum(|k| k.shmem_kib),
+            rss_kib,
+            reaped_kib: kills
+                .iter()
+                .any(|k| k.reclaimed_kib.is_some())
+                .then(|| reaped.sum()),
+            ram_ratio: (total_ram_kib > 0.0).then(|| rss_kib / total_ram_kib),
+        })
+    }
+}
+
+// What happened in a single oom-killer invocation, from the lines around the report
+#[derive(Serialize)]
+pub struct EventSummary {
+    pub timestamp: Option<String>,
+    pub kills: Vec<Kill>,
+}
+
+impl EventSummary {
+    // Summarize one chunk returned by split_events()
+    pub fn new(chunk: &str) -> EventSummary {
+        const KILLED_RE: &str = r"Killed process (\d+) \((.*?)\).*?anon-rss:(\d+)kB, file-rss:(\d+)kB(?:, shmem-rss:(\d+)kB)?";
+        const REAPED_RE: &str = r"oom_reaper: reaped process (\d+) .*?anon-rss:(\d+)kB, file-rss:(\d+)kB, shmem-rss:(\d+)kB";
+        const MEMCG_RE: &str = r"oom-kill:.*\btask_memcg=([^,]*),task=.*?,pid=(\d+)";
+
+        // Kernels before 4.x leave out shmem-rss. The kernel's kB are pages times the page size
+        // over 1024, so KiB already.
+        let kib = |caps: &regex::Captures, i: usize| -> f64 {
+            caps.get(i)
+                .and_then(|m| m.as_str().parse::<f64>().ok())
+                .unwrap_or(0.0)
+        };
+        let sum_kib = |caps: &regex::Captures, first: usize| -> f64 {
+            (first..first + 3).map(|i| kib(caps, i)).sum::<f64>()
+        };
+
+        let memcg: BTreeMap<String, String> = Regex::new(MEMCG_RE)
+            .unwrap()
+            .captures_iter(chunk)
+            .map(|caps| (caps[2].to_string(), caps[1].to_string()))
+            .collect();
+
+        let reaped: BTreeMap<String, f64> = Regex::new(REAPED_RE)
+            .unwrap()
+            .captures_iter(chunk)
+            .map(|caps| (caps[1].to_string(), sum_kib(&caps, 2)))
+            .collect();
+
+        let kills = Regex::new(KILLED_RE)
+            .unwrap()
+            .captures_iter(chunk)
+            .map(|caps| {
+                let pid = caps[1].to_string();
+                let rss_kib = sum_kib(&caps, 3);
+                Kill {
+                    command: caps[2].to_string(),
+                    cgroup: memcg.get(&pid).cloned(),
+                    anon_kib: kib(&caps, 3),
+                    file_kib: kib(&caps, 4),
+                    shmem_kib: kib(&caps, 5),
+                    rss_kib,
+                    reclaimed_kib: reaped.get(&pid).map(|left| rss_kib - left),
+                    pid,
+                }
+            })
+            .collect();
+
+        EventSummary {
+            timestamp: chunk.lines().next().and_then(parse_timestamp),
+            kills,
+        }
+    }
+}
+
+// Return the timestamp prefix of a log line: a syslog date, an ISO 8601 or RFC 3339 date as
+// written by rsyslog and journalctl -o short-iso, or the kernel's uptime. parse_log_time() turns
+// it into a time. The regex is compiled once, as the lines before a report are read one at a
+// time looking for its warning signs, up to a hundred thousand of them.
+pub fn parse_timestamp(line: &str) -> Option<String> {
+    const TIMESTAMP_RE: &str = r"^([A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2})|^(\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?)|\[\s*(\d+\.\d+)\]";
+    static TIMESTAMP: OnceLock<Regex> = OnceLock::new();
+
+    let re = TIMESTAMP.get_or_init(|| Regex::new(TIMESTAMP_RE).unwrap());
+    let caps = re.captures(line)?;
+    caps.get(1)
+        .or_else(|| caps.get(2))
+        .or_else(|| caps.get(3))
+        .map(|m| m.as_str().to_string())
+}
+
+// Totals across every oom-killer invocation in a log
+#[derive(Serialize)]
+pub struct Rollup {
+    pub events: usize,
+    pub kills: usize,
+    pub kills_by_command: Vec<(String, usize)>,
+    pub kills_by_cgroup: Vec<(String, usize)>,
+    pub reclaimed_kib: f64,
+    pub first: Option<String>,
+    pub last: Option<String>,
+    pub mean_seconds_between_kills: Option<f64>,
+    pub duplicates: usize,
+    pub recurrence: Option<Recurrence>,
+}
+
+impl Rollup {
+    // Total the events of a log, in the order they were logged. Syslog dates are counted from
+    // `year`, as parse_log_times() does.
+    pub fn new(summaries: &[EventSummary], year: i32) -> Rollup {
+        let kills = summaries.iter().flat_map(|s| s.kills.iter());
+        let mut by_command = BTreeMap::new();
+        let mut by_cgroup = BTreeMap::new();
+
+        for kill in kills.clone() {
+            *by_command.entry(kill.command.clone()).or_insert(0) += 1;
+            let cgroup = kill.cgroup.clone().unwrap_or_else(|| "unknown".to_string());
+            *by_cgroup.entry(cgroup).or_insert(0) += 1;
+        }
+
+        let timestamps = summaries
+            .iter()
+            .filter_map(|s| s.timestamp.clone())
+            .collect::<Vec<_>>();
+        let times = parse_log_times(
+            &summaries
+                .iter()
+                .map(|s| s.timestamp.clone())
+                .collect::<Vec<_>>(),
+            year,
+        );
+
+        // Mean of the gaps between consecutive events. A gap is skipped when the two timestamps
+        // aren't comparable, ie. one is uptime and the other a date, or time went backwards
+        // because of a reboot.
+        let times = times.into_iter().flatten().collect::<Vec<_>>();
+        let gaps = times
+            .windows(2)
+            .filter_map(|pair| pair[1].seconds_since(&pair[0]))
+            .filter(|gap| *gap >= 0.0)
+            .collect::<Vec<_>>();
+        let mean_seconds_between_kills = match gaps.len() {
+            0 => None,
+            n => Some(gaps.iter().sum::<f64>() / n as f64),
+        };
+
+        Rollup {
+            events: summaries.len(),
+            kills: kills.clone().count(),
+            kills_by_command: sorted_counts(by_command),
+            kills_by_cgroup: sorted_counts(by_cgroup),
+            reclaimed_kib: kills.filter_map(|k| k.reclaimed_kib).sum(),
+            first: timestamps.first().cloned(),
+            last: timestamps.last().cloned(),
+            mean_seconds_between_kills,
+            duplicates: 0,
+            recurrence: None,
+        }
+    }
+}
+
+// RSS in bytes of one command at each event, None where the event couldn't be parsed
+#[derive(Serialize)]
+pub struct TimelineRow {
+    pub command: String,
+    pub rss: Vec<Option<i64>>,
+}
+
+// Follow the largest commands across events, to show which consumers grew from one oom-kill to
+// the next. Commands are ranked by their peak RSS in any event and the top `limit` are kept.
+pub fn command_timeline(events: &[Option<&OomEvent>], limit: usize) -> Vec<TimelineRow> {
+    let per_event = events
+        .iter()
+        .map(|event| event.map(|e| top_consumers(&e.processes, e.task_unit)))
+        .collect::<Vec<_>>();
+
+    let mut peaks: BTreeMap<&str, i64> = BTreeMap::new();
+    for commands in per_event.iter().flatten() {
+        for (command, &rss) in commands.iter() {
+            let peak = peaks.entry(command).or_insert(0);
+            *peak = (*peak).max(rss);
+        }
+    }
+
+    let mut peaks = peaks.into_iter().collect::<Vec<_>>();
+    peaks.sort_by_key(|(_, rss)| std::cmp::Reverse(*rss));
+
+    peaks
+        .into_iter()
+        .take(limit)
+        .map(|(command, _)| TimelineRow {
+            command: command.to_string(),
+            rss: per_event
+                .iter()
+                .map(|commands| Some(*commands.as_ref()?.get(command).unwrap_or(&0)))
+                .collect(),
+        })
+        .collect()
+}
+
+// When the oom-killer is likely to run again. If the largest consumer kept growing from one event
+// to the next, the memory the last kill freed lasts until it has grown by that much; otherwise
+// the kills are expected to keep coming at their mean interval. basis is "growth" or "interval".
+#[derive(Serialize)]
+pub struct Recurrence {
+    pub command: String,
+    pub growth_bytes_per_hour: Option<f64>,
+    pub seconds_to_next: f64,
+    pub basis: &'static str,
+}
+
+// Estimate the time to the next oom-kill from the timeline of command_timeline(), the time of
+// each event and the KiB the last kill freed
+pub fn predict_recurrence(
+    timeline: &[TimelineRow],
+    times: &[Option<LogTime>],
+    mean_seconds_between_kills: Option<f64>,
+    freed_kib: Option<f64>,
+) -> Option<Recurrence> {
+    let row = timeline.first()?;
+    let seen = row
+        .rss
+        .iter()
+        .zip(times)
+        .filter_map(|(rss, time)| Some(((*rss)?, time.as_ref()?)))
+        .filter(|(rss, _)| *rss > 0)
+        .collect::<Vec<_>>();
+
+    let growth = match (seen.first(), seen.last()) {
+        (Some((first, t0)), Some((last, t1))) => t1
+            .seconds_since(t0)
+            .filter(|gap| *gap > 0.0)
+            .map(|gap| (last - first) as f64 / gap),
+        _ => None,
+    };
+
+    let (seconds_to_next, basis) = match (growth, freed_kib) {
+        (Some(rate), Some(freed)) if rate > 0.0 && freed > 0.0 => (freed * 1024.0 / rate, "growth"),
+        _ => (mean_seconds_between_kills?, "interval"),
+    };
+
+    Some(Recurrence {
+        command: row.command.clone(),
+        growth_bytes_per_hour: growth.map(|rate| rate * 3600.0),
+        seconds_to_next,
+        basis,
+    })
+}
+
+// A rough length of time, such as ~40m, ~6h or ~3d
+pub fn approx_duration(seconds: f64) -> String {
+    match seconds {
+        s if s < 3600.0 => format!("~{}m", (s / 60.0).round().max(1.0)),
+        s if s < 48.0 * 3600.0 => format!("~{}h", (s / 3600.0).round()),
+        s => format!("~{}d", (s / 86400.0).round()),
+    }
+}
+
+// Sort counts largest first, breaking ties by name
+pub(crate) fn sorted_counts(counts: BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_rollup() {
+        let messages = include_str!("../messages");
+        let log = format!(
+            "{}{}",
+            messages,
+            messages.replace("Dec 20 03:17:52", "Dec 20 05:17:52")
+        );
+
+        let chunks = split_events(&log);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[1].starts_with("Dec 20 05:17:52"));
+
+        let summaries = chunks
+            .iter()
+            .map(|c| EventSummary::new(c))
+            .collect::<Vec<_>>();
+        let kill = &summaries[0].kills[0];
+        assert_eq!(kill.command, "clamd");
+        assert_eq!(
+            kill.cgroup.as_deref(),
+            Some("/system.slice/clamav-daemon.service")
+        );
+        assert_eq!(kill.reclaimed_kib, Some(1689296.0));
+
+        let rollup = Rollup::new(&summaries, 2023);
+        assert_eq!(rollup.kills, 2);
+        assert_eq!(rollup.kills_by_command, vec![("clamd".to_string(), 2)]);
+        assert_eq!(rollup.first.as_deref(), Some("Dec 20 03:17:52"));
+        assert_eq!(rollup.mean_seconds_between_kills, Some(7200.0));
+
+        let event = OomEvent::parse(chunks[0]).unwrap();
+        let timeline = command_timeline(&[Some(&event), None], 3);
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline[0].command, "clamd");
+        assert_eq!(timeline[1].rss, vec![Some(309186560), None]);
+
+        // clamd grew by 1 GiB in the 2 hours between the events, so the 1 GiB the last kill
+        // freed lasts another 2 hours
+        let times = parse_log_times(
+            &[
+                summaries[0].timestamp.clone(),
+                summaries[1].timestamp.clone(),
+            ],
+            2023,
+        );
+        let grown = TimelineRow {
+            command: "clamd".to_string(),
+            rss: vec![Some(1 << 30), Some(2 << 30)],
+        };
+        let recurrence =
+            predict_recurrence(&[grown], &times, Some(7200.0), Some(1048576.0)).unwrap();
+        assert_eq!(recurrence.basis, "growth");
+        assert_eq!(recurrence.seconds_to_next, 7200.0);
+        assert_eq!(approx_duration(recurrence.seconds_to_next), "~2h");
+        let steady = predict_recurrence(&timeline, &times, Some(5400.0), Some(1048576.0)).unwrap();
+        assert_eq!((steady.basis, steady.seconds_to_next), ("interval", 5400.0));
+        assert_eq!(approx_duration(30.0), "~1m");
+
+        // The same event shipped to a second file, stamped by another host, and a later one
+        let forwarded = messages.replace("localhost", "loghost");
+        let later = messages.replace("[75669.", "[79269.");
+        let merged = format!("{}{}{}", messages, forwarded, later);
+        let (chunks, duplicates) = dedup_events(split_events(&merged));
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(duplicates, 1);
+        assert!(chunks[1].contains("[79269."));
+
+        assert_eq!(
+            parse_timestamp("[542091.439321] x"),
+            Some("542091.439321".to_string())
+        );
+        assert_eq!(
+            parse_timestamp("2023-12-20T03:17:52.123456+01:00 localhost kernel: x"),
+            Some("2023-12-20T03:17:52.123456+01:00".to_string())
+        );
+
+        // The mean gap across New Year
+        let log = format!(
+            "{}{}",
+            messages.replace("Dec 20 03:17:52", "Dec 31 23:00:00"),
+            messages.replace("Dec 20 03:17:52", "Jan  1 01:00:00")
+        );
+        let summaries = split_events(&log)
+            .iter()
+            .map(|c| EventSummary::new(c))
+            .collect::<Vec<_>>();
+        let rollup = Rollup::new(&summaries, 2023);
+        assert_eq!(rollup.mean_seconds_between_kills, Some(7200.0));
+    }
+}
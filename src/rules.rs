@@ -0,0 +1,152 @@
+use crate::{OomEvent, Warning, PAGE_SIZE};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// The unit a custom rule's captured number is in. Memory is converted to KiB; counts are kept
+// as they are. The kernel's kB and MB are binary, so kb is the same as kib.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Kb,
+    Kib,
+    Mb,
+    Pages,
+    Bytes,
+    #[default]
+    Count,
+}
+
+impl Unit {
+    // Convert a value in this unit to KiB, or leave a count alone
+    fn convert(self, value: f64) -> (f64, Unit) {
+        match self {
+            Unit::Kb | Unit::Kib => (value, Unit::Kib),
+            Unit::Mb => (value * 1024.0, Unit::Kib),
+            Unit::Pages => (value * PAGE_SIZE as f64 / 1024.0, Unit::Kib),
+            Unit::Bytes => (value / 1024.0, Unit::Kib),
+            Unit::Count => (value, Unit::Count),
+        }
+    }
+}
+
+// A rule as written in the rules file
+#[derive(Deserialize)]
+struct RuleConfig {
+    name: String,
+    pattern: String,
+    #[serde(default)]
+    unit: Unit,
+}
+
+// A site-specific value to pick out of the report, such as a vendor kernel's extra counters.
+// The pattern's first capture group is the number; when it matches more than once, such as once
+// per node, the matches are summed.
+pub struct Rule {
+    pub name: String,
+    pub re: Regex,
+    pub unit: Unit,
+}
+
+impl Rule {
+    pub fn new(name: &str, pattern: &str, unit: Unit) -> Result<Rule, Box<dyn Error>> {
+        let re = Regex::new(pattern).map_err(|e| format!("rule {}: {}", name, e))?;
+        if re.captures_len() < 2 {
+            return Err(format!("rule {}: pattern has no capture group", name).into());
+        }
+
+        Ok(Rule {
+            name: name.to_string(),
+            re,
+            unit,
+        })
+    }
+
+    // Read rules from a JSON file, ie:
+    //
+    //      [{"name": "vendor_reserved", "pattern": "vendor_reserved:(\\d+)", "unit": "pages"}]
+    //
+    pub fn load(path: &Path) -> Result<Vec<Rule>, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let configs: Vec<RuleConfig> =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        configs
+            .iter()
+            .map(|c| Rule::new(&c.name, &c.pattern, c.unit))
+            .collect()
+    }
+
+    // Sum the rule's matches in a cleaned report, None if it doesn't match
+    pub fn extract(&self, cleaned: &str) -> Option<CustomValue> {
+        let values = self
+            .re
+            .captures_iter(cleaned)
+            .filter_map(|caps| caps.get(1)?.as_str().parse::<f64>().ok())
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        let (value, unit) = self.unit.convert(values.iter().sum());
+
+        Some(CustomValue {
+            name: self.name.clone(),
+            value,
+            unit,
+        })
+    }
+}
+
+// A value extracted by a custom rule, in KiB or as a count
+#[derive(Serialize, JsonSchema)]
+pub struct CustomValue {
+    pub name: String,
+    pub value: f64,
+    pub unit: Unit,
+}
+
+impl OomEvent {
+    // Extract the custom values, warning about rules that matched nothing so that a typo in a
+    // pattern doesn't go unnoticed
+    pub fn apply_rules(&mut self, rules: &[Rule]) {
+        for rule in rules {
+            match rule.extract(&self.cleaned) {
+                Some(value) => self.custom.push(value),
+                None => self.warnings.push(Warning::problem(
+                    "custom",
+                    format!("rule {} matched nothing", rule.name),
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_custom_rules() {
+        let mut event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let rules = [
+            Rule::new("kernel_stack", r"kernel_stack:(\d+)kB", Unit::Kb).unwrap(),
+            Rule::new("oom_reaper", r"oom_reaper:(\d+)", Unit::Count).unwrap(),
+        ];
+        event.apply_rules(&rules);
+
+        assert_eq!(event.custom.len(), 1);
+        assert_eq!(event.custom[0].unit, Unit::Kib);
+        assert_eq!(event.custom[0].value, 2192.0);
+        assert!(event
+            .warnings
+            .iter()
+            .any(|w| w.message == "rule oom_reaper matched nothing"));
+
+        assert!(Rule::new("x", r"\d+", Unit::Count).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use super::SectionParser;
+use crate::OomEvent;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::error::Error;
+
+// Usage and limit of the memory cgroup that ran out of memory, in KiB
+#[derive(Serialize, JsonSchema)]
+pub struct MemcgUsage {
+    pub usage_kib: f64,
+    pub limit_kib: f64,
+    pub failcnt: u64,
+}
+
+// Find the memory cgroup's usage and limit, logged when a cgroup limit invoked the oom-killer:
+//
+//      memory: usage 2097152kB, limit 2097152kB, failcnt 1931
+//
+pub fn parse_memcg_usage(s: &str) -> Option<MemcgUsage> {
+    const MEMCG_USAGE_RE: &str = r"memory: usage (\d+)kB, limit (\d+)kB, failcnt (\d+)";
+
+    let caps = Regex::new(MEMCG_USAGE_RE).unwrap().captures(s)?;
+
+    Some(MemcgUsage {
+        usage_kib: caps[1].parse().ok()?,
+        limit_kib: caps[2].parse().ok()?,
+        failcnt: caps[3].parse().ok()?,
+    })
+}
+
+// The memory cgroup counters
+pub struct Memcg;
+
+impl SectionParser for Memcg {
+    fn name(&self) -> &'static str {
+        "memcg"
+    }
+
+    fn trigger(&self) -> &'static str {
+        r"memory: usage \d+kB"
+    }
+
+    fn claims(&self) -> &'static [&'static str] {
+        &[
+            r"^memory: usage",
+            r"^memory\+swap: usage",
+            r"^(?:kmem|kmem\.tcp|swap): usage",
+            r"^Memory cgroup stats for",
+            r"^Task in \S+ killed as a result of limit of",
+            r"^[a-z_]+ \d+$",
+        ]
+    }
+
+    fn parse(&self, _oom: &str, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        event.memcg = parse_memcg_usage(&event.cleaned);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_memcg_usage() {
+        let usage = parse_memcg_usage(
+            "memory: usage 2097152kB, limit 2097152kB, failcnt 1931\n\
+             memory+swap: usage 2097152kB, limit 9007199254740988kB, failcnt 0",
+        )
+        .unwrap();
+        assert_eq!(usage.limit_kib, 2097152.0);
+        assert_eq!(usage.failcnt, 1931);
+        assert!(parse_memcg_usage("memory: usage 1kB").is_none());
+    }
+}
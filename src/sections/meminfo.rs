@@ -0,0 +1,133 @@
+use super::{parse_memcg_usage, SectionParser};
+use crate::{
+    hugepages_free_kib, hugepages_kib, pages_kib, parse_compressed_swap, parse_hypervisor,
+    parse_kernel_32bit, parse_meminfo_cma_pages, parse_meminfo_free_pages,
+    parse_meminfo_hugepages_count, parse_meminfo_hugepages_free_count,
+    parse_meminfo_isolated_pages, parse_meminfo_shared_pages, parse_meminfo_swap_kb,
+    parse_meminfo_total_pages, parse_meminfo_total_swap_kb, parse_pcp, parse_thp_kb, OomEvent, Thp,
+    Warning, CMA_WARNING_RATIO, PCP_WARNING_RATIO,
+};
+use std::error::Error;
+
+// The Mem-Info block: memory totals, swap, huge pages and the per-node counters
+pub struct Meminfo;
+
+impl SectionParser for Meminfo {
+    fn name(&self) -> &'static str {
+        "meminfo"
+    }
+
+    fn trigger(&self) -> &'static str {
+        r"\d+ pages RAM"
+    }
+
+    fn claims(&self) -> &'static [&'static str] {
+        &[
+            r"^Mem-Info:",
+            r"^\s*[a-z_]+:\d+(?:\s+[a-z_]+:\d+)*\s*$",
+            r"^Node \d+ active_anon:",
+            r"^Node \d+ hugepages_total=",
+            r"^\d+ total pagecache pages",
+            r"^\d+ pages (?:in swap cache|RAM|HighMem/MovableOnly|reserved|hwpoisoned|cma reserved)",
+            r"^Swap cache stats:",
+            r"^(?:Free|Total) swap\s*=",
+        ]
+    }
+
+    fn parse(&self, _oom: &str, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        let cleaned = &event.cleaned;
+        let pages = &mut event.pages;
+
+        // The counts are kept as the kernel printed them for --units pages, and converted to KiB
+        let (ram, reserved) =
+            parse_meminfo_total_pages(cleaned).ok_or("No match for total pages RAM.")?;
+        (pages.ram, pages.reserved) = (Some(ram), Some(reserved));
+        event.total_ram_kib = pages_kib(ram) - pages_kib(reserved);
+
+        let free_swap = parse_meminfo_swap_kb(cleaned).ok_or("No match for swap.")?;
+        pages.free_swap_kb = Some(free_swap);
+        event.free_swap_kib = free_swap as f64;
+        pages.total_swap_kb = parse_meminfo_total_swap_kb(cleaned);
+        event.total_swap_kib = match pages.total_swap_kb {
+            Some(kb) => kb as f64,
+            None => {
+                event.warnings.push(Warning::problem(
+                    "meminfo",
+                    "Total swap not found, assuming no swap".to_string(),
+                ));
+                0.0
+            }
+        };
+
+        let hugepages = parse_meminfo_hugepages_count(cleaned).ok_or("No match for huge pages.")?;
+        (pages.hugepages_2mb, pages.hugepages_1gb) = hugepages;
+        (event.hugepages_2mb_kib, event.hugepages_1gb_kib) = hugepages_kib(hugepages);
+        pages.hugepages_free = parse_meminfo_hugepages_free_count(cleaned).unwrap_or_default();
+        event.hugepages_free_kib = hugepages_free_kib(&pages.hugepages_free);
+        pages.shmem = Some(parse_meminfo_shared_pages(cleaned).ok_or("No match for shmem")?);
+        event.shmem_kib = pages.shmem.map_or(0.0, pages_kib);
+
+        // Trimmed reports may be missing these, and they're zero on most machines anyway
+        let isolated = parse_meminfo_isolated_pages(cleaned);
+        (pages.isolated_anon, pages.isolated_file) = (isolated.map(|i| i.0), isolated.map(|i| i.1));
+        event.isolated_anon_kib = pages.isolated_anon.map_or(0.0, pages_kib);
+        event.isolated_file_kib = pages.isolated_file.map_or(0.0, pages_kib);
+        pages.free_cma = parse_meminfo_cma_pages(cleaned);
+        event.free_cma_kib = pages.free_cma.map_or(0.0, pages_kib);
+        pages.free = parse_meminfo_free_pages(cleaned);
+        event.free_kib = pages.free.map(pages_kib);
+        event.pcp = parse_pcp(cleaned);
+
+        // On machines with hundreds of CPUs, the per-cpu lists can hold a lot of memory that the
+        // failing allocation's CPU couldn't take
+        let pcp_fraction = event
+            .pcp
+            .as_ref()
+            .map_or(0.0, |p| event.ram_fraction(p.free_kib));
+        if pcp_fraction >= PCP_WARNING_RATIO {
+            event.warnings.push(Warning::new(
+                "meminfo",
+                format!(
+                    "{:.1} MiB ({:.1}% of RAM) is free in the per-cpu page lists, where only the \
+                     CPU holding it can allocate it",
+                    pcp_fraction * event.total_ram_kib / 1024.0,
+                    pcp_fraction * 100.0
+                ),
+            ));
+        }
+
+        // Free CMA pages can only be used for movable allocations, so memory held there doesn't
+        // help a kernel or pinned allocation that is failing
+        if event.cma_fraction() >= CMA_WARNING_RATIO {
+            event.warnings.push(Warning::new(
+                "meminfo",
+                format!(
+                    "{:.1} MiB ({:.1}% of RAM) is free but reserved for CMA",
+                    event.free_cma_kib / 1024.0,
+                    event.cma_fraction() * 100.0
+                ),
+            ));
+        }
+
+        event.compressed_swap = parse_compressed_swap(cleaned);
+        let thp = parse_thp_kb(cleaned);
+        (
+            event.pages.thp_anon_kb,
+            event.pages.thp_shmem_kb,
+            event.pages.thp_file_kb,
+        ) = (thp.map(|t| t.0), thp.map(|t| t.1), thp.map(|t| t.2));
+        event.thp = thp.map(Thp::from_kb);
+        event.virtualization.hypervisor = parse_hypervisor(cleaned);
+        event.kernel_32bit = parse_kernel_32bit(cleaned);
+
+        Ok(())
+    }
+
+    // A memory cgroup's report has no Mem-Info, only the cgroup's usage and limit
+    fn missing(&self, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        match parse_memcg_usage(&event.cleaned) {
+            Some(_) => Ok(()),
+            None => Err("No match for total pages RAM.".into()),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::OomEvent;
+use std::error::Error;
+
+mod memcg;
+mod meminfo;
+mod slab;
+mod tasks;
+mod victim;
+mod zones;
+
+pub use memcg::{parse_memcg_usage, MemcgUsage};
+
+// One part of an oom-killer report. To support a new section, add a module implementing this
+// trait and list it in registry().
+pub trait SectionParser {
+    // Name of the section, as used in warnings
+    fn name(&self) -> &'static str;
+
+    // Regular expression matching the report when it contains this section
+    fn trigger(&self) -> &'static str;
+
+    // Fill in the event from a report containing this section. The cleaned report is in
+    // event.cleaned; oom is the report as it was logged.
+    fn parse(&self, oom: &str, event: &mut OomEvent) -> Result<(), Box<dyn Error>>;
+
+    // Regular expressions matching each kind of line of the report this section reads, once
+    // the log's prefix is taken off. Lines no section claims are what --fail-on-unknown-section
+    // lists, so a new kernel's output shows up as soon as it's seen.
+    fn claims(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    // Called instead of parse() when the report doesn't contain this section. Sections the
+    // report can't be understood without return an error.
+    fn missing(&self, _event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+// Every section parser, in the order they run. Later sections may rely on values filled in by
+// earlier ones, ie. the zones section needs the gfp_mask from meminfo's header.
+pub fn registry() -> Vec<Box<dyn SectionParser>> {
+    vec![
+        Box::new(meminfo::Meminfo),
+        Box::new(slab::Slab),
+        Box::new(zones::Zones),
+        Box::new(tasks::Tasks),
+        Box::new(victim::Victim),
+        Box::new(memcg::Memcg),
+    ]
+}
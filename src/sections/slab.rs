@@ -0,0 +1,61 @@
+use super::{parse_memcg_usage, SectionParser};
+use crate::{
+    pages_kib, parse_meminfo_reclaimable_slab_pages, parse_meminfo_slab_pages,
+    parse_unreclaimable_slab_rows, OomEvent, SkippedLine,
+};
+use std::error::Error;
+
+// Slab usage from the Mem-Info counters. The table of the largest unreclaimable caches is
+// optional and only parsed when rendering the report, but its unreadable rows are noted here.
+pub struct Slab;
+
+impl SectionParser for Slab {
+    fn name(&self) -> &'static str {
+        "slab"
+    }
+
+    fn trigger(&self) -> &'static str {
+        r"slab_unreclaimable:\d+"
+    }
+
+    fn claims(&self) -> &'static [&'static str] {
+        &[
+            r"^Unreclaimable slab info:",
+            r"^Name\s+Used\s+Total",
+            r"^\S+\s+\d+KB\s+\d+KB\s*$",
+        ]
+    }
+
+    fn parse(&self, _oom: &str, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        let cleaned = &event.cleaned;
+
+        let unreclaimable = parse_meminfo_slab_pages(cleaned).ok_or("No match for slab.")?;
+        let reclaimable = parse_meminfo_reclaimable_slab_pages(cleaned)
+            .ok_or("No match for reclaimable slab.")?;
+        event.pages.slab_unreclaimable = Some(unreclaimable);
+        event.pages.slab_reclaimable = Some(reclaimable);
+        event.unreclaimable_slab_kib = pages_kib(unreclaimable);
+        event.reclaimable_slab_kib = pages_kib(reclaimable);
+
+        if let Some((_, _, skipped)) = parse_unreclaimable_slab_rows(cleaned) {
+            for row in skipped {
+                event.skipped.push(SkippedLine {
+                    section: "slab",
+                    line: event.cleaned_lines.get(row.line).map_or(0, |l| l + 1),
+                    text: row.text,
+                    reason: row.reason,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Nor has a memory cgroup's report the Mem-Info counters
+    fn missing(&self, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        match parse_memcg_usage(&event.cleaned) {
+            Some(_) => Ok(()),
+            None => Err("No match for slab.".into()),
+        }
+    }
+}
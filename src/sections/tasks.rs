@@ -0,0 +1,105 @@
+use super::SectionParser;
+use crate::{
+    parse_extra_columns, parse_processes, parse_ps_header, parse_ps_list, parse_ps_rows,
+    parse_task_unit, task_counts, OomEvent, SkippedLine, Warning, FORK_BOMB_TASKS, PAGE_SIZE,
+};
+use std::error::Error;
+
+// The process list: "Tasks state (memory values in pages):" and the table after it
+pub struct Tasks;
+
+impl SectionParser for Tasks {
+    fn name(&self) -> &'static str {
+        "processes"
+    }
+
+    fn trigger(&self) -> &'static str {
+        r"pid.+\bname\b"
+    }
+
+    fn claims(&self) -> &'static [&'static str] {
+        &[
+            r"^Tasks state \(memory values in",
+            r"^\[\s*pid\s*\]",
+            r"^\[?\s*\d+\]?(?:\s+-?\d+){3,}\s+\S",
+        ]
+    }
+
+    fn parse(&self, _oom: &str, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        let cleaned = &event.cleaned;
+
+        let (ps_header, pid_col) = match parse_ps_header(cleaned) {
+            Some(header) => header,
+            None => return self.missing(event),
+        };
+        let ps_string = parse_ps_list(cleaned).ok_or("Failed to parse process list")?;
+        let extra = parse_extra_columns(&ps_header[pid_col..]);
+        let (processes, skipped) = parse_processes(ps_string, pid_col, &extra);
+
+        // The process list is a slice of the cleaned text, so its first line can be found from
+        // where it starts
+        let ps_offset = ps_string.as_ptr() as usize - cleaned.as_ptr() as usize;
+        let ps_line = cleaned[..ps_offset].matches('\n').count();
+        for row in skipped {
+            let line = event
+                .cleaned_lines
+                .get(ps_line + row.line)
+                .map_or(0, |l| l + 1);
+            event.warnings.push(Warning::skipped(
+                "processes",
+                format!("skipped process list row: {}", row.reason),
+                line,
+                &row.text,
+            ));
+            event.skipped.push(SkippedLine {
+                section: "processes",
+                line,
+                text: row.text,
+                reason: row.reason,
+            });
+        }
+
+        // Older kernels print no note of the unit, which was always pages
+        let warnings = &mut event.warnings;
+        event.task_unit = parse_task_unit(cleaned).unwrap_or_else(|| {
+            warnings.push(Warning::new(
+                "processes",
+                "process list memory unit not stated, assuming pages".to_string(),
+            ));
+            PAGE_SIZE
+        });
+
+        // The process list is often the only evidence of a fork bomb left after the kill
+        if let Some((command, n)) = task_counts(&processes).first() {
+            if *n >= FORK_BOMB_TASKS {
+                event.warnings.push(Warning::new(
+                    "processes",
+                    format!("{} tasks of {}, possible fork bomb", n, command),
+                ));
+            }
+        }
+
+        // Keep the header from the pid column on, extra columns and all
+        event.ps_header = ps_header[pid_col..].to_vec();
+        event.processes = processes;
+
+        Ok(())
+    }
+
+    // Vendor kernels may leave out or localize the header, but rows of numbers ending in a name
+    // are still the process list
+    fn missing(&self, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        let (ps_header, processes) =
+            parse_ps_rows(&event.cleaned).ok_or("Could not find PID column")?;
+
+        event.warnings.push(Warning::problem(
+            "processes",
+            "no process list header, columns were found from the shape of the rows".to_string(),
+        ));
+        event.task_unit = parse_task_unit(&event.cleaned).unwrap_or(PAGE_SIZE);
+        event.ps_header = ps_header;
+        event.processes = processes;
+
+        Ok(())
+    }
+}
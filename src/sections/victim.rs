@@ -0,0 +1,43 @@
+use super::SectionParser;
+use crate::{parse_victim_memcg, parse_victim_pid, OomEvent, Warning};
+use std::error::Error;
+
+// The oom-kill summary line naming the task that was killed, logged since v4.19, or before it
+// the "Out of memory: Kill process" line
+pub struct Victim;
+
+impl SectionParser for Victim {
+    fn name(&self) -> &'static str {
+        "victim"
+    }
+
+    fn trigger(&self) -> &'static str {
+        r"oom-kill:.*\btask=|Kill(?:ed)? process \d+"
+    }
+
+    fn claims(&self) -> &'static [&'static str] {
+        &[
+            r"^oom-kill:",
+            r"(?i)out of memory",
+            r"Killed process \d+",
+            r"^oom_reaper: reaped process",
+            r"^Tasks in \S+ are going to be killed",
+        ]
+    }
+
+    fn parse(&self, oom: &str, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        event.victim_pid = parse_victim_pid(oom);
+        event.victim_memcg = parse_victim_memcg(oom);
+
+        Ok(())
+    }
+
+    fn missing(&self, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        event.warnings.push(Warning::problem(
+            "victim",
+            "no oom-kill summary or killed process line, the killed process is unknown".to_string(),
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use super::{parse_memcg_usage, SectionParser};
+use crate::{
+    format_node_list, lowmem_exhausted, lowmem_only, online_nodes, parse_alloc_order,
+    parse_gfp_mask, parse_gfp_zone, parse_highmem, parse_kernel_32bit, parse_mems_allowed,
+    parse_zones, ram_exhausted, OomEvent, Warning,
+};
+use std::error::Error;
+
+// The per-zone free memory and watermark lines
+pub struct Zones;
+
+impl SectionParser for Zones {
+    fn name(&self) -> &'static str {
+        "zones"
+    }
+
+    fn trigger(&self) -> &'static str {
+        r"\w+ free:\d+kB (?:boost:\d+kB )?min:"
+    }
+
+    fn claims(&self) -> &'static [&'static str] {
+        &[
+            r"^(?:Node \d+ )?\w+ free:\d+kB",
+            r"^lowmem_reserve\[\]:",
+            r"^(?:Node \d+ )?\w+: (?:\d+\*\d+kB|\d+ kB)",
+            r"^(?:Node \d+ )?\w+ per-cpu:",
+            r"^CPU\s+\d+: hi:",
+            // A zone line a syslog daemon split in two
+            r"^\d+kB(?: [a-z_()]+:\d+kB)+",
+        ]
+    }
+
+    fn parse(&self, oom: &str, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        event.zones = parse_zones(&event.cleaned);
+        event.gfp_zone = parse_gfp_zone(&event.cleaned);
+        event.alloc_order = parse_alloc_order(&event.cleaned);
+        event.highmem = parse_highmem(&event.cleaned, &event.zones, event.total_ram_kib);
+
+        // On a 32-bit kernel, kernel allocations can't use HighMem
+        if event.highmem.is_some() && parse_gfp_mask(&event.cleaned).is_some_and(lowmem_only) {
+            event.gfp_zone = Some("Normal");
+        }
+        let kernel_32bit = parse_kernel_32bit(&event.cleaned);
+        event.lowmem_exhausted = event
+            .gfp_zone
+            .is_some_and(|z| lowmem_exhausted(&event.zones, z, kernel_32bit));
+        event.mems_allowed = parse_mems_allowed(oom);
+        event.ram_exhausted = event
+            .free_kib
+            .map(|free| ram_exhausted(free, event.total_ram_kib, &event.zones));
+
+        // A cpuset or NUMA binding can run a task out of memory while other nodes have plenty
+        let online = online_nodes(&event.zones);
+        if let Some(allowed) = &event.mems_allowed {
+            if online.iter().any(|node| !allowed.contains(node)) {
+                event.warnings.push(Warning::new(
+                    "zones",
+                    format!(
+                        "the task was restricted to node {} of online nodes {} by its cpuset or \
+                         NUMA policy",
+                        format_node_list(allowed),
+                        format_node_list(&online)
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn missing(&self, event: &mut OomEvent) -> Result<(), Box<dyn Error>> {
+        event.ram_exhausted = event
+            .free_kib
+            .map(|free| ram_exhausted(free, event.total_ram_kib, &[]));
+        // A memory cgroup hit its limit, the zones weren't short and aren't in the report
+        if parse_memcg_usage(&event.cleaned).is_none() {
+            event.warnings.push(Warning::problem(
+                "zones",
+                "no per-zone free memory lines, watermarks can't be checked".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
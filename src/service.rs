@@ -0,0 +1,271 @@
+use std::path::{Path, PathBuf};
+
+// A systemd service, and timer when parseoom runs periodically, that runs parseoom with the
+// given arguments. Without an interval the service runs for as long as the machine does, which
+// only makes sense with --follow; with one, a timer starts a one-shot scan at that interval.
+pub struct ServiceUnits {
+    pub name: String,
+    pub exec: String,
+    pub args: Vec<String>,
+    pub every: Option<String>,
+}
+
+// Quote an argument for ExecStart. systemd splits the line on whitespace and expands % and $,
+// so arguments that need it are double quoted and the expansions escaped.
+fn quote_arg(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+
+    if escaped.is_empty() || escaped.contains(|c: char| c.is_whitespace() || c == '\'') {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+// The path in an argument that gives --output its value too, as --output=PATH, -o=PATH or -oPATH
+// do, split into the option and the path
+fn output_value(arg: &str) -> Option<(&str, &str)> {
+    ["--output=", "-o=", "-o"].iter().find_map(|option| {
+        arg.strip_prefix(option)
+            .filter(|path| !path.is_empty())
+            .map(|path| (&arg[..option.len()], path))
+    })
+}
+
+impl ServiceUnits {
+    // The units for running parseoom as the program at exec. With no arguments, a service
+    // follows the journal and keeps its cursor in the service's state directory, and a timer
+    // reads the whole journal. A service following the journal without --state gets one.
+    pub fn new(name: &str, exec: &Path, args: &[String], every: Option<&str>) -> ServiceUnits {
+        let state = format!("/var/lib/{}/cursor", name);
+        let mut args = args.to_vec();
+
+        if args.is_empty() {
+            args.push("--journald".to_string());
+            if every.is_none() {
+                args.push("--follow".to_string());
+            }
+        }
+        let has = |flag: &str| args.iter().any(|a| a == flag);
+        if every.is_none() && has("--journald") && has("--follow") && !has("--state") {
+            args.push("--state".to_string());
+            args.push(state);
+        }
+
+        ServiceUnits {
+            name: name.to_string(),
+            exec: exec.display().to_string(),
+            args,
+            every: every.map(str::to_string),
+        }
+    }
+
+    // Make the arguments that are relative paths among `paths` absolute, as they are from `dir`.
+    // systemd runs the service in /, so a log named relative to where install-service was run
+    // would be looked for in the wrong place. URLs and - for stdin are left as they are.
+    pub fn absolute_paths(&mut self, paths: &[&Path], dir: &Path) {
+        for arg in self.args.iter_mut() {
+            let (option, value) = output_value(arg).unwrap_or(("", arg));
+            let path = PathBuf::from(value);
+            if value == "-" || value.contains("://") || !paths.contains(&path.as_path()) {
+                continue;
+            }
+            if path.is_relative() {
+                *arg = format!("{}{}", option, dir.join(path).display());
+            }
+        }
+    }
+
+    // The hardening settings: parseoom only reads logs and writes to its state directory, so it
+    // gets no privileges and a read-only view of the system. The journal is readable through the
+    // systemd-journal group, and the network is only reachable when a log is read from a URL. Reading the kernel log from /dev/kmsg takes CAP_SYSLOG and the real /dev.
+    fn hardening(&self) -> Vec<String> {
+        let has = |s: &str| self.args.iter().any(|a| a.contains(s));
+        let mut families = "AF_UNIX".to_string();
+        if has("://") {
+            families.push_str(" AF_INET AF_INET6");
+        }
+
+        let mut settings = vec![
+            "DynamicUser=yes".to_string(),
+            "SupplementaryGroups=systemd-journal adm".to_string(),
+            format!("StateDirectory={}", self.name),
+        ];
+        if has("--kmsg") {
+            settings.push("CapabilityBoundingSet=CAP_SYSLOG".to_string());
+            settings.push("AmbientCapabilities=CAP_SYSLOG".to_string());
+        } else {
+            settings.push("CapabilityBoundingSet=".to_string());
+        }
+        settings.extend([
+            "NoNewPrivileges=yes".to_string(),
+            "ProtectSystem=strict".to_string(),
+            "ProtectHome=read-only".to_string(),
+            "PrivateTmp=yes".to_string(),
+        ]);
+        if !has("--kmsg") {
+            settings.push("PrivateDevices=yes".to_string());
+        }
+        settings.extend([
+            "ProtectKernelTunables=yes".to_string(),
+            "ProtectKernelModules=yes".to_string(),
+            "ProtectControlGroups=yes".to_string(),
+            "ProtectClock=yes".to_string(),
+            "ProtectHostname=yes".to_string(),
+            format!("RestrictAddressFamilies={}", families),
+            "RestrictNamespaces=yes".to_string(),
+            "RestrictRealtime=yes".to_string(),
+            "RestrictSUIDSGID=yes".to_string(),
+            "LockPersonality=yes".to_string(),
+            "MemoryDenyWriteExecute=yes".to_string(),
+            "SystemCallArchitectures=native".to_string(),
+            "SystemCallFilter=@system-service".to_string(),
+        ]);
+        // dmesg needs the kernel log
+        if !has("--kmsg") {
+            settings.push("ProtectKernelLogs=yes".to_string());
+        }
+        // Reports written with --output are the one place outside the state directory
+        let output = self
+            .args
+            .iter()
+            .position(|a| a == "-o" || a == "--output")
+            .and_then(|i| self.args.get(i + 1))
+            .map(String::as_str)
+            .or_else(|| {
+                self.args
+                    .iter()
+                    .find_map(|a| output_value(a))
+                    .map(|(_, path)| path)
+            });
+        if let Some(dir) = output.and_then(|o| Path::new(o).parent()) {
+            if !dir.as_os_str().is_empty() {
+                settings.push(format!(
+                    "ReadWritePaths={}",
+                    quote_arg(&dir.to_string_lossy())
+                ));
+            }
+        }
+
+        settings
+    }
+
+    // The unit files to install, each a file name and its contents
+    pub fn files(&self) -> Vec<(String, String)> {
+        let exec_start = std::iter::once(quote_arg(&self.exec))
+            .chain(self.args.iter().map(|a| quote_arg(a)))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut service = String::new();
+        service.push_str("[Unit]\n");
+        service.push_str("Description=Report oom-killer events with parseoom\n");
+        service.push_str("Documentation=man:parseoom(1)\n");
+        service.push_str("After=systemd-journald.service\n\n");
+        service.push_str("[Service]\n");
+        match self.every {
+            Some(_) => service.push_str("Type=oneshot\n"),
+            None => service.push_str("Type=simple\nRestart=on-failure\nRestartSec=5s\n"),
+        }
+        service.push_str(&format!("ExecStart={}\n", exec_start));
+        // The reporter should be the last thing the oom-killer picks
+        service.push_str("OOMScoreAdjust=-900\n");
+        for setting in self.hardening() {
+            service.push_str(&setting);
+            service.push('\n');
+        }
+
+        let every = match &self.every {
+            Some(every) => every,
+            None => {
+                service.push_str("\n[Install]\nWantedBy=multi-user.target\n");
+                return vec![(format!("{}.service", self.name), service)];
+            }
+        };
+
+        let mut timer = String::new();
+        timer.push_str("[Unit]\n");
+        timer.push_str("Description=Scan for oom-killer events with parseoom periodically\n");
+        timer.push_str("Documentation=man:parseoom(1)\n\n");
+        timer.push_str("[Timer]\n");
+        timer.push_str(&format!("OnBootSec={}\n", every));
+        timer.push_str(&format!("OnUnitActiveSec={}\n", every));
+        timer.push_str("\n[Install]\nWantedBy=timers.target\n");
+
+        vec![
+            (format!("{}.service", self.name), service),
+            (format!("{}.timer", self.name), timer),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_service_units() {
+        let exec = Path::new("/usr/local/bin/parseoom");
+        let files = ServiceUnits::new("parseoom", exec, &[], None).files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "parseoom.service");
+        assert!(files[0].1.contains(
+            "ExecStart=/usr/local/bin/parseoom --journald --follow --state \
+             /var/lib/parseoom/cursor\n"
+        ));
+        assert!(files[0].1.contains("Restart=on-failure\n"));
+        assert!(files[0].1.contains("RestrictAddressFamilies=AF_UNIX\n"));
+        assert!(files[0].1.contains("ProtectKernelLogs=yes\n"));
+
+        let args = [
+            "--all",
+            "-o",
+            "/srv/oom/last.txt",
+            "/var/log/my logs/messages",
+        ]
+        .map(String::from);
+        let files = ServiceUnits::new("oom-scan", exec, &args, Some("1h")).files();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].1.contains("Type=oneshot\n"));
+        assert!(files[0]
+            .1
+            .contains(" --all -o /srv/oom/last.txt \"/var/log/my logs/messages\"\n"));
+        assert!(files[0].1.contains("ReadWritePaths=/srv/oom\n"));
+        assert_eq!(files[1].0, "oom-scan.timer");
+        assert!(files[1].1.contains("OnUnitActiveSec=1h\n"));
+
+        // dmesg needs CAP_SYSLOG and /dev/kmsg, and a relative log is found from /
+        let args = ["--kmsg", "--follow"].map(String::from);
+        let files = ServiceUnits::new("parseoom", exec, &args, None).files();
+        assert!(files[0].1.contains("CapabilityBoundingSet=CAP_SYSLOG\n"));
+        assert!(!files[0].1.contains("PrivateDevices="));
+        assert!(!files[0].1.contains("ProtectKernelLogs="));
+        let args = ["--follow", "logs/messages", "-o", "-"].map(String::from);
+        let mut units = ServiceUnits::new("parseoom", exec, &args, None);
+        units.absolute_paths(&[Path::new("logs/messages")], Path::new("/home/ewk"));
+        assert_eq!(
+            units.args,
+            ["--follow", "/home/ewk/logs/messages", "-o", "-"]
+        );
+
+        // --output=PATH and -oPATH give the report's directory too
+        for output in ["--output=out/last.txt", "-o=out/last.txt", "-oout/last.txt"] {
+            let args = ["--all", output, "messages"].map(String::from);
+            let mut units = ServiceUnits::new("oom-scan", exec, &args, Some("1h"));
+            units.absolute_paths(
+                &[Path::new("messages"), Path::new("out/last.txt")],
+                Path::new("/srv"),
+            );
+            let option = &output[..output.len() - "out/last.txt".len()];
+            assert_eq!(units.args[1], format!("{}/srv/out/last.txt", option));
+            assert!(units.files()[0].1.contains("ReadWritePaths=/srv/out\n"));
+        }
+
+        assert_eq!(quote_arg("50%"), "50%%");
+        assert_eq!(quote_arg(""), "\"\"");
+    }
+}
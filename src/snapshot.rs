@@ -0,0 +1,95 @@
+use crate::OomEvent;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// A value has changed dramatically when it grew or shrank by at least this factor...
+const CHANGE_RATIO: f64 = 2.0;
+
+// ...and by at least this much, so that small values moving around aren't flagged
+const CHANGE_MIN_KIB: f64 = 64.0 * 1024.0;
+
+// Parse /proc/meminfo, as saved by sosreport or a monitoring job, into KiB by field name, ie:
+//
+//      MemTotal:       16326972 kB
+//      HugePages_Total:       0
+//
+// Fields without a kB unit, such as the huge page counts, are skipped.
+pub fn parse_proc_meminfo(s: &str) -> BTreeMap<String, f64> {
+    s.lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let value = value
+                .trim()
+                .strip_suffix("kB")?
+                .trim()
+                .parse::<f64>()
+                .ok()?;
+            Some((name.trim().to_string(), value))
+        })
+        .collect()
+}
+
+// One value from the oom-killer report next to the same value in the snapshot, in KiB
+#[derive(Serialize)]
+pub struct MeminfoChange {
+    pub name: &'static str,
+    pub snapshot_kib: f64,
+    pub oom_kib: f64,
+    pub dramatic: bool,
+}
+
+// Line up the values the oom-killer report shares with /proc/meminfo, to show what changed
+// between the snapshot and the kill. Values missing from the snapshot are left out.
+pub fn compare_meminfo(meminfo: &BTreeMap<String, f64>, event: &OomEvent) -> Vec<MeminfoChange> {
+    let thp_kib = event.thp.as_ref().map(|thp| thp.anon_kib);
+    let values = [
+        ("MemTotal", Some(event.total_ram_kib)),
+        ("SwapTotal", Some(event.total_swap_kib)),
+        ("SwapFree", Some(event.free_swap_kib)),
+        ("SUnreclaim", Some(event.unreclaimable_slab_kib)),
+        ("SReclaimable", Some(event.reclaimable_slab_kib)),
+        ("Shmem", Some(event.shmem_kib)),
+        ("Hugetlb", Some(event.hugepages_kib())),
+        ("AnonHugePages", thp_kib),
+    ];
+
+    values
+        .iter()
+        .filter_map(|&(name, oom_kib)| {
+            let oom_kib = oom_kib?;
+            let snapshot_kib = *meminfo.get(name)?;
+            let (low, high) = (snapshot_kib.min(oom_kib), snapshot_kib.max(oom_kib));
+
+            Some(MeminfoChange {
+                name,
+                snapshot_kib,
+                oom_kib,
+                dramatic: high - low >= CHANGE_MIN_KIB && high >= low * CHANGE_RATIO,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_meminfo_snapshot() {
+        let meminfo = parse_proc_meminfo(
+            "MemTotal:       20858880 kB\n\
+             Shmem:            102400 kB\n\
+             SUnreclaim:         1024 kB\n\
+             HugePages_Total:       0\n",
+        );
+        assert_eq!(meminfo.len(), 3);
+        assert_eq!(meminfo["Shmem"], 102400.0);
+
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let changes = compare_meminfo(&meminfo, &event);
+        let names = changes.iter().map(|c| c.name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["MemTotal", "SUnreclaim", "Shmem"]);
+        assert!(!changes[0].dramatic);
+        assert!(changes[2].dramatic);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{report_start_line, OomEvent};
+
+impl OomEvent {
+    // The problems --strict fails on, each a message and the line of the log it's about: every
+    // warning about part of the report the parser skipped or guessed at. Warnings without a
+    // line of their own, such as a missing section, give the first line of the report.
+    pub fn strict_errors(&self, log: &str) -> Vec<(usize, String)> {
+        let first = report_start_line(log);
+
+        self.warnings
+            .iter()
+            .filter(|w| w.problem)
+            .map(|w| {
+                let message = format!("{}: {}", w.section, w.message);
+                (w.line.unwrap_or(first), message)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_strict_errors() {
+        let log = include_str!("../tests/fixtures/ubuntu-22.04-5.15.log");
+        let event = OomEvent::parse(log).unwrap();
+        assert!(event.strict_errors(log).is_empty());
+
+        let line = log
+            .lines()
+            .position(|l| l.contains(" systemd-journal"))
+            .unwrap();
+        let damaged = log.replacen(" systemd-journal", "o systemd-journal", 1);
+        let event = OomEvent::parse(&damaged).unwrap();
+        let errors = event.strict_errors(&damaged);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, line + 1);
+        assert!(errors[0]
+            .1
+            .starts_with("processes: skipped process list row: could not parse"));
+    }
+}
@@ -0,0 +1,510 @@
+use crate::rollup::parse_timestamp;
+use chrono::{DateTime, FixedOffset};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use tracing::info;
+
+// Logs archived in a structured format rather than as lines of text. Each format is recognized
+// from the start of the log and turned back into the lines syslog would have written, so the
+// parser never has to know where a log came from.
+pub struct StructuredFormat {
+    pub name: &'static str,
+    pub detect: fn(&[u8], &JsonFields) -> bool,
+    pub convert: fn(&[u8], &JsonFields) -> String,
+}
+
+pub const FORMATS: &[StructuredFormat] = &[
+    StructuredFormat {
+        name: "journal export",
+        detect: is_journal_export,
+        convert: journal_export_lines,
+    },
+    StructuredFormat {
+        name: "JSON lines",
+        detect: is_json_lines,
+        convert: json_lines,
+    },
+    StructuredFormat {
+        name: "Splunk CSV",
+        detect: is_splunk_csv,
+        convert: splunk_csv_lines,
+    },
+];
+
+// The fields of a JSON log holding each line's message and time, from --json-field and
+// --json-time. ECS names them message and @timestamp.
+pub struct JsonFields {
+    pub message: String,
+    pub time: String,
+}
+
+impl Default for JsonFields {
+    fn default() -> JsonFields {
+        JsonFields {
+            message: "message".to_string(),
+            time: "@timestamp".to_string(),
+        }
+    }
+}
+
+// Whether a log is in one of the structured formats, from its first few kilobytes
+pub fn is_structured(start: &[u8], fields: &JsonFields) -> bool {
+    FORMATS.iter().any(|format| (format.detect)(start, fields))
+}
+
+// Convert a log in one of the structured formats to plain lines, or None when the log is
+// already plain text
+pub fn plain_text(bytes: &[u8], fields: &JsonFields) -> Option<String> {
+    FORMATS
+        .iter()
+        .find(|format| (format.detect)(bytes, fields))
+        .map(|format| {
+            info!(format = format.name, "converting the log to plain text");
+            (format.convert)(bytes, fields)
+        })
+}
+
+// journalctl -o export writes each entry as its fields, one per line, followed by a blank line.
+// Every entry starts with the journal's own fields, which begin with two underscores:
+//
+//      __CURSOR=s=6b3f...;i=1f2a;b=...
+//      __REALTIME_TIMESTAMP=1703042272700103
+//      _SOURCE_MONOTONIC_TIMESTAMP=75669700103
+//      _TRANSPORT=kernel
+//      _HOSTNAME=localhost
+//      MESSAGE=oom-kill:constraint=CONSTRAINT_NONE,...
+//
+fn is_journal_export(bytes: &[u8], _fields: &JsonFields) -> bool {
+    let first = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+
+    first.starts_with(b"__")
+        && first.iter().position(|&b| b == b'=').is_some_and(|i| {
+            first[..i]
+                .iter()
+                .all(|b| b.is_ascii_uppercase() || *b == b'_')
+        })
+}
+
+// Split an export stream into its entries, each a map of field names to values. A field whose
+// value isn't printable text, such as a MESSAGE with a newline in it, is written as its name
+// alone on a line, then its length as a 64-bit little-endian number, then the value and a
+// newline. Streams concatenated from several exports are one longer stream. A length running
+// past the end of the stream, as in a truncated or corrupted export, takes the rest of it.
+fn journal_export_entries(bytes: &[u8]) -> Vec<BTreeMap<String, String>> {
+    let mut entries = Vec::new();
+    let mut entry = BTreeMap::new();
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        let line = &rest[..end];
+        rest = rest.get(end + 1..).unwrap_or_default();
+
+        if line.is_empty() {
+            if !entry.is_empty() {
+                entries.push(std::mem::take(&mut entry));
+            }
+            continue;
+        }
+
+        match line.iter().position(|&b| b == b'=') {
+            Some(i) => {
+                let name = String::from_utf8_lossy(&line[..i]).into_owned();
+                entry.insert(name, String::from_utf8_lossy(&line[i + 1..]).into_owned());
+            }
+            None => {
+                let size = match rest.get(..8) {
+                    Some(size) => u64::from_le_bytes(size.try_into().unwrap()),
+                    None => break,
+                };
+                let end = size
+                    .try_into()
+                    .ok()
+                    .and_then(|size: usize| size.checked_add(8));
+                let value = end.and_then(|end| rest.get(8..end)).unwrap_or(&rest[8..]);
+                let name = String::from_utf8_lossy(line).into_owned();
+                entry.insert(name, String::from_utf8_lossy(value).into_owned());
+                rest = end
+                    .and_then(|end| end.checked_add(1))
+                    .and_then(|next| rest.get(next..))
+                    .unwrap_or_default();
+            }
+        }
+    }
+    if !entry.is_empty() {
+        entries.push(entry);
+    }
+
+    entries
+}
+
+// Write each entry's message as journalctl -o short-iso would, with the kernel's uptime in front
+// of kernel messages as syslog has it:
+//
+//      2023-12-20T03:17:52+0000 localhost kernel: [75669.700103] oom-kill:constraint=...
+//
+fn journal_export_lines(bytes: &[u8], _fields: &JsonFields) -> String {
+    let mut log = String::new();
+
+    for entry in journal_export_entries(bytes) {
+        let message = match entry.get("MESSAGE") {
+            Some(message) => message,
+            None => continue,
+        };
+        let time = entry
+            .get("__REALTIME_TIMESTAMP")
+            .and_then(|t| t.parse::<i64>().ok())
+            .and_then(DateTime::from_timestamp_micros)
+            .map(|t| t.format("%Y-%m-%dT%H:%M:%S%z ").to_string())
+            .unwrap_or_default();
+        let host = entry.get("_HOSTNAME").map_or("localhost", String::as_str);
+        let kernel = entry.get("_TRANSPORT").is_some_and(|t| t == "kernel");
+        let identifier = match kernel {
+            true => "kernel",
+            false => entry
+                .get("SYSLOG_IDENTIFIER")
+                .or_else(|| entry.get("_COMM"))
+                .map_or("unknown", String::as_str),
+        };
+        let uptime = entry
+            .get("_SOURCE_MONOTONIC_TIMESTAMP")
+            .filter(|_| kernel)
+            .and_then(|t| t.parse::<u64>().ok())
+            .map(|t| format!("[{:5}.{:06}] ", t / 1_000_000, t % 1_000_000))
+            .unwrap_or_default();
+
+        for line in message.lines() {
+            log.push_str(&format!(
+                "{}{} {}: {}{}\n",
+                time, host, identifier, uptime, line
+            ));
+        }
+    }
+
+    log
+}
+
+// A field of a JSON log line by its name, which may be a path into nested objects as ECS has
+// them, ie. host.name. Documents exported from Elasticsearch keep the log line in _source.
+fn json_field<'a>(document: &'a Value, name: &str) -> Option<&'a Value> {
+    let document = document.get("_source").unwrap_or(document);
+
+    document.get(name).or_else(|| {
+        name.split('.')
+            .try_fold(document, |value, key| value.get(key))
+    })
+}
+
+fn json_text(document: &Value, name: &str) -> Option<String> {
+    match json_field(document, name)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+// Newline-delimited JSON, as Logstash and Elasticsearch exports write it, one object per line:
+//
+//      {"@timestamp":"2023-12-20T03:17:52.700Z","host":{"name":"db01"},"message":"[75669.700103] oom-kill:..."}
+//
+fn is_json_lines(bytes: &[u8], fields: &JsonFields) -> bool {
+    let first = bytes
+        .split(|&b| b == b'\n')
+        .find(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .unwrap_or_default();
+
+    first.first() == Some(&b'{')
+        && serde_json::from_slice::<Value>(first)
+            .is_ok_and(|document| json_field(&document, &fields.message).is_some())
+}
+
+// Write each JSON line's message as a syslog line. A message that is already a whole syslog line
+// is kept as it is; otherwise the time, host and program are put in front of it, with the
+// program taken to be the kernel when the log doesn't say.
+fn json_lines(bytes: &[u8], fields: &JsonFields) -> String {
+    const HOST_FIELDS: [&str; 3] = ["host.name", "host.hostname", "host"];
+    const PROGRAM_FIELDS: [&str; 4] = [
+        "process.name",
+        "log.syslog.appname",
+        "program",
+        "syslog_program",
+    ];
+
+    let mut lines = Vec::new();
+    for line in String::from_utf8_lossy(bytes).lines() {
+        let document = match serde_json::from_str::<Value>(line) {
+            Ok(document) => document,
+            Err(_) => continue,
+        };
+        let message = match json_text(&document, &fields.message) {
+            Some(message) => message,
+            None => continue,
+        };
+        let time = json_field(&document, &fields.time).and_then(json_time);
+
+        let text = if parse_timestamp(&message).is_some_and(|t| message.starts_with(&t)) {
+            message
+        } else {
+            let first = |names: &[&str]| names.iter().find_map(|name| json_text(&document, name));
+            let host = first(&HOST_FIELDS).unwrap_or_else(|| "localhost".to_string());
+            let program = first(&PROGRAM_FIELDS).unwrap_or_else(|| "kernel".to_string());
+            let prefix = time.map(|t| t.format("%Y-%m-%dT%H:%M:%S%.6f%:z ").to_string());
+            format!(
+                "{}{} {}: {}",
+                prefix.unwrap_or_default(),
+                host,
+                program,
+                message
+            )
+        };
+        lines.push((time, text));
+    }
+
+    time_ordered(lines)
+}
+
+// The time of a JSON log line: an RFC 3339 date, or milliseconds since the epoch as
+// Elasticsearch stores dates
+fn json_time(value: &Value) -> Option<DateTime<FixedOffset>> {
+    match value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s).ok(),
+        Value::Number(n) => DateTime::from_timestamp_millis(n.as_i64()?).map(|t| t.fixed_offset()),
+        _ => None,
+    }
+}
+
+// Split CSV text into records of fields. Quoted fields may hold commas, newlines, and quotes
+// written twice.
+fn csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            ('\r', false) => (),
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
+
+// A CSV export of a Splunk search, with each event's original text in the _raw column and its
+// time in _time:
+//
+//      "_time","host","_raw"
+//      "2023-12-20T03:17:52.700+00:00","db01","Dec 20 03:17:52 db01 kernel: [75669.700103] ..."
+//
+fn is_splunk_csv(bytes: &[u8], _fields: &JsonFields) -> bool {
+    let header = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+
+    !header.starts_with(b"{")
+        && String::from_utf8_lossy(header)
+            .trim_end()
+            .split(',')
+            .any(|column| column.trim_matches('"') == "_raw")
+}
+
+// Take the _raw text of each event, in the order the events happened. Splunk lists the newest
+// first.
+fn splunk_csv_lines(bytes: &[u8], _fields: &JsonFields) -> String {
+    let mut records = csv_records(&String::from_utf8_lossy(bytes)).into_iter();
+    let header = records.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|c| c == name);
+    let (raw, time) = match column("_raw") {
+        Some(raw) => (raw, column("_time")),
+        None => return String::new(),
+    };
+
+    let lines = records
+        .filter_map(|mut record| {
+            let time = time
+                .and_then(|i| record.get(i))
+                .and_then(|t| splunk_time(t));
+            let text = record.get_mut(raw).map(std::mem::take)?;
+            Some((time, text.trim_end().to_string()))
+        })
+        .collect();
+
+    time_ordered(lines)
+}
+
+// Splunk's _time: an ISO 8601 date with its offset, with or without a colon in it, or seconds
+// since the epoch
+fn splunk_time(time: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(time)
+        .or_else(|_| DateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .ok()
+        .or_else(|| {
+            let seconds = time.parse::<f64>().ok()?;
+            DateTime::from_timestamp_millis((seconds * 1000.0).round() as i64)
+                .map(|t| t.fixed_offset())
+        })
+}
+
+// Join lines put in the order they were logged. Exports are often newest first, or in no order
+// at all, but a report can only be read with its lines in order. Lines logged in the same
+// instant keep the order of the export, taken the other way round when it is newest first.
+// Lines are left as they are when any has no time.
+fn time_ordered(mut lines: Vec<(Option<DateTime<FixedOffset>>, String)>) -> String {
+    if lines.iter().all(|(time, _)| time.is_some()) {
+        if lines.first().map(|l| l.0) > lines.last().map(|l| l.0) {
+            lines.reverse();
+        }
+        lines.sort_by_key(|(time, _)| *time);
+    }
+
+    lines.into_iter().map(|(_, line)| line + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OomEvent;
+
+    #[test]
+    fn report_journal_export() {
+        let fields = JsonFields::default();
+        let mut export = Vec::new();
+        for (i, line) in include_str!("../messages").lines().enumerate() {
+            // "Dec 20 03:17:52 localhost kernel: [75669.700103] message"
+            let (uptime, message) = match line.split_once("] ") {
+                Some((prefix, message)) => (prefix.rsplit('[').next().unwrap().trim(), message),
+                None => continue,
+            };
+            let micros = uptime.replace('.', "").parse::<u64>().unwrap();
+            export.extend_from_slice(
+                format!(
+                    "__CURSOR=s=1;i={:x}\n__REALTIME_TIMESTAMP={}\n_SOURCE_MONOTONIC_TIMESTAMP={}\n_TRANSPORT=kernel\n_HOSTNAME=localhost\n",
+                    i,
+                    1_703_042_272_000_000 + i,
+                    micros
+                )
+                .as_bytes(),
+            );
+            // Some messages are written as binary fields
+            if i % 2 == 0 {
+                export.extend_from_slice(format!("MESSAGE={}\n\n", message).as_bytes());
+            } else {
+                export.extend_from_slice(b"MESSAGE\n");
+                export.extend_from_slice(&(message.len() as u64).to_le_bytes());
+                export.extend_from_slice(message.as_bytes());
+                export.extend_from_slice(b"\n\n");
+            }
+        }
+        let concatenated = [export.as_slice(), export.as_slice()].concat();
+
+        assert!(is_journal_export(&export, &fields));
+        assert!(!is_journal_export(include_bytes!("../messages"), &fields));
+        let log = plain_text(&concatenated, &fields).unwrap();
+        assert!(log.starts_with("2023-12-20T03:17:52+0000 localhost kernel: ["));
+        assert!(log.contains(
+            " localhost kernel: [75669.703275] Out of memory: Killed process 517 (clamd)"
+        ));
+
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.victim_pid.as_deref(), Some("517"));
+        assert_eq!(
+            event.processes.len(),
+            OomEvent::parse(include_str!("../messages"))
+                .unwrap()
+                .processes
+                .len()
+        );
+
+        // A length too large to add to ends the stream rather than overflowing
+        let mut corrupt = b"__CURSOR=s=1\nMESSAGE\n".to_vec();
+        corrupt.extend_from_slice(&u64::MAX.to_le_bytes());
+        corrupt.extend_from_slice(b"oom\n");
+        let entries = journal_export_entries(&corrupt);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["MESSAGE"], "oom\n");
+    }
+
+    #[test]
+    fn report_json_lines() {
+        let fields = JsonFields::default();
+        let mut documents = Vec::new();
+        for (i, line) in include_str!("../messages").lines().enumerate() {
+            let message = line.split_once("kernel: ").map_or(line, |(_, m)| m);
+            let time = format!("2023-12-20T03:17:52.{:03}Z", i);
+            let document = match i % 2 {
+                0 => {
+                    serde_json::json!({"@timestamp": time, "host": {"name": "db01"}, "message": message})
+                }
+                _ => {
+                    serde_json::json!({"_index": "logs", "_source": {"@timestamp": time, "message": message}})
+                }
+            };
+            documents.push(document.to_string());
+        }
+        // Newest first, as Elasticsearch returns them by default
+        documents.reverse();
+        let export = documents.join("\n") + "\n";
+
+        assert!(is_json_lines(export.as_bytes(), &fields));
+        assert!(!is_json_lines(b"{not json\n", &fields));
+        let log = plain_text(export.as_bytes(), &fields).unwrap();
+        assert!(log.starts_with("2023-12-20T03:17:52.000000+00:00 db01 kernel: "));
+        assert!(log.contains(
+            ".094000+00:00 db01 kernel: [75669.703275] Out of memory: Killed process 517 (clamd)"
+        ));
+
+        let event = OomEvent::parse(&log).unwrap();
+        assert_eq!(event.victim_pid.as_deref(), Some("517"));
+
+        let document = serde_json::json!({"event": {"original": "text"}, "log.level": "info"});
+        assert_eq!(
+            json_text(&document, "event.original").as_deref(),
+            Some("text")
+        );
+        assert_eq!(json_text(&document, "log.level").as_deref(), Some("info"));
+    }
+
+    #[test]
+    fn report_splunk_csv() {
+        let fields = JsonFields::default();
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+        let mut rows = include_str!("../messages")
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let time = format!("2023-12-20T03:17:52.{:03}+0000", i);
+                format!("{},{},{}\r\n", quote(&time), quote("db01"), quote(line))
+            })
+            .collect::<Vec<_>>();
+        // Newest first, as Splunk lists them
+        rows.reverse();
+        let export = "\"_time\",\"host\",\"_raw\"\r\n".to_string() + &rows.concat();
+
+        assert!(is_splunk_csv(export.as_bytes(), &fields));
+        assert!(!is_splunk_csv(include_bytes!("../messages"), &fields));
+        assert_eq!(
+            plain_text(export.as_bytes(), &fields).unwrap(),
+            include_str!("../messages")
+        );
+
+        assert_eq!(
+            csv_records("a,\"b,\"\"c\"\"\nd\"\n,e"),
+            vec![vec!["a", "b,\"c\"\nd"], vec!["", "e"]]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use crate::report::Report;
+use std::error::Error;
+
+#[cfg(feature = "templates")]
+use crate::{recommendations::recommendations, SCHEMA_VERSION};
+
+// Render a report with a minijinja template, for incident reports in an organization's own
+// format. The template sees the fields of Report at the top level (event, status, top_commands,
+// processes and so on), along with the recommendations and the derived metrics:
+//
+//      {{ event.victim_pid }} was killed with {{ metrics.user_rss_fraction * 100 }}% of RAM
+//      in process RSS
+//      {% for r in recommendations %}- {{ r.message }}
+//      {% endfor %}
+//
+#[cfg(feature = "templates")]
+pub fn render_template(template: &str, report: &Report) -> Result<String, Box<dyn Error>> {
+    let event = &report.event;
+
+    let mut context = serde_json::to_value(report)?;
+    context["schema_version"] = SCHEMA_VERSION.into();
+    context["recommendations"] = serde_json::to_value(recommendations(report))?;
+    context["metrics"] = serde_json::json!({
+        "user_rss_fraction": report.user_rss_fraction(),
+        "slab_fraction": event.slab_fraction(),
+        "reclaimable_slab_fraction": event.reclaimable_slab_fraction(),
+        "hugepage_fraction": event.hugepage_fraction(),
+        "shmem_fraction": event.shmem_fraction(),
+        "swap_used_fraction": event.swap_used_fraction(),
+        "unaccounted_bytes": report.unaccounted_bytes(),
+    });
+
+    let mut env = minijinja::Environment::new();
+    env.add_template("report", template)?;
+
+    Ok(env.get_template("report")?.render(&context)?)
+}
+
+#[cfg(not(feature = "templates"))]
+pub fn render_template(_template: &str, _report: &Report) -> Result<String, Box<dyn Error>> {
+    Err("--template: parseoom was built without the templates feature".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OomEvent;
+
+    #[test]
+    fn report_template() {
+        let report = Report::new(OomEvent::parse(include_str!("../messages")).unwrap());
+        let template = "{{ event.victim_pid }} {{ top_commands[0][0] }} \
+                        {{ metrics.swap_used_fraction }} {{ recommendations | length }}";
+        let rendered = render_template(template, &report);
+
+        if cfg!(feature = "templates") {
+            assert_eq!(rendered.unwrap(), "517 clamd 1.0 2");
+            assert!(render_template("{% if %}", &report).is_err());
+        } else {
+            assert!(rendered.is_err());
+        }
+    }
+}
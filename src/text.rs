@@ -0,0 +1,1600 @@
+#![allow(non_snake_case)] // for MiB, GiB
+
+use crate::numbers::NumberFormat;
+use crate::partial::PartialReport;
+use crate::pressure::{Onset, PressureSample};
+use crate::recommendations::recommendations;
+use crate::render::Renderer;
+use crate::report::{Report, Section, Top};
+use crate::rules::Unit;
+use crate::snapshot::MeminfoChange;
+use crate::thresholds::Status;
+use crate::{
+    format_node_list, online_nodes, parse_extra_columns, zone_names, zone_threshold_kib, OomEvent,
+    ProcessEntry, PAGE_SIZE,
+};
+use std::error::Error;
+use std::str::FromStr;
+
+// Append a line to the text being rendered, as println! would print it
+macro_rules! outln {
+    ($out:expr) => {
+        $out.push('\n')
+    };
+    ($out:expr, $($arg:tt)*) => {{
+        $out.push_str(&format!($($arg)*));
+        $out.push('\n');
+    }};
+}
+
+// How tables are drawn in the text output: aligned columns, or inside ASCII or box-drawing
+// borders
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TableStyle {
+    Plain,
+    Ascii,
+    Unicode,
+}
+
+// The characters a bordered table is drawn with. The rules above the header, below it and
+// below the last row each have a left corner, a joint between columns and a right corner.
+struct Borders {
+    horizontal: char,
+    vertical: char,
+    top: [char; 3],
+    middle: [char; 3],
+    bottom: [char; 3],
+}
+
+impl TableStyle {
+    fn borders(self) -> Option<Borders> {
+        match self {
+            TableStyle::Plain => None,
+            TableStyle::Ascii => Some(Borders {
+                horizontal: '-',
+                vertical: '|',
+                top: ['+', '+', '+'],
+                middle: ['+', '+', '+'],
+                bottom: ['+', '+', '+'],
+            }),
+            TableStyle::Unicode => Some(Borders {
+                horizontal: '─',
+                vertical: '│',
+                top: ['┌', '┬', '┐'],
+                middle: ['├', '┼', '┤'],
+                bottom: ['└', '┴', '┘'],
+            }),
+        }
+    }
+}
+
+impl FromStr for TableStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TableStyle, String> {
+        match s {
+            "plain" => Ok(TableStyle::Plain),
+            "ascii" => Ok(TableStyle::Ascii),
+            "unicode" => Ok(TableStyle::Unicode),
+            _ => Err(format!(
+                "unknown table style '{}', expected plain, ascii or unicode",
+                s
+            )),
+        }
+    }
+}
+
+// The units memory sizes are shown in: KiB, MiB and GiB worked out from the report, or the
+// page counts the kernel printed, with their size in KiB next to them
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Units {
+    Bytes,
+    Pages,
+}
+
+impl FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Units, String> {
+        match s {
+            "bytes" => Ok(Units::Bytes),
+            "pages" => Ok(Units::Pages),
+            _ => Err(format!("unknown units '{}', expected bytes or pages", s)),
+        }
+    }
+}
+
+// How the text output is written, from --plain-numbers and the locale, --table-style,
+// --no-graphics, --units and --wide, and the width of the terminal it goes to. The terminal width
+// is None when the output goes to a pipe or a file, where tables keep their fixed layout so
+// scripts can read them.
+#[derive(Clone)]
+pub struct TextOptions {
+    pub number_format: NumberFormat,
+    pub table_style: TableStyle,
+    pub terminal_width: Option<usize>,
+    pub graphics: bool,
+    pub units: Units,
+    pub wide: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            number_format: NumberFormat::from_env(),
+            table_style: TableStyle::Plain,
+            terminal_width: None,
+            graphics: true,
+            units: Units::Bytes,
+            wide: false,
+        }
+    }
+}
+
+impl TextOptions {
+    // Write a number for the text output with the given number of decimal places
+    pub fn num(&self, value: f64, precision: usize) -> String {
+        self.number_format.format(value, precision)
+    }
+
+    // Write a whole number for the text output exactly
+    pub fn num_exact(&self, value: u128) -> String {
+        self.number_format.format_count(value)
+    }
+
+    // Write a change between two numbers, always with its sign
+    pub fn num_signed(&self, value: f64, precision: usize) -> String {
+        if value >= 0.0 {
+            format!("+{}", self.num(value, precision))
+        } else {
+            self.num(value, precision)
+        }
+    }
+
+    // A bar for a share of RAM, drawn in eighths of a character so that small shares still
+    // show. It's padded to a fixed width after a gap, to line up as a column, or empty with
+    // --no-graphics.
+    fn ram_bar(&self, fraction: f64) -> String {
+        const BAR_WIDTH: usize = 20;
+        const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+        if !self.graphics {
+            return String::new();
+        }
+
+        let eighths = (fraction.clamp(0.0, 1.0) * (BAR_WIDTH * 8) as f64).round() as usize;
+        let (full, part) = (eighths / 8, eighths % 8);
+        let mut bar = "█".repeat(full);
+        if part > 0 {
+            bar.push(EIGHTHS[part]);
+        }
+
+        format!("    {:1$}", bar, BAR_WIDTH)
+    }
+
+    // Append a line with a bar after it for the share of RAM, or of RSS, it's about
+    fn outln_bar(&self, out: &mut String, line: String, fraction: f64) {
+        outln!(
+            out,
+            "{}",
+            format!("{}{}", line, self.ram_bar(fraction)).trim_end()
+        );
+    }
+
+    fn in_pages(&self) -> bool {
+        self.units == Units::Pages
+    }
+
+    // A count of pages of page_kib KiB each as the kernel printed it, when --units pages asks
+    // for it and the report has it, with its exact size in KiB. The whole numbers are multiplied
+    // rather than converted so nothing is rounded.
+    fn pages(&self, count: Option<u64>, page_kib: u64, unit: &str) -> Option<String> {
+        if !self.in_pages() {
+            return None;
+        }
+        let count = count?;
+
+        Some(format!(
+            "{} {} ({} KiB)",
+            self.num_exact(count.into()),
+            unit,
+            self.num_exact(u128::from(count) * u128::from(page_kib))
+        ))
+    }
+}
+
+// Shorten text to a width, marking the cut with an ellipsis
+fn elide(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let kept = text
+            .chars()
+            .take(width.saturating_sub(1))
+            .collect::<String>();
+        format!("{}…", kept)
+    }
+}
+
+// How a table column is aligned
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+// A column of a text table: the header and cells, the width they're padded to, and, for columns
+// that can be left out on a narrow terminal, the order they're dropped in, lowest first
+pub struct Column {
+    header: String,
+    header_align: Align,
+    align: Align,
+    width: usize,
+    drop: Option<usize>,
+    cells: Vec<String>,
+}
+
+impl Column {
+    pub fn new(header: &str, width: usize, align: Align, cells: Vec<String>) -> Column {
+        Column {
+            header: header.to_string(),
+            header_align: align,
+            align,
+            width,
+            drop: None,
+            cells,
+        }
+    }
+
+    // The width the header and every cell fit in
+    fn content_width(&self) -> usize {
+        self.cells
+            .iter()
+            .chain(std::iter::once(&self.header))
+            .map(|cell| cell.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// Pad a cell to a width
+fn pad(text: &str, width: usize, align: Align) -> String {
+    match align {
+        Align::Left => format!("{:<1$}", text, width),
+        Align::Right => format!("{:>1$}", text, width),
+        Align::Center => format!("{:^1$}", text, width),
+    }
+}
+
+// A noun for a count of n, which takes an s, or es after an s, for anything but one
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 {
+        noun.to_string()
+    } else if noun.ends_with('s') {
+        format!("{}es", noun)
+    } else {
+        format!("{}s", noun)
+    }
+}
+
+// Fit a table to the terminal: drop columns in their drop order until the rest fit, then narrow
+// the widest left-aligned column, eliding its cells. With room to spare, a left-aligned column
+// widens to fit its longest cell instead of letting it push the columns after it out of line.
+// Without a terminal the columns keep their widths. With --wide, nothing is dropped or elided
+// and left-aligned columns always widen to fit, terminal or not.
+pub fn fit_columns(columns: &mut Vec<Column>, options: &TextOptions, indent: usize, gap: usize) {
+    if options.wide {
+        for column in columns.iter_mut().filter(|c| c.align == Align::Left) {
+            column.width = column.width.max(column.content_width());
+        }
+        return;
+    }
+
+    let width = match options.terminal_width {
+        Some(width) => width,
+        None => return,
+    };
+    // A bordered cell has a space either side and a line between it and the next
+    let table_width = |columns: &[Column]| {
+        let cells = columns.iter().map(|c| c.width).sum::<usize>();
+        match options.table_style {
+            TableStyle::Plain => indent + cells + gap * columns.len().saturating_sub(1),
+            _ => indent + cells + 3 * columns.len() + 1,
+        }
+    };
+
+    for column in columns.iter_mut().filter(|c| c.align == Align::Left) {
+        column.width = column.width.max(column.content_width());
+    }
+
+    while table_width(columns) > width {
+        let next = columns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| Some((c.drop?, i)))
+            .min();
+        match next {
+            Some((_, i)) => {
+                columns.remove(i);
+            }
+            None => break,
+        }
+    }
+
+    let over = table_width(columns).saturating_sub(width);
+    if let Some(column) = columns
+        .iter_mut()
+        .filter(|c| c.align == Align::Left)
+        .max_by_key(|c| c.width)
+    {
+        // Keep enough of a name to recognize it
+        column.width = column.width.saturating_sub(over).max(8);
+        for cell in column.cells.iter_mut() {
+            *cell = elide(cell, column.width);
+        }
+    }
+}
+
+// Write a table, one line for the header and one per row, with borders if --table-style asks
+// for them
+pub fn write_columns(
+    out: &mut String,
+    options: &TextOptions,
+    columns: &[Column],
+    indent: usize,
+    gap: usize,
+) {
+    let rows = columns.iter().map(|c| c.cells.len()).max().unwrap_or(0);
+    if let Some(borders) = options.table_style.borders() {
+        write_bordered(out, columns, rows, indent, &borders);
+        return;
+    }
+
+    let line = |cell: &dyn Fn(&Column) -> String| {
+        let cells = columns.iter().map(cell).collect::<Vec<_>>();
+        format!("{}{}", " ".repeat(indent), cells.join(&" ".repeat(gap)))
+            .trim_end()
+            .to_string()
+    };
+
+    outln!(
+        out,
+        "{}",
+        line(&|c| pad(&c.header, c.width, c.header_align))
+    );
+    for row in 0..rows {
+        outln!(
+            out,
+            "{}",
+            line(&|c| pad(c.cells.get(row).map_or("", |s| s), c.width, c.align))
+        );
+    }
+}
+
+// Write a table inside borders. Cells too wide for their column widen it, to keep the borders
+// in line.
+fn write_bordered(
+    out: &mut String,
+    columns: &[Column],
+    rows: usize,
+    indent: usize,
+    borders: &Borders,
+) {
+    let widths = columns
+        .iter()
+        .map(|c| c.width.max(c.content_width()))
+        .collect::<Vec<_>>();
+    let indent = " ".repeat(indent);
+
+    let rule = |[left, cross, right]: [char; 3]| {
+        let lines = widths
+            .iter()
+            .map(|&w| borders.horizontal.to_string().repeat(w + 2))
+            .collect::<Vec<_>>();
+        format!(
+            "{}{}{}{}",
+            indent,
+            left,
+            lines.join(&cross.to_string()),
+            right
+        )
+    };
+    let line = |cell: &dyn Fn(&Column, usize) -> String| {
+        let cells = columns
+            .iter()
+            .zip(widths.iter())
+            .map(|(c, &w)| format!(" {} ", cell(c, w)))
+            .collect::<Vec<_>>();
+        let vertical = borders.vertical.to_string();
+        format!(
+            "{}{}{}{}",
+            indent,
+            vertical,
+            cells.join(&vertical),
+            vertical
+        )
+    };
+
+    outln!(out, "{}", rule(borders.top));
+    outln!(out, "{}", line(&|c, w| pad(&c.header, w, c.header_align)));
+    outln!(out, "{}", rule(borders.middle));
+    for row in 0..rows {
+        outln!(
+            out,
+            "{}",
+            line(&|c, w| pad(c.cells.get(row).map_or("", |s| s), w, c.align))
+        );
+    }
+    outln!(out, "{}", rule(borders.bottom));
+}
+
+// Print a line for each of red, yellow and green listing the categories of memory use with that
+// status, the gist of the report at a glance
+fn write_status(out: &mut String, options: &TextOptions, report: &Report) {
+    outln!(out, "\nStatus:");
+    for status in Status::ALL.iter() {
+        let categories = report
+            .status
+            .iter()
+            .filter(|s| s.status == *status)
+            .map(|s| match s.category {
+                "fragmentation" => format!(
+                    "fragmentation (order {}, {}% free)",
+                    report.event.alloc_order.unwrap_or(0),
+                    options.num(s.percent, 1)
+                ),
+                "shmem" if report.event.database_shmem.is_some() => {
+                    let database = report.event.database_shmem.as_ref().unwrap();
+                    format!(
+                        "shmem ({}%, {} {})",
+                        options.num(s.percent, 1),
+                        database.workload,
+                        database.shared_memory
+                    )
+                }
+                _ => format!("{} ({}%)", s.category, options.num(s.percent, 1)),
+            })
+            .collect::<Vec<_>>();
+        if !categories.is_empty() {
+            outln!(
+                out,
+                "    {:7} {}",
+                format!("{}:", status),
+                categories.join(", ")
+            );
+        }
+    }
+
+    // Whether the machine ran out of memory, or only the part of it the allocation could use
+    let event = &report.event;
+    if let (Some(free), Some(exhausted)) = (event.free_fraction(), event.ram_exhausted) {
+        let verdict = if exhausted {
+            "RAM was exhausted"
+        } else {
+            "RAM was not exhausted, only the memory the allocation could use ran out"
+        };
+        let pcp = match &event.pcp {
+            Some(pcp) => format!(
+                " and {} MiB in the per-cpu lists{}",
+                options.num(pcp.free_kib / 1024.0, 1),
+                pcp.cpus
+                    .map_or(String::new(), |n| format!(" of {} CPUs", n))
+            ),
+            None => String::new(),
+        };
+        outln!(
+            out,
+            "    {}% of RAM free{}: {}",
+            options.num(free * 100.0, 1),
+            pcp,
+            verdict
+        );
+    }
+}
+
+// Print the memory totals from the Mem-Info part of the report. The unreclaimable slab listing
+// sits between huge pages and shared memory, so it's printed from here when selected.
+fn write_meminfo(out: &mut String, options: &TextOptions, report: &Report, sections: &[Section]) {
+    let event = &report.event;
+    let total_ram_KiB = event.total_ram_kib;
+    let (m, g) = (event.hugepages_2mb_kib, event.hugepages_1gb_kib);
+    let total_2_MiB_hugepages_MiB = m / 1024.0;
+    let total_1_GiB_hugepages_GiB = g / 1024.0 / 1024.0;
+    let unreclaimable_slab_KiB = event.unreclaimable_slab_kib;
+    let reclaimable_slab_KiB = event.reclaimable_slab_kib;
+    let shmem_KiB = event.shmem_kib;
+    let meminfo = sections.contains(&Section::Meminfo);
+    let counts = &event.pages;
+    let page_kib = (PAGE_SIZE / 1024) as u64;
+    let mib = |kib: f64| format!("{} MiB", options.num(kib / 1024.0, 1));
+    let base_pages = |count: Option<u64>, kib: f64| {
+        options
+            .pages(count, page_kib, "pages")
+            .unwrap_or_else(|| mib(kib))
+    };
+
+    if meminfo {
+        outln!(out, "\nMemory total:");
+        match options.pages(counts.usable_ram(), page_kib, "pages") {
+            Some(ram) => outln!(
+                out,
+                "    Total RAM: {}, {} pages RAM less {} reserved",
+                ram,
+                options.num_exact(counts.ram.unwrap_or(0).into()),
+                options.num_exact(counts.reserved.unwrap_or(0).into())
+            ),
+            None => outln!(
+                out,
+                "    Total RAM: {} GiB ",
+                options.num(total_ram_KiB / 1024.0 / 1024.0, 1)
+            ),
+        }
+        if event.kernel_32bit {
+            outln!(out, "    Kernel: 32-bit");
+        }
+        write_zone_names(out, event);
+        write_virtualization(out, options, event);
+        write_memory_hotplug(out, options, event);
+
+        outln!(out, "\nSwap:");
+        let free_swap = counts.free_swap_kb.map(|kb| kb / page_kib);
+        let free_swap = options
+            .pages(free_swap, page_kib, "pages")
+            .unwrap_or_else(|| format!("{} KiB", options.num(event.free_swap_kib, 0)));
+        outln!(out, "    Free swap: {}", free_swap);
+        write_compressed_swap(out, options, event);
+
+        outln!(out, "\nHuge Pages:");
+        let allocated_2mb = options
+            .pages(Some(counts.hugepages_2mb), 2048, "huge pages")
+            .unwrap_or_else(|| {
+                format!(
+                    "{:>9} GiB",
+                    options.num(total_2_MiB_hugepages_MiB / 1024.0, 1)
+                )
+            });
+        options.outln_bar(
+            out,
+            format!(
+                "    Allocated 2 MiB huge pages: {}  --  ({}%)",
+                allocated_2mb,
+                options.num(event.ram_fraction(m) * 100.0, 1)
+            ),
+            event.ram_fraction(m),
+        );
+        let allocated_1gb = options
+            .pages(Some(counts.hugepages_1gb), 1048576, "huge pages")
+            .unwrap_or_else(|| format!("{:>9} GiB", options.num(total_1_GiB_hugepages_GiB, 1)));
+        options.outln_bar(
+            out,
+            format!(
+                "    Allocated 1 GiB huge pages: {}  --  ({}%)",
+                allocated_1gb,
+                options.num(event.ram_fraction(g) * 100.0, 1)
+            ),
+            event.ram_fraction(g),
+        );
+        if event.hugepages_kib() > 0.0 {
+            let free = &counts.hugepages_free;
+            let free_hugepages = if options.in_pages() && !free.is_empty() {
+                let sizes = free
+                    .iter()
+                    .map(|(size, n)| format!("{} of {} kB", options.num_exact((*n).into()), size))
+                    .collect::<Vec<_>>();
+                let kib = free
+                    .iter()
+                    .map(|(size, n)| u128::from(*size) * u128::from(*n))
+                    .sum::<u128>();
+                format!("{} ({} KiB)", sizes.join(", "), options.num_exact(kib))
+            } else {
+                format!(
+                    "{:>9} GiB",
+                    options.num(event.hugepages_free_kib / 1024.0 / 1024.0, 1)
+                )
+            };
+            outln!(
+                out,
+                "    Free huge pages:            {}  --  ({}% of reserved)",
+                free_hugepages,
+                options.num(event.hugepages_free_kib / event.hugepages_kib() * 100.0, 1)
+            );
+        }
+    }
+
+    if sections.contains(&Section::Thp) {
+        write_thp(out, options, event);
+    }
+
+    if sections.contains(&Section::Slab) {
+        outln!(out, "\nSlab:");
+        options.outln_bar(
+            out,
+            format!(
+                "    Unreclaimable slab: {}  --  ({}%)",
+                base_pages(counts.slab_unreclaimable, unreclaimable_slab_KiB),
+                options.num(event.slab_fraction() * 100.0, 1)
+            ),
+            event.slab_fraction(),
+        );
+        options.outln_bar(
+            out,
+            format!(
+                "    Reclaimable slab:   {}  --  ({}%)",
+                base_pages(counts.slab_reclaimable, reclaimable_slab_KiB),
+                options.num(event.reclaimable_slab_fraction() * 100.0, 1)
+            ),
+            event.reclaimable_slab_fraction(),
+        );
+
+        // Large but reclaimable slab is usually cache the kernel could have dropped, while
+        // large unreclaimable slab points to a kernel memory leak
+        if unreclaimable_slab_KiB > 0.0 {
+            outln!(
+                out,
+                "    Reclaimable/unreclaimable ratio: {}",
+                options.num(reclaimable_slab_KiB / unreclaimable_slab_KiB, 2)
+            );
+        }
+
+        write_unreclaimable_slab(out, options, report);
+    }
+
+    if meminfo {
+        write_highmem(out, options, event);
+        write_lowmem_exhaustion(out, options, event);
+        write_numa_nodes(out, options, event);
+
+        outln!(out, "\nShared Memory:");
+        options.outln_bar(
+            out,
+            format!(
+                "    Shared memory: {}  --  ({}%)",
+                base_pages(counts.shmem, shmem_KiB),
+                options.num(event.shmem_fraction() * 100.0, 1)
+            ),
+            event.shmem_fraction(),
+        );
+        if let Some(database) = &event.database_shmem {
+            outln!(
+                out,
+                "    {} {}: {} MiB, also in its processes' RSS",
+                database.workload,
+                database.shared_memory,
+                options.num(database.kib / 1024.0, 1)
+            );
+        }
+
+        outln!(out, "\nIsolated and CMA Pages:");
+        outln!(
+            out,
+            "    Isolated anon: {}",
+            base_pages(counts.isolated_anon, event.isolated_anon_kib)
+        );
+        outln!(
+            out,
+            "    Isolated file: {}",
+            base_pages(counts.isolated_file, event.isolated_file_kib)
+        );
+        outln!(
+            out,
+            "    Free CMA: {}  --  ({}%)",
+            base_pages(counts.free_cma, event.free_cma_kib),
+            options.num(event.cma_fraction() * 100.0, 1)
+        );
+    }
+}
+
+// Print transparent huge page usage. Unlike preallocated huge pages this memory is counted in
+// process RSS and page cache, but a large share of it can point to THP bloat.
+fn write_thp(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    outln!(out, "\nTransparent Huge Pages:");
+
+    let thp = match &event.thp {
+        Some(x) => x,
+        None => {
+            outln!(out, "    Not reported by this kernel");
+            return;
+        }
+    };
+
+    // The kernel prints these in kB, always a whole number of pages
+    let counts = &event.pages;
+    let page_kib = (PAGE_SIZE / 1024) as u64;
+    for (name, kib, kb) in [
+        ("Anonymous", thp.anon_kib, counts.thp_anon_kb),
+        ("Shared memory", thp.shmem_kib, counts.thp_shmem_kb),
+        ("File", thp.file_kib, counts.thp_file_kb),
+    ] {
+        let size = options
+            .pages(kb.map(|kb| kb / page_kib), page_kib, "pages")
+            .unwrap_or_else(|| format!("{:>9} MiB", options.num(kib / 1024.0, 1)));
+        outln!(
+            out,
+            "    {:14} {}  --  ({}%)",
+            format!("{}:", name),
+            size,
+            options.num(event.ram_fraction(kib) * 100.0, 1)
+        );
+    }
+}
+
+// Note when the report came from a virtual machine, and any balloon driver that could have handed
+// guest memory back to the hypervisor
+fn write_virtualization(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let virt = &event.virtualization;
+
+    if let Some(hypervisor) = virt.hypervisor {
+        outln!(out, "    Hypervisor: {}", hypervisor);
+    }
+
+    if !virt.balloon_drivers.is_empty() {
+        outln!(
+            out,
+            "    Balloon driver: {} (guest RAM may have been reclaimed by the hypervisor)",
+            virt.balloon_drivers.join(", ")
+        );
+    }
+
+    if let Some(missing) = virt.missing_ram_kib {
+        outln!(
+            out,
+            "    Missing RAM: {} GiB less than expected (ballooned or not provisioned)",
+            options.num(missing / 1024.0 / 1024.0, 1)
+        );
+    }
+}
+
+// Show memory taken offline before the oom-kill, which shrank RAM below what the machine booted
+// with
+fn write_memory_hotplug(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let hotplug = match &event.memory_hotplug {
+        Some(x) => x,
+        None => return,
+    };
+
+    if hotplug.offlines > 0 {
+        outln!(
+            out,
+            "    Offlined before the kill: {} MiB ({} memory blocks)",
+            options.num(hotplug.offlined_kib / 1024.0, 1),
+            hotplug.offlines
+        );
+    }
+    if hotplug.hot_removes > 0 {
+        outln!(
+            out,
+            "    Memory blocks hot-removed: {}",
+            hotplug.hot_removes
+        );
+    }
+    if hotplug.failed_offlines > 0 {
+        outln!(
+            out,
+            "    Failed offline attempts: {}",
+            hotplug.failed_offlines
+        );
+    }
+    outln!(out, "    Last hotplug message: {}", hotplug.last);
+}
+
+// Note swap that lives in compressed RAM. Running out of zram swap means RAM is exhausted too,
+// so more of it won't help the way more disk-backed swap would.
+fn write_compressed_swap(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let swap = &event.compressed_swap;
+    let kinds = [("zram", swap.zram), ("zswap", swap.zswap)]
+        .iter()
+        .filter(|(_, found)| *found)
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+
+    if kinds.is_empty() {
+        return;
+    }
+
+    outln!(out, "    Compressed swap: {}", kinds.join(", "));
+    if let (Some(pool), Some(stored)) = (swap.zswap_kib, swap.zswapped_kib) {
+        outln!(
+            out,
+            "    zswap pool: {} MiB holding {} MiB",
+            options.num(pool / 1024.0, 1),
+            options.num(stored / 1024.0, 1)
+        );
+    }
+    if swap.zram {
+        outln!(
+            out,
+            "    zram swap is backed by RAM, so free swap doesn't add to available memory."
+        );
+    }
+}
+
+// Name the memory zones the host has. A 64-bit host without a Normal zone has all of its RAM
+// below 4 GiB, as small VMs do, so DMA32 is where its memory ran out.
+fn write_zone_names(out: &mut String, event: &OomEvent) {
+    let names = zone_names(&event.zones, event.kernel_32bit);
+    if names.is_empty() {
+        return;
+    }
+
+    let small = event.total_ram_kib <= 4.0 * 1024.0 * 1024.0;
+    let note = if small && !event.kernel_32bit && !names.contains(&"Normal") {
+        "  (no Normal zone, all RAM is below 4 GiB)"
+    } else {
+        ""
+    };
+    outln!(out, "    Memory zones: {}{}", names.join(", "), note);
+}
+
+// Show which NUMA nodes the task could allocate from next to the nodes in the report. Single
+// node machines have nothing to show.
+fn write_numa_nodes(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let online = online_nodes(&event.zones);
+    let allowed = event.mems_allowed.as_deref().unwrap_or(&online);
+    if online.len() < 2 && online.iter().all(|node| allowed.contains(node)) {
+        return;
+    }
+
+    outln!(out, "\nNUMA Nodes:");
+    outln!(out, "    Online nodes: {}", format_node_list(&online));
+    outln!(out, "    Allowed nodes: {}", format_node_list(allowed));
+    for node in &online {
+        let free = event
+            .zones
+            .iter()
+            .filter(|z| z.node == *node)
+            .map(|z| z.free_kib)
+            .sum::<f64>();
+        let note = if allowed.contains(node) {
+            ""
+        } else {
+            "  (not allowed)"
+        };
+        outln!(
+            out,
+            "    Node {} free: {:>10} MiB{}",
+            node,
+            options.num(free / 1024.0, 1),
+            note
+        );
+    }
+}
+
+// Show how RAM was split between LowMem and HighMem on a 32-bit kernel
+fn write_highmem(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let highmem = match &event.highmem {
+        Some(x) => x,
+        None => return,
+    };
+
+    outln!(out, "\nHighMem and LowMem:");
+    outln!(
+        out,
+        "    LowMem:  {:>10} MiB    free: {:>10} MiB",
+        options.num(highmem.lowmem_kib / 1024.0, 1),
+        options.num(highmem.lowmem_free_kib / 1024.0, 1)
+    );
+    outln!(
+        out,
+        "    HighMem: {:>10} MiB    free: {:>10} MiB",
+        options.num(highmem.highmem_kib / 1024.0, 1),
+        options.num(highmem.highmem_free_kib / 1024.0, 1)
+    );
+    outln!(
+        out,
+        "    Kernel memory, including slab and page tables, can only come from LowMem."
+    );
+}
+
+// Call out an allocation that failed only because the low zone it was restricted to ran out
+fn write_lowmem_exhaustion(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let gfp_zone = match event.gfp_zone {
+        Some("Normal") if event.lowmem_exhausted && event.highmem.is_some() => "Normal (LowMem)",
+        Some(z) if event.lowmem_exhausted => z,
+        _ => return,
+    };
+
+    outln!(out, "\nLow Memory Zone:");
+    outln!(
+        out,
+        "    The allocation required {} memory and only that zone was exhausted:",
+        gfp_zone
+    );
+    let zone = event.gfp_zone.unwrap_or_default();
+    for z in event.zones.iter() {
+        // What the zone keeps back from allocations that could have used a higher zone
+        let reserve_kib = zone_threshold_kib(z, zone, event.kernel_32bit) - z.min_kib;
+        let reserve = if reserve_kib > 0.0 {
+            format!(
+                "    reserve: {:>10} MiB",
+                options.num(reserve_kib / 1024.0, 1)
+            )
+        } else {
+            String::new()
+        };
+        outln!(
+            out,
+            "    Node {} {:<8} free: {:>10} MiB    min: {:>10} MiB{}",
+            z.node,
+            z.name,
+            options.num(z.free_kib / 1024.0, 1),
+            options.num(z.min_kib / 1024.0, 1),
+            reserve
+        );
+    }
+}
+
+// Print largest unreclaimable slab caches
+fn write_unreclaimable_slab(out: &mut String, options: &TextOptions, report: &Report) {
+    // Return early if unreclaimable slab wasn't reported by the oom-killer
+    let slab = match &report.slab {
+        Some(x) => x,
+        None => return,
+    };
+
+    outln!(out, "\n    Largest unreclaimable slabs:\n");
+
+    let slabs = slab.slabs.iter().take(10).collect::<Vec<_>>();
+    let cells = |i: usize, unit: &str| {
+        slabs
+            .iter()
+            .map(|line| format!("{}{}", line[i], unit))
+            .collect::<Vec<_>>()
+    };
+    let mut columns = vec![
+        Column::new(&slab.header[0], 24, Align::Left, cells(0, "")), // name
+        Column::new(&slab.header[1], 15, Align::Right, cells(1, " KB")), // used
+        Column::new(&slab.header[2], 15, Align::Right, cells(2, " KB")), // total
+    ];
+
+    fit_columns(&mut columns, options, 4, 1);
+    write_columns(out, options, &columns, 4, 1);
+}
+
+// Print the commands using the most memory.
+fn write_top_commands(out: &mut String, options: &TextOptions, report: &Report, top: Top) {
+    outln!(
+        out,
+        "\n{} using {}:\n",
+        top.title("unique commands"),
+        report.rank.description()
+    );
+    for line in report.top_commands.iter().take(top.rows()) {
+        let bytes = line.1 as f64;
+        options.outln_bar(
+            out,
+            format!(
+                "    {:15}    {:>8} MiB",
+                line.0,
+                options.num(bytes / 1024.0 / 1024.0, 1)
+            ),
+            report.event.ram_fraction(bytes / 1024.0),
+        );
+    }
+}
+
+// Print the values extracted by the --rules file
+fn write_custom(out: &mut String, options: &TextOptions, report: &Report) {
+    if report.event.custom.is_empty() {
+        return;
+    }
+
+    outln!(out, "\nCustom values:\n");
+    for custom in report.event.custom.iter() {
+        match custom.unit {
+            Unit::Kib => outln!(
+                out,
+                "    {:30} {:>10} MiB",
+                custom.name,
+                options.num(custom.value / 1024.0, 1)
+            ),
+            _ => outln!(out, "    {:30} {:10}", custom.name, custom.value),
+        }
+    }
+}
+
+// Print the commands grouped under the program that most likely started them
+fn write_parents(out: &mut String, options: &TextOptions, report: &Report, top: Top) {
+    outln!(
+        out,
+        "\n{} using {}, with their workers:\n",
+        top.title("programs"),
+        report.rank.description()
+    );
+    for group in report.parents.iter().take(top.rows()) {
+        outln!(
+            out,
+            "    {:15}    {:>8} MiB{}    {:4} {:5}    {}",
+            group.name,
+            options.num(group.bytes as f64 / 1024.0 / 1024.0, 1),
+            options.ram_bar(report.event.ram_fraction(group.bytes as f64 / 1024.0)),
+            group.tasks,
+            plural(group.tasks, "task"),
+            group.commands.join(", ")
+        );
+    }
+}
+
+// Print the estimated memory of each container, when container runtime shims were running
+fn write_containers(out: &mut String, options: &TextOptions, report: &Report) {
+    if report.containers.is_empty() {
+        return;
+    }
+
+    outln!(
+        out,
+        "\nContainers, estimated from the tasks started after each runtime shim:\n"
+    );
+    for group in report.containers.iter() {
+        outln!(
+            out,
+            "    {:15} {:>7}    {:>8} MiB    {:4} tasks    {}",
+            group.shim,
+            group.shim_pid,
+            options.num(group.rss as f64 / 1024.0 / 1024.0, 1),
+            group.tasks,
+            group.commands.join(", ")
+        );
+    }
+}
+
+// Print how many tasks were running and which commands had the most of them
+fn write_task_counts(out: &mut String, report: &Report) {
+    outln!(out, "\nTasks:\n");
+    outln!(out, "    Total tasks: {}", report.event.processes.len());
+    outln!(out, "\n    Commands with the most tasks:\n");
+    for (command, n) in report.task_counts.iter().take(5) {
+        outln!(out, "    {:15}    {:8}", command, n);
+    }
+}
+
+// Print how processes and their memory are spread across oom_score_adj values.
+fn write_oom_score_adj_bands(out: &mut String, options: &TextOptions, report: &Report) {
+    outln!(out, "\nProcesses by oom_score_adj:\n");
+    for band in report.oom_score_adj.iter() {
+        outln!(
+            out,
+            "    {:15}    {:6} {:9}    {:>8} MiB",
+            band.label,
+            band.processes,
+            plural(band.processes, "process"),
+            options.num(band.rss as f64 / 1024.0 / 1024.0, 1)
+        );
+    }
+}
+
+// Print how many processes there were of each size, with a bar for the share of RSS in each
+fn write_rss_histogram(out: &mut String, options: &TextOptions, report: &Report) {
+    let total = report.rss_histogram.last().map_or(0, |b| b.cumulative_rss);
+
+    outln!(out, "\nProcesses by RSS:\n");
+    for bucket in report.rss_histogram.iter() {
+        let share = if total > 0 {
+            bucket.rss as f64 / total as f64
+        } else {
+            0.0
+        };
+        let line = format!(
+            "    {:15}    {:6} {:9}    {:>10} MiB    {:>10} MiB cumulative",
+            bucket.label,
+            bucket.processes,
+            plural(bucket.processes, "process"),
+            options.num(bucket.rss as f64 / 1024.0 / 1024.0, 1),
+            options.num(bucket.cumulative_rss as f64 / 1024.0 / 1024.0, 1),
+        );
+        options.outln_bar(out, line, share);
+    }
+}
+
+// Print the processes the kernel most likely considered killing, marking the actual victim.
+fn write_badness_ranking(out: &mut String, options: &TextOptions, report: &Report, top: Top) {
+    let victim_pid = report.event.victim_pid.as_ref();
+
+    outln!(out, "\nLikely victim selection (badness = rss + swapents + pgtables, adjusted by oom_score_adj):\n");
+    outln!(
+        out,
+        "    {:>7}  {:<15}  {:>13}  {:>10}  {:>9}",
+        "pid",
+        "name",
+        "oom_score_adj",
+        "badness",
+        "oom_score"
+    );
+
+    let killed =
+        |pid: &String| Some(pid) == victim_pid || report.event.kills.iter().any(|k| &k.pid == pid);
+    for b in report.victims.iter().take(top.rows()) {
+        let marker = if killed(&b.pid) { "*" } else { " " };
+        outln!(
+            out,
+            "  {} {:>7}  {:<15}  {:>13}  {:>10}  {:>9}",
+            marker,
+            b.pid,
+            b.name,
+            b.oom_score_adj,
+            b.points,
+            b.oom_score
+        );
+    }
+
+    if victim_pid.is_some() || !report.event.kills.is_empty() {
+        outln!(out, "\n    * process killed by the kernel");
+    }
+
+    write_kills(out, options, &report.event);
+}
+
+// Print what the kills of the report took: every process killed and the memory freed
+pub fn write_kills(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    write_group_kills(out, options, event);
+    write_freed(out, options, event);
+}
+
+// Print every process killed for the report, when there was more than one, as when
+// memory.oom.group is set, with the memory each had and what the oom_reaper got back
+fn write_group_kills(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let kills = &event.kills;
+    if kills.len() < 2 {
+        return;
+    }
+
+    match &event.oom_group {
+        Some(group) => outln!(
+            out,
+            "\nProcesses killed together (memory.oom.group set on {}):\n",
+            group
+        ),
+        None => outln!(out, "\nProcesses killed:\n"),
+    }
+    outln!(
+        out,
+        "    {:>7}  {:<15}  {:>10}  {:>10}",
+        "pid",
+        "name",
+        "RSS MiB",
+        "reaped MiB"
+    );
+    for kill in kills {
+        outln!(
+            out,
+            "    {:>7}  {:<15}  {:>10}  {:>10}",
+            kill.pid,
+            kill.command,
+            options.num(kill.rss_kib / 1024.0, 1),
+            kill.reclaimed_kib
+                .map_or("-".to_string(), |kib| options.num(kib / 1024.0, 1))
+        );
+    }
+
+    // Summed from 0.0, as an empty sum of f64 is -0.0
+    let rss = kills.iter().fold(0.0, |sum, k| sum + k.rss_kib);
+    let reaped = kills
+        .iter()
+        .filter_map(|k| k.reclaimed_kib)
+        .fold(0.0, |sum, kib| sum + kib);
+    outln!(
+        out,
+        "\n    {} processes, {} MiB RSS, {} MiB reaped",
+        kills.len(),
+        options.num(rss / 1024.0, 1),
+        options.num(reaped / 1024.0, 1)
+    );
+}
+
+// Show how much memory the kill gave back, by kind of memory, and whether oom_reaper got to it.
+// Only the anonymous memory is certain to be free afterwards.
+fn write_freed(out: &mut String, options: &TextOptions, event: &OomEvent) {
+    let freed = match &event.freed {
+        Some(freed) => freed,
+        None => return,
+    };
+
+    outln!(out, "\nMemory freed by the kill:\n");
+    for (name, kib) in [
+        ("anon-rss", freed.anon_kib),
+        ("file-rss", freed.file_kib),
+        ("shmem-rss", freed.shmem_kib),
+    ] {
+        outln!(
+            out,
+            "    {:<10} {:>10} MiB",
+            name,
+            options.num(kib / 1024.0, 1)
+        );
+    }
+    match freed.ram_ratio {
+        Some(ratio) => outln!(
+            out,
+            "    {:<10} {:>10} MiB   --  ({}% of RAM)",
+            "total",
+            options.num(freed.rss_kib / 1024.0, 1),
+            options.num(ratio * 100.0, 1)
+        ),
+        None => outln!(
+            out,
+            "    {:<10} {:>10} MiB",
+            "total",
+            options.num(freed.rss_kib / 1024.0, 1)
+        ),
+    }
+    match freed.reaped_kib {
+        Some(kib) => outln!(
+            out,
+            "    {:<10} {:>10} MiB confirmed by oom_reaper",
+            "reaped",
+            options.num(kib / 1024.0, 1)
+        ),
+        None => outln!(out, "    not confirmed by oom_reaper"),
+    }
+}
+
+// Print the process list, which Report has already sorted by RSS.
+fn write_ps_list(out: &mut String, options: &TextOptions, report: &Report, top: Top) {
+    let header = &report.event.ps_header;
+    let extra = parse_extra_columns(header);
+    let header_vec = header
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !extra.contains(i))
+        .map(|(_, h)| h)
+        .collect::<Vec<_>>();
+
+    // The format may change depending on kernel version, but the number of columns and the
+    // position of pid, rss, and name should remain fixed, ie:
+    //
+    //      [  pid  ]   uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name
+    //
+    // Print the header from header_vec first.
+    outln!(
+        out,
+        "\nProcesses using most {}:\n",
+        report.rank.description()
+    );
+    let processes = report.processes.iter().take(top.rows()).collect::<Vec<_>>();
+    let cells = |value: &dyn Fn(&ProcessEntry) -> String| {
+        processes.iter().map(|p| value(p)).collect::<Vec<_>>()
+    };
+
+    // Narrow terminals lose the columns least often needed first
+    let mut columns = vec![
+        Column {
+            header_align: Align::Center,
+            ..Column::new(
+                header_vec[0],
+                7,
+                Align::Right,
+                cells(&|p| p.pid.to_string()),
+            )
+        },
+        Column {
+            drop: Some(5),
+            ..Column::new(
+                header_vec[1],
+                8,
+                Align::Right,
+                cells(&|p| p.uid.to_string()),
+            )
+        },
+        Column {
+            drop: Some(0),
+            ..Column::new(
+                header_vec[2],
+                6,
+                Align::Right,
+                cells(&|p| p.tgid.to_string()),
+            )
+        },
+        Column {
+            drop: Some(1),
+            ..Column::new(
+                header_vec[3],
+                10,
+                Align::Right,
+                cells(&|p| p.total_vm.to_string()),
+            )
+        },
+        Column::new(
+            header_vec[4],
+            8,
+            Align::Right,
+            cells(&|p| p.rss.to_string()),
+        ),
+        Column {
+            drop: Some(2),
+            ..Column::new(
+                header_vec[5],
+                16,
+                Align::Right,
+                cells(&|p| p.pgtables.to_string()),
+            )
+        },
+        Column {
+            drop: Some(3),
+            ..Column::new(
+                header_vec[6],
+                10,
+                Align::Right,
+                cells(&|p| p.swapents.to_string()),
+            )
+        },
+        Column {
+            drop: Some(4),
+            ..Column::new(
+                header_vec[7],
+                15,
+                Align::Right,
+                cells(&|p| p.oom_score_adj.to_string()),
+            )
+        },
+        Column::new(header_vec[8], 15, Align::Left, cells(&|p| p.name.clone())),
+        Column::new(
+            "MiB",
+            8,
+            Align::Right,
+            cells(&|p| {
+                options.num(
+                    report.rank.bytes(p, &report.event) as f64 / 1024.0 / 1024.0,
+                    1,
+                )
+            }),
+        ),
+    ];
+
+    // Columns only some kernels print go back where the kernel put them
+    if options.wide {
+        for (k, &i) in extra.iter().enumerate() {
+            let column = Column::new(
+                &header[i],
+                header[i].len().max(8),
+                Align::Right,
+                cells(&|p| p.extra.get(k).map_or(String::new(), |v| v.to_string())),
+            );
+            columns.insert(i, column);
+        }
+    }
+
+    fit_columns(&mut columns, options, 0, 2);
+    write_columns(out, options, &columns, 0, 2);
+}
+
+// Print the total memory consumed by user processes.
+fn write_rss_total(out: &mut String, options: &TextOptions, report: &Report) {
+    let rss_sum = report.rss_total as f64;
+
+    options.outln_bar(
+        out,
+        format!(
+            "\nTotal RSS utilized by user processes: {} MiB   --  ({}%)",
+            options.num(rss_sum / 1024.0 / 1024.0, 1),
+            options.num(report.user_rss_fraction() * 100.0, 1)
+        ),
+        report.user_rss_fraction(),
+    );
+    write_accelerators(out, options, report);
+}
+
+// Show system memory that GPU and accelerator drivers held for their devices. It isn't part of
+// any process's RSS, so it ends up in the memory the report can't account for.
+fn write_accelerators(out: &mut String, options: &TextOptions, report: &Report) {
+    if report.event.accelerators.is_empty() {
+        return;
+    }
+
+    outln!(out, "\nAccelerator memory, not counted in RSS:\n");
+    for accelerator in report.event.accelerators.iter() {
+        let size = |kib: Option<f64>| kib.map_or("-".to_string(), |k| options.num(k / 1024.0, 1));
+        outln!(
+            out,
+            "    {:<12} pinned: {:>10} MiB    GTT: {:>10} MiB",
+            accelerator.driver,
+            size(accelerator.pinned_kib),
+            size(accelerator.gtt_kib)
+        );
+        outln!(out, "        last: {}", accelerator.last);
+    }
+    outln!(
+        out,
+        "    Unaccounted RAM: {} MiB",
+        options.num(report.unaccounted_bytes() as f64 / 1024.0 / 1024.0, 1)
+    );
+}
+
+// Print the warning signs logged shortly before the report, if any
+fn write_precursors(out: &mut String, report: &Report) {
+    if report.event.precursors.is_empty() {
+        return;
+    }
+
+    outln!(out, "\nWarnings before the oom-kill:\n");
+    for precursor in report.event.precursors.iter() {
+        outln!(out, "    {} x{}", precursor.kind, precursor.count);
+        outln!(out, "        first: {}", precursor.first.trim());
+        if precursor.count > 1 {
+            outln!(out, "        last:  {}", precursor.last.trim());
+        }
+    }
+}
+
+// List the Mem-Info dumps and process lists found outside of any oom-killer report, when
+// --scan-partial asked for them
+pub fn write_partial_reports(out: &mut String, options: &TextOptions, partials: &[PartialReport]) {
+    if partials.is_empty() {
+        return;
+    }
+
+    outln!(out, "\nPartial reports without an oom-killer invocation:\n");
+    for partial in partials {
+        let mut found = Vec::new();
+        if partial.mem_info {
+            found.push("Mem-Info".to_string());
+        }
+        if let Some(free_kib) = partial.free_kib {
+            found.push(format!("{} MiB free", options.num(free_kib / 1024.0, 1)));
+        }
+        if partial.processes > 0 {
+            found.push(format!("{} processes", partial.processes));
+        }
+        if let Some(largest) = &partial.largest {
+            found.push(format!("most memory: {}", largest));
+        }
+
+        outln!(
+            out,
+            "    line {}, {}: {}",
+            partial.line,
+            partial.timestamp.as_deref().unwrap_or("unknown time"),
+            found.join(", ")
+        );
+    }
+}
+
+// Print the next steps suggested by the report, if any
+fn write_recommendations(out: &mut String, report: &Report) {
+    let recommendations = recommendations(report);
+    if recommendations.is_empty() {
+        return;
+    }
+
+    outln!(out, "\nRecommendations:\n");
+    for recommendation in recommendations.iter() {
+        outln!(out, "    - {}", recommendation.message);
+    }
+}
+
+// Print the last samples of free memory and paging before the kill, and whether memory ran out
+// gradually or suddenly
+pub fn write_pressure(
+    out: &mut String,
+    options: &TextOptions,
+    samples: &[PressureSample],
+    onset: Option<Onset>,
+) {
+    let cell = |value: Option<f64>| match value {
+        Some(v) => format!("{:>12}", options.num(v, 1)),
+        None => format!("{:>12}", "-"),
+    };
+
+    outln!(out, "Memory pressure leading up to the kill:\n");
+    outln!(
+        out,
+        "    {:10}{:>12}{:>12}{:>12}{:>12}",
+        "time",
+        "free MiB",
+        "swap in/s",
+        "swap out/s",
+        "pgscan/s"
+    );
+    for sample in samples.iter().skip(samples.len().saturating_sub(12)) {
+        let time = sample.time.map_or("-".to_string(), |t| {
+            format!("{:02}:{:02}:{:02}", t / 3600, t / 60 % 60, t % 60)
+        });
+        outln!(
+            out,
+            "    {:10}{}{}{}{}",
+            time,
+            cell(sample.free_kib.map(|kib| kib / 1024.0)),
+            cell(sample.swap_in),
+            cell(sample.swap_out),
+            cell(sample.pgscan)
+        );
+    }
+
+    match onset {
+        Some(Onset::Gradual) => outln!(out, "\n    Free memory ran out gradually."),
+        Some(Onset::Sudden) => {
+            outln!(
+                out,
+                "\n    Free memory ran out suddenly, just before the kill."
+            )
+        }
+        None => (),
+    }
+    outln!(out);
+}
+
+// Print the values shared by the report and a /proc/meminfo snapshot, marking the ones that
+// changed dramatically by the time of the oom-kill
+pub fn write_meminfo_changes(out: &mut String, options: &TextOptions, changes: &[MeminfoChange]) {
+    outln!(out, "Compared with /proc/meminfo (MiB):\n");
+    outln!(out, "    {:16}{:>12}{:>12}", "", "snapshot", "oom-kill");
+    for change in changes {
+        outln!(
+            out,
+            "    {:16}{:>12}{:>12}{}",
+            change.name,
+            options.num(change.snapshot_kib / 1024.0, 1),
+            options.num(change.oom_kib / 1024.0, 1),
+            if change.dramatic { "    <--" } else { "" }
+        );
+    }
+    outln!(out);
+}
+
+// Print the selected sections of the report in their usual order, with top rows of each table.
+pub fn write_report(
+    out: &mut String,
+    options: &TextOptions,
+    report: &Report,
+    sections: &[Section],
+    top: Top,
+) {
+    if sections.contains(&Section::Status) {
+        write_status(out, options, report);
+    }
+    write_meminfo(out, options, report, sections);
+
+    for section in sections {
+        match section {
+            Section::Status | Section::Meminfo | Section::Thp | Section::Slab => (),
+            Section::Custom => write_custom(out, options, report),
+            Section::Commands => write_top_commands(out, options, report, top),
+            Section::Parents => write_parents(out, options, report, top),
+            Section::Containers => write_containers(out, options, report),
+            Section::Tasks => write_task_counts(out, report),
+            Section::OomScoreAdj => write_oom_score_adj_bands(out, options, report),
+            Section::Histogram => write_rss_histogram(out, options, report),
+            Section::Victim => write_badness_ranking(out, options, report, top),
+            Section::Processes => write_ps_list(out, options, report, top),
+            Section::Rss => write_rss_total(out, options, report),
+            Section::Precursors => write_precursors(out, report),
+            Section::Recommendations => write_recommendations(out, report),
+        }
+    }
+    write_partial_reports(out, options, &report.event.partial_reports);
+
+    outln!(out);
+}
+
+// The report as parseoom prints it, with the chosen sections and number of rows of each table
+pub struct Text {
+    pub sections: Vec<Section>,
+    pub top: Top,
+    pub options: TextOptions,
+}
+
+impl Default for Text {
+    fn default() -> Text {
+        Text {
+            sections: Section::ALL.to_vec(),
+            top: Top::Rows(10),
+            options: TextOptions::default(),
+        }
+    }
+}
+
+impl Renderer for Text {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn extension(&self) -> &'static str {
+        "txt"
+    }
+
+    fn render(&self, report: &Report) -> Result<String, Box<dyn Error>> {
+        let mut out = String::new();
+        write_report(&mut out, &self.options, report, &self.sections, self.top);
+
+        Ok(out)
+    }
+}
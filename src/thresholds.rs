@@ -0,0 +1,187 @@
+use crate::OomEvent;
+use serde::Serialize;
+use std::fmt;
+
+// How worrying a category of memory use is, from green (normal) to red (the likely cause)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Green,
+    Yellow,
+    Red,
+}
+
+impl Status {
+    pub const ALL: [Status; 3] = [Status::Red, Status::Yellow, Status::Green];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Green => "green",
+            Status::Yellow => "yellow",
+            Status::Red => "red",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// The percentages at which a category turns yellow and red
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Threshold {
+    pub yellow: f64,
+    pub red: f64,
+}
+
+impl Threshold {
+    fn status(self, percent: f64) -> Status {
+        if percent >= self.red {
+            Status::Red
+        } else if percent >= self.yellow {
+            Status::Yellow
+        } else {
+            Status::Green
+        }
+    }
+}
+
+// Thresholds for each category. All but swap and fragmentation are a percentage of RAM; swap is
+// the percentage of swap in use and fragmentation the percentage of RAM free in the zones when a
+// higher-order allocation failed anyway.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Thresholds {
+    pub rss: Threshold,
+    pub slab: Threshold,
+    pub hugepages: Threshold,
+    pub shmem: Threshold,
+    pub swap: Threshold,
+    pub fragmentation: Threshold,
+}
+
+impl Default for Thresholds {
+    fn default() -> Thresholds {
+        Thresholds {
+            rss: Threshold {
+                yellow: 50.0,
+                red: 75.0,
+            },
+            slab: Threshold {
+                yellow: 10.0,
+                red: 20.0,
+            },
+            hugepages: Threshold {
+                yellow: 25.0,
+                red: 50.0,
+            },
+            shmem: Threshold {
+                yellow: 10.0,
+                red: 25.0,
+            },
+            swap: Threshold {
+                yellow: 50.0,
+                red: 90.0,
+            },
+            fragmentation: Threshold {
+                yellow: 2.0,
+                red: 5.0,
+            },
+        }
+    }
+}
+
+// The status of one category, with the percentage it was judged by and the memory behind it
+#[derive(Serialize)]
+pub struct CategoryStatus {
+    pub category: &'static str,
+    pub status: Status,
+    pub percent: f64,
+    pub kib: f64,
+}
+
+impl Thresholds {
+    // Judge each category of memory use in a report. rss_total is the RSS of every process in
+    // bytes, as in Report.
+    pub fn evaluate(&self, event: &OomEvent, rss_total: i64) -> Vec<CategoryStatus> {
+        let of_ram = |category, threshold: Threshold, kib: f64| {
+            let percent = event.ram_fraction(kib) * 100.0;
+            CategoryStatus {
+                category,
+                status: threshold.status(percent),
+                percent,
+                kib,
+            }
+        };
+
+        let mut statuses = vec![
+            of_ram("user RSS", self.rss, rss_total as f64 / 1024.0),
+            of_ram("slab", self.slab, event.unreclaimable_slab_kib),
+            of_ram("hugepages", self.hugepages, event.hugepages_kib()),
+            of_ram("shmem", self.shmem, event.shmem_kib),
+        ];
+
+        // Running out of memory without any swap configured is worth a look in itself
+        statuses.push(if let Some(fraction) = event.swap_used_fraction() {
+            CategoryStatus {
+                category: "swap",
+                status: self.swap.status(fraction * 100.0),
+                percent: fraction * 100.0,
+                kib: event.swap_used_kib(),
+            }
+        } else {
+            CategoryStatus {
+                category: "swap",
+                status: Status::Yellow,
+                percent: 0.0,
+                kib: 0.0,
+            }
+        });
+
+        // An order 0 allocation can't fail for want of contiguous memory, so only higher orders
+        // with memory still free count as fragmentation
+        let mut fragmentation = of_ram("fragmentation", self.fragmentation, event.zone_free_kib());
+        if event.alloc_order.unwrap_or(0) == 0 {
+            fragmentation.status = Status::Green;
+        }
+        statuses.push(fragmentation);
+
+        statuses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_thresholds() {
+        let event = OomEvent::parse(include_str!("../messages")).unwrap();
+        let statuses = Thresholds::default().evaluate(&event, 517275 * 4096);
+        let status = |category| {
+            statuses
+                .iter()
+                .find(|s| s.category == category)
+                .unwrap()
+                .status
+        };
+
+        assert_eq!(status("user RSS"), Status::Green);
+        assert_eq!(status("swap"), Status::Red);
+        assert_eq!(status("fragmentation"), Status::Green);
+
+        let log = include_str!("../messages").replace("order=0", "order=4");
+        let event = OomEvent::parse(&log).unwrap();
+        let thresholds = Thresholds {
+            fragmentation: Threshold {
+                yellow: 0.1,
+                red: 5.0,
+            },
+            ..Default::default()
+        };
+        let statuses = thresholds.evaluate(&event, 0);
+        assert_eq!(statuses[5].category, "fragmentation");
+        assert_eq!(statuses[5].status, Status::Yellow);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::rollup::parse_timestamp;
+use crate::OomEvent;
+use chrono::{
+    DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+// When a log line was written, as far as its timestamp says: the kernel's uptime in seconds, a
+// wall clock time with no time zone, as classic syslog and some ISO 8601 prefixes give, or a
+// time with its offset from UTC, as RFC 3339 prefixes give.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogTime {
+    Uptime(f64),
+    Local(NaiveDateTime),
+    Zoned(DateTime<FixedOffset>),
+}
+
+impl LogTime {
+    // Seconds from an earlier time to this one, when both are the same kind of time. Uptime
+    // can't be compared with a date, nor a time without a zone with one that has it.
+    pub fn seconds_since(&self, earlier: &LogTime) -> Option<f64> {
+        let millis = |d: chrono::TimeDelta| d.num_milliseconds() as f64 / 1000.0;
+
+        match (self, earlier) {
+            (LogTime::Uptime(a), LogTime::Uptime(b)) => Some(a - b),
+            (LogTime::Local(a), LogTime::Local(b)) => Some(millis(*a - *b)),
+            (LogTime::Zoned(a), LogTime::Zoned(b)) => Some(millis(*a - *b)),
+            _ => None,
+        }
+    }
+
+    // Whether this is a time of day rather than the kernel's uptime
+    pub fn is_date(&self) -> bool {
+        !matches!(self, LogTime::Uptime(_))
+    }
+
+    // The time in UTC, taking a time without a zone to be in the zone the log was written in.
+    // Uptime has no time of day.
+    pub fn to_utc(&self, log_zone: Zone) -> Option<DateTime<Utc>> {
+        match self {
+            LogTime::Uptime(_) => None,
+            LogTime::Local(local) => log_zone.to_utc(*local),
+            LogTime::Zoned(zoned) => Some(zoned.with_timezone(&Utc)),
+        }
+    }
+}
+
+// A time zone given on the command line: UTC, the zone of the machine parseoom runs on, or one
+// from the tz database such as Europe/Berlin
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zone {
+    Utc,
+    Local,
+    Named(Tz),
+}
+
+impl Zone {
+    // The time in UTC of a wall clock time in this zone. A time repeated when the clocks go back
+    // is taken to be the first of the two, and one skipped when they go forward has none.
+    pub fn to_utc(self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::Utc => Some(local.and_utc()),
+            Zone::Local => chrono::Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+        }
+    }
+
+    // Write a time as the wall clock time in this zone, ie. "2023-12-20 03:17:52 CET"
+    pub fn format(self, time: DateTime<Utc>) -> String {
+        const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+        match self {
+            Zone::Utc => format!("{} UTC", time.format(FORMAT)),
+            Zone::Local => {
+                let local = time.with_timezone(&chrono::Local);
+                format!("{} {}", local.format(FORMAT), local.format("%:z"))
+            }
+            Zone::Named(tz) => {
+                let named = time.with_timezone(&tz);
+                format!("{} {}", named.format(FORMAT), named.format("%Z"))
+            }
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Zone, String> {
+        match s {
+            "UTC" | "utc" => Ok(Zone::Utc),
+            "local" => Ok(Zone::Local),
+            _ => s.parse::<Tz>().map(Zone::Named).map_err(|_| {
+                format!(
+                    "unknown time zone '{}', expected UTC, local or a name such as Europe/Berlin",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+// Parse a timestamp from parse_timestamp() or one given on the command line:
+//
+//      542091.439321                       the kernel's uptime
+//      Dec 20 03:17:52                     classic syslog, in the given year
+//      2023-12-20T03:17:52.123456          ISO 8601
+//      2023-12-20T03:17:52+01:00           RFC 3339, or +0100 or Z
+//
+// Month names are always English, as syslog writes them whatever the locale.
+pub fn parse_log_time(timestamp: &str, year: i32) -> Option<LogTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let timestamp = timestamp.trim();
+    if let Ok(uptime) = timestamp.parse::<f64>() {
+        return Some(LogTime::Uptime(uptime));
+    }
+
+    let mut fields = timestamp.split_whitespace();
+    if let Some(month) = MONTHS
+        .iter()
+        .position(|&m| Some(m) == fields.clone().next())
+    {
+        fields.next();
+        let day = fields.next()?.parse::<u32>().ok()?;
+        let time = fields.next()?;
+        let date = NaiveDate::from_ymd_opt(year, month as u32 + 1, day)?;
+        let time = chrono::NaiveTime::parse_from_str(time, "%H:%M:%S%.f").ok()?;
+        return Some(LogTime::Local(date.and_time(time)));
+    }
+
+    let iso = timestamp.replacen('T', " ", 1).replace(',', ".");
+    let iso = match iso.strip_suffix('Z') {
+        Some(utc) => format!("{}+00:00", utc),
+        None => iso,
+    };
+    for format in ["%Y-%m-%d %H:%M:%S%.f%:z", "%Y-%m-%d %H:%M:%S%.f%z"].iter() {
+        if let Ok(time) = DateTime::parse_from_str(&iso, format) {
+            return Some(LogTime::Zoned(time));
+        }
+    }
+    NaiveDateTime::parse_from_str(&iso, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(LogTime::Local)
+}
+
+// Parse the timestamps of a log's lines or events, in the order they were logged. Classic
+// syslog dates have no year, so the first is taken to be in `year` and the year goes up each
+// time the month goes back, as it does from Dec to Jan.
+pub fn parse_log_times(timestamps: &[Option<String>], year: i32) -> Vec<Option<LogTime>> {
+    let mut year = year;
+    let mut last_month = 0;
+
+    timestamps
+        .iter()
+        .map(|timestamp| {
+            let time = parse_log_time(timestamp.as_deref()?, year)?;
+            if let (LogTime::Local(local), Some(first)) = (time, timestamp.as_deref()) {
+                // Only syslog dates, which start with the month, take the year given
+                if first.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    let month = local.month();
+                    if month < last_month {
+                        year += 1;
+                        last_month = month;
+                        return parse_log_time(first, year);
+                    }
+                    last_month = month;
+                }
+            }
+            Some(time)
+        })
+        .collect()
+}
+
+// The year classic syslog dates are taken to start in when --year isn't given: the current
+// year, or the one before when that would put the last of them in the future, as for a log
+// from December read in January
+pub fn default_year(timestamps: &[Option<String>], now: NaiveDateTime) -> i32 {
+    let year = now.year();
+    let times = parse_log_times(timestamps, year);
+    let future = times.iter().rev().flatten().find_map(|time| match time {
+        LogTime::Local(local) => Some(*local > now),
+        _ => None,
+    });
+
+    if future == Some(true) {
+        year - 1
+    } else {
+        year
+    }
+}
+
+// A time as the JSON output gives it, in UTC, so times logged in different zones line up:
+// 2023-12-20T02:17:52Z
+pub fn json_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// The time of the first line of a log or event in UTC, reading a syslog date in `year` and a
+// time without a zone in `log_zone`. None for the kernel's uptime, which has no time of day.
+pub fn first_line_utc(log: &str, year: i32, log_zone: Zone) -> Option<DateTime<Utc>> {
+    let timestamp = parse_timestamp(log.lines().next()?)?;
+
+    parse_log_time(&timestamp, year)?.to_utc(log_zone)
+}
+
+impl OomEvent {
+    // Note the time of the event, that of its first line, for the JSON output
+    pub fn note_time(&mut self, event: &str, year: i32, log_zone: Zone) {
+        self.time = first_line_utc(event, year, log_zone).map(json_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_log_times() {
+        let date = |y, m, d, h, min, s| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, min, s)
+                .unwrap()
+        };
+
+        assert_eq!(
+            parse_log_time("Dec 20 03:17:52", 2023),
+            Some(LogTime::Local(date(2023, 12, 20, 3, 17, 52)))
+        );
+        assert_eq!(
+            parse_log_time("2023-12-20T03:17:52.5", 0),
+            Some(LogTime::Local(
+                date(2023, 12, 20, 3, 17, 52) + chrono::TimeDelta::milliseconds(500)
+            ))
+        );
+        let zoned = parse_log_time("2023-12-20T03:17:52+01:00", 0).unwrap();
+        assert_eq!(parse_log_time("2023-12-20T03:17:52+0100", 0), Some(zoned));
+        let utc = parse_log_time("2023-12-20T02:17:52Z", 0).unwrap();
+        assert_eq!(zoned.seconds_since(&utc), Some(0.0));
+        assert_eq!(
+            parse_log_time("542091.439321", 0),
+            Some(LogTime::Uptime(542091.439321))
+        );
+        assert_eq!(parse_log_time("Feb 29 00:00:00", 2023), None);
+        assert_eq!(parse_log_time("Dez 20 03:17:52", 2023), None);
+
+        // A log spanning New Year, and a leap day after it
+        let timestamps = ["Dec 31 23:59:00", "Jan  1 00:01:00", "Feb 29 12:00:00"]
+            .iter()
+            .map(|t| Some(t.to_string()))
+            .collect::<Vec<_>>();
+        let times = parse_log_times(&timestamps, 2023);
+        assert_eq!(
+            times[1].unwrap().seconds_since(&times[0].unwrap()),
+            Some(120.0)
+        );
+        assert_eq!(times[2], Some(LogTime::Local(date(2024, 2, 29, 12, 0, 0))));
+
+        assert_eq!(default_year(&timestamps, date(2024, 3, 1, 0, 0, 0)), 2023);
+        assert_eq!(
+            default_year(&timestamps[..1], date(2024, 1, 5, 0, 0, 0)),
+            2023
+        );
+        assert_eq!(
+            default_year(&timestamps[..1], date(2024, 12, 31, 23, 59, 59)),
+            2024
+        );
+
+        // The same moment logged in Berlin in winter and in UTC
+        let berlin = "Europe/Berlin".parse::<Zone>().unwrap();
+        let local = parse_log_time("Dec 20 03:17:52", 2023).unwrap();
+        let utc = local.to_utc(berlin).unwrap();
+        assert_eq!(Some(utc), zoned.to_utc(Zone::Utc));
+        assert_eq!(Zone::Utc.format(utc), "2023-12-20 02:17:52 UTC");
+        assert_eq!(berlin.format(utc), "2023-12-20 03:17:52 CET");
+        let messages = include_str!("../messages");
+        assert_eq!(
+            first_line_utc(messages, 2023, berlin).map(json_time),
+            Some("2023-12-20T02:17:52Z".to_string())
+        );
+        assert_eq!(LogTime::Uptime(1.0).to_utc(Zone::Utc), None);
+        assert!("Mars/Olympus_Mons".parse::<Zone>().is_err());
+
+        // 02:30 was skipped when Berlin's clocks went forward
+        let skipped = parse_log_time("Mar 26 02:30:00", 2023).unwrap();
+        assert_eq!(skipped.to_utc(berlin), None);
+    }
+}
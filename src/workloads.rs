@@ -0,0 +1,346 @@
+use crate::report::Report;
+use crate::sections::{parse_memcg_usage, MemcgUsage};
+use crate::{
+    clean_report, parse_extra_columns, parse_processes, parse_ps_header, parse_ps_list,
+    parse_task_unit, OomEvent, ProcessEntry, PAGE_SIZE,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// A well-known workload, recognized by the names its processes run as, and what to check when it
+// is one of the largest consumers of memory. Names are as the kernel logs them, cut to 15
+// characters; a name ending in _ matches every command starting with it. Servers that run on
+// the JVM, such as Elasticsearch, run as java and can't be told apart. shared_memory is what
+// a database calls the shared memory segment its processes all map, when it has one, and
+// huge_pages the setting that has it use huge pages.
+pub struct Workload {
+    pub name: &'static str,
+    pub commands: &'static [&'static str],
+    pub shared_memory: Option<&'static str>,
+    pub huge_pages: Option<&'static str>,
+    pub note: &'static str,
+}
+
+pub const WORKLOADS: [Workload; 6] = [
+    Workload {
+        name: "java",
+        commands: &["java"],
+        shared_memory: None,
+        huge_pages: None,
+        note: "size the heap explicitly with -Xmx, or -XX:MaxRAMPercentage in a container, and \
+               leave room outside it for metaspace, thread stacks and direct buffers \
+               (-XX:MaxMetaspaceSize, -XX:MaxDirectMemorySize); for Elasticsearch, keep the \
+               heap to at most half of RAM and below 31 GiB, leaving the rest to the page cache \
+               Lucene relies on",
+    },
+    Workload {
+        name: "mysql",
+        commands: &["mysqld", "mariadbd"],
+        shared_memory: None,
+        huge_pages: Some("large_pages"),
+        note: "keep innodb_buffer_pool_size to about 70% of the memory the server may use, and \
+               count the per-connection buffers, such as sort_buffer_size and join_buffer_size, \
+               times max_connections",
+    },
+    Workload {
+        name: "postgres",
+        commands: &["postgres", "postmaster"],
+        shared_memory: Some("shared_buffers"),
+        huge_pages: Some("huge_pages=on"),
+        note: "keep shared_buffers to about 25% of RAM, remember that work_mem is per sort or \
+               hash in every connection, and set vm.overcommit_memory=2 so a backend gets an \
+               out of memory error instead of an oom-kill",
+    },
+    Workload {
+        name: "mongodb",
+        commands: &["mongod"],
+        shared_memory: None,
+        huge_pages: None,
+        note: "cap the WiredTiger cache with storage.wiredTiger.engineConfig.cacheSizeGB; it \
+               takes half of RAM less 1 GiB by default, and older versions don't see a \
+               container's limit",
+    },
+    Workload {
+        name: "redis",
+        commands: &["redis-server"],
+        shared_memory: None,
+        huge_pages: None,
+        note: "set maxmemory and an eviction policy, and vm.overcommit_memory=1 so background \
+               saves can fork; a save can need as much copy-on-write memory again as the dataset",
+    },
+    Workload {
+        name: "oracle",
+        commands: &["oracle", "oracle_", "ora_"],
+        shared_memory: Some("SGA"),
+        huge_pages: Some("use_large_pages=ONLY"),
+        note: "keep sga_target and pga_aggregate_limit together well below RAM, and put the SGA \
+               in huge pages with use_large_pages=ONLY so it can't be swapped or counted twice",
+    },
+];
+
+// Only workloads among this many of the largest commands, using at least this share of RAM,
+// get a note
+const WORKLOAD_TOP_COMMANDS: usize = 5;
+const WORKLOAD_RSS_RATIO: f64 = 0.05;
+
+// A JVM uses memory beyond its heap for metaspace, the code cache, thread stacks, GC structures
+// and direct buffers, so a heap of about this share of its RSS, or of the memory it may have, is
+// typical
+const JVM_HEAP_RATIO: f64 = 0.75;
+
+// Java dominates a report when its processes hold at least this share of the processes' RSS
+const JVM_DOMINANT_RATIO: f64 = 0.5;
+
+// And fills its cgroup when they hold at least this share of the cgroup's limit
+const JVM_LIMIT_RATIO: f64 = 0.9;
+
+// A limit at least this large is the kernel's way of saying there's none
+const MEMCG_NO_LIMIT_KIB: f64 = (1u64 << 50) as f64;
+
+// The java processes of a report taken together, when they dominate it. share is their part of
+// the processes' RSS, and limit_kib the memory cgroup's limit, when the report was for one.
+pub struct JvmUsage {
+    pub processes: usize,
+    pub rss_kib: f64,
+    pub share: f64,
+    pub limit_kib: Option<f64>,
+}
+
+impl JvmUsage {
+    pub fn new(
+        processes: &[ProcessEntry],
+        task_unit: i64,
+        memcg: Option<&MemcgUsage>,
+    ) -> Option<JvmUsage> {
+        let rss = |p: &ProcessEntry| p.rss as f64 * task_unit as f64 / 1024.0;
+        let jvms = processes
+            .iter()
+            .filter(|p| workload(&p.name).is_some_and(|w| w.name == "java"));
+        let total = processes.iter().map(rss).sum::<f64>();
+        let rss_kib = jvms.clone().map(rss).sum::<f64>();
+        if total <= 0.0 || rss_kib / total < JVM_DOMINANT_RATIO {
+            return None;
+        }
+
+        Some(JvmUsage {
+            processes: jvms.count(),
+            rss_kib,
+            share: rss_kib / total,
+            limit_kib: memcg
+                .map(|m| m.limit_kib)
+                .filter(|limit| *limit < MEMCG_NO_LIMIT_KIB),
+        })
+    }
+
+    // The JVMs filled their cgroup: the classic heap sized for the host rather than the
+    // container, with -Xmx at or above the container's limit
+    pub fn fills_limit(&self) -> bool {
+        self.limit_kib
+            .is_some_and(|limit| self.rss_kib >= limit * JVM_LIMIT_RATIO)
+    }
+
+    pub fn hint(&self) -> String {
+        let mib = |kib: f64| kib / 1024.0;
+        match self.limit_kib {
+            Some(limit) if self.fills_limit() => format!(
+                "java uses {:.1} MiB of its cgroup's {:.1} MiB limit: a heap (-Xmx) close to or \
+                 above the container's limit leaves no room for metaspace, thread stacks and \
+                 direct buffers; set -Xmx to at most {:.1} MiB, or use -XX:MaxRAMPercentage=75 \
+                 so the JVM sizes its heap from the limit",
+                mib(self.rss_kib),
+                mib(limit),
+                mib(limit * JVM_HEAP_RATIO)
+            ),
+            _ => format!(
+                "{} java processes hold {:.1} MiB, {:.1}% of the processes' RSS: with the heap \
+                 typically about 75% of a JVM's RSS, that's about {:.1} MiB of heap; check that \
+                 the -Xmx of each adds up to no more than the machine has room for",
+                self.processes,
+                mib(self.rss_kib),
+                self.share * 100.0,
+                mib(self.rss_kib * JVM_HEAP_RATIO)
+            ),
+        }
+    }
+}
+
+// The java processes of a memory cgroup's report, which parseoom only summarizes, from its
+// process list and the cgroup's usage line
+pub fn memcg_jvm_usage(oom: &str) -> Option<JvmUsage> {
+    let cleaned = clean_report(oom);
+    let (header, pid_col) = parse_ps_header(&cleaned)?;
+    let extra = parse_extra_columns(&header[pid_col..]);
+    let (processes, _) = parse_processes(parse_ps_list(&cleaned)?, pid_col, &extra);
+    let task_unit = parse_task_unit(&cleaned).unwrap_or(PAGE_SIZE);
+
+    JvmUsage::new(&processes, task_unit, parse_memcg_usage(&cleaned).as_ref())
+}
+
+// The workload a command belongs to, if it's a well-known one
+pub fn workload(command: &str) -> Option<&'static Workload> {
+    WORKLOADS.iter().find(|w| {
+        w.commands.iter().any(|c| match c.strip_suffix('_') {
+            Some(_) => command.starts_with(c),
+            None => command == *c,
+        })
+    })
+}
+
+// Huge pages count as unused by a database when at least this share of them was free
+const HUGEPAGES_UNUSED_RATIO: f64 = 0.5;
+
+// Memory reserved for huge pages, most of it free, on a machine running a database that can
+// use them: the database was likely never set up for them, or failed to get them at start and
+// fell back to normal pages, which then ran out. free_kib and total_kib are the huge pages.
+pub struct UnusedHugepages {
+    pub workload: &'static Workload,
+    pub free_kib: f64,
+    pub total_kib: f64,
+}
+
+impl UnusedHugepages {
+    pub fn new(event: &OomEvent) -> Option<UnusedHugepages> {
+        let total_kib = event.hugepages_kib();
+        if total_kib <= 0.0 || event.hugepages_free_kib < total_kib * HUGEPAGES_UNUSED_RATIO {
+            return None;
+        }
+        let workload = event
+            .processes
+            .iter()
+            .filter_map(|p| workload(&p.name))
+            .find(|w| w.huge_pages.is_some())?;
+
+        Some(UnusedHugepages {
+            workload,
+            free_kib: event.hugepages_free_kib,
+            total_kib,
+        })
+    }
+}
+
+// Shared memory is only put down to a database when it makes up at least this share of RAM
+const DATABASE_SHMEM_RATIO: f64 = 0.05;
+
+// Shared memory that a database's processes map as its shared buffers. The kernel counts those
+// pages both in Shmem and in the RSS of each process that touched them, so kib, the part of
+// the shared memory put down to the database, is left out of the processes' RSS when the
+// report's memory is added up. It is at most the database processes' RSS.
+#[derive(Serialize, JsonSchema)]
+pub struct DatabaseShmem {
+    pub workload: &'static str,
+    pub shared_memory: &'static str,
+    pub kib: f64,
+}
+
+impl DatabaseShmem {
+    pub fn new(event: &OomEvent) -> Option<DatabaseShmem> {
+        if event.shmem_fraction() < DATABASE_SHMEM_RATIO {
+            return None;
+        }
+
+        // The database with the most memory, if more than one is running
+        let mut rss: BTreeMap<&'static str, f64> = BTreeMap::new();
+        for process in event.processes.iter() {
+            if let Some(w) = workload(&process.name).filter(|w| w.shared_memory.is_some()) {
+                *rss.entry(w.name).or_default() +=
+                    process.rss as f64 * event.task_unit as f64 / 1024.0;
+            }
+        }
+        let (name, rss_kib) = rss.into_iter().max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let workload = WORKLOADS.iter().find(|w| w.name == name)?;
+
+        Some(DatabaseShmem {
+            workload: workload.name,
+            shared_memory: workload.shared_memory?,
+            kib: event.shmem_kib.min(rss_kib),
+        })
+    }
+}
+
+// The well-known workloads among the largest commands of a report, each with the command and
+// its RSS in bytes, largest first
+pub fn recognize_workloads(report: &Report) -> Vec<(&'static Workload, &str, i64)> {
+    let mut found: Vec<(&'static Workload, &str, i64)> = Vec::new();
+    for (command, rss) in report.top_commands.iter().take(WORKLOAD_TOP_COMMANDS) {
+        if report.event.ram_fraction(*rss as f64 / 1024.0) < WORKLOAD_RSS_RATIO {
+            continue;
+        }
+        match workload(command) {
+            Some(w) if !found.iter().any(|(f, _, _)| f.name == w.name) => {
+                found.push((w, command, *rss))
+            }
+            _ => (),
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OomEvent;
+
+    #[test]
+    fn report_workloads() {
+        // Every command belongs to one workload
+        let mut commands = WORKLOADS
+            .iter()
+            .flat_map(|w| w.commands.iter())
+            .collect::<Vec<_>>();
+        let n = commands.len();
+        commands.sort();
+        commands.dedup();
+        assert_eq!(commands.len(), n);
+        assert!(commands.iter().all(|c| c.len() <= 15));
+
+        assert_eq!(workload("mariadbd").map(|w| w.name), Some("mysql"));
+        assert!(workload("mysql").is_none());
+        assert_eq!(workload("ora_pmon_orcl").map(|w| w.name), Some("oracle"));
+        assert!(workload("orakel").is_none());
+
+        // mariadbd and redis-server are among the largest commands in the sample log, but too
+        // small to matter
+        let log = include_str!("../messages");
+        let report = Report::new(OomEvent::parse(log).unwrap());
+        assert!(recognize_workloads(&report).is_empty());
+
+        let log = log.replace("clamd", "mongod");
+        let report = Report::new(OomEvent::parse(&log).unwrap());
+        let found = recognize_workloads(&report);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0.name, found[0].1), ("mongodb", "mongod"));
+        assert!(JvmUsage::new(&report.event.processes, PAGE_SIZE, None).is_none());
+
+        // A JVM filling its container
+        let log = include_str!("../tests/fixtures/rhel-8-4.18-memcg.log");
+        let jvm = memcg_jvm_usage(log).unwrap();
+        assert_eq!(jvm.processes, 1);
+        assert_eq!(jvm.limit_kib, Some(2097152.0));
+        assert!(jvm.fills_limit());
+        assert!(jvm.hint().contains("set -Xmx to at most 1536.0 MiB"));
+
+        let log = log.replace("limit 2097152kB", "limit 8388608kB");
+        assert!(!memcg_jvm_usage(&log).unwrap().fills_limit());
+
+        // Oracle's SGA makes up most of the shared memory, and its processes' RSS
+        let log = include_str!("../tests/fixtures/rhel-9-5.14.log");
+        let event = OomEvent::parse(log).unwrap();
+        assert!(event.database_shmem.is_none());
+        let log = log.replace(" shmem:1501120 ", " shmem:3001120 ");
+        let event = OomEvent::parse(&log).unwrap();
+        let shmem = event.database_shmem.as_ref().unwrap();
+        assert_eq!((shmem.workload, shmem.shared_memory), ("oracle", "SGA"));
+        assert!(shmem.kib > 0.0 && shmem.kib <= event.shmem_kib);
+
+        // A third of the huge pages free is in use, three quarters free isn't
+        assert!(UnusedHugepages::new(&event).is_none());
+        let log = log.replace("hugepages_free=4096", "hugepages_free=10240");
+        let log = log.replace("hugepages_free=4 ", "hugepages_free=6 ");
+        let event = OomEvent::parse(&log).unwrap();
+        let unused = UnusedHugepages::new(&event).unwrap();
+        assert_eq!(unused.workload.name, "oracle");
+        assert_eq!(unused.free_kib / unused.total_kib, 0.8125);
+    }
+}
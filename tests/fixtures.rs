@@ -0,0 +1,702 @@
+// End-to-end tests over the anonymized oom-killer reports in tests/fixtures. Each fixture is run
+// through the parseoom binary and the rendered output is compared against its snapshot in
+// tests/snapshots.
+//
+// To add a fixture, drop a log named <distro>-<version>-<kernel>.log into tests/fixtures, run
+// `cargo test`, and review the new snapshot with `cargo insta review`.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn render(path: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(path)
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}: {}",
+        path.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn fixtures_match_snapshots() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect::<Vec<_>>();
+    fixtures.sort();
+
+    assert!(!fixtures.is_empty());
+
+    for path in fixtures {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        insta::assert_snapshot!(name, render(&path));
+    }
+}
+
+// Every line of every fixture is claimed by a section parser, so a line --fail-on-unknown-section
+// lists is one from a kernel the fixtures don't cover yet
+#[test]
+fn fixtures_unknown_sections() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path
+            .file_stem()
+            .is_some_and(|stem| stem == "android-13-lmkd")
+        {
+            continue;
+        }
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .arg("--fail-on-unknown-section")
+            .arg(&path)
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let log = fs::read_to_string(dir.join("mainline-6.6.log")).unwrap();
+    let log = log.replace(
+        "Mem-Info:\n",
+        "Mem-Info:\nMar  4 11:02:30 laptop kernel: [580410.700000] Node 0 memtier=2\n",
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg("--fail-on-unknown-section")
+        .arg("-")
+        .env("LC_ALL", "C")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(log.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("    Node N memtier= (1 lines)\n"));
+    assert!(stderr.contains(": Node 0 memtier=2\n"));
+}
+
+// None of the fixtures is damaged, so --strict finds nothing wrong with any of them, old kernels
+// that leave out the oom-kill summary and the process list unit included
+#[test]
+fn fixtures_strict() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "log")
+            || path
+                .file_stem()
+                .is_some_and(|stem| stem == "android-13-lmkd")
+        {
+            continue;
+        }
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .arg("--strict")
+            .arg(&path)
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn fixtures_diff() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg("diff")
+        .arg(dir.join("rhel-8-4.18.log"))
+        .arg(dir.join("rhel-9-5.14.log"))
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    insta::assert_snapshot!("diff", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn fixtures_text_and_json() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rhel-9-5.14.log");
+    let base = std::env::temp_dir().join(format!("parseoom-{}-report", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(&path)
+        .args(["--format", "text,json", "-o"])
+        .arg(&base)
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Both come from the one parse, and match what each format writes on its own
+    let text = fs::read_to_string(base.with_extension("txt")).unwrap();
+    let json = fs::read_to_string(base.with_extension("json")).unwrap();
+    fs::remove_file(base.with_extension("txt")).unwrap();
+    fs::remove_file(base.with_extension("json")).unwrap();
+
+    assert_eq!(text, render(&path));
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(value["schema_version"].is_number());
+    assert!(!value["processes"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn fixtures_demo() {
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg("demo")
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("Total RSS utilized by user processes"));
+}
+
+#[test]
+fn fixtures_narrow_terminal() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ubuntu-22.04-5.15.log");
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(&path)
+        .args(["--width", "40", "--sections", "processes"])
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Columns are dropped until the process table fits
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.lines().all(|line| line.chars().count() <= 40));
+    assert!(!text.contains("pgtables_bytes"));
+    assert!(text.contains("rss") && text.contains("MiB"));
+}
+
+#[test]
+fn fixtures_wide() {
+    // The rhel-7 report as a v4.0 to v4.14 kernel prints it, with nr_pmds after nr_ptes
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rhel-7-3.10.log");
+    let log = fs::read_to_string(&path).unwrap();
+    let row = regex::Regex::new(r"(\[\s*\d+\]\s+\d+\s+\d+\s+\d+\s+\d+\s+\d+)").unwrap();
+    let log = row
+        .replace_all(&log, "${1}       3")
+        .replace("nr_ptes swapents", "nr_ptes nr_pmds swapents");
+
+    let run = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .args(["-", "--sections", "processes"])
+            .args(args)
+            .env("LC_ALL", "C")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(log.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // The extra column is set aside, leaving the table as the kernel's without it
+    let original = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(&path)
+        .args(["--sections", "processes"])
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    assert_eq!(run(&[]), String::from_utf8(original.stdout).unwrap());
+
+    // --wide shows it in its place, and keeps every column on a narrow terminal
+    let text = run(&["--wide", "--width", "40"]);
+    assert!(text.contains("  nr_ptes   nr_pmds    swapents"));
+    assert!(text.contains("  6449         3        2441  "));
+}
+
+#[test]
+fn fixtures_table_style() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rhel-9-5.14.log");
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(&path)
+        .args(["--table-style", "ascii", "--sections", "processes"])
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Every line of the table is drawn to the same width, between borders
+    let text = String::from_utf8(output.stdout).unwrap();
+    let table = text
+        .lines()
+        .filter(|line| line.starts_with(['+', '|']))
+        .collect::<Vec<_>>();
+    assert!(table.len() > 4);
+    assert!(table.iter().all(|line| line.len() == table[0].len()));
+    assert!(table.iter().all(|line| line.ends_with(['+', '|'])));
+}
+
+#[test]
+fn fixtures_units_pages() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("messages");
+    let log = fs::read_to_string(&path).unwrap();
+    let run = |log: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .args(["-", "--units", "pages", "--sections", "meminfo,thp,slab"])
+            .args(["--no-graphics", "--plain-numbers"])
+            .env("LC_ALL", "C")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(log.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // The counts are those in the log, with their size in KiB worked out exactly
+    let text = run(&log);
+    for line in [
+        "Total RAM: 5225002 pages (20900008 KiB), 5241540 pages RAM less 16538 reserved",
+        "Allocated 1 GiB huge pages: 2 huge pages (2097152 KiB)  --  (10.0%)",
+        "Free huge pages:            0 of 2048 kB, 0 of 1048576 kB (0 KiB)",
+        "Anonymous:     214016 pages (856064 KiB)  --  (4.1%)",
+        "Unreclaimable slab: 4465 pages (17860 KiB)  --  (0.1%)",
+        "Shared memory: 147 pages (588 KiB)  --  (0.0%)",
+        "Isolated file: 2 pages (8 KiB)",
+    ] {
+        assert!(text.contains(line), "{} not in\n{}", line, text);
+    }
+
+    // Even counts too large for a float to hold exactly
+    let text = run(&log.replace("5241540 pages RAM", "9007199254740993 pages RAM"));
+    assert!(text.contains("Total RAM: 9007199254724455 pages (36028797018897820 KiB), "));
+    assert!(text.contains(", 9007199254740993 pages RAM less 16538 reserved"));
+}
+
+#[test]
+fn fixtures_precursor_window() {
+    // An allocation stall five minutes before the report
+    let messages = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("messages"));
+    let stall = "Dec 20 03:12:52 localhost kernel: [75369.000000] kworker/0:1: page allocation \
+                 stalls for 10012ms, order:0\n";
+    let path = std::env::temp_dir().join(format!("parseoom-{}-stall.log", std::process::id()));
+    fs::write(&path, format!("{}{}", stall, messages.unwrap())).unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .arg(&path)
+            .arg("--json")
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    let last = run(&[]);
+    let narrow = run(&["--precursor-window", "60"]);
+    let all = run(&["--all"]);
+    let all_narrow = run(&["--all", "--precursor-window", "60"]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(last["precursors"][0]["kind"], "allocation stall");
+    assert_eq!(narrow["precursors"], serde_json::json!([]));
+    assert_eq!(
+        all["events"][0]["precursors"][0]["kind"],
+        "allocation stall"
+    );
+    assert_eq!(all_narrow["events"][0]["precursors"], serde_json::json!([]));
+}
+
+#[test]
+fn fixtures_broken_pipe() {
+    let messages = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("messages"));
+    let path = std::env::temp_dir().join(format!("parseoom-{}-pipe.log", std::process::id()));
+    fs::write(&path, messages.unwrap().repeat(100)).unwrap();
+
+    // Stop reading after the first line, as head or a pager that's quit early does
+    let mut child = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(&path)
+        .arg("--all")
+        .env("LC_ALL", "C")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut first)
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(!stderr.contains("error"), "{}", stderr);
+}
+
+#[test]
+fn fixtures_no_graphics() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("messages");
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .arg(&path)
+            .args(["--sections", "commands,histogram"])
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // clamd holds 8% of RAM, a bar of a character and five eighths
+    let text = run(&[]);
+    let clamd = text.lines().find(|l| l.contains("clamd")).unwrap();
+    assert!(clamd.ends_with("MiB    █▋"));
+    // and the one process over 1 GiB 82% of the RSS
+    let giant = text.lines().find(|l| l.contains("> 1 GiB")).unwrap();
+    assert!(giant.ends_with("cumulative    ████████████████▍"));
+
+    let text = run(&["--no-graphics"]);
+    let clamd = text.lines().find(|l| l.contains("clamd")).unwrap();
+    assert!(clamd.ends_with("MiB"));
+    let giant = text.lines().find(|l| l.contains("> 1 GiB")).unwrap();
+    assert!(giant.ends_with("cumulative"));
+}
+
+#[test]
+fn fixtures_time_zones() {
+    // The same report logged by a host in New York just before New Year, then one in Tokyo
+    let messages = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("messages"))
+        .unwrap()
+        .replace("Dec 20 03:17:52", "2023-12-31T23:00:00-05:00");
+    let tokyo = messages
+        .replace("2023-12-31T23:00:00-05:00", "2024-01-01T14:30:00+09:00")
+        .replace("75669.", "79269.");
+    let path = std::env::temp_dir().join(format!("parseoom-{}-zones.log", std::process::id()));
+    fs::write(&path, format!("{}{}", tokyo, messages)).unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .arg(&path)
+            .args(["--all"])
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let text = run(&["--tz", "UTC"]);
+    let json = run(&["--json"]);
+    fs::remove_file(&path).unwrap();
+
+    // Put back in the order they happened, half an hour apart
+    assert!(text.contains("==== Event 1 of 2: 2024-01-01 04:00:00 UTC ===="));
+    assert!(text.contains("==== Event 2 of 2: 2024-01-01 05:30:00 UTC ===="));
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["events"][0]["time"], "2024-01-01T04:00:00Z");
+    assert_eq!(value["events"][1]["time"], "2024-01-01T05:30:00Z");
+
+    // A syslog date logged in Berlin, which has no zone of its own
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("messages"))
+        .args(["--json", "--year", "2023", "--log-tz", "Europe/Berlin"])
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value["time"], "2023-12-20T02:17:52Z");
+}
+
+#[test]
+fn fixtures_hosts() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("parseoom-{}-hosts", std::process::id()));
+    let logs = dir.join("logs");
+    for host in ["app3", "db01", "web07"] {
+        fs::create_dir_all(logs.join(host)).unwrap();
+    }
+    fs::copy(root.join("messages"), logs.join("db01/messages")).unwrap();
+    fs::copy(
+        root.join("tests/fixtures/ubuntu-22.04-5.15.log"),
+        logs.join("app3/messages"),
+    )
+    .unwrap();
+    fs::write(
+        logs.join("web07/messages"),
+        "Dec 20 03:17:52 web07 kernel: up\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .arg("--hosts")
+            .arg(&logs)
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let out = dir.join("out");
+    let text = run(&["--output-dir", out.to_str().unwrap(), "--jobs", "2"]);
+    let berlin = ["--year", "2023", "--log-tz", "Europe/Berlin"];
+    let json = run(&[&["--json"], &berlin[..]].concat());
+    let utc = run(&[&["--tz", "UTC"], &berlin[..]].concat());
+    let db01 = fs::read_to_string(out.join("db01.txt")).unwrap();
+    let written = fs::read_dir(&out).unwrap().count();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(text.contains("==== Fleet: 3 hosts, 2 with oom-kills ===="));
+    let line = text.lines().find(|l| l.contains("db01")).unwrap();
+    assert!(line.contains("Dec 20 03:17:52") && line.ends_with("clamd            swap"));
+    assert_eq!(db01, render(&root.join("messages")));
+    assert_eq!(written, 4);
+
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["hosts"][2]["host"], "web07");
+    assert_eq!(value["hosts"][2]["events"], 0);
+
+    // Times as logged, and in UTC
+    assert_eq!(value["hosts"][1]["last"], "Dec 20 03:17:52");
+    assert_eq!(value["hosts"][1]["last_time"], "2023-12-20T02:17:52Z");
+    let line = utc.lines().find(|l| l.contains("db01")).unwrap();
+    assert!(line.contains("  2023-12-20 02:17:52 UTC  clamd"));
+
+    // Rankings across the hosts
+    assert!(text.contains("Across the fleet: 2 events, 2 processes killed"));
+    let swap = text
+        .lines()
+        .find(|l| l.trim_start().starts_with("swap"))
+        .unwrap();
+    assert!(swap.ends_with("2  app3, db01"));
+    assert_eq!(value["fleet"]["affected_hosts"], 2);
+    assert_eq!(value["fleet"]["hosts_by_verdict"]["slab"][0], "app3");
+}
+
+#[test]
+fn fixtures_small_vm_zones() {
+    // The sample report as if from a 2 GiB VM, where DMA32 is the only zone of any size
+    let messages = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("messages"))
+        .unwrap()
+        .replace("5241540 pages RAM", "524154 pages RAM");
+    let path = std::env::temp_dir().join(format!("parseoom-{}-small-vm.log", std::process::id()));
+    fs::write(&path, messages).unwrap();
+    let text = render(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert!(
+        text.contains("    Memory zones: DMA, DMA32  (no Normal zone, all RAM is below 4 GiB)\n")
+    );
+}
+
+#[test]
+fn fixtures_memcg_group_kills() {
+    // The cgroup report with memory.oom.group set, so tini goes along with java
+    let log = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rhel-8-4.18-memcg.log"),
+    )
+    .unwrap()
+        + "Mar  4 11:02:28 k8s-node3 kernel: [806608.258200] Tasks in /system.slice/docker-4b1c.scope are going to be killed due to memory.oom.group set\n"
+        + "Mar  4 11:02:28 k8s-node3 kernel: [806608.258300] Memory cgroup out of memory: Killed process 9901 (tini) total-vm:2476kB, anon-rss:64kB, file-rss:0kB, shmem-rss:0kB, UID:1000 pgtables:44kB oom_score_adj:999\n";
+    let path = std::env::temp_dir().join(format!("parseoom-{}-memcg.log", std::process::id()));
+    fs::write(&path, log).unwrap();
+    let text = render(&path);
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg(&path)
+        .arg("--json")
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(text.contains(
+        "Processes killed together (memory.oom.group set on /system.slice/docker-4b1c.scope):"
+    ));
+    assert!(text.contains("    2 processes, 2,035.3 MiB RSS, 0.0 MiB reaped\n"));
+    assert!(text.contains("Memory freed by the kill:"));
+
+    // There's no Mem-Info in a cgroup's report, which is no reason not to parse it
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value["oom_group"], "/system.slice/docker-4b1c.scope");
+    assert_eq!(value["kills"].as_array().unwrap().len(), 2);
+    assert_eq!(value["memcg"]["limit_kib"], 2097152.0);
+    assert_eq!(value["freed"]["rss_kib"], 2084108.0);
+}
+
+#[test]
+fn fixtures_victim_before_4_19() {
+    // Kernels without the oom-kill summary line only name the victim as they kill it
+    for (name, pid, command) in [
+        ("rhel-7-3.10.log", "23111", "java"),
+        ("debian-8-3.16-686-pae.log", "1874", "apache2"),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .arg(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("tests/fixtures")
+                    .join(name),
+            )
+            .arg("--json")
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(value["victim_pid"], pid, "{}", name);
+        assert_eq!(value["fingerprint"]["victim"], command, "{}", name);
+    }
+}
+
+#[test]
+fn fixtures_verbose() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rhel-8-4.18.log");
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg("-vv")
+        .arg(&path)
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), render(&path));
+    assert!(stderr.contains("read_log{source="));
+    assert!(stderr.contains("section{name=\"meminfo\"}: close time.busy="));
+    assert!(stderr.contains("render{format=\"text\"}: close time.busy="));
+}
+
+#[test]
+fn fixtures_completions_and_man() {
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let bash = run(&["completions", "bash"]);
+    assert!(bash.starts_with("_parseoom() {"));
+    for word in [
+        "events",
+        "diff",
+        "install-service",
+        "--all",
+        "--hosts",
+        "--json",
+        "--tz",
+    ] {
+        assert!(bash.contains(word), "{} isn't completed", word);
+    }
+
+    // The man page is roff, which escapes the dashes of options
+    let man = run(&["man"]);
+    assert!(man.starts_with(".ie \\n(.g .ds Aq \\(aq"));
+    assert!(man.contains(".TH parseoom 1"));
+    for word in ["parseoom\\-events(1)", "parseoom\\-install\\-service(1)"] {
+        assert!(man.contains(word), "{} missing from the man page", word);
+    }
+    for flag in ["all", "hosts", "json", "precursor\\-window"] {
+        assert!(
+            man.contains(&format!("\\fB\\-\\-{}\\fR", flag)),
+            "--{} missing",
+            flag
+        );
+    }
+}
+
+#[test]
+fn fixtures_events() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let first = dir.join("rhel-7-3.10.log");
+    let log = fs::read_to_string(&first).unwrap()
+        + &fs::read_to_string(dir.join("rhel-9-5.14.log")).unwrap();
+    let path = std::env::temp_dir().join(format!("parseoom-{}-events.log", std::process::id()));
+    fs::write(&path, log).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .arg("events")
+        .arg(&path)
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("==== 2 oom-killer reports ===="));
+    assert!(stdout.contains(" java (23111)\n"));
+    assert!(stdout.contains(" oracle_4242_orc (9189)\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .args(["--event", "1"])
+        .arg(&path)
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), render(&first));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_parseoom"))
+        .args(["--event", "3"])
+        .arg(&path)
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("event 3 not found, the log has 2"));
+}
@@ -0,0 +1,299 @@
+// Property-based tests that generate synthetic oom-killer reports across kernel versions, process
+// list layouts, NUMA node counts, and optional sections, then check that the parser recovers the
+// values used to build them.
+
+use parseoom::{parse_unreclaimable_slab, top_consumers, OomEvent};
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+struct Task {
+    pid: u32,
+    uid: u32,
+    total_vm: u64,
+    rss: u64,
+    pgtables: u64,
+    swapents: u64,
+    oom_score_adj: i32,
+    name: String,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    hugepages_2mb: Option<u64>,
+    hugepages_1gb: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Report {
+    kernel: (u32, u32),
+    syslog_prefix: bool,
+    values_in_kb: bool,
+    nodes: Vec<Node>,
+    slab_unreclaimable: u64,
+    shmem: u64,
+    free_swap_kb: u64,
+    total_swap_kb: u64,
+    pages_ram: u64,
+    pages_reserved: u64,
+    slab_info: Option<Vec<(String, u64, u64)>>,
+    tasks: Vec<Task>,
+}
+
+impl Report {
+    // Kernels before 4.15 report page tables as nr_ptes instead of pgtables_bytes
+    fn legacy_columns(&self) -> bool {
+        self.kernel < (4, 15)
+    }
+
+    // The unreclaimable slab dump and the "Tasks state" line only exist on newer kernels
+    fn tasks_state(&self) -> bool {
+        self.kernel >= (4, 19)
+    }
+
+    fn to_log(&self) -> String {
+        let mut lines = Vec::new();
+        let mut ts = 75669.581768;
+        let prefix = |ts: f64| {
+            if self.syslog_prefix {
+                format!("Dec 20 03:17:52 localhost kernel: [{:.6}]", ts)
+            } else {
+                format!("[{:.6}]", ts)
+            }
+        };
+        let mut push = |line: String| {
+            lines.push(format!("{} {}", prefix(ts), line));
+            ts += 0.001;
+        };
+
+        push("postgres invoked oom-killer: gfp_mask=0x100cca(GFP_HIGHUSER_MOVABLE), order=0, oom_score_adj=0".to_string());
+        push(format!(
+            "CPU: 0 PID: 754 Comm: postgres Not tainted {}.{}.0-generic #1",
+            self.kernel.0, self.kernel.1
+        ));
+        push("Mem-Info:".to_string());
+        push("active_anon:69704 inactive_anon:407086 isolated_anon:0".to_string());
+        push(format!(
+            " slab_reclaimable:4158 slab_unreclaimable:{}",
+            self.slab_unreclaimable
+        ));
+        push(format!(
+            " mapped:70 shmem:{} pagetables:2089 bounce:0",
+            self.shmem
+        ));
+        push(" free:13191 free_pcp:370 free_cma:0".to_string());
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            if let Some(n) = node.hugepages_1gb {
+                push(format!(
+                    "Node {} hugepages_total={} hugepages_free=0 hugepages_surp=0 hugepages_size=1048576kB",
+                    i, n
+                ));
+            }
+            if let Some(n) = node.hugepages_2mb {
+                push(format!(
+                    "Node {} hugepages_total={} hugepages_free=0 hugepages_surp=0 hugepages_size=2048kB",
+                    i, n
+                ));
+            }
+        }
+
+        push(format!("Free swap  = {}kB", self.free_swap_kb));
+        push(format!("Total swap = {}kB", self.total_swap_kb));
+        push(format!("{} pages RAM", self.pages_ram));
+        push("0 pages HighMem/MovableOnly".to_string());
+        push(format!("{} pages reserved", self.pages_reserved));
+
+        if let Some(slabs) = &self.slab_info {
+            push("Unreclaimable slab info:".to_string());
+            push("Name                      Used          Total".to_string());
+            for (name, used, total) in slabs {
+                push(format!("{:<24} {:>8}KB {:>12}KB", name, used, total));
+            }
+        }
+
+        // Some vendor kernels report task memory in kB rather than pages
+        let scale = if self.tasks_state() && self.values_in_kb {
+            push("Tasks state (memory values in kB):".to_string());
+            4
+        } else if self.tasks_state() {
+            push("Tasks state (memory values in pages):".to_string());
+            1
+        } else {
+            1
+        };
+
+        if self.legacy_columns() {
+            push(
+                "[ pid ]   uid  tgid total_vm      rss nr_ptes swapents oom_score_adj name"
+                    .to_string(),
+            );
+        } else {
+            push("[  pid  ]   uid  tgid total_vm      rss pgtables_bytes swapents oom_score_adj name".to_string());
+        }
+
+        for t in &self.tasks {
+            push(format!(
+                "[{:>7}] {:>5} {:>5} {:>8} {:>8} {:>8} {:>8} {:>13} {}",
+                t.pid,
+                t.uid,
+                t.pid,
+                t.total_vm * scale,
+                t.rss * scale,
+                t.pgtables,
+                t.swapents * scale,
+                t.oom_score_adj,
+                t.name
+            ));
+        }
+
+        push("oom-kill:constraint=CONSTRAINT_NONE,nodemask=(null),cpuset=/,mems_allowed=0,global_oom".to_string());
+        push("Out of memory: Killed process 517 (postgres) total-vm:2361456kB, anon-rss:1689296kB, file-rss:0kB, shmem-rss:0kB".to_string());
+
+        lines.join("\n") + "\n"
+    }
+}
+
+fn task() -> impl Strategy<Value = Task> {
+    (
+        1..4_000_000u32,
+        0..70_000u32,
+        0..10_000_000u64,
+        0..5_000_000u64,
+        0..10_000_000u64,
+        0..1_000_000u64,
+        -1000..=1000i32,
+        "[a-z][a-z0-9_.-]{0,14}",
+    )
+        .prop_map(
+            |(pid, uid, total_vm, rss, pgtables, swapents, oom_score_adj, name)| Task {
+                pid,
+                uid,
+                total_vm,
+                rss,
+                pgtables,
+                swapents,
+                oom_score_adj,
+                name,
+            },
+        )
+}
+
+fn node() -> impl Strategy<Value = Node> {
+    (
+        proptest::option::of(0..100_000u64),
+        proptest::option::of(0..1024u64),
+    )
+        .prop_map(|(hugepages_2mb, hugepages_1gb)| Node {
+            hugepages_2mb,
+            hugepages_1gb,
+        })
+}
+
+fn report() -> impl Strategy<Value = Report> {
+    (
+        prop::sample::select(vec![(3, 10), (4, 9), (4, 15), (4, 19), (5, 10), (6, 1)]),
+        (any::<bool>(), any::<bool>()),
+        prop::collection::vec(node(), 1..5),
+        0..100_000_000u64,
+        0..100_000_000u64,
+        (0..100_000_000u64).prop_flat_map(|total| (0..=total, Just(total))),
+        (100_000..1_000_000_000u64, 0..100_000u64),
+        proptest::option::of(prop::collection::vec(
+            ("[a-z][a-z0-9_-]{0,20}", 0..1_000_000u64, 0..1_000_000u64),
+            1..20,
+        )),
+        prop::collection::vec(task(), 1..200),
+    )
+        .prop_map(
+            |(
+                kernel,
+                (syslog_prefix, values_in_kb),
+                nodes,
+                slab,
+                shmem,
+                (free_swap, total_swap),
+                (ram, reserved),
+                slab_info,
+                tasks,
+            )| {
+                Report {
+                    kernel,
+                    syslog_prefix,
+                    values_in_kb,
+                    nodes,
+                    slab_unreclaimable: slab,
+                    shmem,
+                    free_swap_kb: free_swap,
+                    total_swap_kb: total_swap,
+                    pages_ram: ram,
+                    pages_reserved: reserved,
+                    slab_info: if kernel >= (4, 19) { slab_info } else { None },
+                    tasks,
+                }
+            },
+        )
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn generated_report_round_trip(r in report()) {
+        let log = r.to_log();
+        let event = OomEvent::parse(&log).unwrap();
+
+        // The expected values are worked out in whole KiB and bytes from the generated numbers,
+        // not with the parser's own arithmetic
+        let total_ram_kib = (r.pages_ram - r.pages_reserved) * 4;
+        prop_assert_eq!(event.total_ram_kib, total_ram_kib as f64);
+        prop_assert_eq!(event.free_swap_kib, r.free_swap_kb as f64);
+        prop_assert_eq!(event.total_swap_kib, r.total_swap_kb as f64);
+        prop_assert!(event.free_swap_kib <= event.total_swap_kib);
+        prop_assert_eq!(event.unreclaimable_slab_kib, (r.slab_unreclaimable * 4) as f64);
+        prop_assert_eq!(event.reclaimable_slab_kib, 16632.0);
+        prop_assert_eq!(event.shmem_kib, (r.shmem * 4) as f64);
+
+        let hugepages_2mb: u64 = r.nodes.iter().filter_map(|n| n.hugepages_2mb).sum();
+        let hugepages_1gb: u64 = r.nodes.iter().filter_map(|n| n.hugepages_1gb).sum();
+        prop_assert_eq!(event.hugepages_2mb_kib, (hugepages_2mb * 2048) as f64);
+        prop_assert_eq!(event.hugepages_1gb_kib, (hugepages_1gb * 1_048_576) as f64);
+
+        // Every row, in order, whether the list is in pages or kB
+        prop_assert_eq!(event.processes.len(), r.tasks.len());
+        for (p, t) in event.processes.iter().zip(r.tasks.iter()) {
+            prop_assert_eq!(p.pid, t.pid);
+            prop_assert_eq!(p.uid, t.uid);
+            prop_assert_eq!(&p.name, &t.name);
+            prop_assert_eq!(p.oom_score_adj, i64::from(t.oom_score_adj));
+            prop_assert_eq!(p.total_vm * event.task_unit, (t.total_vm * 4096) as i64);
+            prop_assert_eq!(p.rss * event.task_unit, (t.rss * 4096) as i64);
+            prop_assert_eq!(p.swapents * event.task_unit, (t.swapents * 4096) as i64);
+        }
+
+        let mut commands = BTreeMap::new();
+        for t in &r.tasks {
+            *commands.entry(t.name.clone()).or_insert(0) += (t.rss * 4096) as i64;
+        }
+        prop_assert_eq!(top_consumers(&event.processes, event.task_unit), commands);
+
+        match &r.slab_info {
+            Some(slabs) => {
+                let (_, slab_vec) = parse_unreclaimable_slab(&event.cleaned).unwrap();
+                prop_assert_eq!(slab_vec.len(), slabs.len());
+                let largest = slabs.iter().map(|s| s.2).max().unwrap();
+                prop_assert_eq!(slab_vec[0][2].parse::<u64>().unwrap(), largest);
+
+                let mut parsed = slab_vec
+                    .iter()
+                    .map(|s| (s[0].clone(), s[1].parse().unwrap(), s[2].parse().unwrap()))
+                    .collect::<Vec<(String, u64, u64)>>();
+                let mut generated = slabs.clone();
+                parsed.sort();
+                generated.sort();
+                prop_assert_eq!(parsed, generated);
+            }
+            None => prop_assert!(parse_unreclaimable_slab(&event.cleaned).is_none()),
+        }
+    }
+}